@@ -0,0 +1,55 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use twee_v3::{scan, testing::generate_story, ParseOptions, Story};
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for n_passages in [10, 1_000, 10_000] {
+        let twee = generate_story(n_passages);
+        group.throughput(Throughput::Bytes(twee.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::new("borrowed", n_passages),
+            &twee,
+            |b, twee| b.iter(|| Story::try_from(twee.as_str()).unwrap()),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("verbatim", n_passages),
+            &twee,
+            |b, twee| {
+                let options = ParseOptions::default().verbatim(true);
+                b.iter(|| Story::parse_with(twee, options.clone()).unwrap())
+            },
+        );
+        group.bench_with_input(BenchmarkId::new("owned", n_passages), &twee, |b, twee| {
+            b.iter(|| Story::try_from(twee.clone()).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("scan", n_passages), &twee, |b, twee| {
+            b.iter(|| scan::passages(twee).count())
+        });
+    }
+    group.finish();
+}
+
+fn write(c: &mut Criterion) {
+    let mut group = c.benchmark_group("to_twee");
+    for n_passages in [10, 1_000, 10_000] {
+        let twee = generate_story(n_passages);
+        let story = Story::try_from(twee.as_str()).unwrap();
+        group.throughput(Throughput::Bytes(twee.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(n_passages),
+            &story,
+            |b, story| b.iter(|| story.to_twee()),
+        );
+    }
+    group.finish();
+}
+
+fn analysis(c: &mut Criterion) {
+    let twee = generate_story(1_000);
+    let story = Story::try_from(twee.as_str()).unwrap();
+    c.bench_function("validate/1000", |b| b.iter(|| story.validate()));
+    c.bench_function("stats/1000", |b| b.iter(|| story.stats()));
+}
+
+criterion_group!(benches, parse, write, analysis);
+criterion_main!(benches);
@@ -11,9 +11,15 @@ fn main() {
     for (count, node) in start.nodes().iter().enumerate() {
         match node {
             ContentNode::Text(text) => print!("{text}"),
-            ContentNode::Link { text, target: _ } => {
+            ContentNode::Link { text, .. } => {
                 print!("{emoji} {text}", emoji = number_to_emoji(count));
             }
+            ContentNode::Comment(_)
+            | ContentNode::Html(_)
+            | ContentNode::Image(_)
+            | ContentNode::Custom(_)
+            | ContentNode::Raw { .. }
+            | ContentNode::Paragraph(_) => {}
         }
     }
     println!();
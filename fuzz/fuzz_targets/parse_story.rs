@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use twee_v3::{parse_events, scan, ParseOptions, Story};
+
+fuzz_target!(|input: &str| {
+    let _ = Story::try_from(input);
+    let options = ParseOptions::default().verbatim(true).html(true);
+    if let Ok(story) = Story::parse_with(input, options) {
+        assert_eq!(story.to_twee(), input);
+    }
+    // Scanning must end too, libFuzzer reports inputs that hang as timeouts.
+    scan::passages(input).for_each(drop);
+    let _ = parse_events(input, drop);
+});
@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use twee_v3::Story;
+
+fuzz_target!(|story: Story<String>| {
+    let twee = story.to_twee();
+    let reparsed = Story::try_from(twee.as_str()).expect("Written stories can be parsed");
+    assert_eq!(
+        reparsed.iter().collect::<Vec<_>>(),
+        story.iter().collect::<Vec<_>>()
+    );
+});
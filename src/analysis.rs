@@ -0,0 +1,497 @@
+//! Analysis of the structure of a story, to check that every branch ends on purpose.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    ops::Deref,
+};
+
+use crate::{passage_map::PassageMap, validation::Issue, ContentNode, Passage, Story};
+
+/// Tags marking a passage as an intended ending.
+pub const ENDING_TAGS: [&str; 2] = ["end", "ending"];
+
+/// Tags of passages that are never shown, and so can't be dead ends.
+const HIDDEN_TAGS: [&str; 3] = ["script", "stylesheet", "widget"];
+
+fn has_tag<T>(passage: &Passage<T>, tags: &[&str]) -> bool
+where
+    T: AsRef<str>,
+{
+    passage
+        .tags
+        .iter()
+        .any(|tag| tags.contains(&tag.value.as_ref()))
+}
+
+/// Whether the passage leads somewhere, through a link or an image with a target.
+fn has_exit<T>(passage: &Passage<T>) -> bool {
+    passage
+        .content
+        .iter()
+        .flat_map(ContentNode::leaves)
+        .any(|node| match node {
+            ContentNode::Link { .. } => true,
+            ContentNode::Image(image) => image.target.is_some(),
+            _ => false,
+        })
+}
+
+/// Figures describing how much a story branches, see [`Story::branching_metrics`].
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct BranchingMetrics {
+    /// Number of passages by number of choices: `out_degrees[2]` passages offer two choices.
+    pub out_degrees: Vec<usize>,
+    pub average_choices: f64,
+    /// Number of choices between the start and the passage farthest from it, going to each
+    /// passage the shortest way.
+    pub max_depth: usize,
+    /// Passages tagged as endings.
+    pub endings: usize,
+    pub dead_ends: usize,
+}
+
+/// A part of a story, see [`Story::subset`] and [`Story::filter_tags`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subset {
+    pub story: Story<String>,
+    /// Links of the subset that go nowhere, as [`Issue::BrokenLink`]. For [`Story::subset`],
+    /// roots that weren't found are listed from an empty passage name.
+    pub dangling: Vec<Issue>,
+}
+
+/// A section of a story, see [`Story::chapters`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chapter {
+    /// The name given by the chapter tags, or `None` for the passages without one.
+    pub name: Option<String>,
+    /// The passages of the chapter, with the title and StoryData of the story, to get the
+    /// statistics of the chapter or export it alone.
+    pub story: Story<String>,
+}
+
+/// How a link target differs from the title of a passage, see [`Story::title_mismatches`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TitleDifference {
+    /// Whitespace around the target or the title.
+    Whitespace,
+    /// Characters written in another Unicode normalization form, like an `é` made of an `e`
+    /// and an accent.
+    Normalization,
+    Case,
+}
+
+/// A link whose target is most likely a misspelled title, see [`Story::title_mismatches`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TitleMismatch {
+    /// The passage with the link.
+    pub passage: String,
+    pub target: String,
+    /// The title of the passage the link was likely meant for.
+    pub title: String,
+    /// What sets the target and the title apart: each of them is needed to explain the
+    /// mismatch.
+    pub differences: Vec<TitleDifference>,
+}
+
+#[cfg(feature = "normalization")]
+fn nfc(text: &str) -> String {
+    use unicode_normalization::UnicodeNormalization;
+
+    text.nfc().collect()
+}
+
+#[cfg(not(feature = "normalization"))]
+fn nfc(text: &str) -> String {
+    text.to_string()
+}
+
+/// The form of a title that the mismatches of [`Story::title_mismatches`] share.
+fn title_key(title: &str) -> String {
+    nfc(title.trim()).to_lowercase()
+}
+
+/// How `target` differs from `title`, when they have the same [`title_key`].
+fn title_differences(target: &str, title: &str) -> Vec<TitleDifference> {
+    let mut differences = vec![];
+    if nfc(target).to_lowercase() != nfc(title).to_lowercase() {
+        differences.push(TitleDifference::Whitespace);
+    }
+    if target.trim().to_lowercase() != title.trim().to_lowercase() {
+        differences.push(TitleDifference::Normalization);
+    }
+    if nfc(target.trim()) != nfc(title.trim()) {
+        differences.push(TitleDifference::Case);
+    }
+    differences
+}
+
+/// The distinct passages a passage leads to.
+pub(crate) fn choices<'a>(passage: &Passage<&'a str>) -> Vec<&'a str> {
+    let mut choices = vec![];
+    for node in passage.content.iter().flat_map(ContentNode::leaves) {
+        let target = match node {
+            ContentNode::Link { target, .. } => *target,
+            ContentNode::Image(image) => match image.target {
+                Some(target) => target,
+                None => continue,
+            },
+            _ => continue,
+        };
+        if !choices.contains(&target) {
+            choices.push(target);
+        }
+    }
+    choices
+}
+
+impl<T> Story<T>
+where
+    T: Deref<Target = str>,
+{
+    /// Passages without any way out that aren't tagged as endings, see [`ENDING_TAGS`], in
+    /// source order. Scripts, stylesheets and widgets are left out.
+    pub fn dead_ends(&self) -> Vec<&str> {
+        self.iter()
+            .filter(|passage| {
+                !has_exit(passage)
+                    && !has_tag(passage, &ENDING_TAGS)
+                    && !has_tag(passage, &HIDDEN_TAGS)
+            })
+            .map(|passage| passage.title)
+            .collect()
+    }
+
+    /// Measures the choices offered by the passages, scripts, stylesheets and widgets left out.
+    pub fn branching_metrics(&self) -> BranchingMetrics {
+        let mut metrics = BranchingMetrics {
+            endings: self.endings().len(),
+            dead_ends: self.dead_ends().len(),
+            ..Default::default()
+        };
+
+        let mut graph = HashMap::new();
+        for passage in self.iter().filter(|p| !has_tag(p, &HIDDEN_TAGS)) {
+            let choices = choices(&passage);
+            if metrics.out_degrees.len() <= choices.len() {
+                metrics.out_degrees.resize(choices.len() + 1, 0);
+            }
+            metrics.out_degrees[choices.len()] += 1;
+            graph.insert(passage.title, choices);
+        }
+        let total: usize = graph.values().map(Vec::len).sum();
+        if !graph.is_empty() {
+            metrics.average_choices = total as f64 / graph.len() as f64;
+        }
+
+        if let Some(start) = self.start() {
+            let mut seen = HashSet::from([start.title]);
+            let mut queue = VecDeque::from([(start.title, 0)]);
+            while let Some((name, depth)) = queue.pop_front() {
+                metrics.max_depth = metrics.max_depth.max(depth);
+                for &target in graph.get(name).into_iter().flatten() {
+                    if graph.contains_key(target) && seen.insert(target) {
+                        queue.push_back((target, depth + 1));
+                    }
+                }
+            }
+        }
+
+        metrics
+    }
+
+    /// The passages reachable from `roots` through links, images and includes, with the title
+    /// and StoryData of the story.
+    pub fn subset(&self, roots: &[&str]) -> Subset {
+        let mut dangling = vec![];
+        let mut kept = HashSet::new();
+        let mut queue: VecDeque<(&str, &str)> = roots.iter().map(|root| ("", *root)).collect();
+        while let Some((source, name)) = queue.pop_front() {
+            let Some(passage) = self.get_passage(name) else {
+                dangling.push(Issue::BrokenLink {
+                    passage: source.to_string(),
+                    target: name.to_string(),
+                    suggestions: vec![],
+                });
+                continue;
+            };
+            if !kept.insert(passage.title) {
+                continue;
+            }
+            let includes = self.includes(name);
+            queue.extend(
+                choices(&passage)
+                    .into_iter()
+                    .chain(includes)
+                    .map(|target| (passage.title, target)),
+            );
+        }
+
+        Subset {
+            story: self.keep_passages(&kept),
+            dangling,
+        }
+    }
+
+    /// The story without the passages tagged with one of `exclude`, such as `debug` passages
+    /// in release builds. When `include` isn't empty, only passages tagged with one of
+    /// `include` are kept.
+    ///
+    /// The links and includes of the kept passages to removed ones are listed as dangling.
+    pub fn filter_tags(&self, include: &[&str], exclude: &[&str]) -> Subset {
+        let kept: HashSet<&str> = self
+            .iter()
+            .filter(|passage| {
+                (include.is_empty() || has_tag(passage, include)) && !has_tag(passage, exclude)
+            })
+            .map(|passage| passage.title)
+            .collect();
+
+        let mut dangling = vec![];
+        for passage in self.iter().filter(|passage| kept.contains(passage.title)) {
+            let targets = choices(&passage)
+                .into_iter()
+                .chain(self.includes(passage.title));
+            for target in targets {
+                if self.contains_passage(target) && !kept.contains(target) {
+                    dangling.push(Issue::BrokenLink {
+                        passage: passage.title.to_string(),
+                        target: target.to_string(),
+                        suggestions: vec![],
+                    });
+                }
+            }
+        }
+        Subset {
+            story: self.keep_passages(&kept),
+            dangling,
+        }
+    }
+
+    /// The passages grouped by the tags starting with `prefix`, such as `chapter:1` for the
+    /// prefix `chapter:`, in the order chapters first appear. A passage with several chapter
+    /// tags goes to the first; passages without one are gathered last, in a chapter without
+    /// name.
+    pub fn chapters(&self, prefix: &str) -> Vec<Chapter> {
+        let mut names: Vec<Option<&str>> = vec![];
+        let mut members: HashMap<Option<&str>, HashSet<&str>> = HashMap::new();
+        for passage in self.iter() {
+            let name = passage
+                .tags
+                .iter()
+                .find_map(|tag| tag.value.strip_prefix(prefix));
+            if !members.contains_key(&name) {
+                names.push(name);
+            }
+            members.entry(name).or_default().insert(passage.title);
+        }
+        // Untagged passages go last.
+        names.sort_by_key(Option::is_none);
+        names
+            .into_iter()
+            .map(|name| Chapter {
+                name: name.map(str::to_owned),
+                story: self.keep_passages(&members[&name]),
+            })
+            .collect()
+    }
+
+    /// A copy of the story with only the passages named in `kept`.
+    fn keep_passages(&self, kept: &HashSet<&str>) -> Story<String> {
+        let mut story = self.to_owned_story();
+        let mut passages = PassageMap::new();
+        for (name, passage) in story.passages.into_entries() {
+            if kept.contains(name.as_str()) {
+                passages.insert(name, passage);
+            }
+        }
+        story.passages = passages;
+        story.warnings.retain(|warning| {
+            warning
+                .passage()
+                .is_none_or(|passage| kept.contains(passage))
+        });
+        story.invalidate_derived();
+        story
+    }
+
+    /// Links and images going nowhere whose target differs from a title only by whitespace
+    /// around it, case, or Unicode normalization with the `normalization` feature, in source
+    /// order. These are the most common typos in links.
+    pub fn title_mismatches(&self) -> Vec<TitleMismatch> {
+        let mut titles: HashMap<String, Vec<&str>> = HashMap::new();
+        for passage in self.iter() {
+            titles
+                .entry(title_key(passage.title))
+                .or_default()
+                .push(passage.title);
+        }
+
+        let mut mismatches = vec![];
+        for passage in self.iter() {
+            for target in choices(&passage) {
+                if self.contains_passage(target) {
+                    continue;
+                }
+                for title in titles.get(&title_key(target)).into_iter().flatten() {
+                    mismatches.push(TitleMismatch {
+                        passage: passage.title.to_string(),
+                        target: target.to_string(),
+                        title: title.to_string(),
+                        differences: title_differences(target, title),
+                    });
+                }
+            }
+        }
+        mismatches
+    }
+
+    /// Passages tagged as endings, see [`ENDING_TAGS`], in source order.
+    pub fn endings(&self) -> Vec<&str> {
+        self.iter()
+            .filter(|passage| has_tag(passage, &ENDING_TAGS))
+            .map(|passage| passage.title)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TitleDifference, TitleMismatch};
+    use crate::{validation::Issue, Story};
+
+    const STORY: &str = ":: Start\n[[Left]] [[Right]] [img[map.png][Map]]\n\n:: Left\nYou fall.\n\n:: Right [end]\nYou win.\n\n:: Map\n[[Start]]\n\n:: Code [script]\nlet x;\n";
+
+    #[test]
+    fn test_title_mismatches() {
+        let input = ":: Start\n[[the cave]] [[Go|Forest ]] [[Forest]] [[Nowhere]]\n\n:: The Cave\n\n:: Forest\n\n:: Caf\u{e9}\n[[Cafe\u{301}]]\n";
+        let story = Story::try_from(input).unwrap();
+        let mismatch = |passage: &str, target: &str, title: &str, differences| TitleMismatch {
+            passage: passage.to_string(),
+            target: target.to_string(),
+            title: title.to_string(),
+            differences,
+        };
+        #[allow(unused_mut)]
+        let mut expected = vec![
+            mismatch("Start", "the cave", "The Cave", vec![TitleDifference::Case]),
+            mismatch(
+                "Start",
+                "Forest ",
+                "Forest",
+                vec![TitleDifference::Whitespace],
+            ),
+        ];
+        #[cfg(feature = "normalization")]
+        expected.push(mismatch(
+            "Caf\u{e9}",
+            "Cafe\u{301}",
+            "Caf\u{e9}",
+            vec![TitleDifference::Normalization],
+        ));
+
+        assert_eq!(story.title_mismatches(), expected);
+    }
+
+    #[test]
+    fn test_dead_ends() {
+        let story = Story::try_from(STORY).unwrap();
+
+        assert_eq!(story.dead_ends(), ["Left"]);
+    }
+
+    #[test]
+    fn test_branching_metrics() {
+        let story = Story::try_from(STORY).unwrap();
+        let metrics = story.branching_metrics();
+
+        assert_eq!(metrics.out_degrees, [2, 1, 0, 1]);
+        assert_eq!(metrics.average_choices, 1.0);
+        assert_eq!(metrics.max_depth, 1);
+        assert_eq!((metrics.endings, metrics.dead_ends), (1, 1));
+    }
+
+    #[test]
+    fn test_subset() {
+        let input = ":: StoryTitle\nBig\n\n:: Start\n[[Demo]] [[Full]]\n\n:: Demo\n<<include \"Intro\">> [[Gone]]\n\n:: Intro\nHi\n\n:: Full\n[[Start]]\n";
+        let story = Story::try_from(input).unwrap();
+        let subset = story.subset(&["Demo", "Missing"]);
+
+        let names: Vec<_> = subset.story.iter().map(|p| p.title().to_string()).collect();
+        assert_eq!(names, ["Demo", "Intro"]);
+        assert_eq!(subset.story.title(), Some("Big"));
+        assert_eq!(
+            subset.dangling,
+            [
+                Issue::BrokenLink {
+                    passage: String::new(),
+                    target: "Missing".to_string(),
+                    suggestions: vec![]
+                },
+                Issue::BrokenLink {
+                    passage: "Demo".to_string(),
+                    target: "Gone".to_string(),
+                    suggestions: vec![]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filter_tags() {
+        let input = ":: Start\n[[Play]] [[Cheats]]\n\n:: Play\nHi [[Nowhere]]\n\n:: Cheats [debug]\n[[Start]]\n\n:: Notes [debug draft]\n";
+        let story = Story::try_from(input).unwrap();
+
+        let release = story.filter_tags(&[], &["debug"]);
+        let names: Vec<_> = release
+            .story
+            .iter()
+            .map(|p| p.title().to_string())
+            .collect();
+        assert_eq!(names, ["Start", "Play"]);
+        assert_eq!(
+            release.dangling,
+            [Issue::BrokenLink {
+                passage: "Start".to_string(),
+                target: "Cheats".to_string(),
+                suggestions: vec![]
+            }]
+        );
+
+        let debug = story.filter_tags(&["debug"], &["draft"]);
+        assert_eq!(debug.story.len(), 1);
+        assert!(debug.story.contains_passage("Cheats"));
+    }
+
+    #[test]
+    fn test_chapters() {
+        let input = ":: Start\n[[Road]]\n\n:: Road [chapter:1]\nA long road. [[Town]]\n\n:: Town [chapter:2 night]\nLights.\n\n:: Inn [chapter:1]\nRest.\n";
+        let story = Story::try_from(input).unwrap();
+        let chapters: Vec<_> = story
+            .chapters("chapter:")
+            .into_iter()
+            .map(|chapter| {
+                (
+                    chapter.name,
+                    chapter.story.len(),
+                    chapter.story.stats().words,
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            chapters,
+            [
+                (Some("1".to_string()), 2, 5),
+                (Some("2".to_string()), 1, 1),
+                (None, 1, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_endings() {
+        let story = Story::try_from(STORY).unwrap();
+
+        assert_eq!(story.endings(), ["Right"]);
+    }
+}
@@ -0,0 +1,136 @@
+//! [`Arbitrary`] stories, for fuzzing and property tests.
+//!
+//! Generated stories only hold content that survives a [`Story::to_twee`] round trip: text,
+//! links, comments, tags and metadata.
+
+use ::arbitrary::{Arbitrary, Result, Unstructured};
+
+use crate::{ContentNode, Metadata, Passage, PassageMap, Story, Tag, TextBlock};
+
+const NAME_CHARS: &[char] = &[
+    'a', 'b', 'Z', '0', '_', '\'', '!', '?', 'é', '[', ']', '{', '}', '\\',
+];
+const TEXT_CHARS: &[char] = &[
+    'a', 'b', ' ', ' ', '\n', ':', '[', ']', '/', '*', '%', '<', '>', '-', '|', '\\', '!', '{',
+];
+
+fn word(u: &mut Unstructured<'_>, chars: &[char]) -> Result<String> {
+    let len = u.int_in_range(1..=8)?;
+    (0..len).map(|_| u.choose(chars).copied()).collect()
+}
+
+fn name(u: &mut Unstructured<'_>) -> Result<String> {
+    let words = u.int_in_range(1..=3)?;
+    let words = (0..words)
+        .map(|_| word(u, NAME_CHARS))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(words.join(" "))
+}
+
+fn node(u: &mut Unstructured<'_>, names: &[String]) -> Result<ContentNode<String>> {
+    Ok(match u.int_in_range(0..=3)? {
+        0 if !names.is_empty() => {
+            let target = u.choose(names)?.clone();
+            ContentNode::link_node(target.clone(), target)
+        }
+        1 if !names.is_empty() => ContentNode::link_node(name(u)?, u.choose(names)?.clone()),
+        2 => ContentNode::comment_node(format!("/* {} */", word(u, &['a', 'b', ' '])?)),
+        _ => {
+            let len = u.int_in_range(1..=40)?;
+            let text = (0..len)
+                .map(|_| u.choose(TEXT_CHARS).copied())
+                .collect::<Result<String>>()?;
+            ContentNode::text_node(text)
+        }
+    })
+}
+
+fn passage(u: &mut Unstructured<'_>, title: String, names: &[String]) -> Result<Passage<String>> {
+    let tags = (0..u.int_in_range(0..=2)?)
+        .map(|_| word(u, NAME_CHARS).map(Tag::new))
+        .collect::<Result<Vec<_>>>()?;
+    let metadata = if u.arbitrary()? {
+        let (x, y) = (u.int_in_range(0..=5000u32)?, u.int_in_range(0..=5000u32)?);
+        Some(Metadata::new(format!("{{\"position\":\"{x},{y}\"}}")))
+    } else {
+        None
+    };
+
+    let mut content: Vec<ContentNode<String>> = vec![];
+    for _ in 0..u.int_in_range(0..=6)? {
+        match (content.last_mut(), node(u, names)?) {
+            // Adjacent text nodes would be read back as one.
+            (Some(ContentNode::Text(previous)), ContentNode::Text(text)) => {
+                previous.push_str(&text)
+            }
+            (_, node) => content.push(node),
+        }
+    }
+    // Trailing line breaks are not part of the content.
+    if let Some(ContentNode::Text(text)) = content.last_mut() {
+        text.truncate(text.trim_end_matches('\n').len());
+        if text.is_empty() {
+            content.pop();
+        }
+    }
+
+    Ok(Passage::new(title, tags, metadata, content))
+}
+
+impl<'a> Arbitrary<'a> for Story<String> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut names: Vec<String> = vec![];
+        for _ in 0..u.int_in_range(0..=8)? {
+            let name = name(u)?;
+            if !names.contains(&name) && name != "StoryTitle" && name != "StoryData" {
+                names.push(name);
+            }
+        }
+
+        let mut passages = PassageMap::new();
+        for title in &names {
+            let passage = passage(u, title.clone(), &names)?;
+            passages.insert(title.clone(), passage.into_blocks());
+        }
+        let title = Option::<()>::arbitrary(u)?
+            .map(|_| name(u))
+            .transpose()?
+            .map(TextBlock::Owned);
+        let start = match names.is_empty() {
+            false if u.arbitrary()? => Some(TextBlock::Owned(u.choose(&names)?.clone())),
+            _ => None,
+        };
+
+        Ok(Story::new(String::new(), title, start, None, passages))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::arbitrary::{Arbitrary, Unstructured};
+    use proptest::{collection::vec, prelude::any, proptest};
+
+    use crate::Story;
+
+    proptest! {
+        #[test]
+        fn test_to_twee_round_trip(bytes in vec(any::<u8>(), 0..2048)) {
+            let mut u = Unstructured::new(&bytes);
+            let story = Story::<String>::arbitrary(&mut u).unwrap();
+            let twee = story.to_twee();
+            let reparsed = Story::try_from(twee.as_str()).unwrap();
+
+            assert_eq!(reparsed.title(), story.title());
+            assert_eq!(
+                reparsed.start().map(|p| p.title().to_string()),
+                story.start().map(|p| p.title().to_string()),
+                "{}",
+                twee
+            );
+            assert_eq!(reparsed.iter().count(), story.iter().count(), "{}", twee);
+            for (reparsed, passage) in reparsed.iter().zip(story.iter()) {
+                assert_eq!(reparsed, passage, "{}", passage.to_twee());
+            }
+        }
+    }
+}
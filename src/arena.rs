@@ -0,0 +1,190 @@
+//! Stories allocated in a bump arena, for batch processing of many stories: everything a story
+//! holds is freed at once when the arena is reset, instead of piece by piece.
+//!
+//! ```
+//! use twee_v3::arena::{parse_in, Bump};
+//!
+//! let mut arena = Bump::new();
+//! for input in [":: Start\nHello [[End]]\n", ":: Other\nBye\n"] {
+//!     let story = parse_in(&arena, input).unwrap();
+//!     assert_eq!(story.passages().len(), 1);
+//!     arena.reset();
+//! }
+//! ```
+//!
+//! The parser still uses the heap for its temporary buffers.
+
+use std::collections::{hash_map::Entry, HashMap};
+
+pub use bumpalo::Bump;
+
+use crate::{
+    encoding::strip_bom,
+    error::ParsingError,
+    parser::story::{parse_story_block, split_preamble, StoryBlock},
+    utils::{ensure_progress, escape_string_content},
+    ContentNode, ParseOptions,
+};
+
+/// A story whose passages are allocated in an arena, see [`parse_in`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArenaStory<'a> {
+    title: Option<&'a str>,
+    start: Option<&'a str>,
+    data: Option<&'a str>,
+    passages: &'a [ArenaPassage<'a>],
+}
+
+impl<'a> ArenaStory<'a> {
+    pub fn title(&self) -> Option<&'a str> {
+        self.title
+    }
+
+    /// Name of the start passage given in StoryData.
+    pub fn start(&self) -> Option<&'a str> {
+        self.start
+    }
+
+    pub fn data(&self) -> Option<&'a str> {
+        self.data
+    }
+
+    /// The passages, in source order.
+    pub fn passages(&self) -> &'a [ArenaPassage<'a>] {
+        self.passages
+    }
+
+    pub fn passage(&self, name: &str) -> Option<&'a ArenaPassage<'a>> {
+        self.passages.iter().find(|passage| passage.title == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArenaPassage<'a> {
+    title: &'a str,
+    tags: &'a [&'a str],
+    metadata: Option<&'a str>,
+    nodes: &'a [ContentNode<&'a str>],
+}
+
+impl<'a> ArenaPassage<'a> {
+    pub fn title(&self) -> &'a str {
+        self.title
+    }
+
+    pub fn tags(&self) -> &'a [&'a str] {
+        self.tags
+    }
+
+    pub fn metadata(&self) -> Option<&'a str> {
+        self.metadata
+    }
+
+    pub fn nodes(&self) -> &'a [ContentNode<&'a str>] {
+        self.nodes
+    }
+}
+
+/// Resolves the escaped characters of `text`, copying it to `arena` only when it has some.
+fn unescape_in<'a>(arena: &'a Bump, text: &'a str) -> &'a str {
+    match escape_string_content(text) {
+        Some(unescaped) => arena.alloc_str(&unescaped),
+        None => text,
+    }
+}
+
+/// Parses `input`, allocating the passages and their nodes in `arena`. Passages are read as
+/// with the default [`ParseOptions`]: text before the first header is skipped, and a passage
+/// replaces an earlier one with the same name, at its position.
+pub fn parse_in<'a>(
+    arena: &'a Bump,
+    input: &'a str,
+) -> Result<ArenaStory<'a>, ParsingError<&'a str>> {
+    let options = ParseOptions::default();
+    let (_, mut input) = split_preamble(strip_bom(input));
+    let mut story = ArenaStory {
+        title: None,
+        start: None,
+        data: None,
+        passages: &[],
+    };
+    let mut passages = bumpalo::collections::Vec::new_in(arena);
+    let mut positions = HashMap::new();
+    while !input.is_empty() {
+        let (rest, block) = parse_story_block(input, &options)?;
+        ensure_progress(input, rest)?;
+        input = rest;
+        match block {
+            StoryBlock::Title(title) => story.title = Some(unescape_in(arena, title)),
+            StoryBlock::StoryData(data) => {
+                story.start = data.start.map(|start| &*arena.alloc_str(&start));
+                story.data = Some(data.content);
+            }
+            StoryBlock::Passage(passage) => {
+                let passage = passage.map(|text| unescape_in(arena, text));
+                let passage = ArenaPassage {
+                    title: passage.title,
+                    tags: arena.alloc_slice_fill_iter(passage.tags.iter().map(|tag| tag.value)),
+                    metadata: passage.metadata.map(|metadata| metadata.content),
+                    nodes: arena.alloc_slice_fill_iter(passage.content),
+                };
+                match positions.entry(passage.title) {
+                    Entry::Occupied(entry) => passages[*entry.get()] = passage,
+                    Entry::Vacant(entry) => {
+                        entry.insert(passages.len());
+                        passages.push(passage);
+                    }
+                }
+            }
+        }
+    }
+    story.passages = passages.into_bump_slice();
+    Ok(story)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_in, Bump};
+    use crate::{ContentNode, Story};
+
+    const SAMPLE: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/sample/sample.twee"));
+
+    fn assert_matches_story(input: &str) {
+        let arena = Bump::new();
+        let arena_story = parse_in(&arena, input).unwrap();
+        let story = Story::try_from(input).unwrap();
+
+        assert_eq!(arena_story.title(), story.title());
+        assert_eq!(arena_story.data(), story.data());
+        assert_eq!(arena_story.passages().len(), story.len());
+        for (arena_passage, passage) in arena_story.passages().iter().zip(story.iter()) {
+            assert_eq!(arena_passage.title(), *passage.title());
+            assert_eq!(arena_passage.nodes(), passage.nodes());
+        }
+    }
+
+    #[test]
+    fn test_parse_in_matches_story() {
+        assert_matches_story(SAMPLE);
+        assert_matches_story("Notes\n:: A\nx\n");
+        assert_matches_story(":: A\nx\n\n:: B\nz\n\n:: A\ny\n");
+
+        let arena = Bump::new();
+        let story = parse_in(&arena, ":: A\nx\n\n:: A\ny\n").unwrap();
+        assert_eq!(story.passages().len(), 1);
+        assert_eq!(
+            story.passage("A").unwrap().nodes(),
+            [ContentNode::text_node("y")]
+        );
+    }
+
+    #[test]
+    fn test_parse_in_unescapes() {
+        let arena = Bump::new();
+        let story = parse_in(&arena, ":: A\\[1\\] [x\\]y]\n[[A\\[1\\]]]\n").unwrap();
+        let passage = story.passage("A[1]").unwrap();
+
+        assert_eq!(passage.tags(), ["x]y"]);
+        assert_eq!(passage.nodes(), [ContentNode::link_node("A[1]", "A[1]")]);
+    }
+}
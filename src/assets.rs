@@ -0,0 +1,224 @@
+//! External files and URLs referenced by the content of a story, for build pipelines to check
+//! that everything shipped with the story exists.
+
+use std::{ops::Deref, ops::Range, path::Path};
+
+use crate::{ContentNode, Story};
+
+const AUDIO_EXTENSIONS: &[&str] = &[
+    "mp3", "ogg", "oga", "wav", "m4a", "aac", "flac", "opus", "weba",
+];
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "svg", "avif", "bmp"];
+const VIDEO_EXTENSIONS: &[&str] = &["mp4", "webm", "ogv", "mov"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AssetKind {
+    Image,
+    Audio,
+    Video,
+    /// A web address that isn't a known media file.
+    Url,
+}
+
+/// A reference to an asset, see [`Story::asset_refs`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetRef<'a> {
+    pub passage: &'a str,
+    /// Index of the node in [`Passage::nodes`](crate::Passage::nodes).
+    pub node: usize,
+    /// Index of the inline in the node, when it is a paragraph, see
+    /// [`ParseOptions::paragraphs`](crate::ParseOptions::paragraphs).
+    pub inline: Option<usize>,
+    /// Byte range of the reference in the node: in its text, its raw HTML tag or its image
+    /// source.
+    pub range: Range<usize>,
+    pub kind: AssetKind,
+    pub reference: &'a str,
+}
+
+impl AssetRef<'_> {
+    pub fn is_url(&self) -> bool {
+        is_url(self.reference)
+    }
+}
+
+/// `reference` without its query string and fragment.
+fn file_path(reference: &str) -> &str {
+    reference.split(['?', '#']).next().unwrap_or(reference)
+}
+
+fn is_url(reference: &str) -> bool {
+    reference.starts_with("http://") || reference.starts_with("https://")
+}
+
+/// The kind of asset `reference` points to, if it looks like one.
+fn classify(reference: &str) -> Option<AssetKind> {
+    if reference.is_empty() || reference.starts_with("data:") || reference.contains('\n') {
+        return None;
+    }
+    let extension = file_path(reference)
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_lowercase())
+        .filter(|extension| !extension.contains('/'));
+    let has = |extensions: &[&str]| {
+        extension
+            .as_deref()
+            .is_some_and(|extension| extensions.contains(&extension))
+    };
+    if has(AUDIO_EXTENSIONS) {
+        Some(AssetKind::Audio)
+    } else if has(IMAGE_EXTENSIONS) {
+        Some(AssetKind::Image)
+    } else if has(VIDEO_EXTENSIONS) {
+        Some(AssetKind::Video)
+    } else if is_url(reference) {
+        Some(AssetKind::Url)
+    } else {
+        None
+    }
+}
+
+/// References in `text`: quoted paths to media files or URLs, as given to macros and HTML
+/// attributes, and bare URLs.
+fn scan_text(text: &str) -> Vec<(Range<usize>, AssetKind)> {
+    let mut found = vec![];
+    let mut index = 0;
+    while let Some(position) = text[index..].find(['"', '\'', 'h']) {
+        let start = index + position;
+        let rest = &text[start..];
+        if let Some(quote) = rest.strip_prefix(['"', '\'']).map(|_| &rest[..1]) {
+            let quoted = text[start + 1..]
+                .find(quote)
+                .map(|len| start + 1..start + 1 + len);
+            match quoted.and_then(|range| Some((range.clone(), classify(&text[range])?))) {
+                Some((range, kind)) => {
+                    index = range.end + 1;
+                    found.push((range, kind));
+                }
+                // Apostrophes in prose aren't quotes, what follows is still scanned.
+                None => index = start + 1,
+            }
+            continue;
+        }
+        let after_word = text[..start].ends_with(|c: char| c.is_alphanumeric());
+        if is_url(rest) && !after_word {
+            let len = rest
+                .find(|c: char| c.is_whitespace() || "\"'<>()[]".contains(c))
+                .unwrap_or(rest.len());
+            let len = rest[..len]
+                .trim_end_matches(['.', ',', ';', ':', '!', '?'])
+                .len();
+            let range = start..start + len;
+            found.extend(classify(&text[range.clone()]).map(|kind| (range, kind)));
+            index = start + len;
+        } else {
+            index = start + 1;
+        }
+    }
+    found
+}
+
+impl<T> Story<T>
+where
+    T: Deref<Target = str>,
+{
+    /// The assets referenced by the passages, in source order: image sources, quoted paths to
+    /// media files such as `<<cacheaudio "rain" "rain.ogg">>` or `src="map.png"`, and URLs.
+    /// Comments are skipped.
+    pub fn asset_refs(&self) -> Vec<AssetRef<'_>> {
+        let mut refs = vec![];
+        for passage in self.iter() {
+            let nodes = passage.content.iter().enumerate();
+            for (index, inline, node) in nodes.flat_map(|(index, node)| {
+                node.flatten_indexed()
+                    .into_iter()
+                    .map(move |(inline, node)| (index, inline, node))
+            }) {
+                let text = match node {
+                    ContentNode::Text(text) => text,
+                    ContentNode::Html(tag) => tag.raw,
+                    ContentNode::Custom(node) => node.raw,
+                    ContentNode::Raw { raw, .. } => raw,
+                    ContentNode::Image(image) => {
+                        if !image.source.starts_with("data:") {
+                            refs.push(AssetRef {
+                                passage: passage.title,
+                                node: index,
+                                inline,
+                                range: 0..image.source.len(),
+                                kind: AssetKind::Image,
+                                reference: image.source,
+                            });
+                        }
+                        continue;
+                    }
+                    ContentNode::Link { .. }
+                    | ContentNode::Comment(_)
+                    | ContentNode::Paragraph(_) => continue,
+                };
+                refs.extend(scan_text(text).into_iter().map(|(range, kind)| AssetRef {
+                    passage: passage.title,
+                    node: index,
+                    inline,
+                    reference: &text[range.clone()],
+                    range,
+                    kind,
+                }));
+            }
+        }
+        refs
+    }
+
+    /// The references to files that don't exist under `root`, ignoring query strings. URLs
+    /// aren't checked.
+    pub fn missing_assets(&self, root: impl AsRef<Path>) -> Vec<AssetRef<'_>> {
+        let root = root.as_ref();
+        self.asset_refs()
+            .into_iter()
+            .filter(|asset| !asset.is_url() && !root.join(file_path(asset.reference)).exists())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{scan_text, AssetKind};
+    use crate::{ParseOptions, Story};
+
+    #[test]
+    fn test_scan_text() {
+        let text = "It's <<cacheaudio \"rain\" 'sfx/rain.ogg'>> on https://example.com/a, don't (see: \"x\").";
+        let found: Vec<_> = scan_text(text)
+            .into_iter()
+            .map(|(range, kind)| (&text[range], kind))
+            .collect();
+
+        assert_eq!(
+            found,
+            [
+                ("sfx/rain.ogg", AssetKind::Audio),
+                ("https://example.com/a", AssetKind::Url),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_asset_refs() {
+        let input = ":: A\n[img[map.png]] <img src=\"photo.JPG?v=2\"> /* \"old.mp3\" */\n";
+        let story = Story::parse_with(input, ParseOptions::default().html(true)).unwrap();
+        let refs: Vec<_> = story
+            .asset_refs()
+            .into_iter()
+            .map(|asset| (asset.node, asset.range, asset.kind, asset.reference))
+            .collect();
+
+        assert_eq!(
+            refs,
+            [
+                (0, 0..7, AssetKind::Image, "map.png"),
+                (2, 10..23, AssetKind::Image, "photo.JPG?v=2"),
+            ]
+        );
+        assert_eq!(story.missing_assets(env!("CARGO_MANIFEST_DIR")).len(), 2);
+    }
+}
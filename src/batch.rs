@@ -0,0 +1,142 @@
+//! Parsing of many independent stories at once.
+
+use std::thread;
+
+use crate::{error::ParsingError, Story};
+
+pub type StoryResult<'a> = Result<Story<&'a str>, ParsingError<&'a str>>;
+
+/// Parse results, in the same order as the sources.
+#[derive(Debug)]
+pub struct BatchResult<'a> {
+    pub results: Vec<StoryResult<'a>>,
+}
+
+/// Totals over a whole batch.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BatchDiagnostics {
+    pub stories: usize,
+    pub failed: usize,
+    pub passages: usize,
+    pub issues: usize,
+}
+
+impl<'a> BatchResult<'a> {
+    pub fn parsed(&self) -> impl Iterator<Item = &Story<&'a str>> {
+        self.results
+            .iter()
+            .filter_map(|result| result.as_ref().ok())
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = (usize, &ParsingError<&'a str>)> {
+        self.results
+            .iter()
+            .enumerate()
+            .filter_map(|(index, result)| result.as_ref().err().map(|error| (index, error)))
+    }
+
+    pub fn diagnostics(&self) -> BatchDiagnostics {
+        let mut diagnostics = BatchDiagnostics {
+            stories: self.results.len(),
+            failed: self.failed().count(),
+            ..Default::default()
+        };
+        for story in self.parsed() {
+            diagnostics.passages += story.passages.len();
+            diagnostics.issues += story.validate().len();
+        }
+        diagnostics
+    }
+}
+
+pub fn parse_many<'a, I>(sources: I) -> BatchResult<'a>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    BatchResult {
+        results: sources.into_iter().map(Story::try_from).collect(),
+    }
+}
+
+/// Like [`parse_many`], spreading the stories over all available cores.
+pub fn parse_many_parallel<'a, I>(sources: I) -> BatchResult<'a>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let sources: Vec<_> = sources.into_iter().collect();
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = sources.len().div_ceil(threads).max(1);
+
+    let results = thread::scope(|scope| {
+        let handles: Vec<_> = sources
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|source| Story::try_from(*source))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("Parsing thread panicked"))
+            .collect()
+    });
+
+    BatchResult { results }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_many, parse_many_parallel, BatchDiagnostics};
+
+    const SAMPLE: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/sample/sample.twee"));
+
+    const BROKEN: &str = ":: Start\n[[Nowhere]]\n";
+
+    const INVALID: &str = "::\nNot a story";
+
+    #[test]
+    fn test_parse_many() {
+        let result = parse_many([SAMPLE, BROKEN, INVALID]);
+
+        assert_eq!(result.parsed().count(), 2);
+        assert_eq!(
+            result.failed().map(|(index, _)| index).collect::<Vec<_>>(),
+            vec![2]
+        );
+        assert_eq!(
+            result.diagnostics(),
+            BatchDiagnostics {
+                stories: 3,
+                failed: 1,
+                passages: 5,
+                issues: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_many_parallel_keeps_order() {
+        let sources: Vec<_> = (0..20)
+            .map(|i| if i % 3 == 0 { INVALID } else { SAMPLE })
+            .collect();
+
+        let sequential = parse_many(sources.iter().copied());
+        let parallel = parse_many_parallel(sources.iter().copied());
+
+        assert_eq!(
+            sequential
+                .failed()
+                .map(|(index, _)| index)
+                .collect::<Vec<_>>(),
+            parallel
+                .failed()
+                .map(|(index, _)| index)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(sequential.diagnostics(), parallel.diagnostics());
+    }
+}
@@ -0,0 +1,92 @@
+//! [Bevy](https://bevyengine.org) integration: load `.twee` files as [`StoryAsset`]s.
+//!
+//! ```rust,ignore
+//! app.add_plugins(TweePlugin);
+//!
+//! let handle: Handle<StoryAsset> = asset_server.load("dialogue.twee");
+//! commands.insert_resource(StoryHandle(handle));
+//! ```
+
+use std::fmt::Display;
+
+use bevy_app::{App, Plugin};
+use bevy_asset::{io::Reader, Asset, AssetApp, AssetLoader, Handle, LoadContext};
+use bevy_ecs::{component::Component, resource::Resource};
+use bevy_reflect::TypePath;
+
+use crate::{
+    encoding::{decode, DecodeError},
+    error::ParsingError,
+    Story,
+};
+
+/// Registers [`StoryAsset`] and its loader.
+pub struct TweePlugin;
+
+impl Plugin for TweePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<StoryAsset>()
+            .init_asset_loader::<StoryLoader>();
+    }
+}
+
+#[derive(Asset, TypePath, Debug)]
+pub struct StoryAsset {
+    pub story: Story<String>,
+}
+
+/// A handle to a story, usable both as a resource and as a component.
+///
+/// Systems can react to reloads with `Res<Assets<StoryAsset>>::is_changed` or by reading
+/// `AssetEvent<StoryAsset>` messages for this handle's id.
+#[derive(Resource, Component, Clone, Debug, Default, PartialEq, Eq)]
+pub struct StoryHandle(pub Handle<StoryAsset>);
+
+#[derive(Default, TypePath)]
+pub struct StoryLoader;
+
+#[derive(Debug)]
+pub enum StoryLoaderError {
+    Io(std::io::Error),
+    Encoding(DecodeError),
+    Parsing(ParsingError<String>),
+}
+
+impl Display for StoryLoaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StoryLoaderError::Io(error) => write!(f, "Could not read story: {error}"),
+            StoryLoaderError::Encoding(error) => write!(f, "Could not decode story: {error}"),
+            StoryLoaderError::Parsing(error) => write!(f, "Could not parse story: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for StoryLoaderError {}
+
+impl AssetLoader for StoryLoader {
+    type Asset = StoryAsset;
+    type Settings = ();
+    type Error = StoryLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &Self::Settings,
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(StoryLoaderError::Io)?;
+        let content = decode(bytes).map_err(StoryLoaderError::Encoding)?;
+        let story = Story::try_from(content).map_err(StoryLoaderError::Parsing)?;
+
+        Ok(StoryAsset { story })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["twee", "tw"]
+    }
+}
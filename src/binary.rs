@@ -0,0 +1,373 @@
+//! A compact binary form of parsed stories, to parse a story once at build time and load it
+//! quickly at startup.
+//!
+//! The binary starts with `TWBN` and a version byte, followed by the story encoded with
+//! postcard. Warnings and verbatim sources aren't kept.
+
+use std::{fmt::Display, ops::Deref};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ContentNode, CustomNode, Dialect, HtmlTag, Image, ImageAlign, Inline, Metadata, Passage, Story,
+    Tag, TextBlock,
+};
+
+const MAGIC: &[u8; 4] = b"TWBN";
+const VERSION: u8 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BinaryError {
+    /// The bytes don't start like a binary story.
+    NotBinary,
+    UnsupportedVersion(u8),
+    /// The story can't be decoded, with the error message.
+    Corrupted(String),
+}
+
+impl Display for BinaryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BinaryError::NotBinary => write!(f, "Not a binary story"),
+            BinaryError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported binary story version {version}")
+            }
+            BinaryError::Corrupted(error) => write!(f, "Cannot decode the story: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for BinaryError {}
+
+#[derive(Serialize, Deserialize)]
+struct StoryRecord<S> {
+    title: Option<S>,
+    start: Option<S>,
+    data: Option<S>,
+    passages: Vec<PassageRecord<S>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PassageRecord<S> {
+    title: S,
+    tags: Vec<S>,
+    metadata: Option<S>,
+    nodes: Vec<NodeRecord<S>>,
+}
+
+/// A node, its paragraphs holding `P`.
+#[derive(Serialize, Deserialize)]
+enum NodeRecord<S, P = Vec<InlineRecord<S>>> {
+    Text(S),
+    Link {
+        text: S,
+        target: S,
+        setter: Option<S>,
+    },
+    Comment(S),
+    Html {
+        raw: S,
+        name: S,
+        attributes: Vec<(S, Option<S>)>,
+        closing: bool,
+    },
+    Image {
+        source: S,
+        title: Option<S>,
+        target: Option<S>,
+        setter: Option<S>,
+        align: Option<AlignRecord>,
+    },
+    Custom {
+        kind: String,
+        raw: S,
+    },
+    Raw {
+        dialect: DialectRecord,
+        raw: S,
+    },
+    Paragraph(P),
+}
+
+/// What paragraphs inside paragraphs hold: nothing, as they don't nest.
+#[derive(Serialize, Deserialize)]
+enum NoParagraph {}
+
+#[derive(Serialize, Deserialize)]
+enum InlineRecord<S> {
+    Text(S),
+    LineBreak,
+    Node(NodeRecord<S, NoParagraph>),
+}
+
+#[derive(Serialize, Deserialize)]
+enum AlignRecord {
+    Left,
+    Right,
+}
+
+#[derive(Serialize, Deserialize)]
+enum DialectRecord {
+    SugarCube,
+    Harlowe,
+    Mustache,
+}
+
+fn node_record<'a, P>(
+    node: &ContentNode<&'a str>,
+    paragraph: impl FnOnce(&[Inline<&'a str>]) -> P,
+) -> NodeRecord<&'a str, P> {
+    match node {
+        ContentNode::Text(text) => NodeRecord::Text(text),
+        ContentNode::Link {
+            text,
+            target,
+            setter,
+        } => NodeRecord::Link {
+            text,
+            target,
+            setter: *setter,
+        },
+        ContentNode::Comment(comment) => NodeRecord::Comment(comment),
+        ContentNode::Html(tag) => NodeRecord::Html {
+            raw: tag.raw,
+            name: tag.name,
+            attributes: tag.attributes.clone(),
+            closing: tag.closing,
+        },
+        ContentNode::Image(image) => NodeRecord::Image {
+            source: image.source,
+            title: image.title,
+            target: image.target,
+            setter: image.setter,
+            align: image.align.map(|align| match align {
+                ImageAlign::Left => AlignRecord::Left,
+                ImageAlign::Right => AlignRecord::Right,
+            }),
+        },
+        ContentNode::Custom(node) => NodeRecord::Custom {
+            kind: node.kind().to_string(),
+            raw: node.raw,
+        },
+        ContentNode::Raw { dialect, raw } => NodeRecord::Raw {
+            dialect: match dialect {
+                Dialect::SugarCube => DialectRecord::SugarCube,
+                Dialect::Harlowe => DialectRecord::Harlowe,
+                Dialect::Mustache => DialectRecord::Mustache,
+            },
+            raw,
+        },
+        ContentNode::Paragraph(inlines) => NodeRecord::Paragraph(paragraph(inlines)),
+    }
+}
+
+/// The records of the pieces of a paragraph, the pieces of paragraphs nested in it included.
+fn inline_records<'a>(inlines: &[Inline<&'a str>]) -> Vec<InlineRecord<&'a str>> {
+    let mut records = vec![];
+    for inline in inlines {
+        match inline {
+            Inline::Text(text) | Inline::Node(ContentNode::Text(text)) => {
+                records.push(InlineRecord::Text(*text))
+            }
+            Inline::LineBreak => records.push(InlineRecord::LineBreak),
+            Inline::Node(ContentNode::Paragraph(inlines)) => {
+                records.extend(inline_records(inlines))
+            }
+            Inline::Node(node) => records.push(InlineRecord::Node(node_record(node, |_| {
+                unreachable!("Nested paragraphs are flattened")
+            }))),
+        }
+    }
+    records
+}
+
+impl<'a> From<&ContentNode<&'a str>> for NodeRecord<&'a str> {
+    fn from(node: &ContentNode<&'a str>) -> Self {
+        node_record(node, inline_records)
+    }
+}
+
+fn node_from_record<P>(
+    node: NodeRecord<String, P>,
+    paragraph: impl FnOnce(P) -> ContentNode<String>,
+) -> ContentNode<String> {
+    match node {
+        NodeRecord::Text(text) => ContentNode::Text(text),
+        NodeRecord::Link {
+            text,
+            target,
+            setter,
+        } => ContentNode::Link {
+            text,
+            target,
+            setter,
+        },
+        NodeRecord::Comment(comment) => ContentNode::Comment(comment),
+        NodeRecord::Html {
+            raw,
+            name,
+            attributes,
+            closing,
+        } => ContentNode::Html(HtmlTag::new(raw, name, attributes, closing)),
+        NodeRecord::Image {
+            source,
+            title,
+            target,
+            setter,
+            align,
+        } => ContentNode::Image(Image {
+            source,
+            title,
+            target,
+            setter,
+            align: align.map(|align| match align {
+                AlignRecord::Left => ImageAlign::Left,
+                AlignRecord::Right => ImageAlign::Right,
+            }),
+        }),
+        NodeRecord::Custom { kind, raw } => ContentNode::Custom(CustomNode::new(kind, raw)),
+        NodeRecord::Raw { dialect, raw } => ContentNode::Raw {
+            dialect: match dialect {
+                DialectRecord::SugarCube => Dialect::SugarCube,
+                DialectRecord::Harlowe => Dialect::Harlowe,
+                DialectRecord::Mustache => Dialect::Mustache,
+            },
+            raw,
+        },
+        NodeRecord::Paragraph(inlines) => paragraph(inlines),
+    }
+}
+
+impl From<NodeRecord<String>> for ContentNode<String> {
+    fn from(node: NodeRecord<String>) -> Self {
+        node_from_record(node, |inlines| {
+            ContentNode::Paragraph(
+                inlines
+                    .into_iter()
+                    .map(|inline| match inline {
+                        InlineRecord::Text(text) | InlineRecord::Node(NodeRecord::Text(text)) => {
+                            Inline::Text(text)
+                        }
+                        InlineRecord::LineBreak => Inline::LineBreak,
+                        InlineRecord::Node(node) => {
+                            Inline::Node(node_from_record(node, |never| match never {}))
+                        }
+                    })
+                    .collect(),
+            )
+        })
+    }
+}
+
+impl<T> Story<T>
+where
+    T: Deref<Target = str>,
+{
+    /// The parsed story in binary form, see [`Story::from_binary`].
+    pub fn to_binary(&self) -> Vec<u8> {
+        let record = StoryRecord {
+            title: self.title(),
+            start: self.start.as_ref().map(|start| start.as_str(&self.content)),
+            data: self.data(),
+            passages: self
+                .iter()
+                .map(|passage| PassageRecord {
+                    title: passage.title,
+                    tags: passage.tags.iter().map(|tag| tag.value).collect(),
+                    metadata: passage.metadata.as_ref().map(|metadata| metadata.content),
+                    nodes: passage.content.iter().map(NodeRecord::from).collect(),
+                })
+                .collect(),
+        };
+        let mut binary = MAGIC.to_vec();
+        binary.push(VERSION);
+        // Writing to a vector only fails when running out of memory.
+        postcard::to_extend(&record, binary).expect("Cannot encode the story")
+    }
+}
+
+impl Story<String> {
+    /// Loads a story written by [`Story::to_binary`], without parsing it again.
+    pub fn from_binary(bytes: &[u8]) -> Result<Self, BinaryError> {
+        let rest = bytes.strip_prefix(MAGIC).ok_or(BinaryError::NotBinary)?;
+        let [version, rest @ ..] = rest else {
+            return Err(BinaryError::NotBinary);
+        };
+        if *version != VERSION {
+            return Err(BinaryError::UnsupportedVersion(*version));
+        }
+        let record: StoryRecord<String> = postcard::from_bytes(rest)
+            .map_err(|error| BinaryError::Corrupted(error.to_string()))?;
+
+        let mut story: Story<String> = record
+            .passages
+            .into_iter()
+            .map(|passage| {
+                Passage::new(
+                    passage.title,
+                    passage.tags.into_iter().map(Tag::new).collect(),
+                    passage.metadata.map(Metadata::new),
+                    passage.nodes.into_iter().map(ContentNode::from).collect(),
+                )
+            })
+            .collect();
+        story.title = record.title.map(TextBlock::Owned);
+        story.start = record.start.map(TextBlock::Owned);
+        story.data = record.data.map(TextBlock::Owned);
+        Ok(story)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BinaryError;
+    use crate::{ParseOptions, Story};
+
+    const SAMPLE: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/sample/sample.twee"));
+
+    #[test]
+    fn test_binary_roundtrip() {
+        let input =
+            format!("{SAMPLE}\n:: Html\n<span class=\"x\" hidden>[<img[a.png][Start]]</span>\n");
+        let story = Story::parse_with(&input, ParseOptions::default().html(true)).unwrap();
+        let binary = story.to_binary();
+        let loaded = Story::from_binary(&binary).unwrap();
+
+        assert_eq!(loaded.title(), story.title());
+        assert_eq!(loaded.data(), story.data());
+        assert_eq!(loaded.start(), story.start());
+        assert_eq!(
+            loaded.iter().collect::<Vec<_>>(),
+            story.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Story::from_binary(&binary[..binary.len() / 2]),
+            Err(BinaryError::Corrupted(
+                "Hit the end of buffer, expected more data".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_binary_paragraphs() {
+        let input = ":: A\nOne [[B]]\n\nTwo\n";
+        let story = Story::parse_with(input, ParseOptions::default().paragraphs(true)).unwrap();
+        let loaded = Story::from_binary(&story.to_binary()).unwrap();
+        assert_eq!(
+            loaded.iter().collect::<Vec<_>>(),
+            story.iter().collect::<Vec<_>>()
+        );
+
+        // A passage "A" holding a paragraph in a paragraph in a paragraph.
+        let mut nested = b"TWBN\x02\0\0\0\x01\x01A\0\0\x01".to_vec();
+        nested.extend([7, 1, 2].repeat(3));
+        assert!(matches!(
+            Story::from_binary(&nested),
+            Err(BinaryError::Corrupted(_))
+        ));
+        assert_eq!(
+            Story::from_binary(b"TWBN\x01"),
+            Err(BinaryError::UnsupportedVersion(1))
+        );
+    }
+}
@@ -0,0 +1,290 @@
+//! Compare parsed stories with the output of other twee tools, such as Tweego or tweep.
+//!
+//! Reference outputs are expected to be normalized to the JSON shape produced by
+//! [`normalize`]:
+//!
+//! ```json
+//! {
+//!   "title": "Test Story",
+//!   "start": "Start",
+//!   "passages": {
+//!     "Start": { "tags": ["intro"], "metadata": { "position": "600,150" }, "links": ["First"] }
+//!   }
+//! }
+//! ```
+
+use std::{fmt::Display, ops::Deref};
+
+use serde_json::{json, Map, Value};
+
+use crate::Story;
+
+/// A difference between this crate's parse and a reference output.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Divergence {
+    ParseFailed(String),
+    Title {
+        expected: Value,
+        actual: Value,
+    },
+    Start {
+        expected: Value,
+        actual: Value,
+    },
+    MissingPassage(String),
+    ExtraPassage(String),
+    Tags {
+        passage: String,
+        expected: Value,
+        actual: Value,
+    },
+    Metadata {
+        passage: String,
+        expected: Value,
+        actual: Value,
+    },
+    Links {
+        passage: String,
+        expected: Value,
+        actual: Value,
+    },
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Divergence::ParseFailed(error) => write!(f, "parsing failed: {error}"),
+            Divergence::Title { expected, actual } => {
+                write!(f, "title: expected {expected}, got {actual}")
+            }
+            Divergence::Start { expected, actual } => {
+                write!(f, "start: expected {expected}, got {actual}")
+            }
+            Divergence::MissingPassage(name) => write!(f, "missing passage {name:?}"),
+            Divergence::ExtraPassage(name) => write!(f, "unexpected passage {name:?}"),
+            Divergence::Tags {
+                passage,
+                expected,
+                actual,
+            } => write!(f, "tags of {passage:?}: expected {expected}, got {actual}"),
+            Divergence::Metadata {
+                passage,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "metadata of {passage:?}: expected {expected}, got {actual}"
+            ),
+            Divergence::Links {
+                passage,
+                expected,
+                actual,
+            } => write!(f, "links of {passage:?}: expected {expected}, got {actual}"),
+        }
+    }
+}
+
+/// Divergences found for each story of a corpus.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CorpusReport {
+    pub stories: Vec<(String, Vec<Divergence>)>,
+}
+
+impl CorpusReport {
+    pub fn matching(&self) -> usize {
+        self.stories
+            .iter()
+            .filter(|(_, divergences)| divergences.is_empty())
+            .count()
+    }
+
+    pub fn divergence_count(&self) -> usize {
+        self.stories
+            .iter()
+            .map(|(_, divergences)| divergences.len())
+            .sum()
+    }
+}
+
+/// Converts a story to the normalized JSON shape used for comparisons.
+pub fn normalize<T>(story: &Story<T>) -> Value
+where
+    T: Deref<Target = str>,
+{
+    let passages: Map<String, Value> = story
+        .iter()
+        .map(|passage| {
+            let tags: Vec<_> = passage.tags.iter().map(|tag| tag.value).collect();
+            let metadata = passage
+                .metadata
+                .as_ref()
+                .and_then(|metadata| serde_json::from_str::<Value>(metadata.content).ok())
+                .unwrap_or(Value::Null);
+            let links: Vec<_> = passage.links().map(|link| *link.target).collect();
+
+            (
+                passage.title.to_string(),
+                json!({ "tags": tags, "metadata": metadata, "links": links }),
+            )
+        })
+        .collect();
+
+    json!({
+        "title": story.title(),
+        "start": story.start.as_ref().map(|start| start.as_str(&story.content)),
+        "passages": passages,
+    })
+}
+
+/// Compares a story with a normalized reference output.
+pub fn compare<T>(story: &Story<T>, reference: &Value) -> Vec<Divergence>
+where
+    T: Deref<Target = str>,
+{
+    let actual = normalize(story);
+    let mut divergences = vec![];
+
+    if actual["title"] != reference["title"] {
+        divergences.push(Divergence::Title {
+            expected: reference["title"].clone(),
+            actual: actual["title"].clone(),
+        });
+    }
+    if actual["start"] != reference["start"] {
+        divergences.push(Divergence::Start {
+            expected: reference["start"].clone(),
+            actual: actual["start"].clone(),
+        });
+    }
+
+    let empty = Map::new();
+    let actual_passages = actual["passages"].as_object().unwrap_or(&empty);
+    let expected_passages = reference["passages"].as_object().unwrap_or(&empty);
+
+    for (name, expected) in expected_passages {
+        let Some(actual) = actual_passages.get(name) else {
+            divergences.push(Divergence::MissingPassage(name.clone()));
+            continue;
+        };
+        if actual["tags"] != expected["tags"] {
+            divergences.push(Divergence::Tags {
+                passage: name.clone(),
+                expected: expected["tags"].clone(),
+                actual: actual["tags"].clone(),
+            });
+        }
+        if actual["metadata"] != expected["metadata"] {
+            divergences.push(Divergence::Metadata {
+                passage: name.clone(),
+                expected: expected["metadata"].clone(),
+                actual: actual["metadata"].clone(),
+            });
+        }
+        if actual["links"] != expected["links"] {
+            divergences.push(Divergence::Links {
+                passage: name.clone(),
+                expected: expected["links"].clone(),
+                actual: actual["links"].clone(),
+            });
+        }
+    }
+    for name in actual_passages.keys() {
+        if !expected_passages.contains_key(name) {
+            divergences.push(Divergence::ExtraPassage(name.clone()));
+        }
+    }
+
+    divergences
+}
+
+/// Parses and compares every `(name, twee source, normalized reference)` entry of a corpus.
+pub fn compare_corpus<'a, I>(corpus: I) -> CorpusReport
+where
+    I: IntoIterator<Item = (&'a str, &'a str, &'a Value)>,
+{
+    let stories = corpus
+        .into_iter()
+        .map(|(name, source, reference)| {
+            let divergences = match Story::try_from(source) {
+                Ok(story) => compare(&story, reference),
+                Err(error) => vec![Divergence::ParseFailed(error.to_string())],
+            };
+            (name.to_string(), divergences)
+        })
+        .collect();
+
+    CorpusReport { stories }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{compare, compare_corpus, normalize, Divergence};
+    use crate::Story;
+
+    const SAMPLE: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/sample/sample.twee"));
+
+    #[test]
+    fn test_normalize() {
+        let story = Story::try_from(SAMPLE).unwrap();
+        let normalized = normalize(&story);
+
+        assert_eq!(normalized["title"], json!("Test Story"));
+        assert_eq!(normalized["start"], json!("Start"));
+        assert_eq!(
+            normalized["passages"]["Second"],
+            json!({
+                "tags": ["name-test"],
+                "metadata": { "position": "700,400", "size": "200,200" },
+                "links": ["First"]
+            })
+        );
+    }
+
+    #[test]
+    fn test_compare_with_itself() {
+        let story = Story::try_from(SAMPLE).unwrap();
+
+        assert_eq!(compare(&story, &normalize(&story)), vec![]);
+    }
+
+    #[test]
+    fn test_compare_divergences() {
+        let story = Story::try_from(SAMPLE).unwrap();
+        let mut reference = normalize(&story);
+        reference["passages"]["Second"]["tags"] = json!([]);
+        reference["passages"]
+            .as_object_mut()
+            .unwrap()
+            .remove("Third");
+
+        assert_eq!(
+            compare(&story, &reference),
+            vec![
+                Divergence::Tags {
+                    passage: "Second".to_string(),
+                    expected: json!([]),
+                    actual: json!(["name-test"])
+                },
+                Divergence::ExtraPassage("Third".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compare_corpus() {
+        let story = Story::try_from(SAMPLE).unwrap();
+        let reference = normalize(&story);
+        let wrong_title =
+            json!({ "title": "Other", "start": "Start", "passages": reference["passages"] });
+
+        let report = compare_corpus([
+            ("sample", SAMPLE, &reference),
+            ("wrong title", SAMPLE, &wrong_title),
+        ]);
+
+        assert_eq!(report.matching(), 1);
+        assert_eq!(report.divergence_count(), 1);
+    }
+}
@@ -0,0 +1,221 @@
+//! A conformance harness comparing parsed stories with snapshots of the expected result.
+//!
+//! It runs cases made from the examples of the twee 3 specification, see [`spec_cases`], and
+//! corpora of real stories, see [`corpus_cases`]. Forks and code using other
+//! [`ParseOptions`] can run it on their own corpus:
+//!
+//! ```no_run
+//! use twee_v3::{conformance, ParseOptions};
+//!
+//! let options = ParseOptions::default().html(true);
+//! let cases = conformance::corpus_cases("stories").unwrap();
+//! // Records the current results the first time, when snapshots are missing.
+//! conformance::update_snapshots(&cases, &options, false).unwrap();
+//! let mismatches = conformance::run(&cases, &options);
+//! assert!(mismatches.is_empty(), "{mismatches:#?}");
+//! ```
+
+use std::{
+    fmt::Write,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{ParseOptions, Story};
+
+/// Extension of snapshot files, next to the stories they describe.
+pub const SNAPSHOT_EXTENSION: &str = "snap";
+
+/// A story to parse, with the snapshot of the expected result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Case {
+    pub name: String,
+    pub input: String,
+    /// `None` when the snapshot hasn't been recorded yet.
+    pub expected: Option<String>,
+    /// Where the snapshot is stored, for cases read from files.
+    pub snapshot_path: Option<PathBuf>,
+}
+
+/// A case whose result differs from its snapshot, see [`run`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub case: String,
+    pub expected: Option<String>,
+    pub actual: String,
+}
+
+macro_rules! spec_case {
+    ($name:literal) => {
+        (
+            $name,
+            include_str!(concat!("../conformance/spec/", $name, ".twee")),
+            include_str!(concat!("../conformance/spec/", $name, ".snap")),
+        )
+    };
+}
+
+/// The cases made from the examples of the twee 3 specification, expected to parse the same
+/// with the default options.
+pub fn spec_cases() -> Vec<Case> {
+    [
+        spec_case!("story-title"),
+        spec_case!("story-data"),
+        spec_case!("passage-header"),
+        spec_case!("escapes"),
+        spec_case!("escaping-table"),
+        spec_case!("special-tags"),
+    ]
+    .into_iter()
+    .map(|(name, input, expected)| Case {
+        name: name.to_string(),
+        input: input.to_string(),
+        expected: Some(expected.to_string()),
+        snapshot_path: None,
+    })
+    .collect()
+}
+
+/// The `.twee` stories of `dir`, sorted by name, each with the snapshot of the same name if
+/// there is one.
+pub fn corpus_cases(dir: impl AsRef<Path>) -> io::Result<Vec<Case>> {
+    let mut paths = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "twee")
+        {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|path| {
+            let snapshot_path = path.with_extension(SNAPSHOT_EXTENSION);
+            let expected = match fs::read_to_string(&snapshot_path) {
+                Ok(snapshot) => Some(snapshot),
+                Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+                Err(error) => return Err(error),
+            };
+            Ok(Case {
+                name: path.display().to_string(),
+                input: fs::read_to_string(&path)?,
+                expected,
+                snapshot_path: Some(snapshot_path),
+            })
+        })
+        .collect()
+}
+
+/// A description of how `input` parses with `options`, stable across releases unless parsing
+/// changes: the story information, warnings, and every passage with its nodes.
+pub fn snapshot(input: &str, options: &ParseOptions) -> String {
+    let story = match Story::parse_with(input, options.clone()) {
+        Ok(story) => story,
+        Err(error) => return format!("error: {error}\n"),
+    };
+    let mut output = String::new();
+    let _ = writeln!(output, "title: {:?}", story.title());
+    let start = story
+        .start
+        .as_ref()
+        .map(|start| start.as_str(story.content));
+    let _ = writeln!(output, "start: {start:?}");
+    let _ = writeln!(output, "data: {:?}", story.data());
+    for warning in story.warnings() {
+        let _ = writeln!(output, "warning: {warning:?}");
+    }
+    for passage in story.iter() {
+        let tags: Vec<_> = passage.tags.iter().map(|tag| tag.value).collect();
+        let metadata = passage.metadata.as_ref().map(|metadata| metadata.content);
+        let _ = writeln!(output, "\npassage: {:?}", passage.title);
+        let _ = writeln!(output, "  tags: {tags:?}");
+        let _ = writeln!(output, "  metadata: {metadata:?}");
+        for node in &passage.content {
+            let _ = writeln!(output, "  {node:?}");
+        }
+    }
+    output
+}
+
+/// Parses every case with `options`, returning those that don't match their snapshot,
+/// including those without one.
+pub fn run(cases: &[Case], options: &ParseOptions) -> Vec<Mismatch> {
+    cases
+        .iter()
+        .filter_map(|case| {
+            let actual = snapshot(&case.input, options);
+            (case.expected.as_ref() != Some(&actual)).then(|| Mismatch {
+                case: case.name.clone(),
+                expected: case.expected.clone(),
+                actual,
+            })
+        })
+        .collect()
+}
+
+/// Parses every case with `options`, writes it back with [`Story::to_twee`] and parses the
+/// result, returning those whose snapshot changes. The snapshot of the input is given as
+/// expected. Cases that don't parse are skipped.
+pub fn run_roundtrip(cases: &[Case], options: &ParseOptions) -> Vec<Mismatch> {
+    cases
+        .iter()
+        .filter_map(|case| {
+            let story = Story::parse_with(&case.input, options.clone()).ok()?;
+            let expected = snapshot(&case.input, options);
+            let actual = snapshot(&story.to_twee(), options);
+            (expected != actual).then(|| Mismatch {
+                case: case.name.clone(),
+                expected: Some(expected),
+                actual,
+            })
+        })
+        .collect()
+}
+
+/// Records the snapshots of the cases read from files, with `options`. Only the missing ones
+/// are written, unless `overwrite` is set.
+pub fn update_snapshots(cases: &[Case], options: &ParseOptions, overwrite: bool) -> io::Result<()> {
+    for case in cases {
+        let Some(path) = &case.snapshot_path else {
+            continue;
+        };
+        if overwrite || case.expected.is_none() {
+            fs::write(path, snapshot(&case.input, options))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{corpus_cases, run, run_roundtrip, snapshot, spec_cases};
+    use crate::ParseOptions;
+
+    #[test]
+    fn test_spec_conformance() {
+        let mismatches = run(&spec_cases(), &ParseOptions::default());
+        assert!(mismatches.is_empty(), "{mismatches:#?}");
+
+        let mismatches = run_roundtrip(&spec_cases(), &ParseOptions::default());
+        assert!(mismatches.is_empty(), "{mismatches:#?}");
+    }
+
+    #[test]
+    fn test_corpus_conformance() {
+        let cases = corpus_cases(concat!(env!("CARGO_MANIFEST_DIR"), "/sample")).unwrap();
+        let mismatches = run(&cases, &ParseOptions::default());
+
+        assert_eq!(cases.len(), 3);
+        assert!(mismatches.is_empty(), "{mismatches:#?}");
+    }
+
+    #[test]
+    fn test_snapshot_error() {
+        let options = ParseOptions::default().max_passages(1);
+
+        assert!(snapshot(":: A\na\n\n:: B\nb\n", &options).starts_with("error: "));
+    }
+}
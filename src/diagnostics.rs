@@ -0,0 +1,202 @@
+use std::fmt::Display;
+
+/// A location in the source of a story. Lines and columns start at 1, columns count chars.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Moves `offset` back to the end of `source`, or to the start of the char it falls in.
+fn floor_char_boundary(source: &str, offset: usize) -> usize {
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+impl Position {
+    /// The position at `offset`. An offset past the end or inside a char is moved back to the
+    /// end or to the start of the char.
+    pub fn from_offset(source: &str, offset: usize) -> Self {
+        let offset = floor_char_boundary(source, offset);
+        let before = &source[..offset];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+        let column = before[line_start..].chars().count() + 1;
+        Self {
+            offset,
+            line,
+            column,
+        }
+    }
+}
+
+/// Finds the positions of increasing offsets in a source, reading it once instead of from the
+/// start for each position.
+pub(crate) struct PositionTracker<'a> {
+    source: &'a str,
+    last: Position,
+}
+
+impl<'a> PositionTracker<'a> {
+    pub(crate) fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            last: Position::from_offset(source, 0),
+        }
+    }
+
+    pub(crate) fn position(&mut self, offset: usize) -> Position {
+        let offset = floor_char_boundary(self.source, offset);
+        if offset < self.last.offset {
+            return Position::from_offset(self.source, offset);
+        }
+        let between = &self.source[self.last.offset..offset];
+        let Position { line, column, .. } = self.last;
+        let (line, column) = match between.rfind('\n') {
+            Some(index) => (
+                line + between.matches('\n').count(),
+                between[index + 1..].chars().count() + 1,
+            ),
+            None => (line, column + between.chars().count()),
+        };
+        self.last = Position {
+            offset,
+            line,
+            column,
+        };
+        self.last
+    }
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// A problem found while parsing, that didn't prevent the story from being parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// Two passages share a name. The last one is kept.
+    DuplicatePassage {
+        name: String,
+        first: Position,
+        second: Position,
+    },
+    /// StoryData contains a field that isn't part of the twee 3 specification.
+    UnknownStoryDataField(String),
+    /// The passage has no content.
+    EmptyPassage(String),
+    /// Twine uses tags starting with a dash for special purposes, which is likely a typo.
+    TagWithLeadingDash {
+        passage: String,
+        tag: String,
+    },
+    LinkToSelf(String),
+    /// The metadata of the passage isn't valid JSON.
+    InvalidMetadata(String),
+    /// Text before the first passage header, which is skipped, starting at this position.
+    Preamble(Position),
+    /// The deadline of the options passed before this position, where parsing stopped.
+    DeadlineExceeded(Position),
+}
+
+impl Warning {
+    /// The passage the warning is about, if any.
+    pub fn passage(&self) -> Option<&str> {
+        match self {
+            Warning::DuplicatePassage { name, .. } => Some(name),
+            Warning::UnknownStoryDataField(_)
+            | Warning::Preamble(_)
+            | Warning::DeadlineExceeded(_) => None,
+            Warning::EmptyPassage(passage)
+            | Warning::TagWithLeadingDash { passage, .. }
+            | Warning::LinkToSelf(passage)
+            | Warning::InvalidMetadata(passage) => Some(passage),
+        }
+    }
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Warning::DuplicatePassage {
+                name,
+                first,
+                second,
+            } => write!(
+                f,
+                "Passage {name:?} at {second} replaces the passage with the same name at {first}"
+            ),
+            Warning::UnknownStoryDataField(field) => {
+                write!(f, "Unknown StoryData field {field:?}")
+            }
+            Warning::EmptyPassage(name) => write!(f, "Passage {name:?} is empty"),
+            Warning::TagWithLeadingDash { passage, tag } => {
+                write!(f, "Tag {tag:?} of passage {passage:?} starts with a dash")
+            }
+            Warning::LinkToSelf(name) => write!(f, "Passage {name:?} links to itself"),
+            Warning::InvalidMetadata(name) => {
+                write!(f, "Metadata of passage {name:?} is not valid JSON")
+            }
+            Warning::Preamble(position) => {
+                write!(
+                    f,
+                    "Skipped text before the first passage header at {position}"
+                )
+            }
+            Warning::DeadlineExceeded(position) => {
+                write!(f, "Parsing stopped at {position}, past the deadline")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Position, PositionTracker};
+
+    #[test]
+    fn test_position_from_offset() {
+        let source = "ab\nçd\nef";
+
+        assert_eq!(
+            Position::from_offset(source, 0),
+            Position {
+                offset: 0,
+                line: 1,
+                column: 1
+            }
+        );
+        assert_eq!(
+            Position::from_offset(source, 6),
+            Position {
+                offset: 6,
+                line: 2,
+                column: 3
+            }
+        );
+        assert_eq!(
+            Position::from_offset(source, 4),
+            Position::from_offset(source, 3)
+        );
+        assert_eq!(Position::from_offset(source, 99).offset, source.len());
+    }
+
+    #[test]
+    fn test_position_tracker() {
+        let source = "ab\nçd\nef\n\ngh";
+        let mut tracker = PositionTracker::new(source);
+
+        for offset in [1, 3, 4, 6, 6, 9, 12, 99, 5] {
+            assert_eq!(
+                tracker.position(offset),
+                Position::from_offset(source, offset)
+            );
+        }
+    }
+}
@@ -0,0 +1,169 @@
+//! Lines of dialogue and their speakers, following a convention such as `Ada: Hello!` or a
+//! `speaker:Ada` tag on the passage.
+//!
+//! Lines are read over the content nodes of a passage, so links can appear in them.
+
+use crate::{ContentNode, Passage};
+
+/// How the speaker of a line is written, see [`Passage::dialogue`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpeakerConvention {
+    /// Read lines written `Speaker: text`.
+    pub colon: bool,
+    /// Most words in a speaker name before a colon, so that sentences with a colon aren't read
+    /// as speakers.
+    pub max_name_words: usize,
+    /// Tags starting with this prefix, such as `speaker:Ada`, name the speaker of the lines
+    /// without one.
+    pub tag_prefix: Option<String>,
+}
+
+impl Default for SpeakerConvention {
+    fn default() -> Self {
+        Self {
+            colon: true,
+            max_name_words: 3,
+            tag_prefix: None,
+        }
+    }
+}
+
+impl SpeakerConvention {
+    pub fn colon(mut self, colon: bool) -> Self {
+        self.colon = colon;
+        self
+    }
+
+    pub fn max_name_words(mut self, max_name_words: usize) -> Self {
+        self.max_name_words = max_name_words;
+        self
+    }
+
+    pub fn tag_prefix(mut self, tag_prefix: impl Into<String>) -> Self {
+        self.tag_prefix = Some(tag_prefix.into());
+        self
+    }
+
+    /// Splits `Speaker: text` into the speaker and the text.
+    fn split_speaker<'a>(&self, line: &'a str) -> Option<(&'a str, &'a str)> {
+        if !self.colon {
+            return None;
+        }
+        let (name, text) = line.split_once(':')?;
+        let name = name.trim();
+        let words = name.split_whitespace().count();
+        let valid = (1..=self.max_name_words).contains(&words)
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || " '-._".contains(c))
+            && (text.is_empty() || text.starts_with(char::is_whitespace));
+        valid.then(|| (name, text.trim_start()))
+    }
+}
+
+/// A line of a passage, with its speaker if it has one.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DialogueLine<'a> {
+    pub speaker: Option<&'a str>,
+    /// The content of the line, without the speaker.
+    pub nodes: Vec<ContentNode<&'a str>>,
+}
+
+impl DialogueLine<'_> {
+    /// The text of the line, as shown to players.
+    pub fn text(&self) -> String {
+        self.nodes.iter().map(ToString::to_string).collect()
+    }
+}
+
+impl<'a> Passage<&'a str> {
+    /// Reads the passage as lines of dialogue, following `convention`. Blank lines and lines
+    /// holding only comments are skipped.
+    pub fn dialogue(&self, convention: &SpeakerConvention) -> Vec<DialogueLine<'a>> {
+        let tag_speaker = convention.tag_prefix.as_ref().and_then(|prefix| {
+            self.tags
+                .iter()
+                .find_map(|tag| tag.value.strip_prefix(prefix.as_str()))
+        });
+        let mut lines = vec![];
+        let mut current = DialogueLine::default();
+        let mut finish = |mut line: DialogueLine<'a>| {
+            let blank = line.nodes.iter().all(|node| match node {
+                ContentNode::Text(text) => text.trim().is_empty(),
+                ContentNode::Comment(_) => true,
+                _ => false,
+            });
+            if !blank {
+                line.speaker = line.speaker.or(tag_speaker);
+                lines.push(line);
+            }
+        };
+        for node in self.content.iter().flat_map(ContentNode::flatten) {
+            let ContentNode::Text(text) = node else {
+                current.nodes.push(node);
+                continue;
+            };
+            for (index, mut part) in text.split('\n').enumerate() {
+                if index > 0 {
+                    finish(std::mem::take(&mut current));
+                }
+                if current.nodes.is_empty() {
+                    if let Some((speaker, text)) = convention.split_speaker(part) {
+                        current.speaker = Some(speaker);
+                        part = text;
+                    }
+                }
+                if !part.is_empty() {
+                    current.nodes.push(ContentNode::Text(part));
+                }
+            }
+        }
+        finish(current);
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SpeakerConvention;
+    use crate::{ParseOptions, Story};
+
+    #[test]
+    fn test_dialogue() {
+        let input = ":: A [speaker:Narrator]\nAda: Where does it go?\nOld Bob: To [[the sea|B]].\n\nThe wind rises.\nNote to self: it doesn't\n";
+        let story = Story::try_from(input).unwrap();
+        let passage = story.get_passage("A").unwrap();
+        let convention = SpeakerConvention::default()
+            .max_name_words(2)
+            .tag_prefix("speaker:");
+        let lines: Vec<_> = passage
+            .dialogue(&convention)
+            .iter()
+            .map(|line| (line.speaker, line.text()))
+            .collect();
+
+        assert_eq!(
+            lines,
+            [
+                (Some("Ada"), "Where does it go?".to_string()),
+                (Some("Old Bob"), "To the sea.".to_string()),
+                (Some("Narrator"), "The wind rises.".to_string()),
+                (Some("Narrator"), "Note to self: it doesn't".to_string()),
+            ]
+        );
+        assert_eq!(
+            passage.dialogue(&SpeakerConvention::default().colon(false))[0].speaker,
+            None
+        );
+
+        let story = Story::parse_with(input, ParseOptions::default().paragraphs(true)).unwrap();
+        let paragraph_lines: Vec<_> = story
+            .get_passage("A")
+            .unwrap()
+            .dialogue(&convention)
+            .iter()
+            .map(|line| (line.speaker, line.text()))
+            .collect();
+        assert_eq!(paragraph_lines, lines);
+    }
+}
@@ -0,0 +1,628 @@
+//! Editing operations on stories, as performed by editor frontends.
+//!
+//! [`Story::edit`] groups edits in a transaction, recording the edits undoing them:
+//!
+//! ```
+//! use twee_v3::Story;
+//!
+//! let mut story = Story::try_from(":: A\nGo to [[B]]\n\n:: B\nEnd\n").unwrap();
+//! let changes = story
+//!     .edit(|tx| {
+//!         tx.rename("B", "End")?;
+//!         tx.set_tags("End", ["final"])
+//!     })
+//!     .unwrap();
+//! assert!(story.backlinks("End").eq(["A"]));
+//!
+//! story.apply_edits(changes.undo).unwrap();
+//! assert!(story.backlinks("B").eq(["A"]));
+//! ```
+
+use std::{fmt::Display, ops::Deref};
+
+use serde_json::Value;
+
+use crate::{ContentNode, Inline, Passage, SourceBlock, Story, Tag, TextBlock, UnknownPassage};
+
+/// What [`Story::remove_passage`] does with the links to the removed passage, those of images
+/// included.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkPolicy {
+    /// Turns the links into their text, and images into plain images.
+    Unlink,
+    /// Points the links to another passage, renaming simple links like `[[B]]`.
+    Retarget(String),
+    /// Leaves the links broken.
+    Keep,
+}
+
+/// A passage taken out of a story by [`Story::remove_passage`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Removal {
+    pub passage: Passage<String>,
+    /// The passages that linked to it, in source order.
+    pub linking: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditError {
+    UnknownPassage(String),
+    /// A passage of this name already exists.
+    DuplicatePassage(String),
+}
+
+impl Display for EditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EditError::UnknownPassage(title) => write!(f, "No passage named {title:?}"),
+            EditError::DuplicatePassage(title) => {
+                write!(f, "A passage named {title:?} already exists")
+            }
+        }
+    }
+}
+
+impl std::error::Error for EditError {}
+
+impl From<UnknownPassage> for EditError {
+    fn from(error: UnknownPassage) -> Self {
+        EditError::UnknownPassage(error.0)
+    }
+}
+
+/// A change made to a story in a [`Transaction`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Edit {
+    /// Renames a passage, keeping its position, and points the links to it to its new name
+    /// when `relink` is set. The start passage of StoryData follows the rename.
+    Rename {
+        from: String,
+        to: String,
+        relink: bool,
+    },
+    SetTags {
+        passage: String,
+        tags: Vec<String>,
+    },
+    SetContent {
+        passage: String,
+        content: Vec<ContentNode<String>>,
+    },
+    /// Adds a passage at `position` in the source order, or last.
+    Insert {
+        passage: Passage<String>,
+        position: usize,
+        /// Where the passage goes among the blocks written back by a verbatim story, see
+        /// [`ParseOptions::verbatim`](crate::ParseOptions::verbatim), or after them.
+        block: Option<usize>,
+    },
+    /// Removes a passage, leaving the links to it.
+    Remove {
+        passage: String,
+    },
+}
+
+impl Display for Edit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Edit::Rename { from, to, .. } => write!(f, "Renamed {from:?} to {to:?}"),
+            Edit::SetTags { passage, tags } => write!(f, "Tagged {passage:?} with {tags:?}"),
+            Edit::SetContent { passage, .. } => write!(f, "Changed the text of {passage:?}"),
+            Edit::Insert { passage, .. } => write!(f, "Added {:?}", passage.title),
+            Edit::Remove { passage } => write!(f, "Removed {passage:?}"),
+        }
+    }
+}
+
+/// The edits made by a transaction, see [`Story::edit`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Changes {
+    pub edits: Vec<Edit>,
+    /// The edits restoring the story, to give to [`Story::apply_edits`].
+    pub undo: Vec<Edit>,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+}
+
+/// A summary of the edits, one per line.
+impl Display for Changes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for edit in &self.edits {
+            writeln!(f, "{edit}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Edits made to a story as a whole, see [`Story::edit`].
+pub struct Transaction<'a, T>
+where
+    T: Deref<Target = str>,
+{
+    story: &'a mut Story<T>,
+    changes: Changes,
+}
+
+impl<T> Transaction<'_, T>
+where
+    T: Deref<Target = str>,
+{
+    /// The story, with the edits made so far.
+    pub fn story(&self) -> &Story<T> {
+        self.story
+    }
+
+    pub fn apply(&mut self, edit: Edit) -> Result<(), EditError> {
+        let mut undo = self.story.apply_edit(edit.clone())?;
+        // The last edit is undone first.
+        undo.append(&mut self.changes.undo);
+        self.changes.undo = undo;
+        self.changes.edits.push(edit);
+        Ok(())
+    }
+
+    /// Renames a passage and the links to it.
+    pub fn rename(&mut self, from: &str, to: &str) -> Result<(), EditError> {
+        self.apply(Edit::Rename {
+            from: from.to_string(),
+            to: to.to_string(),
+            relink: true,
+        })
+    }
+
+    pub fn set_tags<I, S>(&mut self, passage: &str, tags: I) -> Result<(), EditError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.apply(Edit::SetTags {
+            passage: passage.to_string(),
+            tags: tags.into_iter().map(Into::into).collect(),
+        })
+    }
+
+    pub fn set_content(
+        &mut self,
+        passage: &str,
+        content: Vec<ContentNode<String>>,
+    ) -> Result<(), EditError> {
+        self.apply(Edit::SetContent {
+            passage: passage.to_string(),
+            content,
+        })
+    }
+
+    /// Adds a passage after the others.
+    pub fn insert(&mut self, passage: Passage<String>) -> Result<(), EditError> {
+        let position = self.story.len();
+        self.apply(Edit::Insert {
+            passage,
+            position,
+            block: None,
+        })
+    }
+
+    pub fn remove(&mut self, passage: &str) -> Result<(), EditError> {
+        self.apply(Edit::Remove {
+            passage: passage.to_string(),
+        })
+    }
+}
+
+/// Whether `node` links to `title`, images included.
+fn links_to(node: &ContentNode<TextBlock>, content: &str, title: &str) -> bool {
+    node.leaves().any(|node| match node {
+        ContentNode::Link { target, .. } => target.as_str(content) == title,
+        ContentNode::Image(image) => image
+            .target
+            .as_ref()
+            .is_some_and(|target| target.as_str(content) == title),
+        _ => false,
+    })
+}
+
+/// Handles a link to `title` in `node` as `policy` says.
+fn relink_node(node: &mut ContentNode<TextBlock>, content: &str, title: &str, policy: &LinkPolicy) {
+    if !links_to(node, content, title) {
+        return;
+    }
+    if let ContentNode::Paragraph(inlines) = node {
+        for inline in inlines {
+            let Inline::Node(inner) = inline else {
+                continue;
+            };
+            relink_node(inner, content, title, policy);
+            // Paragraphs hold their text as inlines.
+            if let ContentNode::Text(text) = inner {
+                *inline = Inline::Text(text.clone());
+            }
+        }
+        return;
+    }
+    match (node, policy) {
+        (node @ ContentNode::Link { .. }, LinkPolicy::Unlink) => {
+            if let ContentNode::Link { text, .. } = node {
+                *node = ContentNode::Text(text.clone());
+            }
+        }
+        (ContentNode::Link { text, target, .. }, LinkPolicy::Retarget(new_title)) => {
+            // Simple links, `[[B]]`, show their target.
+            if text.as_str(content) == title {
+                *text = TextBlock::Owned(new_title.clone());
+            }
+            *target = TextBlock::Owned(new_title.clone());
+        }
+        (ContentNode::Image(image), LinkPolicy::Unlink) => {
+            image.target = None;
+            image.setter = None;
+        }
+        (ContentNode::Image(image), LinkPolicy::Retarget(new_title)) => {
+            image.target = Some(TextBlock::Owned(new_title.clone()));
+        }
+        _ => {}
+    }
+}
+
+impl<T> Story<T>
+where
+    T: Deref<Target = str>,
+{
+    /// Removes the passage named `title`, handling the links to it as `policy` says. Retargeting
+    /// fails when the new target isn't in the story.
+    ///
+    /// StoryData keeps naming the passage if it was the start one, see [`Story::set_start`].
+    pub fn remove_passage(
+        &mut self,
+        title: &str,
+        policy: LinkPolicy,
+    ) -> Result<Removal, UnknownPassage> {
+        if let LinkPolicy::Retarget(target) = &policy {
+            if target == title || !self.contains_passage(target) {
+                return Err(UnknownPassage(target.clone()));
+            }
+        }
+        let Some(removed) = self.passages.remove(title) else {
+            return Err(UnknownPassage(title.to_string()));
+        };
+        let passage = removed.as_borrowed(&self.content).into_owned();
+        if let Some(source_blocks) = &mut self.source_blocks {
+            source_blocks
+                .retain(|block| !matches!(block, SourceBlock::Passage(name) if name == title));
+        }
+
+        let linking = self.linking_passages(title);
+        if policy != LinkPolicy::Keep {
+            let content = &self.content;
+            for name in &linking {
+                let Some(other) = self.passages.get_mut(name) else {
+                    continue;
+                };
+                for node in &mut other.content {
+                    relink_node(node, content, title, &policy);
+                }
+                other.source = None;
+            }
+        }
+        self.invalidate_derived();
+
+        Ok(Removal { passage, linking })
+    }
+
+    /// Names of the passages linking to `title`, in source order.
+    fn linking_passages(&self, title: &str) -> Vec<String> {
+        self.passages
+            .iter()
+            .filter(|(_, passage)| {
+                passage
+                    .content
+                    .iter()
+                    .any(|node| links_to(node, &self.content, title))
+            })
+            .map(|(name, _)| name.to_string())
+            .collect()
+    }
+
+    /// Makes the edits of `f` in a transaction: when `f` fails, the edits it made are undone
+    /// and its error returned.
+    pub fn edit<F>(&mut self, f: F) -> Result<Changes, EditError>
+    where
+        F: FnOnce(&mut Transaction<'_, T>) -> Result<(), EditError>,
+    {
+        let mut transaction = Transaction {
+            story: self,
+            changes: Changes::default(),
+        };
+        match f(&mut transaction) {
+            Ok(()) => Ok(transaction.changes),
+            Err(error) => {
+                for edit in transaction.changes.undo {
+                    let _ = transaction.story.apply_edit(edit);
+                }
+                Err(error)
+            }
+        }
+    }
+
+    /// Makes `edits` in order in a transaction, such as the [`Changes::undo`] of another one.
+    pub fn apply_edits(
+        &mut self,
+        edits: impl IntoIterator<Item = Edit>,
+    ) -> Result<Changes, EditError> {
+        self.edit(|transaction| {
+            edits
+                .into_iter()
+                .try_for_each(|edit| transaction.apply(edit))
+        })
+    }
+
+    /// Makes `edit`, returning the edits undoing it.
+    fn apply_edit(&mut self, edit: Edit) -> Result<Vec<Edit>, EditError> {
+        let content = &self.content;
+        let undo = match edit {
+            Edit::Rename { from, to, relink } => {
+                if !self.passages.contains_key(&from) {
+                    return Err(EditError::UnknownPassage(from));
+                }
+                if from == to {
+                    return Ok(vec![]);
+                }
+                if !self.passages.rename(&from, to.clone()) {
+                    return Err(EditError::DuplicatePassage(to));
+                }
+                if let Some(passage) = self.passages.get_mut(&to) {
+                    passage.title = TextBlock::Owned(to.clone());
+                    passage.source = None;
+                }
+                for block in self.source_blocks.iter_mut().flatten() {
+                    match block {
+                        SourceBlock::Passage(name) if *name == from => *name = to.clone(),
+                        _ => {}
+                    }
+                }
+
+                let mut undo = vec![];
+                let linking = match relink {
+                    true => self.linking_passages(&from),
+                    false => vec![],
+                };
+                let policy = LinkPolicy::Retarget(to.clone());
+                for name in linking {
+                    let Some(passage) = self.passages.get_mut(&name) else {
+                        continue;
+                    };
+                    let previous = passage
+                        .content
+                        .iter()
+                        .map(|node| node.as_borrowed(content).into_owned())
+                        .collect();
+                    for node in &mut passage.content {
+                        relink_node(node, content, &from, &policy);
+                    }
+                    passage.source = None;
+                    undo.push(Edit::SetContent {
+                        passage: name,
+                        content: previous,
+                    });
+                }
+                // Undone by the rename back.
+                if self.start_title() == Some(from.as_str()) {
+                    self.set_data_field("start", Value::String(to.clone()));
+                }
+                undo.push(Edit::Rename {
+                    from: to,
+                    to: from,
+                    relink: false,
+                });
+                undo
+            }
+            Edit::SetTags {
+                passage: name,
+                tags,
+            } => {
+                let passage = self
+                    .passages
+                    .get_mut(&name)
+                    .ok_or_else(|| EditError::UnknownPassage(name.clone()))?;
+                let previous = passage
+                    .tags
+                    .iter()
+                    .map(|tag| tag.value.as_str(content).to_string())
+                    .collect();
+                passage.tags = tags
+                    .into_iter()
+                    .map(|tag| Tag::new(TextBlock::Owned(tag)))
+                    .collect();
+                passage.source = None;
+                vec![Edit::SetTags {
+                    passage: name,
+                    tags: previous,
+                }]
+            }
+            Edit::SetContent {
+                passage: name,
+                content: nodes,
+            } => {
+                let passage = self
+                    .passages
+                    .get_mut(&name)
+                    .ok_or_else(|| EditError::UnknownPassage(name.clone()))?;
+                let previous = passage
+                    .content
+                    .iter()
+                    .map(|node| node.as_borrowed(content).into_owned())
+                    .collect();
+                passage.content = nodes.into_iter().map(ContentNode::into_blocks).collect();
+                passage.source = None;
+                vec![Edit::SetContent {
+                    passage: name,
+                    content: previous,
+                }]
+            }
+            Edit::Insert {
+                passage,
+                position,
+                block,
+            } => {
+                let name = passage.title.clone();
+                if !self
+                    .passages
+                    .insert_at(position, name.clone(), passage.into_blocks())
+                {
+                    return Err(EditError::DuplicatePassage(name));
+                }
+                if let (Some(source_blocks), Some(block)) = (&mut self.source_blocks, block) {
+                    let block = block.min(source_blocks.len());
+                    source_blocks.insert(block, SourceBlock::Passage(name.clone()));
+                }
+                vec![Edit::Remove { passage: name }]
+            }
+            Edit::Remove { passage: name } => {
+                let position = self
+                    .passages
+                    .position(&name)
+                    .ok_or_else(|| EditError::UnknownPassage(name.clone()))?;
+                let block = self.source_blocks.as_ref().and_then(|source_blocks| {
+                    source_blocks.iter().position(
+                        |block| matches!(block, SourceBlock::Passage(other) if *other == name),
+                    )
+                });
+                let removal = self.remove_passage(&name, LinkPolicy::Keep)?;
+                vec![Edit::Insert {
+                    passage: removal.passage,
+                    position,
+                    block,
+                }]
+            }
+        };
+        self.invalidate_derived();
+        Ok(undo)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Edit, EditError, LinkPolicy};
+    use crate::{ContentNode, ParseOptions, Passage, Story, UnknownPassage};
+
+    const INPUT: &str =
+        ":: A\nGo [[east|B]] or [[C]]\n\n:: B\nEast [img[map.png][B]]\n\n:: C\nBack to [[B]]\n\n";
+
+    fn removed(policy: LinkPolicy) -> (Vec<String>, String) {
+        let options = ParseOptions::default().verbatim(true);
+        let mut story = Story::parse_with(INPUT, options).unwrap().into_owned();
+        let removal = story.remove_passage("B", policy).unwrap();
+
+        assert_eq!(removal.passage.title, "B");
+        assert!(!story.contains_passage("B"));
+        (removal.linking, story.to_twee())
+    }
+
+    #[test]
+    fn test_remove_passage() {
+        let (linking, twee) = removed(LinkPolicy::Keep);
+        assert_eq!(linking, ["A", "C"]);
+        assert_eq!(
+            twee,
+            ":: A\nGo [[east|B]] or [[C]]\n\n:: C\nBack to [[B]]\n\n"
+        );
+
+        let (_, twee) = removed(LinkPolicy::Unlink);
+        assert_eq!(twee, ":: A\nGo east or [[C]]\n\n:: C\nBack to B\n\n");
+
+        let (_, twee) = removed(LinkPolicy::Retarget("C".to_string()));
+        assert_eq!(
+            twee,
+            ":: A\nGo [[east|C]] or [[C]]\n\n:: C\nBack to [[C]]\n\n"
+        );
+
+        let mut story = Story::try_from(INPUT).unwrap();
+        assert_eq!(
+            story.remove_passage("B", LinkPolicy::Retarget("Z".to_string())),
+            Err(UnknownPassage("Z".to_string()))
+        );
+        assert_eq!(
+            story.remove_passage("Z", LinkPolicy::Keep),
+            Err(UnknownPassage("Z".to_string()))
+        );
+        assert_eq!(story.len(), 3);
+    }
+
+    #[test]
+    fn test_edit_and_undo() {
+        let mut story = Story::try_from(INPUT).unwrap().into_owned();
+        let original = story.to_twee();
+        let changes = story
+            .edit(|tx| {
+                tx.rename("B", "East")?;
+                tx.set_tags("A", ["intro"])?;
+                tx.set_content("C", vec![ContentNode::text_node("Lost".to_string())])?;
+                tx.remove("C")?;
+                tx.insert(Passage::new("D".to_string(), vec![], None, vec![]))
+            })
+            .unwrap();
+
+        assert_eq!(
+            story.to_twee(),
+            ":: A [intro]\nGo [[east|East]] or [[C]]\n\n:: East\nEast [img[map.png][East]]\n\n:: D\n\n\n"
+        );
+        assert_eq!(
+            changes.to_string(),
+            "Renamed \"B\" to \"East\"\nTagged \"A\" with [\"intro\"]\nChanged the text of \"C\"\nRemoved \"C\"\nAdded \"D\"\n"
+        );
+
+        let redo = story.apply_edits(changes.undo).unwrap();
+        assert_eq!(story.to_twee(), original);
+        story.apply_edits(redo.undo).unwrap();
+        assert_eq!(story.len(), 3);
+        assert!(story.contains_passage("East"));
+    }
+
+    #[test]
+    fn test_undo_remove_verbatim() {
+        let input = ":: A\nGo [[B]]\n\n:: B\nMiddle\n\n:: C\nEnd\n";
+        let options = ParseOptions::default().verbatim(true);
+        let mut story = Story::parse_with(input, options).unwrap();
+        let changes = story.edit(|tx| tx.remove("B")).unwrap();
+        assert_eq!(story.to_twee(), ":: A\nGo [[B]]\n\n:: C\nEnd\n");
+
+        story.apply_edits(changes.undo).unwrap();
+        assert_eq!(story.to_twee(), input);
+    }
+
+    #[test]
+    fn test_rename_start_passage() {
+        let input = ":: StoryData\n{\"start\":\"Begin\"}\n\n:: Begin\nHi\n";
+        let mut story = Story::try_from(input).unwrap().into_owned();
+        let changes = story.edit(|tx| tx.rename("Begin", "Intro")).unwrap();
+
+        assert_eq!(story.start_title(), Some("Intro"));
+        assert_eq!(story.start().unwrap().title, "Intro");
+        assert!(story.to_twee().contains("\"start\": \"Intro\""));
+
+        story.apply_edits(changes.undo).unwrap();
+        assert_eq!(story.start_title(), Some("Begin"));
+    }
+
+    #[test]
+    fn test_failed_edit_is_rolled_back() {
+        let mut story = Story::try_from(INPUT).unwrap();
+        let original = story.to_twee();
+        let result = story.edit(|tx| {
+            tx.rename("A", "Z")?;
+            tx.rename("Z", "C")
+        });
+
+        assert_eq!(result, Err(EditError::DuplicatePassage("C".to_string())));
+        assert_eq!(story.to_twee(), original);
+        assert_eq!(
+            story.apply_edits([Edit::Remove {
+                passage: "Q".to_string()
+            }]),
+            Err(EditError::UnknownPassage("Q".to_string()))
+        );
+    }
+}
@@ -0,0 +1,79 @@
+//! Data only used by the Twine editor, which changes whenever passages are moved around.
+
+use std::ops::Deref;
+
+use serde_json::Value;
+
+use crate::{Metadata, Story, TextBlock};
+
+const PASSAGE_FIELDS: &[&str] = &["position", "size"];
+
+/// `json` without `fields`, `None` when nothing is left. Invalid JSON is kept as is.
+fn without_fields(json: &str, fields: &[&str]) -> Option<String> {
+    let Ok(Value::Object(mut object)) = serde_json::from_str::<Value>(json) else {
+        return Some(json.to_string());
+    };
+    if !fields.iter().any(|field| object.contains_key(*field)) {
+        return Some(json.to_string());
+    }
+    object.retain(|key, _| !fields.contains(&key.as_str()));
+    (!object.is_empty()).then(|| Value::Object(object).to_string())
+}
+
+impl<T> Story<T>
+where
+    T: Deref<Target = str>,
+{
+    /// A copy of the story without the position and size of its passages, and the zoom level
+    /// of StoryData if `zoom` is set, for diffs showing only changes to the story.
+    pub fn strip_editor_metadata(&self, zoom: bool) -> Story<String> {
+        let mut story = self.to_owned_story();
+        let content = story.content.as_str();
+        story.passages = story.passages.map(|mut passage| {
+            let Some(metadata) = passage.metadata.take() else {
+                return passage;
+            };
+            passage.metadata = without_fields(metadata.content.as_str(content), PASSAGE_FIELDS)
+                .map(|json| Metadata::new(TextBlock::Owned(json)));
+            passage.source = None;
+            passage
+        });
+        if zoom {
+            story.data = story.data.take().map(|data| {
+                let data = without_fields(data.as_str(content), &["zoom"]);
+                TextBlock::Owned(data.unwrap_or_else(|| "{}".to_string()))
+            });
+        }
+        story.source_blocks = None;
+        story.invalidate_derived();
+        story
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Story;
+
+    #[test]
+    fn test_strip_editor_metadata() {
+        let input = ":: StoryData\n{\"ifid\": \"X\", \"zoom\": 0.6}\n\n:: Start {\"position\":\"100,200\",\"size\":\"200,100\"}\nHi\n\n:: End {\"position\":\"0,0\",\"note\":\"kept\"}\nBye\n";
+        let story = Story::try_from(input).unwrap();
+
+        let stripped = story.strip_editor_metadata(false);
+        assert_eq!(stripped.get_passage("Start").unwrap().metadata(), None);
+        assert_eq!(
+            stripped
+                .get_passage("End")
+                .unwrap()
+                .metadata()
+                .unwrap()
+                .content(),
+            &"{\"note\":\"kept\"}"
+        );
+        assert_eq!(stripped.data(), story.data());
+
+        let stripped = story.strip_editor_metadata(true);
+        assert_eq!(stripped.data(), Some("{\"ifid\":\"X\"}"));
+        assert!(!stripped.to_twee().contains("position"));
+    }
+}
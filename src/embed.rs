@@ -0,0 +1,409 @@
+//! Stories parsed at build time and embedded in the binary.
+//!
+//! In `build.rs`, with this crate as a build dependency:
+//!
+//! ```no_run
+//! twee_v3::embed::build("story.twee").unwrap();
+//! ```
+//!
+//! Parsing errors then fail the build, and the program gets the story without parsing it:
+//!
+//! ```ignore
+//! static STORY: twee_v3::embed::EmbeddedStory = twee_v3::embed!("story");
+//! ```
+
+use std::{
+    fmt::{Display, Write},
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    parser::html::parse_html_tag, ContentNode, CustomNode, Dialect, Image, ImageAlign, Inline,
+    Metadata, ParseOptions, Passage, Story, Tag,
+};
+
+/// Includes the story embedded by [`build`], named after the file stem of the story.
+#[macro_export]
+macro_rules! embed {
+    ($name:literal) => {
+        include!(concat!(env!("OUT_DIR"), "/", $name, ".twee.rs"))
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmbeddedStory {
+    pub title: Option<&'static str>,
+    pub start: Option<&'static str>,
+    pub data: Option<&'static str>,
+    pub passages: &'static [EmbeddedPassage],
+}
+
+impl EmbeddedStory {
+    pub fn passage(&self, name: &str) -> Option<Passage<&'static str>> {
+        self.passages
+            .iter()
+            .find(|passage| passage.title == name)
+            .map(EmbeddedPassage::to_passage)
+    }
+
+    pub fn passages(&self) -> impl Iterator<Item = Passage<&'static str>> + '_ {
+        self.passages.iter().map(EmbeddedPassage::to_passage)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmbeddedPassage {
+    pub title: &'static str,
+    pub tags: &'static [&'static str],
+    pub metadata: Option<&'static str>,
+    pub nodes: &'static [EmbeddedNode],
+}
+
+impl EmbeddedPassage {
+    pub fn to_passage(&self) -> Passage<&'static str> {
+        Passage::new(
+            self.title,
+            self.tags.iter().map(|tag| Tag::new(*tag)).collect(),
+            self.metadata.map(Metadata::new),
+            self.nodes.iter().map(EmbeddedNode::to_node).collect(),
+        )
+    }
+}
+
+/// A [`ContentNode`] that can be built in a constant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmbeddedNode {
+    Text(&'static str),
+    Link {
+        text: &'static str,
+        target: &'static str,
+        setter: Option<&'static str>,
+    },
+    Comment(&'static str),
+    /// The tag as written, read again when converted.
+    Html(&'static str),
+    Image {
+        source: &'static str,
+        title: Option<&'static str>,
+        target: Option<&'static str>,
+        setter: Option<&'static str>,
+        align: Option<ImageAlign>,
+    },
+    Custom {
+        kind: &'static str,
+        raw: &'static str,
+    },
+    Raw {
+        dialect: Dialect,
+        raw: &'static str,
+    },
+    Paragraph(&'static [EmbeddedInline]),
+}
+
+/// An [`Inline`] that can be built in a constant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmbeddedInline {
+    Text(&'static str),
+    LineBreak,
+    Node(EmbeddedNode),
+}
+
+impl EmbeddedInline {
+    pub fn to_inline(&self) -> Inline<&'static str> {
+        match *self {
+            EmbeddedInline::Text(text) => Inline::Text(text),
+            EmbeddedInline::LineBreak => Inline::LineBreak,
+            EmbeddedInline::Node(node) => Inline::Node(node.to_node()),
+        }
+    }
+}
+
+impl EmbeddedNode {
+    pub fn to_node(&self) -> ContentNode<&'static str> {
+        match *self {
+            EmbeddedNode::Text(text) => ContentNode::Text(text),
+            EmbeddedNode::Link {
+                text,
+                target,
+                setter,
+            } => ContentNode::Link {
+                text,
+                target,
+                setter,
+            },
+            EmbeddedNode::Comment(comment) => ContentNode::Comment(comment),
+            EmbeddedNode::Html(raw) => match parse_html_tag(raw) {
+                Ok((_, tag)) => ContentNode::Html(tag),
+                Err(_) => ContentNode::Text(raw),
+            },
+            EmbeddedNode::Image {
+                source,
+                title,
+                target,
+                setter,
+                align,
+            } => ContentNode::Image(Image {
+                source,
+                title,
+                target,
+                setter,
+                align,
+            }),
+            EmbeddedNode::Custom { kind, raw } => ContentNode::Custom(CustomNode::new(kind, raw)),
+            EmbeddedNode::Raw { dialect, raw } => ContentNode::Raw { dialect, raw },
+            EmbeddedNode::Paragraph(inlines) => {
+                ContentNode::Paragraph(inlines.iter().map(EmbeddedInline::to_inline).collect())
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum EmbedError {
+    Io(io::Error),
+    /// The story doesn't parse, with the error message.
+    Parsing(String),
+    /// `OUT_DIR` isn't set, [`build`] must be called from a build script.
+    MissingOutDir,
+}
+
+impl Display for EmbedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EmbedError::Io(error) => write!(f, "Cannot read or write the story: {error}"),
+            EmbedError::Parsing(error) => write!(f, "Cannot parse the story: {error}"),
+            EmbedError::MissingOutDir => write!(f, "OUT_DIR is not set, run from build.rs"),
+        }
+    }
+}
+
+impl std::error::Error for EmbedError {}
+
+impl From<io::Error> for EmbedError {
+    fn from(error: io::Error) -> Self {
+        EmbedError::Io(error)
+    }
+}
+
+fn write_option(output: &mut String, value: Option<&str>) {
+    match value {
+        Some(value) => {
+            let _ = write!(output, "Some({value:?})");
+        }
+        None => output.push_str("None"),
+    }
+}
+
+fn write_node(output: &mut String, node: &ContentNode<&str>) {
+    const PATH: &str = "::twee_v3::embed::EmbeddedNode";
+    match node {
+        ContentNode::Text(text) => {
+            let _ = write!(output, "{PATH}::Text({text:?})");
+        }
+        ContentNode::Link {
+            text,
+            target,
+            setter,
+        } => {
+            let _ = write!(
+                output,
+                "{PATH}::Link {{ text: {text:?}, target: {target:?}, setter: "
+            );
+            write_option(output, *setter);
+            output.push_str(" }");
+        }
+        ContentNode::Comment(comment) => {
+            let _ = write!(output, "{PATH}::Comment({comment:?})");
+        }
+        ContentNode::Html(tag) => {
+            let _ = write!(output, "{PATH}::Html({:?})", tag.raw);
+        }
+        ContentNode::Image(image) => {
+            let _ = write!(
+                output,
+                "{PATH}::Image {{ source: {:?}, title: ",
+                image.source
+            );
+            write_option(output, image.title);
+            output.push_str(", target: ");
+            write_option(output, image.target);
+            output.push_str(", setter: ");
+            write_option(output, image.setter);
+            let align = match image.align {
+                Some(ImageAlign::Left) => "Some(::twee_v3::ImageAlign::Left)",
+                Some(ImageAlign::Right) => "Some(::twee_v3::ImageAlign::Right)",
+                None => "None",
+            };
+            let _ = write!(output, ", align: {align} }}");
+        }
+        ContentNode::Custom(node) => {
+            let _ = write!(
+                output,
+                "{PATH}::Custom {{ kind: {:?}, raw: {:?} }}",
+                node.kind(),
+                node.raw
+            );
+        }
+        ContentNode::Raw { dialect, raw } => {
+            let _ = write!(
+                output,
+                "{PATH}::Raw {{ dialect: ::twee_v3::Dialect::{dialect:?}, raw: {raw:?} }}"
+            );
+        }
+        ContentNode::Paragraph(inlines) => {
+            let _ = write!(output, "{PATH}::Paragraph(&[");
+            for inline in inlines {
+                match inline {
+                    Inline::Text(text) => {
+                        let _ = write!(output, "::twee_v3::embed::EmbeddedInline::Text({text:?})");
+                    }
+                    Inline::LineBreak => {
+                        output.push_str("::twee_v3::embed::EmbeddedInline::LineBreak")
+                    }
+                    Inline::Node(node) => {
+                        output.push_str("::twee_v3::embed::EmbeddedInline::Node(");
+                        write_node(output, node);
+                        output.push(')');
+                    }
+                }
+                output.push_str(", ");
+            }
+            output.push_str("])");
+        }
+    }
+}
+
+/// The Rust expression building `story` as an [`EmbeddedStory`].
+pub fn to_rust<T>(story: &Story<T>) -> String
+where
+    T: std::ops::Deref<Target = str>,
+{
+    let mut output = String::from("::twee_v3::embed::EmbeddedStory {\n    title: ");
+    write_option(&mut output, story.title());
+    output.push_str(",\n    start: ");
+    let start = story
+        .start
+        .as_ref()
+        .map(|start| start.as_str(&story.content));
+    write_option(&mut output, start);
+    output.push_str(",\n    data: ");
+    write_option(&mut output, story.data());
+    output.push_str(",\n    passages: &[\n");
+    for passage in story.iter() {
+        let _ = write!(
+            output,
+            "        ::twee_v3::embed::EmbeddedPassage {{\n            title: {:?},\n            tags: &{:?},\n            metadata: ",
+            passage.title,
+            passage.tags.iter().map(|tag| tag.value).collect::<Vec<_>>()
+        );
+        write_option(&mut output, passage.metadata.as_ref().map(|m| m.content));
+        output.push_str(",\n            nodes: &[\n");
+        for node in &passage.content {
+            output.push_str("                ");
+            write_node(&mut output, node);
+            output.push_str(",\n");
+        }
+        output.push_str("            ],\n        },\n");
+    }
+    output.push_str("    ],\n}\n");
+    output
+}
+
+/// Parses the story at `path` and writes it to `OUT_DIR`, for [`embed!`] to include.
+///
+/// To be called from a build script, which reruns when the story changes.
+pub fn build(path: impl AsRef<Path>) -> Result<(), EmbedError> {
+    build_with(path, ParseOptions::default())
+}
+
+/// Same as [`build`], parsing the story with `options`.
+pub fn build_with(path: impl AsRef<Path>, options: ParseOptions) -> Result<(), EmbedError> {
+    let path = path.as_ref();
+    let out_dir = std::env::var_os("OUT_DIR").ok_or(EmbedError::MissingOutDir)?;
+    println!("cargo:rerun-if-changed={}", path.display());
+
+    let content = std::fs::read_to_string(path)?;
+    let story = Story::parse_with(content.as_str(), options)
+        .map_err(|error| EmbedError::Parsing(format!("{}: {error}", path.display())))?;
+
+    // Appended rather than set as the extension, which would replace any dot in the stem.
+    let mut file_name = path.file_stem().unwrap_or(path.as_os_str()).to_owned();
+    file_name.push(".twee.rs");
+    let output = PathBuf::from(out_dir).join(file_name);
+    std::fs::write(output, to_rust(&story))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_rust, EmbeddedNode, EmbeddedPassage, EmbeddedStory};
+    use crate::{ImageAlign, Story};
+
+    const STORY: &str = ":: StoryTitle\nTiny\n\n:: Start [a b]\nHi <b>\"you\"</b> [[Go|End][$x to 1]]\n\n:: End\n[<img[end.png][Start]]\n";
+
+    // What `to_rust` generates for `STORY` with `html` enabled, the `::twee_v3` paths being
+    // this crate.
+    static EMBEDDED: EmbeddedStory = EmbeddedStory {
+        title: Some("Tiny"),
+        start: None,
+        data: None,
+        passages: &[
+            EmbeddedPassage {
+                title: "Start",
+                tags: &["a", "b"],
+                metadata: None,
+                nodes: &[
+                    EmbeddedNode::Text("Hi "),
+                    EmbeddedNode::Html("<b>"),
+                    EmbeddedNode::Text("\"you\""),
+                    EmbeddedNode::Html("</b>"),
+                    EmbeddedNode::Text(" "),
+                    EmbeddedNode::Link {
+                        text: "Go",
+                        target: "End",
+                        setter: Some("$x to 1"),
+                    },
+                ],
+            },
+            EmbeddedPassage {
+                title: "End",
+                tags: &[],
+                metadata: None,
+                nodes: &[EmbeddedNode::Image {
+                    source: "end.png",
+                    title: None,
+                    target: Some("Start"),
+                    setter: None,
+                    align: Some(ImageAlign::Left),
+                }],
+            },
+        ],
+    };
+
+    #[test]
+    fn test_embedded_passages() {
+        let options = crate::ParseOptions::default().html(true);
+        let story = Story::parse_with(STORY, options).unwrap();
+
+        assert_eq!(
+            EMBEDDED.passages().collect::<Vec<_>>(),
+            story.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(EMBEDDED.passage("End"), story.get_passage("End"));
+    }
+
+    #[test]
+    fn test_to_rust() {
+        let story = Story::try_from(STORY).unwrap();
+        let rust = to_rust(&story);
+
+        assert!(rust.starts_with(
+            "::twee_v3::embed::EmbeddedStory {\n    title: Some(\"Tiny\"),\n    start: None,"
+        ));
+        assert!(rust.contains("tags: &[\"a\", \"b\"]"));
+        assert!(rust.contains("::twee_v3::embed::EmbeddedNode::Text(\"Hi <b>\\\"you\\\"</b> \")"));
+        assert!(rust.contains("::twee_v3::embed::EmbeddedNode::Link { text: \"Go\", target: \"End\", setter: Some(\"$x to 1\") }"));
+        assert!(rust.contains("align: Some(::twee_v3::ImageAlign::Left) }"));
+    }
+}
@@ -0,0 +1,176 @@
+//! Normalization of twee input coming from various tools: byte order marks, line endings and,
+//! with the `utf16` feature, UTF-16 files.
+
+use std::{borrow::Cow, fmt::Display, ops::Deref, string::FromUtf8Error};
+
+use crate::Story;
+
+const BOM: char = '\u{feff}';
+
+/// The line ending of a file, for serializers to write it back the same way.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    /// The line ending used by most lines of `input`, `\n` when there is a tie.
+    pub fn detect(input: &str) -> Self {
+        let lines = input.matches('\n').count();
+        let crlf = input.matches("\r\n").count();
+        match crlf * 2 > lines {
+            true => LineEnding::CrLf,
+            false => LineEnding::Lf,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+
+    /// Converts the line endings of `input` to this one.
+    pub fn apply(self, input: &str) -> Cow<'_, str> {
+        match (self, normalize_line_endings(input)) {
+            (LineEnding::Lf, normalized) => normalized,
+            (LineEnding::CrLf, normalized) => Cow::Owned(normalized.replace('\n', "\r\n")),
+        }
+    }
+}
+
+impl<T> Story<T>
+where
+    T: Deref<Target = str>,
+{
+    /// The line ending of the source of the story, see [`LineEnding::detect`].
+    pub fn line_ending(&self) -> LineEnding {
+        LineEnding::detect(&self.content)
+    }
+}
+
+pub fn strip_bom(input: &str) -> &str {
+    input.strip_prefix(BOM).unwrap_or(input)
+}
+
+/// Converts Windows (`\r\n`) and classic Mac (`\r`) line endings to `\n`.
+pub fn normalize_line_endings(input: &str) -> Cow<'_, str> {
+    if input.contains('\r') {
+        Cow::Owned(input.replace("\r\n", "\n").replace('\r', "\n"))
+    } else {
+        Cow::Borrowed(input)
+    }
+}
+
+/// Strips the byte order mark and normalizes line endings.
+pub fn normalize(input: &str) -> Cow<'_, str> {
+    normalize_line_endings(strip_bom(input))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    Utf8(FromUtf8Error),
+    #[cfg(feature = "utf16")]
+    Utf16,
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::Utf8(error) => write!(f, "Input is not valid UTF-8: {error}"),
+            #[cfg(feature = "utf16")]
+            DecodeError::Utf16 => write!(f, "Input is not valid UTF-16"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+#[cfg(feature = "utf16")]
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> Result<String, DecodeError> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err(DecodeError::Utf16);
+    }
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| from_bytes([pair[0], pair[1]]))
+        .collect();
+    String::from_utf16(&units).map_err(|_| DecodeError::Utf16)
+}
+
+/// Decodes a twee file, then [`normalize`]s it.
+///
+/// UTF-8 is expected. With the `utf16` feature, input starting with a UTF-16 byte order mark
+/// is decoded as UTF-16.
+pub fn decode(bytes: Vec<u8>) -> Result<String, DecodeError> {
+    #[cfg(feature = "utf16")]
+    let decoded = match bytes.as_slice() {
+        [0xff, 0xfe, rest @ ..] => decode_utf16(rest, u16::from_le_bytes)?,
+        [0xfe, 0xff, rest @ ..] => decode_utf16(rest, u16::from_be_bytes)?,
+        _ => String::from_utf8(bytes).map_err(DecodeError::Utf8)?,
+    };
+    #[cfg(not(feature = "utf16"))]
+    let decoded = String::from_utf8(bytes).map_err(DecodeError::Utf8)?;
+
+    Ok(match normalize(&decoded) {
+        Cow::Borrowed(normalized) if normalized.len() == decoded.len() => decoded,
+        normalized => normalized.into_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, normalize, LineEnding};
+    use crate::Story;
+
+    #[test]
+    fn test_line_ending() {
+        assert_eq!(LineEnding::detect(":: A\r\nB\r\n\n"), LineEnding::CrLf);
+        assert_eq!(LineEnding::detect(":: A\r\nB\n"), LineEnding::Lf);
+        assert_eq!(LineEnding::CrLf.apply(":: A\nB\r\n"), ":: A\r\nB\r\n");
+
+        let story = Story::try_from(":: A\r\nB\r\n").unwrap();
+        assert_eq!(story.line_ending(), LineEnding::CrLf);
+        assert_eq!(
+            story.line_ending().apply(&story.to_twee()),
+            ":: A\r\nB\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("\u{feff}:: A\r\nB\rC\n"), ":: A\nB\nC\n");
+        assert_eq!(normalize(":: A\n"), ":: A\n");
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(
+            decode(b"\xef\xbb\xbf:: A\r\n".to_vec()),
+            Ok(":: A\n".to_string())
+        );
+        assert!(decode(vec![0xc3, 0x28]).is_err());
+    }
+
+    #[cfg(feature = "utf16")]
+    #[test]
+    fn test_decode_utf16() {
+        let mut little = vec![0xff, 0xfe];
+        little.extend(":: Été\r\n".encode_utf16().flat_map(u16::to_le_bytes));
+        let mut big = vec![0xfe, 0xff];
+        big.extend(":: Été\r\n".encode_utf16().flat_map(u16::to_be_bytes));
+
+        assert_eq!(decode(little), Ok(":: Été\n".to_string()));
+        assert_eq!(decode(big), Ok(":: Été\n".to_string()));
+        assert!(decode(vec![0xff, 0xfe, 0x00]).is_err());
+    }
+
+    #[test]
+    fn test_parse_with_bom() {
+        let story = Story::try_from("\u{feff}:: Start\nHello\n").unwrap();
+        assert!(story.get_passage("Start").is_some());
+    }
+}
@@ -1,8 +1,75 @@
 use std::fmt::{Debug, Display};
 
+use crate::{Limit, Position, Warning};
+
 #[derive(Debug)]
 pub enum ParsingError<T> {
     Parsing(T),
+    DuplicatePassage {
+        name: String,
+        first: Position,
+        second: Position,
+    },
+    LimitExceeded {
+        limit: Limit,
+        position: Position,
+    },
+    /// The metadata of the passage isn't valid JSON, which Twine refuses to import.
+    InvalidMetadata(String),
+    /// Text comes before the first passage header, at this position.
+    Preamble(Position),
+    /// Parsing was cancelled, before the block at this position.
+    Cancelled(Position),
+    /// The deadline passed before the block at this position.
+    DeadlineExceeded(Position),
+}
+
+impl<T> ParsingError<T> {
+    /// The error a warning turns into when parsing strictly, if any.
+    pub(crate) fn from_warning(warning: &Warning) -> Option<Self> {
+        match warning {
+            Warning::DuplicatePassage {
+                name,
+                first,
+                second,
+            } => Some(ParsingError::DuplicatePassage {
+                name: name.clone(),
+                first: *first,
+                second: *second,
+            }),
+            Warning::InvalidMetadata(passage) => {
+                Some(ParsingError::InvalidMetadata(passage.clone()))
+            }
+            Warning::Preamble(position) => Some(ParsingError::Preamble(*position)),
+            Warning::DeadlineExceeded(position) => Some(ParsingError::DeadlineExceeded(*position)),
+            _ => None,
+        }
+    }
+}
+
+impl ParsingError<&str> {
+    /// Copies the error out of the input it borrows.
+    pub fn into_owned(self) -> ParsingError<String> {
+        match self {
+            ParsingError::Parsing(input) => ParsingError::Parsing(input.to_string()),
+            ParsingError::DuplicatePassage {
+                name,
+                first,
+                second,
+            } => ParsingError::DuplicatePassage {
+                name,
+                first,
+                second,
+            },
+            ParsingError::LimitExceeded { limit, position } => {
+                ParsingError::LimitExceeded { limit, position }
+            }
+            ParsingError::InvalidMetadata(passage) => ParsingError::InvalidMetadata(passage),
+            ParsingError::Preamble(position) => ParsingError::Preamble(position),
+            ParsingError::Cancelled(position) => ParsingError::Cancelled(position),
+            ParsingError::DeadlineExceeded(position) => ParsingError::DeadlineExceeded(position),
+        }
+    }
 }
 
 impl<T> Display for ParsingError<T>
@@ -12,6 +79,30 @@ where
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ParsingError::Parsing(error) => f.write_fmt(format_args!("{error}")),
+            ParsingError::DuplicatePassage {
+                name,
+                first,
+                second,
+            } => write!(
+                f,
+                "Passage {name:?} is defined twice, at {first} and at {second}"
+            ),
+            ParsingError::LimitExceeded { limit, position } => {
+                write!(f, "Story exceeds the {limit} at {position}")
+            }
+            ParsingError::InvalidMetadata(passage) => {
+                write!(f, "Metadata of passage {passage:?} is not valid JSON")
+            }
+            ParsingError::Preamble(position) => {
+                write!(
+                    f,
+                    "Text at {position} comes before the first passage header"
+                )
+            }
+            ParsingError::Cancelled(position) => write!(f, "Parsing cancelled at {position}"),
+            ParsingError::DeadlineExceeded(position) => {
+                write!(f, "Parsing stopped at {position}, past the deadline")
+            }
         }
     }
 }
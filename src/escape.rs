@@ -0,0 +1,140 @@
+//! Escaping as per the twee 3 specification, for code writing twee.
+//!
+//! A backslash escapes the character after it, so [`unescape`] reverses all the escape functions.
+
+use std::borrow::Cow;
+
+use crate::utils::{escape_string_content, COMMENT_DELIMITERS};
+
+/// Characters escaped in passage names and tags, as per the twee 3 specification.
+const NAME_SPECIAL_CHARS: &[char] = &['\\', '[', ']', '{', '}'];
+
+/// Spaces and tabs, which surround names and separate tags unless escaped.
+const NAME_WHITESPACE: [char; 2] = [' ', '\t'];
+
+/// Escapes the characters of `input` for which `escape` is true, given their byte offset.
+fn escape_chars(input: &str, escape: impl Fn(usize, char) -> bool) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for (index, c) in input.char_indices() {
+        if NAME_SPECIAL_CHARS.contains(&c) || escape(index, c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Escapes a passage name, including its leading and trailing spaces and tabs, which would
+/// otherwise be trimmed.
+pub fn escape_name(input: &str) -> String {
+    let start = input.len() - input.trim_start_matches(NAME_WHITESPACE).len();
+    let end = input.trim_end_matches(NAME_WHITESPACE).len();
+    escape_chars(input, |index, c| {
+        NAME_WHITESPACE.contains(&c) && (index < start || index >= end)
+    })
+}
+
+/// Escapes a tag, including its spaces and tabs, which would otherwise separate tags.
+pub fn escape_tag(input: &str) -> String {
+    escape_chars(input, |_, c| NAME_WHITESPACE.contains(&c))
+}
+
+/// Escapes the text of a passage, so that it isn't read as links, images, comments or headers.
+pub fn escape_text(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        // A `[` could open a link with the next character, or with the node that follows.
+        if c == '\\' || (c == '[' && matches!(chars.peek(), Some('[') | None)) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    for opening in ["[img[", "[<img[", "[>img["] {
+        escaped = escaped.replace(opening, &format!("\\{opening}"));
+    }
+    for (opening, _) in COMMENT_DELIMITERS {
+        escaped = escaped.replace(opening, &format!("\\{opening}"));
+    }
+    // A line starting with `::` would be read as a passage header.
+    if let Some(stripped) = escaped.strip_prefix("::") {
+        format!("\\::{}", stripped.replace("\n::", "\n\\::"))
+    } else {
+        escaped.replace("\n::", "\n\\::")
+    }
+}
+
+/// Escapes the text, target or setter of a link, or a part of an image.
+pub fn escape_link_part(input: &str) -> String {
+    input
+        .replace('\\', "\\\\")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace('|', "\\|")
+        .replace("->", "\\->")
+        .replace("<-", "\\<-")
+}
+
+/// Resolves the escaped characters of `input`.
+pub fn unescape(input: &str) -> Cow<'_, str> {
+    match escape_string_content(input) {
+        Some(unescaped) => Cow::Owned(unescaped),
+        None => Cow::Borrowed(input),
+    }
+}
+
+/// Turns `input` into a valid passage title: line breaks and runs of whitespace become a single
+/// space, and it is trimmed. The result still needs [`escape_name`] to be written as twee.
+///
+/// Returns `None` if nothing is left.
+pub fn sanitize_title(input: &str) -> Option<String> {
+    let title = input.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!title.is_empty()).then_some(title)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape_link_part, escape_name, escape_tag, escape_text, sanitize_title, unescape};
+
+    #[test]
+    fn test_escape_name() {
+        assert_eq!(escape_name("osef]"), r"osef\]");
+        assert_eq!(escape_name(r"a\{b}"), r"a\\\{b\}");
+        assert_eq!(escape_name(" a b\t"), "\\ a b\\\t");
+        assert_eq!(escape_tag("a b]"), r"a\ b\]");
+    }
+
+    #[test]
+    fn test_escape_text() {
+        assert_eq!(escape_text("Hello [[world"), r"Hello \[[world");
+        assert_eq!(escape_text("Hello /* world */"), r"Hello \/* world */");
+        assert_eq!(
+            escape_text(":: Not a title\n:: Nor this"),
+            "\\:: Not a title\n\\:: Nor this"
+        );
+    }
+
+    #[test]
+    fn test_escape_link_part() {
+        assert_eq!(escape_link_part("a->b|c]"), r"a\->b\|c\]");
+    }
+
+    #[test]
+    fn test_unescape() {
+        for input in ["a{b}\\c", "[[x]] :: y", "a->b|c<-d"] {
+            assert_eq!(unescape(&escape_name(input)), input);
+            assert_eq!(unescape(&escape_text(input)), input);
+            assert_eq!(unescape(&escape_link_part(input)), input);
+        }
+        assert_eq!(unescape(r"no escape"), "no escape");
+    }
+
+    #[test]
+    fn test_sanitize_title() {
+        assert_eq!(
+            sanitize_title("  The\n  cave\r\n").as_deref(),
+            Some("The cave")
+        );
+        assert_eq!(sanitize_title(" \t"), None);
+    }
+}
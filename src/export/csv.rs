@@ -0,0 +1,106 @@
+use std::ops::Deref;
+
+use serde_json::{Map, Value};
+
+use crate::{stats::word_count, Story};
+
+/// Quotes `field` when it holds a separator, a quote or a line break.
+fn escape_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn write_row<I, S>(output: &mut String, fields: I)
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let fields: Vec<_> = fields
+        .into_iter()
+        .map(|field| escape_field(field.as_ref()))
+        .collect();
+    output.push_str(&fields.join(","));
+    output.push_str("\r\n");
+}
+
+/// Writes one row per passage, in source order, for spreadsheets: the title, the tags
+/// separated by spaces, the number of words and of links, then a column for each field found
+/// in the metadata of the passages, in the order they first appear, sorted within a passage.
+///
+/// Text metadata is written as is, other values as JSON.
+pub fn to_csv<T>(story: &Story<T>) -> String
+where
+    T: Deref<Target = str>,
+{
+    let passages: Vec<_> = story
+        .iter()
+        .map(|passage| {
+            let metadata = passage
+                .metadata
+                .as_ref()
+                .and_then(|metadata| {
+                    serde_json::from_str::<Map<String, Value>>(metadata.content).ok()
+                })
+                .unwrap_or_default();
+            (passage, metadata)
+        })
+        .collect();
+    let mut fields: Vec<&str> = vec![];
+    for (_, metadata) in &passages {
+        for field in metadata.keys() {
+            if !fields.contains(&field.as_str()) {
+                fields.push(field);
+            }
+        }
+    }
+
+    let mut output = String::new();
+    write_row(
+        &mut output,
+        ["title", "tags", "words", "links"]
+            .into_iter()
+            .chain(fields.iter().copied()),
+    );
+    for (passage, metadata) in &passages {
+        let tags: Vec<_> = passage.tags.iter().map(|tag| tag.value).collect();
+        let values = fields.iter().map(|field| match metadata.get(*field) {
+            Some(Value::String(value)) => value.clone(),
+            Some(value) => value.to_string(),
+            None => String::new(),
+        });
+        write_row(
+            &mut output,
+            [
+                passage.title.to_string(),
+                tags.join(" "),
+                word_count(passage).to_string(),
+                passage.links().count().to_string(),
+            ]
+            .into_iter()
+            .chain(values),
+        );
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_csv;
+    use crate::Story;
+
+    #[test]
+    fn test_to_csv() {
+        let input = ":: Start [intro draft] {\"position\":\"100,100\",\"owner\":\"Ada\"}\nHello, \"you\". [[Next]]\n\n:: Next {\"owner\":\"Bob\",\"act\":2}\nBye\n";
+        let story = Story::try_from(input).unwrap();
+
+        assert_eq!(
+            to_csv(&story),
+            "title,tags,words,links,owner,position,act\r\n\
+             Start,intro draft,3,1,Ada,\"100,100\",\r\n\
+             Next,,1,0,Bob,,2\r\n"
+        );
+    }
+}
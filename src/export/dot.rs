@@ -0,0 +1,93 @@
+use std::{fmt::Write, ops::Deref};
+
+use crate::Story;
+
+/// `text` as a quoted DOT identifier. Only quotes and backslashes are escaped, and line breaks
+/// written as `\n`, other characters being valid as is.
+fn dot_quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Renders the passages and their links as a Graphviz graph.
+///
+/// Passages are colored like in the Twine editor, after the first of their tags with a color.
+pub fn to_dot<T>(story: &Story<T>) -> String
+where
+    T: Deref<Target = str>,
+{
+    let passages: Vec<_> = story.iter().collect();
+    let colors = story.tag_color_map();
+
+    let mut output = String::from("digraph {\n");
+    for passage in &passages {
+        let color = passage.tags.iter().find_map(|tag| colors.get(tag.value));
+        match color {
+            Some(color) => {
+                let (title, color) = (dot_quote(passage.title), dot_quote(color));
+                let _ = writeln!(output, "    {title} [color={color}];");
+            }
+            None => {
+                let _ = writeln!(output, "    {};", dot_quote(passage.title));
+            }
+        }
+    }
+    for passage in &passages {
+        for link in passage.links() {
+            let (title, target) = (dot_quote(passage.title), dot_quote(link.target));
+            let _ = writeln!(output, "    {title} -> {target};");
+        }
+    }
+    output.push_str("}\n");
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dot_quote, to_dot};
+    use crate::Story;
+
+    #[test]
+    fn test_to_dot() {
+        let input = ":: B\nEnd\n\n:: A\n[[B]] [[C]]\n";
+        let story = Story::try_from(input).unwrap();
+
+        assert_eq!(
+            to_dot(&story),
+            "digraph {\n    \"B\";\n    \"A\";\n    \"A\" -> \"B\";\n    \"A\" -> \"C\";\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_to_dot_tag_colors() {
+        let input = ":: StoryData\n{\"tag-colors\":{\"end\":\"red\"}}\n\n:: A [start end]\nDone\n";
+        let story = Story::try_from(input).unwrap();
+
+        assert_eq!(to_dot(&story), "digraph {\n    \"A\" [color=\"red\"];\n}\n");
+    }
+
+    #[test]
+    fn test_to_dot_quoting() {
+        assert_eq!(dot_quote("a\\b\nc"), "\"a\\\\b\\nc\"");
+
+        let input = ":: Say \"hi\"\t😀\n[[Next]]\n";
+        let story = Story::try_from(input).unwrap();
+        assert_eq!(
+            to_dot(&story),
+            "digraph {\n    \"Say \\\"hi\\\"\t😀\";\n    \"Say \\\"hi\\\"\t😀\" -> \"Next\";\n}\n"
+        );
+    }
+}
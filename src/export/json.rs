@@ -0,0 +1,431 @@
+//! Stories as JSON, for tools written in other languages.
+//!
+//! The JSON carries a `version`, raised whenever its shape changes, and follows [`SCHEMA`].
+//! [`Story::from_story_json`] reads it back.
+
+use std::{fmt::Display, ops::Deref};
+
+use serde_json::{json, Map, Value};
+
+use crate::{
+    ContentNode, CustomNode, Dialect, HtmlTag, Image, ImageAlign, Inline, Metadata, Passage, Story,
+    Tag, TextBlock,
+};
+
+/// Version of the JSON written by [`to_json`].
+pub const VERSION: u64 = 4;
+
+/// The JSON Schema of the JSON written by [`to_json`].
+pub const SCHEMA: &str = include_str!("story.schema.json");
+
+fn node_json(node: &ContentNode<&str>) -> Value {
+    match node {
+        ContentNode::Text(text) => json!({ "type": "text", "text": text }),
+        ContentNode::Link {
+            text,
+            target,
+            setter,
+        } => {
+            let mut link = json!({ "type": "link", "text": text, "target": target });
+            if let Some(setter) = setter {
+                link["setter"] = json!(setter);
+            }
+            link
+        }
+        ContentNode::Comment(comment) => {
+            json!({ "type": "comment", "text": comment })
+        }
+        ContentNode::Image(image) => json!({
+            "type": "image",
+            "source": image.source(),
+            "title": image.title(),
+            "target": image.target(),
+            "setter": image.setter(),
+            "align": image.align().map(|align| match align {
+                ImageAlign::Left => "left",
+                ImageAlign::Right => "right",
+            }),
+        }),
+        ContentNode::Html(tag) => {
+            let attributes: Map<_, _> = tag
+                .attributes()
+                .map(|(name, value)| (name.to_owned(), json!(value)))
+                .collect();
+            json!({
+                "type": "html",
+                "raw": tag.raw(),
+                "name": tag.name(),
+                "closing": tag.is_closing(),
+                "attributes": attributes,
+            })
+        }
+        ContentNode::Custom(node) => json!({
+            "type": "custom",
+            "kind": node.kind(),
+            "raw": node.raw(),
+        }),
+        ContentNode::Raw { dialect, raw } => json!({
+            "type": "raw",
+            "dialect": match dialect {
+                Dialect::SugarCube => "sugarcube",
+                Dialect::Harlowe => "harlowe",
+                Dialect::Mustache => "mustache",
+            },
+            "raw": raw,
+        }),
+        ContentNode::Paragraph(inlines) => {
+            let inlines: Vec<_> = inlines
+                .iter()
+                .map(|inline| match inline {
+                    Inline::Text(text) => json!({ "type": "text", "text": text }),
+                    Inline::LineBreak => json!({ "type": "break" }),
+                    Inline::Node(node) => node_json(node),
+                })
+                .collect();
+            json!({ "type": "paragraph", "inlines": inlines })
+        }
+    }
+}
+
+pub fn to_json<T>(story: &Story<T>) -> Value
+where
+    T: Deref<Target = str>,
+{
+    let passages: Vec<_> = story
+        .iter()
+        .map(|passage| {
+            let tags: Vec<_> = passage.tags.iter().map(|tag| tag.value).collect();
+            // Metadata that isn't valid JSON is kept as written.
+            let metadata = passage.metadata.as_ref().map(|metadata| {
+                serde_json::from_str::<Value>(metadata.content)
+                    .unwrap_or_else(|_| json!(metadata.content))
+            });
+            let nodes: Vec<_> = passage.content.iter().map(node_json).collect();
+
+            json!({
+                "name": passage.title,
+                "tags": tags,
+                "metadata": metadata,
+                "nodes": nodes,
+            })
+        })
+        .collect();
+
+    let data = story
+        .data()
+        .and_then(|data| serde_json::from_str::<Value>(data).ok());
+
+    json!({
+        "version": VERSION,
+        "title": story.title(),
+        "start": story.start.as_ref().map(|start| start.as_str(&story.content)),
+        "data": data,
+        "passages": passages,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StoryJsonError {
+    /// The JSON was written by a newer version, or has no version.
+    UnsupportedVersion(Option<u64>),
+    /// The JSON doesn't follow the schema, with the path of the faulty value, such as
+    /// `passages[2].nodes[0].text`.
+    Invalid(String),
+}
+
+impl Display for StoryJsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StoryJsonError::UnsupportedVersion(Some(version)) => {
+                write!(f, "Unsupported story JSON version {version}")
+            }
+            StoryJsonError::UnsupportedVersion(None) => write!(f, "The story JSON has no version"),
+            StoryJsonError::Invalid(path) => write!(f, "Invalid story JSON at {path}"),
+        }
+    }
+}
+
+impl std::error::Error for StoryJsonError {}
+
+/// Reads the JSON written by [`to_json`], checking its shape as it goes.
+struct Reader<'a> {
+    value: &'a Value,
+    path: String,
+}
+
+impl<'a> Reader<'a> {
+    fn field(&self, name: &str) -> Reader<'a> {
+        Reader {
+            value: self.value.get(name).unwrap_or(&Value::Null),
+            path: match self.path.is_empty() {
+                true => name.to_string(),
+                false => format!("{}.{name}", self.path),
+            },
+        }
+    }
+
+    fn invalid(&self) -> StoryJsonError {
+        StoryJsonError::Invalid(match self.path.is_empty() {
+            true => "the root".to_string(),
+            false => self.path.clone(),
+        })
+    }
+
+    fn string(&self) -> Result<String, StoryJsonError> {
+        self.value
+            .as_str()
+            .map(str::to_owned)
+            .ok_or_else(|| self.invalid())
+    }
+
+    /// A string, or nothing for `null` or a missing field.
+    fn optional_string(&self) -> Result<Option<String>, StoryJsonError> {
+        match self.value {
+            Value::Null => Ok(None),
+            _ => self.string().map(Some),
+        }
+    }
+
+    /// Any JSON but `null`, written back as text.
+    fn optional_json(&self) -> Option<String> {
+        (!self.value.is_null()).then(|| self.value.to_string())
+    }
+
+    /// Like [`Reader::optional_json`], but a string is the text itself.
+    fn optional_raw_json(&self) -> Option<String> {
+        match self.value {
+            Value::String(raw) => Some(raw.clone()),
+            _ => self.optional_json(),
+        }
+    }
+
+    fn items(&self) -> Result<Vec<Reader<'a>>, StoryJsonError> {
+        let items = self.value.as_array().ok_or_else(|| self.invalid())?;
+        Ok(items
+            .iter()
+            .enumerate()
+            .map(|(index, value)| Reader {
+                value,
+                path: format!("{}[{index}]", self.path),
+            })
+            .collect())
+    }
+
+    fn node(&self) -> Result<ContentNode<String>, StoryJsonError> {
+        let kind = self.field("type").string()?;
+        let node = match kind.as_str() {
+            "text" => ContentNode::Text(self.field("text").string()?),
+            "link" => ContentNode::Link {
+                text: self.field("text").string()?,
+                target: self.field("target").string()?,
+                setter: self.field("setter").optional_string()?,
+            },
+            "comment" => ContentNode::Comment(self.field("text").string()?),
+            "image" => ContentNode::Image(Image {
+                source: self.field("source").string()?,
+                title: self.field("title").optional_string()?,
+                target: self.field("target").optional_string()?,
+                setter: self.field("setter").optional_string()?,
+                align: match self.field("align").optional_string()?.as_deref() {
+                    None => None,
+                    Some("left") => Some(ImageAlign::Left),
+                    Some("right") => Some(ImageAlign::Right),
+                    Some(_) => return Err(self.field("align").invalid()),
+                },
+            }),
+            "html" => {
+                let attributes = self.field("attributes");
+                let attributes = attributes
+                    .value
+                    .as_object()
+                    .ok_or_else(|| attributes.invalid())?
+                    .keys()
+                    .map(|name| Ok((name.clone(), attributes.field(name).optional_string()?)))
+                    .collect::<Result<_, _>>()?;
+                ContentNode::Html(HtmlTag::new(
+                    self.field("raw").string()?,
+                    self.field("name").string()?,
+                    attributes,
+                    self.field("closing").value.as_bool().unwrap_or_default(),
+                ))
+            }
+            "custom" => ContentNode::Custom(CustomNode::new(
+                self.field("kind").string()?,
+                self.field("raw").string()?,
+            )),
+            "raw" => ContentNode::Raw {
+                dialect: match self.field("dialect").string()?.as_str() {
+                    "sugarcube" => Dialect::SugarCube,
+                    "harlowe" => Dialect::Harlowe,
+                    "mustache" => Dialect::Mustache,
+                    _ => return Err(self.field("dialect").invalid()),
+                },
+                raw: self.field("raw").string()?,
+            },
+            "paragraph" => ContentNode::Paragraph(
+                self.field("inlines")
+                    .items()?
+                    .iter()
+                    .map(Reader::inline)
+                    .collect::<Result<_, _>>()?,
+            ),
+            _ => return Err(self.field("type").invalid()),
+        };
+        Ok(node)
+    }
+
+    fn inline(&self) -> Result<Inline<String>, StoryJsonError> {
+        match self.field("type").string()?.as_str() {
+            "text" => Ok(Inline::Text(self.field("text").string()?)),
+            "break" => Ok(Inline::LineBreak),
+            // Paragraphs don't nest.
+            "paragraph" => Err(self.field("type").invalid()),
+            _ => self.node().map(Inline::Node),
+        }
+    }
+
+    fn passage(&self) -> Result<Passage<String>, StoryJsonError> {
+        let tags = self
+            .field("tags")
+            .items()?
+            .iter()
+            .map(|tag| tag.string().map(Tag::new))
+            .collect::<Result<_, _>>()?;
+        let nodes = self
+            .field("nodes")
+            .items()?
+            .iter()
+            .map(Reader::node)
+            .collect::<Result<_, _>>()?;
+        Ok(Passage::new(
+            self.field("name").string()?,
+            tags,
+            self.field("metadata")
+                .optional_raw_json()
+                .map(Metadata::new),
+            nodes,
+        ))
+    }
+}
+
+impl Story<String> {
+    /// Reads a story from the JSON written by [`to_json`], of this version or an older one.
+    pub fn from_story_json(json: &str) -> Result<Self, StoryJsonError> {
+        let value: Value = serde_json::from_str(json)
+            .map_err(|_| StoryJsonError::Invalid("the root".to_string()))?;
+        let root = Reader {
+            value: &value,
+            path: String::new(),
+        };
+        match root.field("version").value.as_u64() {
+            Some(version) if (1..=VERSION).contains(&version) => {}
+            version => return Err(StoryJsonError::UnsupportedVersion(version)),
+        }
+
+        let mut story: Story<String> = root
+            .field("passages")
+            .items()?
+            .iter()
+            .map(Reader::passage)
+            .collect::<Result<_, _>>()?;
+        story.title = root.field("title").optional_string()?.map(TextBlock::Owned);
+        story.start = root.field("start").optional_string()?.map(TextBlock::Owned);
+        story.data = root.field("data").optional_json().map(TextBlock::Owned);
+        Ok(story)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::{to_json, StoryJsonError, SCHEMA};
+    use crate::{ParseOptions, Story};
+
+    #[test]
+    fn test_to_json() {
+        let input =
+            ":: StoryTitle\nTiny\n\n:: Start [a] {\"size\":\"100,100\"}\nGo [[there|There]]\n";
+        let story = Story::try_from(input).unwrap();
+
+        assert_eq!(
+            to_json(&story),
+            json!({
+                "version": 4,
+                "title": "Tiny",
+                "start": null,
+                "data": null,
+                "passages": [{
+                    "name": "Start",
+                    "tags": ["a"],
+                    "metadata": { "size": "100,100" },
+                    "nodes": [
+                        { "type": "text", "text": "Go " },
+                        { "type": "link", "text": "there", "target": "There" }
+                    ]
+                }]
+            })
+        );
+    }
+
+    #[test]
+    fn test_from_story_json() {
+        let input = ":: StoryData\n{\"start\":\"A\",\"ifid\":\"X\"}\n\n:: A [t] {\"position\":\"1,2\"}\n<b class=\"x\">Hi</b> [>img[Map|map.png][B]] /* c */ [[Go|B][$x to 1]]\n\n:: B\n<<set $x to 2>>End\n";
+        let options = ParseOptions::default().html(true).raw_sigils(true);
+        let story = Story::parse_with(input, options.clone()).unwrap();
+        let json = to_json(&story).to_string();
+        let loaded = Story::from_story_json(&json).unwrap();
+
+        assert_eq!(
+            loaded.iter().collect::<Vec<_>>(),
+            story.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(loaded.start(), story.start());
+        assert_eq!(to_json(&loaded), to_json(&story));
+
+        let story = Story::parse_with(input, options.paragraphs(true)).unwrap();
+        let loaded = Story::from_story_json(&to_json(&story).to_string()).unwrap();
+        assert_eq!(
+            loaded.iter().collect::<Vec<_>>(),
+            story.iter().collect::<Vec<_>>()
+        );
+
+        assert_eq!(
+            Story::from_story_json(r#"{"version":5,"passages":[]}"#),
+            Err(StoryJsonError::UnsupportedVersion(Some(5)))
+        );
+        assert_eq!(
+            Story::from_story_json(
+                r#"{"version":1,"passages":[{"name":"A","tags":[],"nodes":[{"type":"text"}]}]}"#
+            ),
+            Err(StoryJsonError::Invalid(
+                "passages[0].nodes[0].text".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_invalid_metadata_round_trip() {
+        let input = ":: A {position:\"1,2\"}\nHi\n";
+        let story = Story::try_from(input).unwrap();
+        let json = to_json(&story);
+
+        assert_eq!(json["passages"][0]["metadata"], json!("{position:\"1,2\"}"));
+        let loaded = Story::from_story_json(&json.to_string()).unwrap();
+        assert_eq!(
+            loaded.iter().collect::<Vec<_>>(),
+            story.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(loaded.to_twee(), story.to_twee());
+    }
+
+    #[test]
+    fn test_schema() {
+        let schema: Value = serde_json::from_str(SCHEMA).unwrap();
+
+        assert_eq!(
+            schema["properties"]["version"]["const"],
+            json!(super::VERSION)
+        );
+    }
+}
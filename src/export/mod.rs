@@ -0,0 +1,6 @@
+//! Conversions of stories to other formats.
+
+pub mod csv;
+pub mod dot;
+pub mod json;
+pub mod screenplay;
@@ -0,0 +1,69 @@
+use std::{collections::HashSet, fmt::Write, ops::Deref};
+
+use crate::Story;
+
+/// Writes the story as a linear script for table reads and voice-over recording, in Markdown:
+/// each passage under its own heading, its prose, then its choices as a list.
+///
+/// Passages are written as reached walking the story depth first from the start passage,
+/// following the first choice first. Passages that can't be reached are left out.
+pub fn to_screenplay<T>(story: &Story<T>) -> String
+where
+    T: Deref<Target = str>,
+{
+    let mut output = String::new();
+    if let Some(title) = story.title() {
+        let _ = writeln!(output, "# {title}\n");
+    }
+    let mut visited = HashSet::new();
+    let mut stack: Vec<String> = story
+        .start()
+        .map(|start| start.title.to_string())
+        .into_iter()
+        .collect();
+    while let Some(name) = stack.pop() {
+        let Some(passage) = story.get_passage(&name) else {
+            continue;
+        };
+        if !visited.insert(passage.title) {
+            continue;
+        }
+        let _ = writeln!(output, "## {}\n", passage.title);
+        let prose = passage.to_string();
+        let prose = prose.trim();
+        if !prose.is_empty() {
+            let _ = writeln!(output, "{prose}\n");
+        }
+        let choices = passage.choices();
+        for choice in &choices {
+            let _ = match choice.text == choice.target {
+                true => writeln!(output, "- {}", choice.target),
+                false => writeln!(output, "- {} → {}", choice.text, choice.target),
+            };
+        }
+        if !choices.is_empty() {
+            output.push('\n');
+        }
+        stack.extend(choices.iter().rev().map(|choice| choice.target.to_string()));
+    }
+    // Sections end with a blank line, but the last one.
+    output.truncate(output.trim_end().len() + 1);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_screenplay;
+    use crate::Story;
+
+    #[test]
+    fn test_to_screenplay() {
+        let input = ":: StoryTitle\nCave\n\n:: Start\nIt is dark.\n[[Light a match|Match]]\n[[Wait]]\n\n:: Wait\nNothing happens. [[Start]]\n\n:: Match\nYou see a door.\n\n:: Unused\nNever read.\n";
+        let story = Story::try_from(input).unwrap();
+
+        assert_eq!(
+            to_screenplay(&story),
+            "# Cave\n\n## Start\n\nIt is dark.\nLight a match\nWait\n\n- Light a match → Match\n- Wait\n\n## Match\n\nYou see a door.\n\n## Wait\n\nNothing happens. Start\n\n- Start\n"
+        );
+    }
+}
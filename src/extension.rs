@@ -0,0 +1,183 @@
+//! Node kinds added by story format plugins, so that syntax such as Harlowe hooks or the macros
+//! of a house format is read as its own [`ContentNode::Custom`] nodes.
+//!
+//! ```
+//! use std::ops::Range;
+//! use twee_v3::{extension::NodeKind, ContentNode, ParseOptions, Story};
+//!
+//! /// `{{variable}}` placeholders.
+//! struct Placeholder;
+//!
+//! impl NodeKind for Placeholder {
+//!     fn name(&self) -> &str {
+//!         "placeholder"
+//!     }
+//!
+//!     fn find(&self, text: &str) -> Option<Range<usize>> {
+//!         let start = text.find("{{")?;
+//!         let len = text[start..].find("}}")? + 2;
+//!         Some(start..start + len)
+//!     }
+//! }
+//!
+//! let options = ParseOptions::default().node_kind(Placeholder);
+//! let story = Story::parse_with(":: Start\nHello {{name}}!\n", options).unwrap();
+//! let passage = story.get_passage("Start").unwrap();
+//! assert!(matches!(
+//!     &passage.nodes()[1],
+//!     ContentNode::Custom(node) if node.kind() == "placeholder" && *node.raw() == "{{name}}"
+//! ));
+//! ```
+
+use std::{fmt::Debug, ops::Range, sync::Arc};
+
+use crate::{ContentNode, CustomNode};
+
+/// A kind of node recognized in the text of passages, see [`ParseOptions::node_kind`](crate::ParseOptions::node_kind).
+pub trait NodeKind: Send + Sync {
+    /// The name given to the nodes of this kind, see [`CustomNode::kind`].
+    fn name(&self) -> &str;
+
+    /// Byte range of the first node of this kind in `text`, a run of passage text between
+    /// links and comments. Empty ranges are ignored.
+    fn find(&self, text: &str) -> Option<Range<usize>>;
+}
+
+/// The node kinds registered in [`ParseOptions`](crate::ParseOptions), tried in order.
+#[derive(Clone, Default)]
+pub struct NodeKinds {
+    kinds: Vec<Arc<dyn NodeKind>>,
+}
+
+impl NodeKinds {
+    pub fn push(&mut self, kind: impl NodeKind + 'static) {
+        self.kinds.push(Arc::new(kind));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.kinds.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn NodeKind> {
+        self.kinds.iter().map(|kind| &**kind)
+    }
+
+    /// Splits the nodes of the registered kinds out of a text node. When matches overlap, the
+    /// earliest wins, then the kind registered first.
+    pub(crate) fn split<'a>(&self, text: &'a str) -> Vec<ContentNode<&'a str>> {
+        let mut nodes = vec![];
+        let mut index = 0;
+        loop {
+            let found = self
+                .kinds
+                .iter()
+                .filter_map(|kind| {
+                    let range = kind.find(&text[index..])?;
+                    let valid = !range.is_empty() && text[index..].get(range.clone()).is_some();
+                    valid.then(|| (index + range.start..index + range.end, kind))
+                })
+                .min_by_key(|(range, _)| range.start);
+            let Some((range, kind)) = found else {
+                break;
+            };
+            if index < range.start {
+                nodes.push(ContentNode::text_node(&text[index..range.start]));
+            }
+            nodes.push(ContentNode::Custom(CustomNode::new(
+                kind.name(),
+                &text[range.clone()],
+            )));
+            index = range.end;
+        }
+        if index < text.len() {
+            nodes.push(ContentNode::text_node(&text[index..]));
+        }
+        nodes
+    }
+}
+
+impl Debug for NodeKinds {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.kinds.iter().map(|kind| kind.name()))
+            .finish()
+    }
+}
+
+/// Node kinds are compared by identity, clones of the same registration being equal.
+impl PartialEq for NodeKinds {
+    fn eq(&self, other: &Self) -> bool {
+        self.kinds.len() == other.kinds.len()
+            && self
+                .kinds
+                .iter()
+                .zip(&other.kinds)
+                .all(|(kind, other)| Arc::ptr_eq(kind, other))
+    }
+}
+
+impl Eq for NodeKinds {}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use super::{NodeKind, NodeKinds};
+    use crate::{ContentNode, CustomNode, ParseOptions, Story};
+
+    struct Delimited(&'static str, &'static str, &'static str);
+
+    impl NodeKind for Delimited {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn find(&self, text: &str) -> Option<Range<usize>> {
+            let start = text.find(self.1)?;
+            let len = text[start..].find(self.2)? + self.2.len();
+            Some(start..start + len)
+        }
+    }
+
+    #[test]
+    fn test_split() {
+        let mut kinds = NodeKinds::default();
+        kinds.push(Delimited("hook", "|h>[", "]"));
+        kinds.push(Delimited("macro", "(", ")"));
+
+        assert_eq!(
+            kinds.split("a (set: $x) |h>[b (c)] d"),
+            [
+                ContentNode::text_node("a "),
+                ContentNode::Custom(CustomNode::new("macro", "(set: $x)")),
+                ContentNode::text_node(" "),
+                ContentNode::Custom(CustomNode::new("hook", "|h>[b (c)]")),
+                ContentNode::text_node(" d"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_with_node_kinds() {
+        let options = ParseOptions::default()
+            .html(true)
+            .node_kind(Delimited("macro", "<<", ">>"));
+        let input = ":: A\n<b><<set $x to 1>></b> [[B]]\n\n:: B\nx\n\n";
+        let story = Story::parse_with(input, options.clone()).unwrap();
+        let passage = story.get_passage("A").unwrap();
+
+        assert!(matches!(
+            passage.nodes(),
+            [
+                ContentNode::Html(_),
+                ContentNode::Custom(node),
+                ContentNode::Html(_),
+                ContentNode::Text(_),
+                ContentNode::Link { .. },
+            ] if node.kind() == "macro" && *node.raw() == "<<set $x to 1>>"
+        ));
+        assert_eq!(story.to_twee(), input);
+        assert_eq!(options.clone(), options);
+        assert_ne!(options, ParseOptions::default());
+    }
+}
@@ -0,0 +1,93 @@
+//! Story formats, read from the `format` field of StoryData as Tweego does.
+
+use std::ops::Deref;
+
+use crate::Story;
+
+/// The story format a story targets, deciding which macros and markup its content uses.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StoryFormat {
+    Harlowe,
+    SugarCube,
+    Chapbook,
+    Snowman,
+    /// Unknown or unspecified: the syntaxes of all formats are read.
+    #[default]
+    Plain,
+}
+
+impl StoryFormat {
+    /// The format named `name` in StoryData, ignoring case.
+    pub fn from_name(name: &str) -> Self {
+        match name.trim().to_lowercase().as_str() {
+            "harlowe" => StoryFormat::Harlowe,
+            "sugarcube" => StoryFormat::SugarCube,
+            "chapbook" => StoryFormat::Chapbook,
+            "snowman" => StoryFormat::Snowman,
+            _ => StoryFormat::Plain,
+        }
+    }
+
+    /// Whether the story uses SugarCube macros, `<<name ...>>`.
+    pub(crate) fn sugarcube(self) -> bool {
+        matches!(self, StoryFormat::SugarCube | StoryFormat::Plain)
+    }
+
+    /// Whether the story uses Harlowe macros, `(name: ...)`.
+    pub(crate) fn harlowe(self) -> bool {
+        matches!(self, StoryFormat::Harlowe | StoryFormat::Plain)
+    }
+
+    /// Whether the story uses Chapbook inserts, `{name: ...}`.
+    pub(crate) fn chapbook(self) -> bool {
+        matches!(self, StoryFormat::Chapbook | StoryFormat::Plain)
+    }
+}
+
+impl<T> Story<T>
+where
+    T: Deref<Target = str>,
+{
+    /// The format of the story: the one set with
+    /// [`ParseOptions::story_format`](crate::ParseOptions::story_format), or else the one named
+    /// in StoryData.
+    pub fn story_format(&self) -> StoryFormat {
+        self.format.unwrap_or_else(|| {
+            self.format_name()
+                .map_or(StoryFormat::Plain, |name| StoryFormat::from_name(&name))
+        })
+    }
+
+    /// Sets the format of the story, overriding StoryData.
+    pub fn set_story_format(&mut self, format: StoryFormat) {
+        self.format = Some(format);
+        self.invalidate_derived();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StoryFormat;
+    use crate::{ParseOptions, Story};
+
+    const STORY: &str = ":: StoryData\n{\"format\": \"Harlowe\", \"format-version\": \"3.3.8\"}\n\n:: Start\n<<include \"A\">> (display: \"B\")\n\n:: A\nA\n\n:: B\nB\n";
+
+    #[test]
+    fn test_story_format() {
+        let story = Story::try_from(STORY).unwrap();
+
+        assert_eq!(story.story_format(), StoryFormat::Harlowe);
+        assert_eq!(story.format_version().as_deref(), Some("3.3.8"));
+        assert_eq!(story.includes("Start"), ["B"]);
+        assert_eq!(story.dependents_of("A").count(), 0);
+    }
+
+    #[test]
+    fn test_story_format_override() {
+        let options = ParseOptions::default().story_format(StoryFormat::SugarCube);
+        let story = Story::parse_with(STORY, options).unwrap();
+
+        assert_eq!(story.story_format(), StoryFormat::SugarCube);
+        assert_eq!(story.includes("Start"), ["A"]);
+    }
+}
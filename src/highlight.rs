@@ -0,0 +1,214 @@
+//! Classified spans of a story source, for syntax highlighters and web editors.
+
+use std::ops::{Deref, Range};
+
+use crate::{
+    lexer::{tokenize, TokenKind},
+    parser::passage::parse_content,
+    prose::macro_ranges,
+    ContentNode, LinkDialects, Story,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SpanKind {
+    /// The `::` and title of a passage header.
+    Header,
+    Tag,
+    Metadata,
+    LinkText,
+    /// The target of a link or image, or a whole simple link like `[[B]]`.
+    LinkTarget,
+    LinkSetter,
+    /// A backslash and the character it escapes.
+    Escape,
+    /// A macro of the story format, see [`Story::story_format`].
+    Macro,
+    Comment,
+    /// Text that isn't part of a passage, or a line starting with `::` that isn't a header.
+    Invalid,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HighlightSpan {
+    pub kind: SpanKind,
+    /// Byte range of the span in the source.
+    pub range: Range<usize>,
+}
+
+/// Pushes an escape span for each backslash of `text`, starting at `start`, that escapes
+/// one of `escaped`.
+fn push_escapes(spans: &mut Vec<HighlightSpan>, text: &str, start: usize, escaped: &[char]) {
+    let mut chars = text.char_indices();
+    while let Some((index, c)) = chars.next() {
+        if c != '\\' {
+            continue;
+        }
+        if let Some((_, next)) = chars.next().filter(|(_, next)| escaped.contains(next)) {
+            spans.push(HighlightSpan {
+                kind: SpanKind::Escape,
+                range: start + index..start + index + 1 + next.len_utf8(),
+            });
+        }
+    }
+}
+
+impl<T> Story<T>
+where
+    T: Deref<Target = str>,
+{
+    /// Classified spans of the source the story was parsed from, sorted by start. Escapes may
+    /// lie inside header and tag spans, other spans don't overlap. Plain text has no span.
+    ///
+    /// Links are read with the default [`LinkDialects`]. A story built in code has no source,
+    /// and so no span.
+    pub fn highlight_spans(&self) -> Vec<HighlightSpan> {
+        let source: &str = &self.content;
+        let format = self.story_format();
+        let offset = |slice: &str| slice.as_ptr() as usize - source.as_ptr() as usize;
+        let mut spans = vec![];
+        let push = |spans: &mut Vec<HighlightSpan>, kind, range| {
+            spans.push(HighlightSpan { kind, range });
+        };
+
+        let tokens = tokenize(source);
+        let mut tokens = tokens.iter().peekable();
+        let mut header_start = 0;
+        while let Some(token) = tokens.next() {
+            let start = token.range.start;
+            match token.kind {
+                TokenKind::HeaderMarker => header_start = start,
+                TokenKind::Title => {
+                    push(&mut spans, SpanKind::Header, header_start..token.range.end);
+                    push_escapes(&mut spans, token.text, start, &['[', ']', '{', '}', '\\']);
+                }
+                TokenKind::TagBlock => {
+                    let tags = token.text.trim_start_matches('[').trim_end_matches(']');
+                    for tag in tags.split(' ').filter(|tag| !tag.is_empty()) {
+                        let tag_start = start + offset(tag) - offset(token.text);
+                        push(&mut spans, SpanKind::Tag, tag_start..tag_start + tag.len());
+                        push_escapes(&mut spans, tag, tag_start, &['[', ']', '{', '}', '\\']);
+                    }
+                }
+                TokenKind::Metadata => push(&mut spans, SpanKind::Metadata, token.range.clone()),
+                TokenKind::Invalid => push(&mut spans, SpanKind::Invalid, token.range.clone()),
+                TokenKind::Content => {
+                    // The content lines of a passage are parsed together, as links may span
+                    // several lines.
+                    let mut end = token.range.end;
+                    while let Some(next) = tokens.next_if(|next| next.kind == TokenKind::Content) {
+                        end = next.range.end;
+                    }
+                    let Ok((_, nodes)) =
+                        parse_content(&source[start..end], LinkDialects::default())
+                    else {
+                        continue;
+                    };
+                    for (node_source, node) in nodes {
+                        let node_start = offset(node_source);
+                        let range_of = |text: &str| offset(text)..offset(text) + text.len();
+                        match node {
+                            ContentNode::Text(text) => {
+                                push_escapes(&mut spans, text, node_start, &['[']);
+                                for range in macro_ranges(text, format) {
+                                    let range = node_start + range.start..node_start + range.end;
+                                    push(&mut spans, SpanKind::Macro, range);
+                                }
+                            }
+                            ContentNode::Link {
+                                text,
+                                target,
+                                setter,
+                            } => {
+                                if text.as_ptr() != target.as_ptr() {
+                                    push(&mut spans, SpanKind::LinkText, range_of(text));
+                                }
+                                push(&mut spans, SpanKind::LinkTarget, range_of(target));
+                                if let Some(setter) = setter {
+                                    push(&mut spans, SpanKind::LinkSetter, range_of(setter));
+                                }
+                            }
+                            ContentNode::Image(image) => {
+                                if let Some(target) = image.target() {
+                                    push(&mut spans, SpanKind::LinkTarget, range_of(target));
+                                }
+                            }
+                            ContentNode::Comment(_) => push(
+                                &mut spans,
+                                SpanKind::Comment,
+                                node_start..node_start + node_source.len(),
+                            ),
+                            ContentNode::Html(_)
+                            | ContentNode::Custom(_)
+                            | ContentNode::Raw { .. }
+                            | ContentNode::Paragraph(_) => {}
+                        }
+                    }
+                }
+            }
+        }
+        // The ranges are in the source, byte order mark included.
+        for span in &mut spans {
+            span.range = span.range.start + self.bom..span.range.end + self.bom;
+        }
+        spans.sort_by_key(|span| span.range.start);
+        spans
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SpanKind;
+    use crate::Story;
+
+    #[test]
+    fn test_highlight_spans() {
+        let input = ":: StoryData\n{\"format\":\"SugarCube\"}\n\n:: A\\[1\\] [x y] {\"n\":1}\nGo [[east->B][$d to 1]] <<set $x to 2>> /* c */ \\[[no]] [[B]]\n\n:: B\nEnd\n";
+        let story = Story::try_from(input).unwrap();
+        let spans: Vec<_> = story
+            .highlight_spans()
+            .into_iter()
+            .map(|span| (span.kind, &input[span.range]))
+            .collect();
+
+        assert_eq!(
+            spans,
+            [
+                (SpanKind::Header, ":: StoryData"),
+                (SpanKind::Header, ":: A\\[1\\]"),
+                (SpanKind::Escape, "\\["),
+                (SpanKind::Escape, "\\]"),
+                (SpanKind::Tag, "x"),
+                (SpanKind::Tag, "y"),
+                (SpanKind::Metadata, "{\"n\":1}"),
+                (SpanKind::LinkText, "east"),
+                (SpanKind::LinkTarget, "B"),
+                (SpanKind::LinkSetter, "$d to 1"),
+                (SpanKind::Macro, "<<set $x to 2>>"),
+                (SpanKind::Comment, "/* c */"),
+                (SpanKind::Escape, "\\["),
+                (SpanKind::LinkTarget, "B"),
+                (SpanKind::Header, ":: B"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_highlight_spans_after_bom() {
+        let input = "\u{feff}:: A [x]\nGo [[B]]\n";
+        let story = Story::try_from(input).unwrap();
+        let spans: Vec<_> = story
+            .highlight_spans()
+            .into_iter()
+            .map(|span| (span.kind, &input[span.range]))
+            .collect();
+
+        assert_eq!(
+            spans,
+            [
+                (SpanKind::Header, ":: A"),
+                (SpanKind::Tag, "x"),
+                (SpanKind::LinkTarget, "B"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,325 @@
+//! Extraction and application of translations.
+//!
+//! Every text node and link text gets a key made of the passage title and the node index,
+//! like `Start#2`. Keys are stable as long as the passage structure doesn't change. Inside
+//! paragraphs, the index of the inline is added, like `Start#0.3`.
+
+use std::{collections::HashMap, fmt::Write, ops::Deref};
+
+use serde_json::{Map, Value};
+
+use crate::{ContentNode, Inline, Story};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CatalogEntry {
+    pub key: String,
+    pub source: String,
+    pub translation: Option<String>,
+}
+
+/// Translatable strings of a story.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Catalog {
+    pub entries: Vec<CatalogEntry>,
+}
+
+pub fn key(passage: &str, node: usize) -> String {
+    format!("{passage}#{node}")
+}
+
+/// The key of the inline at `inline` in the paragraph at `node`.
+pub fn inline_key(passage: &str, node: usize, inline: usize) -> String {
+    format!("{passage}#{node}.{inline}")
+}
+
+/// The translatable text of `node`, if any.
+fn source<'a>(node: &ContentNode<&'a str>) -> Option<&'a str> {
+    match node {
+        ContentNode::Text(text) if !text.trim().is_empty() => Some(text),
+        ContentNode::Link { text, .. } => Some(text),
+        _ => None,
+    }
+}
+
+/// `node` with its text replaced by `translation`, for text nodes and links.
+fn translate(node: &ContentNode<&str>, translation: &str) -> Option<ContentNode<String>> {
+    match node {
+        ContentNode::Text(_) => Some(ContentNode::Text(translation.to_string())),
+        ContentNode::Link { target, setter, .. } => Some(ContentNode::Link {
+            text: translation.to_string(),
+            target: target.to_string(),
+            setter: setter.map(str::to_owned),
+        }),
+        ContentNode::Comment(_)
+        | ContentNode::Html(_)
+        | ContentNode::Image(_)
+        | ContentNode::Custom(_)
+        | ContentNode::Raw { .. }
+        | ContentNode::Paragraph(_) => None,
+    }
+}
+
+fn escape_po(input: &str) -> String {
+    input
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t")
+}
+
+fn escape_xml(input: &str) -> String {
+    crate::render::escape_html(input)
+}
+
+impl Catalog {
+    pub fn set_translation(&mut self, key: &str, translation: impl Into<String>) -> bool {
+        match self.entries.iter_mut().find(|entry| entry.key == key) {
+            Some(entry) => {
+                entry.translation = Some(translation.into());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// A JSON object of keys to translations, or to source strings when not translated.
+    pub fn to_json(&self) -> Value {
+        let entries: Map<String, Value> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let text = entry.translation.as_ref().unwrap_or(&entry.source);
+                (entry.key.clone(), Value::String(text.clone()))
+            })
+            .collect();
+        Value::Object(entries)
+    }
+
+    /// Reads translations from a JSON object of keys to strings. Unknown keys are ignored.
+    pub fn load_json(&mut self, translations: &Value) {
+        let Some(translations) = translations.as_object() else {
+            return;
+        };
+        for entry in &mut self.entries {
+            if let Some(text) = translations.get(&entry.key).and_then(Value::as_str) {
+                entry.translation = Some(text.to_string());
+            }
+        }
+    }
+
+    /// A gettext PO file, using keys as message contexts.
+    pub fn to_po(&self) -> String {
+        let mut output = String::new();
+        for entry in &self.entries {
+            let translation = entry.translation.as_deref().unwrap_or_default();
+            let _ = write!(
+                output,
+                "msgctxt \"{}\"\nmsgid \"{}\"\nmsgstr \"{}\"\n\n",
+                escape_po(&entry.key),
+                escape_po(&entry.source),
+                escape_po(translation)
+            );
+        }
+        output
+    }
+
+    /// An XLIFF 1.2 document.
+    pub fn to_xliff(&self, source_language: &str, target_language: &str) -> String {
+        let mut output = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(
+            output,
+            "<xliff version=\"1.2\" xmlns=\"urn:oasis:names:tc:xliff:document:1.2\">\n  <file source-language=\"{}\" target-language=\"{}\" datatype=\"plaintext\" original=\"story\">\n    <body>",
+            escape_xml(source_language),
+            escape_xml(target_language)
+        );
+        for entry in &self.entries {
+            let _ = writeln!(
+                output,
+                "      <trans-unit id=\"{}\">\n        <source>{}</source>",
+                escape_xml(&entry.key),
+                escape_xml(&entry.source)
+            );
+            if let Some(translation) = &entry.translation {
+                let _ = writeln!(
+                    output,
+                    "        <target>{}</target>",
+                    escape_xml(translation)
+                );
+            }
+            output.push_str("      </trans-unit>\n");
+        }
+        output.push_str("    </body>\n  </file>\n</xliff>\n");
+        output
+    }
+}
+
+impl<T> Story<T>
+where
+    T: Deref<Target = str>,
+{
+    /// Collects text nodes and link texts, skipping blank text.
+    pub fn extract_strings(&self) -> Catalog {
+        let mut entries = vec![];
+        for passage in self.iter() {
+            for (index, node) in passage.content.iter().enumerate() {
+                let ContentNode::Paragraph(inlines) = node else {
+                    if let Some(source) = source(node) {
+                        entries.push(CatalogEntry {
+                            key: key(passage.title, index),
+                            source: source.to_string(),
+                            translation: None,
+                        });
+                    }
+                    continue;
+                };
+                for (inline, node) in inlines.iter().enumerate() {
+                    let source = match node {
+                        Inline::Text(text) => source(&ContentNode::Text(text)),
+                        Inline::LineBreak => None,
+                        Inline::Node(node) => source(node),
+                    };
+                    if let Some(source) = source {
+                        entries.push(CatalogEntry {
+                            key: inline_key(passage.title, index, inline),
+                            source: source.to_string(),
+                            translation: None,
+                        });
+                    }
+                }
+            }
+        }
+        Catalog { entries }
+    }
+
+    /// Builds a copy of the story with the translated strings of `catalog`. Link targets are
+    /// left untouched.
+    pub fn apply_translations(&self, catalog: &Catalog) -> Story<String> {
+        let translations: HashMap<_, _> = catalog
+            .entries
+            .iter()
+            .filter_map(|entry| Some((entry.key.as_str(), entry.translation.as_ref()?)))
+            .collect();
+
+        let mut localized = self.to_owned_story();
+        for passage in self.iter() {
+            let mut translated = passage.clone().into_owned();
+            let mut changed = false;
+            for (index, node) in passage.content.iter().enumerate() {
+                let node = match node {
+                    ContentNode::Paragraph(inlines) => {
+                        let keys: Vec<_> = (0..inlines.len())
+                            .map(|inline| inline_key(passage.title, index, inline))
+                            .collect();
+                        if !keys
+                            .iter()
+                            .any(|key| translations.contains_key(key.as_str()))
+                        {
+                            continue;
+                        }
+                        let inlines = inlines.iter().zip(&keys).map(|(inline, key)| {
+                            let translation = translations.get(key.as_str());
+                            match (inline, translation) {
+                                (Inline::Text(_), Some(translation)) => {
+                                    Inline::Text(translation.to_string())
+                                }
+                                (Inline::Node(node), Some(translation)) => {
+                                    match translate(node, translation) {
+                                        Some(node) => Inline::Node(node),
+                                        None => Inline::Node(node.clone().into_owned()),
+                                    }
+                                }
+                                (inline, _) => inline.clone().into_owned(),
+                            }
+                        });
+                        ContentNode::Paragraph(inlines.collect())
+                    }
+                    node => {
+                        let translation = translations.get(key(passage.title, index).as_str());
+                        match translation.and_then(|translation| translate(node, translation)) {
+                            Some(node) => node,
+                            None => continue,
+                        }
+                    }
+                };
+                translated.replace_node(index, node);
+                changed = true;
+            }
+            if changed {
+                localized.insert_passage(translated);
+            }
+        }
+        localized
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::Story;
+
+    const INPUT: &str = ":: Start\nHello!\n[[Leave->End]]\n\n:: End\nBye \"friend\"\n";
+
+    #[test]
+    fn test_extract_strings() {
+        let story = Story::try_from(INPUT).unwrap();
+        let catalog = story.extract_strings();
+
+        assert_eq!(
+            catalog.to_json(),
+            json!({
+                "Start#0": "Hello!\n",
+                "Start#1": "Leave",
+                "End#0": "Bye \"friend\"",
+            })
+        );
+    }
+
+    #[test]
+    fn test_apply_translations() {
+        let story = Story::try_from(INPUT).unwrap();
+        let mut catalog = story.extract_strings();
+        catalog.load_json(&json!({ "Start#0": "Bonjour !\n", "Start#1": "Partir" }));
+
+        let localized = story.apply_translations(&catalog);
+
+        let start = localized.get_passage("Start").unwrap();
+        assert_eq!(start.to_string(), "Bonjour !\nPartir");
+        assert_eq!(start.links().next().map(|link| *link.target), Some("End"));
+        assert_eq!(
+            localized.get_passage("End").unwrap().to_string(),
+            "Bye \"friend\""
+        );
+    }
+
+    #[test]
+    fn test_to_po() {
+        let story = Story::try_from(INPUT).unwrap();
+        let mut catalog = story.extract_strings();
+        assert!(catalog.set_translation("End#0", "Salut"));
+
+        assert!(catalog
+            .to_po()
+            .ends_with("msgctxt \"End#0\"\nmsgid \"Bye \\\"friend\\\"\"\nmsgstr \"Salut\"\n\n"));
+    }
+
+    #[test]
+    fn test_to_po_crlf() {
+        let story = Story::try_from(":: A\r\nOne\tline\r\nTwo\r\n").unwrap();
+        let po = story.extract_strings().to_po();
+
+        assert!(!po.contains(['\r', '\t']));
+        assert!(po.contains("msgid \"One\\tline\\r\\nTwo\""));
+    }
+
+    #[test]
+    fn test_to_xliff() {
+        let story = Story::try_from(":: A\nFish & chips\n").unwrap();
+        let xliff = story.extract_strings().to_xliff("en", "fr");
+
+        assert!(
+            xliff.contains("<trans-unit id=\"A#0\">\n        <source>Fish &amp; chips</source>")
+        );
+    }
+}
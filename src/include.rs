@@ -0,0 +1,504 @@
+//! Resolution of passages included in others, with SugarCube's `<<include "Passage">>` or
+//! `<<include [[Passage]]>>`, Harlowe's `(display: "Passage")` or Chapbook's `{embed passage: "Passage"}`, as allowed by
+//! the story format.
+
+use std::{collections::HashMap, ops::Deref};
+
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_until},
+    character::complete::{char, multispace0, multispace1},
+    error::{Error, ErrorKind, ParseError},
+    sequence::{delimited, pair},
+    Err, IResult,
+};
+
+use crate::{ContentNode, Inline, Passage, Story, StoryFormat};
+
+fn parse_quoted(input: &str) -> IResult<&str, &str> {
+    alt((
+        delimited(char('"'), take_until("\""), char('"')),
+        delimited(char('\''), take_until("'"), char('\'')),
+    ))(input)
+}
+
+fn fail(input: &str) -> IResult<&str, &str> {
+    Err(Err::Error(Error::from_error_kind(input, ErrorKind::Tag)))
+}
+
+/// Parses an include macro of `format`, returning the name of the included passage.
+fn parse_include(input: &str, format: StoryFormat) -> IResult<&str, &str> {
+    let sugarcube = |input| match format.sugarcube() {
+        true => delimited(
+            pair(tag("<<include"), multispace1),
+            parse_quoted,
+            pair(take_until(">>"), tag(">>")),
+        )(input),
+        false => fail(input),
+    };
+    let harlowe = |input| match format.harlowe() {
+        true => delimited(
+            pair(tag("(display:"), multispace0),
+            parse_quoted,
+            pair(multispace0, char(')')),
+        )(input),
+        false => fail(input),
+    };
+    let chapbook = |input| match format.chapbook() {
+        true => delimited(
+            pair(tag("{embed passage:"), multispace0),
+            parse_quoted,
+            pair(multispace0, char('}')),
+        )(input),
+        false => fail(input),
+    };
+    alt((sugarcube, harlowe, chapbook))(input)
+}
+
+/// Splits `text` around its include macros, giving each part with the passage it includes, if
+/// any.
+fn split_includes(text: &str, format: StoryFormat) -> Vec<(&str, Option<&str>)> {
+    let mut parts = vec![];
+    let mut rest = text;
+    let mut index = 0;
+    while let Some(position) = rest[index..].find(['<', '(', '{']) {
+        let start = index + position;
+        match parse_include(&rest[start..], format) {
+            Ok((after, target)) => {
+                if start > 0 {
+                    parts.push((&rest[..start], None));
+                }
+                let end = rest.len() - after.len();
+                parts.push((&rest[start..end], Some(target)));
+                rest = after;
+                index = 0;
+            }
+            Err(_) => index = start + 1,
+        }
+    }
+    if !rest.is_empty() {
+        parts.push((rest, None));
+    }
+    parts
+}
+
+/// The start of a SugarCube include macro taking a link, `<<include [[Passage]]>>`, at the end
+/// of `text`, the link being the next node.
+fn link_include_start(text: &str) -> Option<usize> {
+    let start = text.rfind("<<include")?;
+    let spaces = &text[start + "<<include".len()..];
+    (!spaces.is_empty() && spaces.trim().is_empty()).then_some(start)
+}
+
+/// The length of the end of a SugarCube include macro taking a link, at the start of `text`.
+fn link_include_end(text: &str) -> Option<usize> {
+    let end = text.find(">>")?;
+    (!text[..end].contains("<<")).then_some(end + ">>".len())
+}
+
+/// Splits `nodes` around their include macros, giving each part with the passage it includes,
+/// if any. Includes taking a link span the link and the text around it.
+fn split_include_nodes(
+    nodes: Vec<ContentNode<&str>>,
+    format: StoryFormat,
+) -> Vec<(Vec<ContentNode<&str>>, Option<&str>)> {
+    let mut parts = vec![];
+    let mut index = 0;
+    // The text after an include taking a link, not split yet.
+    let mut rest = None;
+    loop {
+        let text = match rest.take() {
+            Some(text) => text,
+            None => match nodes.get(index) {
+                Some(ContentNode::Text(text)) => {
+                    index += 1;
+                    *text
+                }
+                Some(node) => {
+                    index += 1;
+                    parts.push((vec![node.clone()], None));
+                    continue;
+                }
+                None => break,
+            },
+        };
+        let mut text_parts = split_includes(text, format);
+        let link_include = match (text_parts.last(), nodes.get(index), nodes.get(index + 1)) {
+            (
+                Some((before, None)),
+                Some(link @ ContentNode::Link { target, .. }),
+                Some(ContentNode::Text(after)),
+            ) if format.sugarcube() => link_include_start(before)
+                .zip(link_include_end(after))
+                .map(|(start, end)| (start, link.clone(), *target, after.split_at(end))),
+            _ => None,
+        };
+        let Some((start, link, target, (end, after))) = link_include else {
+            parts.extend(
+                text_parts
+                    .into_iter()
+                    .map(|(part, target)| (vec![ContentNode::Text(part)], target)),
+            );
+            continue;
+        };
+        let (before, _) = text_parts.pop().unwrap_or_default();
+        let (before, open) = before.split_at(start);
+        if !before.is_empty() {
+            text_parts.push((before, None));
+        }
+        parts.extend(
+            text_parts
+                .into_iter()
+                .map(|(part, target)| (vec![ContentNode::Text(part)], target)),
+        );
+        let include = vec![ContentNode::Text(open), link, ContentNode::Text(end)];
+        parts.push((include, Some(target)));
+        index += 2;
+        rest = (!after.is_empty()).then_some(after);
+    }
+    parts
+}
+
+/// How many levels of includes [`Story::flattened_passage`] resolves.
+const MAX_INCLUDE_DEPTH: usize = 64;
+
+/// How many nodes [`Story::flattened_passage`] produces at most before it stops resolving
+/// includes.
+const MAX_INCLUDED_NODES: usize = 100_000;
+
+/// The state of [`Story::flattened_passage`].
+struct Flattening<'a> {
+    format: StoryFormat,
+    /// The passages being included, the flattened one first.
+    stack: Vec<&'a str>,
+    /// The flattened content of passages already included.
+    cache: HashMap<&'a str, Vec<ContentNode<&'a str>>>,
+    nodes: usize,
+    /// Whether an include was kept as text because of the stack or a limit.
+    cut: bool,
+}
+
+/// Names of the passages included by `passage`, in order.
+pub(crate) fn included_passages<'a>(
+    passage: &Passage<&'a str>,
+    format: StoryFormat,
+) -> Vec<&'a str> {
+    let nodes = passage
+        .content
+        .iter()
+        .flat_map(ContentNode::flatten)
+        .collect();
+    split_include_nodes(nodes, format)
+        .into_iter()
+        .filter_map(|(_, target)| target)
+        .collect()
+}
+
+impl<T> Story<T>
+where
+    T: Deref<Target = str>,
+{
+    /// Names of the passages included by the passage `name`, in order.
+    pub fn includes(&self, name: &str) -> Vec<&str> {
+        self.passages
+            .get(name)
+            .map(|passage| {
+                included_passages(&passage.as_borrowed(&self.content), self.story_format())
+            })
+            .unwrap_or_default()
+    }
+
+    /// The passage `name` with its include macros replaced by the nodes of the passages they
+    /// include, recursively.
+    ///
+    /// Includes of missing passages, includes that would loop, see [`Story::include_cycles`],
+    /// and includes past 64 levels or once the passage has 100,000 nodes are kept as text.
+    pub fn flattened_passage(&self, name: &str) -> Option<Passage<&str>> {
+        let mut passage = self.get_passage(name)?;
+        let content = std::mem::take(&mut passage.content);
+        let mut flattening = Flattening {
+            format: self.story_format(),
+            stack: vec![passage.title],
+            cache: HashMap::new(),
+            nodes: 0,
+            cut: false,
+        };
+        passage.content = self.flatten_nodes(content, &mut flattening);
+        passage.source = None;
+        Some(passage)
+    }
+
+    /// The flattened content of the passage `name`, or `None` if it's missing or can't be
+    /// included here.
+    fn flatten_include<'a>(
+        &'a self,
+        name: &'a str,
+        flattening: &mut Flattening<'a>,
+    ) -> Option<Vec<ContentNode<&'a str>>> {
+        if let Some(cached) = flattening.cache.get(name) {
+            if flattening.nodes + cached.len() > MAX_INCLUDED_NODES {
+                flattening.cut = true;
+                return None;
+            }
+            flattening.nodes += cached.len();
+            return Some(cached.clone());
+        }
+        if flattening.stack.contains(&name)
+            || flattening.stack.len() > MAX_INCLUDE_DEPTH
+            || flattening.nodes >= MAX_INCLUDED_NODES
+        {
+            flattening.cut = true;
+            return None;
+        }
+        let included = self.get_passage(name)?;
+        let outer_cut = std::mem::take(&mut flattening.cut);
+        flattening.stack.push(included.title);
+        let content = self.flatten_nodes(included.content, flattening);
+        flattening.stack.pop();
+        // Content cut short depends on where the passage is included from.
+        if !flattening.cut {
+            flattening.cache.insert(included.title, content.clone());
+        }
+        flattening.cut |= outer_cut;
+        Some(content)
+    }
+
+    fn flatten_nodes<'a>(
+        &'a self,
+        nodes: Vec<ContentNode<&'a str>>,
+        flattening: &mut Flattening<'a>,
+    ) -> Vec<ContentNode<&'a str>> {
+        let mut flattened = vec![];
+        for (part, target) in split_include_nodes(nodes, flattening.format) {
+            if let Some(included) =
+                target.and_then(|target| self.flatten_include(target, flattening))
+            {
+                flattened.extend(included);
+                continue;
+            }
+            for node in part {
+                match node {
+                    ContentNode::Paragraph(inlines) => {
+                        let paragraph = self.flatten_inlines(inlines, flattening);
+                        flattened.push(ContentNode::Paragraph(paragraph));
+                    }
+                    node => {
+                        flattening.nodes += 1;
+                        flattened.push(node);
+                    }
+                }
+            }
+        }
+        flattened
+    }
+
+    /// Same as [`Story::flatten_nodes`] for the content of a paragraph, line by line. The
+    /// paragraphs of included passages are merged into it.
+    fn flatten_inlines<'a>(
+        &'a self,
+        inlines: Vec<Inline<&'a str>>,
+        flattening: &mut Flattening<'a>,
+    ) -> Vec<Inline<&'a str>> {
+        let mut lines = vec![];
+        let mut line = vec![];
+        for inline in inlines {
+            match inline {
+                Inline::Text(text) => line.push(ContentNode::Text(text)),
+                Inline::LineBreak => lines.push(std::mem::take(&mut line)),
+                Inline::Node(node) => line.push(node),
+            }
+        }
+        lines.push(line);
+
+        let mut flattened = vec![];
+        for (index, line) in lines.into_iter().enumerate() {
+            if index > 0 {
+                flattened.push(Inline::LineBreak);
+            }
+            for node in self.flatten_nodes(line, flattening) {
+                match node {
+                    ContentNode::Text(text) => flattened.push(Inline::Text(text)),
+                    ContentNode::Paragraph(inlines) => flattened.extend(inlines),
+                    node => flattened.push(Inline::Node(node)),
+                }
+            }
+        }
+        flattened
+    }
+
+    /// Passages including each other in a loop, each cycle listed once, starting with its
+    /// passage first in the story.
+    pub fn include_cycles(&self) -> Vec<Vec<&str>> {
+        let order: Vec<&str> = self.passages.iter().map(|(name, _)| name).collect();
+        let mut cycles = vec![];
+        for (index, &start) in order.iter().enumerate() {
+            // Only cycles through passages coming after `start` are new.
+            let allowed = |name: &str| order[index..].contains(&name);
+            let mut stack = vec![(start, self.includes(start).into_iter())];
+            while let Some((_, targets)) = stack.last_mut() {
+                let Some(target) = targets.next() else {
+                    stack.pop();
+                    continue;
+                };
+                if target == start {
+                    cycles.push(stack.iter().map(|(name, _)| *name).collect());
+                } else if allowed(target) && !stack.iter().any(|(name, _)| *name == target) {
+                    let includes = self.includes(target).into_iter();
+                    stack.push((target, includes));
+                }
+            }
+        }
+        cycles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_includes;
+    use crate::{ContentNode, Inline, ParseOptions, Story, StoryFormat};
+
+    #[test]
+    fn test_split_includes() {
+        assert_eq!(
+            split_includes(
+                "A <<include \"Map\">> and (display: 'Key')(x:)",
+                StoryFormat::Plain
+            ),
+            [
+                ("A ", None),
+                ("<<include \"Map\">>", Some("Map")),
+                (" and ", None),
+                ("(display: 'Key')", Some("Key")),
+                ("(x:)", None),
+            ]
+        );
+        assert_eq!(
+            split_includes("<<includes>>", StoryFormat::Plain),
+            [("<<includes>>", None)]
+        );
+        assert_eq!(
+            split_includes(
+                "{embed passage: 'Map'} <<include 'Key'>>",
+                StoryFormat::Chapbook
+            ),
+            [
+                ("{embed passage: 'Map'}", Some("Map")),
+                (" <<include 'Key'>>", None)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_flattened_passage() {
+        let input = ":: Start\nYou see <<include \"Room\">>.\n\n:: Room\na room with [[a door|Door]] (display: \"Missing\")\n";
+        let story = Story::try_from(input).unwrap();
+        let start = story.flattened_passage("Start").unwrap();
+
+        assert_eq!(
+            start.nodes(),
+            [
+                ContentNode::Text("You see "),
+                ContentNode::Text("a room with "),
+                ContentNode::Link {
+                    text: "a door",
+                    target: "Door",
+                    setter: None
+                },
+                ContentNode::Text(" "),
+                ContentNode::Text("(display: \"Missing\")"),
+                ContentNode::Text("."),
+            ]
+        );
+        assert_eq!(story.includes("Room"), ["Missing"]);
+
+        let options = ParseOptions::default().paragraphs(true);
+        let story = Story::parse_with(input, options).unwrap();
+        assert_eq!(story.includes("Start"), ["Room"]);
+        assert_eq!(
+            story.flattened_passage("Start").unwrap().nodes(),
+            [ContentNode::Paragraph(vec![
+                Inline::Text("You see "),
+                Inline::Text("a room with "),
+                Inline::Node(ContentNode::link_node("a door", "Door")),
+                Inline::Text(" "),
+                Inline::Text("(display: \"Missing\")"),
+                Inline::Text("."),
+            ])]
+        );
+    }
+
+    #[test]
+    fn test_link_includes() {
+        let input = ":: StoryData\n{\"format\":\"SugarCube\"}\n\n:: A\nSee <<include [[B]]>>, <<include [[the c|C]] \"div\">>.\n\n:: B\nb <<include [[A]]>>\n\n:: C\nc\n";
+        let story = Story::try_from(input).unwrap();
+
+        assert_eq!(story.includes("A"), ["B", "C"]);
+        assert_eq!(story.include_cycles(), [vec!["A", "B"]]);
+        assert_eq!(
+            story.flattened_passage("A").unwrap().nodes(),
+            [
+                ContentNode::Text("See "),
+                ContentNode::Text("b "),
+                ContentNode::Text("<<include "),
+                ContentNode::link_node("A", "A"),
+                ContentNode::Text(">>"),
+                ContentNode::Text(", "),
+                ContentNode::Text("c"),
+                ContentNode::Text("."),
+            ]
+        );
+
+        let story = Story::parse_with(input, ParseOptions::default().paragraphs(true)).unwrap();
+        assert_eq!(story.includes("A"), ["B", "C"]);
+        assert_eq!(
+            story.flattened_passage("C").unwrap().nodes(),
+            [ContentNode::Paragraph(vec![Inline::Text("c")])]
+        );
+        assert_eq!(
+            story.flattened_passage("A").unwrap().to_string(),
+            "See b <<include A>>, c."
+        );
+    }
+
+    #[test]
+    fn test_include_cycles() {
+        let input = ":: A\n<<include \"B\">>\n\n:: B\n<<include \"C\">> <<include \"B\">>\n\n:: C\n<<include \"A\">>\n";
+        let story = Story::try_from(input).unwrap();
+
+        assert_eq!(story.include_cycles(), [vec!["A", "B", "C"], vec!["B"]]);
+        assert_eq!(
+            story.flattened_passage("A").unwrap().to_string(),
+            "<<include \"A\">> <<include \"B\">>"
+        );
+    }
+
+    #[test]
+    fn test_flattened_passage_limits() {
+        // Each passage includes the next one twice, 2^40 nodes once expanded.
+        let mut input: String = (0..40)
+            .map(|index| {
+                let next = index + 1;
+                format!(":: P{index}\n<<include \"P{next}\">><<include \"P{next}\">>\n\n")
+            })
+            .collect();
+        input.push_str(":: P40\nleaf\n");
+        let story = Story::try_from(input.as_str()).unwrap();
+        let nodes = story.flattened_passage("P0").unwrap().content;
+        assert!(nodes.len() < 100_100);
+        assert!(nodes.contains(&ContentNode::Text("leaf")));
+        assert!(nodes.contains(&ContentNode::Text("<<include \"P1\">>")));
+
+        let nodes = story.flattened_passage("P24").unwrap().content;
+        assert_eq!(nodes, vec![ContentNode::Text("leaf"); 1 << 16]);
+
+        let mut input: String = (0..100)
+            .map(|index| format!(":: Q{index}\n<<include \"Q{}\">>\n\n", index + 1))
+            .collect();
+        input.push_str(":: Q100\nleaf\n");
+        let story = Story::try_from(input.as_str()).unwrap();
+        assert_eq!(
+            story.flattened_passage("Q0").unwrap().to_string(),
+            "<<include \"Q65\">>"
+        );
+    }
+}
@@ -0,0 +1,351 @@
+//! Incremental re-parsing of stories after small edits, for editors.
+
+use std::{collections::HashSet, ops::Range};
+
+use crate::{
+    error::ParsingError,
+    parser::story::{parse_story_with, starts_with_header},
+    passage_map::PassageMap,
+    Story, Strictness, TextBlock, Warning,
+};
+
+/// Replaces `range` of the story source with `text`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+impl TextEdit {
+    pub fn new(range: Range<usize>, text: impl Into<String>) -> Self {
+        Self {
+            range,
+            text: text.into(),
+        }
+    }
+}
+
+/// Offsets of the passage headers, lines starting with `::`.
+fn header_offsets(input: &str) -> impl Iterator<Item = usize> + '_ {
+    input
+        .starts_with("::")
+        .then_some(0)
+        .into_iter()
+        .chain(input.match_indices("\n::").map(|(index, _)| index + 1))
+}
+
+fn has_special_passage(blocks: &str) -> bool {
+    header_offsets(blocks).any(|offset| {
+        let header = &blocks[offset..];
+        starts_with_header(header, "StoryTitle") || starts_with_header(header, "StoryData")
+    })
+}
+
+fn shift(block: &mut TextBlock, from: usize, start: usize, end: usize) {
+    if let TextBlock::Borrowed(range) = block {
+        if range.start >= from {
+            *range = range.start + end - start..range.end + end - start;
+        }
+    }
+}
+
+impl Story<String> {
+    /// Applies `edit` to the source and re-parses only the passages it touches, reusing the
+    /// others.
+    ///
+    /// Edits are parsed with the [`ParseOptions`](crate::ParseOptions) the story was parsed
+    /// with. The story is re-parsed as a whole when the edit touches StoryTitle or StoryData,
+    /// changes passage names in a way that creates duplicates, or when the story was parsed
+    /// verbatim, strictly or with limits. On error, the story is left unchanged.
+    ///
+    /// The range is in the source, byte order mark included, which the edit can't touch.
+    pub fn reparse_range(&mut self, edit: TextEdit) -> Result<(), ParsingError<String>> {
+        let TextEdit { range, text } = edit;
+        let bom = self.bom;
+        let valid = |offset: usize| offset >= bom && self.content.is_char_boundary(offset - bom);
+        if range.start > range.end || !valid(range.start) || !valid(range.end) {
+            return Err(ParsingError::Parsing(format!(
+                "Invalid edit range {range:?}"
+            )));
+        }
+        let range = range.start - bom..range.end - bom;
+
+        let mut content = self.content.clone();
+        content.replace_range(range.clone(), &text);
+
+        // The edit is extended to the whole passages it touches.
+        let block_start = header_offsets(&self.content)
+            .take_while(|&offset| offset <= range.start)
+            .last()
+            .unwrap_or(0);
+        let old_end = header_offsets(&self.content)
+            .find(|&offset| offset > range.end)
+            .unwrap_or(self.content.len());
+        let new_end = old_end + text.len() - range.len();
+
+        if let Err(mut content) = self.reparse_blocks(content, block_start, old_end, new_end) {
+            if bom > 0 {
+                content.insert(0, '\u{feff}');
+            }
+            *self = Story::parse_owned(content, self.options.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Re-parses `content[start..new_end]`, which replaces `self.content[start..old_end]`.
+    /// Gives the content back when the whole story has to be re-parsed instead.
+    fn reparse_blocks(
+        &mut self,
+        content: String,
+        start: usize,
+        old_end: usize,
+        new_end: usize,
+    ) -> Result<(), String> {
+        let old_blocks = &self.content[start..old_end];
+        let new_blocks = &content[start..new_end];
+        let options = &self.options;
+        // Blocks not starting with a header hold a preamble, whose warning isn't per passage.
+        // Strictness and limits apply to the whole story.
+        if self.source_blocks.is_some()
+            || options.strictness == Strictness::Strict
+            || options.max_passages.is_some()
+            || options.max_passage_size.is_some()
+            || options.max_nesting.is_some()
+            || !old_blocks.starts_with("::")
+            || !new_blocks.starts_with("::")
+            || has_special_passage(old_blocks)
+            || has_special_passage(new_blocks)
+            || self
+                .warnings
+                .iter()
+                .any(|warning| matches!(warning, Warning::DuplicatePassage { .. }))
+        {
+            return Err(content);
+        }
+
+        let (Ok((_, old)), Ok((_, new))) = (
+            parse_story_with(old_blocks, options),
+            parse_story_with(new_blocks, options),
+        ) else {
+            return Err(content);
+        };
+        // Lines starting with `::` are not always headers: one right after a header is
+        // content. The blocks must parse the same alone as within the whole story.
+        let matches_story = old.passages.iter().all(|(name, passage)| {
+            let mut passage = passage.clone();
+            passage.for_each_block(|block| shift(block, 0, 0, start));
+            self.passages.get(name) == Some(&passage)
+        });
+        let next_line_end = content[new_end..]
+            .find('\n')
+            .map_or(content.len(), |index| new_end + index + 1);
+        let keeps_next_header = new_end == content.len()
+            || parse_story_with(&content[start..next_line_end], options)
+                .is_ok_and(|(_, story)| story.passages.len() == new.passages.len() + 1);
+        if !matches_story || !keeps_next_header {
+            return Err(content);
+        }
+        let removed: HashSet<&str> = old.passages.iter().map(|(name, _)| name).collect();
+        let collides = new
+            .passages
+            .iter()
+            .any(|(name, _)| !removed.contains(name) && self.passages.contains_key(name));
+        if removed.is_empty()
+            || collides
+            || new
+                .warnings
+                .iter()
+                .any(|warning| matches!(warning, Warning::DuplicatePassage { .. }))
+        {
+            return Err(content);
+        }
+
+        let mut passages = PassageMap::new();
+        let mut new_passages = Some(new.passages);
+        for (name, passage) in self.passages.iter() {
+            if removed.contains(name) {
+                for (name, mut passage) in new_passages
+                    .take()
+                    .into_iter()
+                    .flat_map(|p| p.into_entries())
+                {
+                    passage.for_each_block(|block| shift(block, 0, 0, start));
+                    passages.insert(name, passage);
+                }
+                continue;
+            }
+            let mut passage = passage.clone();
+            passage.for_each_block(|block| shift(block, old_end, old_end, new_end));
+            passages.insert(name.to_string(), passage);
+        }
+
+        for block in [&mut self.title, &mut self.start, &mut self.data]
+            .into_iter()
+            .flatten()
+        {
+            shift(block, old_end, old_end, new_end);
+        }
+        self.warnings.retain(|warning| {
+            warning
+                .passage()
+                .is_none_or(|passage| !removed.contains(passage))
+        });
+        self.warnings.extend(new.warnings);
+        self.passages = passages;
+        self.content = content;
+        self.invalidate_derived();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::proptest;
+
+    use super::TextEdit;
+    use crate::{ContentNode, ParseOptions, Story};
+
+    const STORY: &str = ":: StoryTitle\nEdits\n\n:: Start\nGo [[North]]\n\n:: North\nCold.\n\n:: South\nWarm [[Start]]\n";
+
+    fn assert_same_as_full_parse(story: &Story<String>) {
+        let full = Story::try_from(story.content.clone()).unwrap();
+        assert_eq!(
+            story.iter().collect::<Vec<_>>(),
+            full.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(story.title(), full.title());
+        assert_eq!(story.warnings(), full.warnings());
+    }
+
+    #[test]
+    fn test_reparse_inside_passage() {
+        let mut story = Story::try_from(STORY.to_string()).unwrap();
+        let offset = STORY.find("Cold").unwrap();
+        story
+            .reparse_range(TextEdit::new(offset..offset + 4, "Freezing, [[South]]"))
+            .unwrap();
+
+        assert_same_as_full_parse(&story);
+        assert_eq!(story.backlinks("South").collect::<Vec<_>>(), ["North"]);
+    }
+
+    #[test]
+    fn test_reparse_split_and_rename() {
+        let mut story = Story::try_from(STORY.to_string()).unwrap();
+        let offset = STORY.find(":: North").unwrap();
+        story
+            .reparse_range(TextEdit::new(
+                offset..offset + 8,
+                ":: West\nWind\n\n:: East",
+            ))
+            .unwrap();
+
+        assert_same_as_full_parse(&story);
+        assert!(story.get_passage("North").is_none());
+        assert_eq!(story.iter().count(), 4);
+    }
+
+    #[test]
+    fn test_reparse_falls_back_on_duplicates() {
+        let mut story = Story::try_from(STORY.to_string()).unwrap();
+        let offset = STORY.find("North\nCold").unwrap();
+        story
+            .reparse_range(TextEdit::new(offset..offset + 5, "South"))
+            .unwrap();
+
+        assert_same_as_full_parse(&story);
+        assert_eq!(story.warnings().len(), 1);
+    }
+
+    #[test]
+    fn test_reparse_escaped_name_warnings() {
+        let input = ":: A\\]b\n";
+        let mut story = Story::try_from(input.to_string()).unwrap();
+        story.reparse_range(TextEdit::new(7..7, "text")).unwrap();
+
+        assert_same_as_full_parse(&story);
+        assert_eq!(story.warnings().len(), 1);
+    }
+
+    #[test]
+    fn test_reparse_special_passage_without_space() {
+        let mut story = Story::try_from(":: Start\nText\n".to_string()).unwrap();
+        let offset = story.content.len();
+        story
+            .reparse_range(TextEdit::new(offset..offset, "\n::StoryTitle\nNew\n"))
+            .unwrap();
+
+        assert_same_as_full_parse(&story);
+        assert_eq!(story.title(), Some("New"));
+    }
+
+    #[test]
+    fn test_reparse_invalid_range() {
+        let mut story = Story::try_from(STORY.to_string()).unwrap();
+
+        assert!(story
+            .reparse_range(TextEdit::new(4..STORY.len() + 1, ""))
+            .is_err());
+        assert_eq!(story.content, STORY);
+    }
+
+    #[test]
+    fn test_reparse_keeps_options() {
+        let options = ParseOptions::default().paragraphs(true);
+        let mut story = Story::parse_owned(STORY.to_string(), options.clone()).unwrap();
+        let offset = STORY.find("Cold").unwrap();
+        story
+            .reparse_range(TextEdit::new(offset..offset + 4, "Freezing"))
+            .unwrap();
+
+        let full = Story::parse_owned(story.content.clone(), options).unwrap();
+        assert_eq!(
+            story.iter().collect::<Vec<_>>(),
+            full.iter().collect::<Vec<_>>()
+        );
+        let north = story.get_passage("North").unwrap();
+        assert!(matches!(north.content[0], ContentNode::Paragraph(_)));
+
+        let input = "Notes\n:: A\nx\n\n:: B\ny\n";
+        let options = ParseOptions::default().verbatim(true);
+        let mut story = Story::parse_owned(input.to_string(), options).unwrap();
+        story.reparse_range(TextEdit::new(19..20, "z")).unwrap();
+        assert_eq!(story.to_twee(), "Notes\n:: A\nx\n\n:: B\nz\n");
+    }
+
+    #[test]
+    fn test_reparse_after_bom() {
+        let source = format!("\u{feff}{STORY}");
+        let mut story = Story::try_from(source.clone()).unwrap();
+        let offset = source.find("Cold").unwrap();
+        story
+            .reparse_range(TextEdit::new(offset..offset + 4, "Freezing"))
+            .unwrap();
+
+        assert_same_as_full_parse(&story);
+        assert!(story.to_twee().contains("Freezing."));
+        assert!(story.reparse_range(TextEdit::new(1..1, "")).is_err());
+
+        let offset = source.find(":: StoryTitle\nEdits").unwrap();
+        story
+            .reparse_range(TextEdit::new(offset + 14..offset + 19, "Changes"))
+            .unwrap();
+        assert_eq!(story.title(), Some("Changes"));
+        assert_eq!(story.highlight_spans()[0].range, 3..16);
+    }
+
+    proptest! {
+        #[test]
+        fn test_reparse_matches_full_parse(
+            start in 0..STORY.len(),
+            len in 0..20usize,
+            text in r"(:: |\n|\[\[|\]\]|Start|North|a| )*",
+        ) {
+            let end = (start + len).min(STORY.len());
+            let mut story = Story::try_from(STORY.to_string()).unwrap();
+            if story.reparse_range(TextEdit::new(start..end, text)).is_ok() {
+                assert_same_as_full_parse(&story);
+            }
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! Reverse indexes over a story, built on first use.
+
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap},
+    hash::{Hash, Hasher},
+    ops::Deref,
+    sync::{Arc, OnceLock},
+};
+
+use crate::{
+    include::included_passages,
+    intern::{Interner, Symbol},
+    stats::StoryStats,
+    ContentNode, Passage, Story,
+};
+
+/// Data derived from a story, built on first use and dropped when the story changes. It takes
+/// no part in comparing stories.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Derived {
+    /// Shared between clones of the story until it changes.
+    indexes: OnceLock<Arc<Indexes>>,
+    pub stats: OnceLock<StoryStats>,
+    /// The `tag-colors` field of StoryData, by tag.
+    pub tag_colors: OnceLock<BTreeMap<String, String>>,
+}
+
+impl PartialEq for Derived {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Derived {}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct Indexes {
+    /// Passage names and tags, each stored once.
+    symbols: Interner,
+    /// Passage names, by the names of the passages they link to.
+    backlinks: HashMap<Symbol, Vec<Symbol>>,
+    /// Passage names, by tag.
+    tags: HashMap<Symbol, Vec<Symbol>>,
+    /// Tags, in the order of their first use.
+    tag_order: Vec<Symbol>,
+    /// Tags, by passage name.
+    passage_tags: HashMap<Symbol, Vec<Symbol>>,
+    /// Passage names, by the names of the passages they link to or include.
+    dependents: HashMap<Symbol, Vec<Symbol>>,
+    /// Hashes of the passages, by passage name.
+    hashes: HashMap<Symbol, u64>,
+}
+
+fn push_once(symbols: &mut Vec<Symbol>, symbol: Symbol) {
+    if !symbols.contains(&symbol) {
+        symbols.push(symbol);
+    }
+}
+
+impl Indexes {
+    pub fn build<T>(story: &Story<T>) -> Self
+    where
+        T: Deref<Target = str>,
+    {
+        let mut indexes = Self::default();
+        let format = story.story_format();
+        for passage in story.iter() {
+            let source = indexes.symbols.intern(passage.title);
+            let mut hasher = DefaultHasher::new();
+            passage.hash(&mut hasher);
+            indexes.hashes.insert(source, hasher.finish());
+            for link in passage.links() {
+                let target = indexes.symbols.intern(link.target);
+                push_once(indexes.backlinks.entry(target).or_default(), source);
+            }
+            let image_targets = passage
+                .content
+                .iter()
+                .flat_map(ContentNode::leaves)
+                .filter_map(|node| match node {
+                    ContentNode::Image(image) => image.target,
+                    _ => None,
+                });
+            let dependencies = passage
+                .links()
+                .map(|link| *link.target)
+                .chain(image_targets)
+                .chain(included_passages(&passage, format));
+            for dependency in dependencies {
+                let dependency = indexes.symbols.intern(dependency);
+                push_once(indexes.dependents.entry(dependency).or_default(), source);
+            }
+            for tag in &passage.tags {
+                let tag = indexes.symbols.intern(tag.value);
+                if !indexes.tags.contains_key(&tag) {
+                    indexes.tag_order.push(tag);
+                }
+                push_once(indexes.tags.entry(tag).or_default(), source);
+                push_once(indexes.passage_tags.entry(source).or_default(), tag);
+            }
+        }
+        indexes
+    }
+
+    fn resolve_all<'a>(
+        &'a self,
+        symbols: Option<&'a Vec<Symbol>>,
+    ) -> impl Iterator<Item = &'a str> {
+        symbols
+            .into_iter()
+            .flatten()
+            .map(|symbol| self.symbols.resolve(*symbol))
+    }
+}
+
+impl<T> Story<T>
+where
+    T: Deref<Target = str>,
+{
+    /// Drops the data derived from the story, to be built again on first use. To be called
+    /// whenever the story changes.
+    pub(crate) fn invalidate_derived(&mut self) {
+        self.derived = Derived::default();
+    }
+
+    pub(crate) fn indexes(&self) -> &Indexes {
+        self.derived
+            .indexes
+            .get_or_init(|| Arc::new(Indexes::build(self)))
+    }
+
+    /// A hash of the passage `name`, computed once until the story changes.
+    pub(crate) fn passage_hash(&self, name: &str) -> Option<u64> {
+        let indexes = self.indexes();
+        let symbol = indexes.symbols.get(name)?;
+        indexes.hashes.get(&symbol).copied()
+    }
+
+    /// Names of the passages linking to `name`, in source order.
+    pub fn backlinks(&self, name: &str) -> impl Iterator<Item = &str> {
+        let indexes = self.indexes();
+        let symbol = indexes.symbols.get(name);
+        indexes.resolve_all(symbol.and_then(|symbol| indexes.backlinks.get(&symbol)))
+    }
+
+    /// Names of the passages referencing `name` through a link, an image link or an include,
+    /// in source order: those to reload when `name` changes.
+    pub fn dependents_of(&self, name: &str) -> impl Iterator<Item = &str> {
+        let indexes = self.indexes();
+        let symbol = indexes.symbols.get(name);
+        indexes.resolve_all(symbol.and_then(|symbol| indexes.dependents.get(&symbol)))
+    }
+
+    /// Passages tagged with `tag`, in source order.
+    pub fn passages_with_tag(&self, tag: &str) -> impl Iterator<Item = Passage<&str>> {
+        let indexes = self.indexes();
+        let symbol = indexes.symbols.get(tag);
+        indexes
+            .resolve_all(symbol.and_then(|symbol| indexes.tags.get(&symbol)))
+            .filter_map(|name| self.get_passage(name))
+    }
+
+    /// Every tag used in the story, in the order they're first used in the source.
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        let indexes = self.indexes();
+        indexes.resolve_all(Some(&indexes.tag_order))
+    }
+
+    /// The interned passage names and tags of the story, to resolve [`Symbol`]s.
+    pub fn symbols(&self) -> &Interner {
+        &self.indexes().symbols
+    }
+
+    /// The tags of the passage `name` as symbols, cheaper to compare than strings.
+    pub fn tag_symbols(&self, name: &str) -> &[Symbol] {
+        let indexes = self.indexes();
+        indexes
+            .symbols
+            .get(name)
+            .and_then(|symbol| indexes.passage_tags.get(&symbol))
+            .map_or(&[], Vec::as_slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ParseOptions, Story};
+
+    const SAMPLE: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/sample/sample.twee"));
+
+    #[test]
+    fn test_backlinks() {
+        let story = Story::try_from(SAMPLE).unwrap();
+
+        assert_eq!(
+            story.backlinks("First").collect::<Vec<_>>(),
+            vec!["Second", "Start"]
+        );
+        assert_eq!(story.backlinks("Nowhere").count(), 0);
+    }
+
+    #[test]
+    fn test_backlinks_counted_once_per_passage() {
+        let story = Story::try_from(":: A\n[[B]] [[again->B]]\n\n:: B\nEnd\n").unwrap();
+
+        assert_eq!(story.backlinks("B").collect::<Vec<_>>(), vec!["A"]);
+    }
+
+    #[test]
+    fn test_dependents_of() {
+        let input =
+            ":: A\n[[B]]\n\n:: B\nEnd\n\n:: C\n<<include \"B\">> [img[b.png][B]]\n\n:: D\n[[A]]\n";
+        let story = Story::try_from(input).unwrap();
+
+        assert_eq!(story.dependents_of("B").collect::<Vec<_>>(), ["A", "C"]);
+        assert_eq!(story.dependents_of("C").count(), 0);
+
+        let input = ":: A\nSee [img[b.png][C]]\n\n:: C\nEnd\n";
+        let story = Story::parse_with(input, ParseOptions::default().paragraphs(true)).unwrap();
+        assert_eq!(story.dependents_of("C").collect::<Vec<_>>(), ["A"]);
+    }
+
+    #[test]
+    fn test_passages_with_tag() {
+        let story = Story::try_from(":: A [x y]\n\n:: B [y]\n\n:: C\n").unwrap();
+
+        let titles: Vec<_> = story.passages_with_tag("y").map(|p| *p.title()).collect();
+        assert_eq!(titles, vec!["A", "B"]);
+
+        let story = Story::try_from(":: A [y]\n\n:: B [x z y]\n\n:: C [w x]\n").unwrap();
+        assert_eq!(story.tags().collect::<Vec<_>>(), ["y", "x", "z", "w"]);
+    }
+
+    #[test]
+    fn test_tag_symbols() {
+        let story = Story::try_from(":: A [x y]\n\n:: B [y]\n\n:: C\n").unwrap();
+
+        let a = story.tag_symbols("A");
+        let b = story.tag_symbols("B");
+        assert_eq!(a[1], b[0]);
+        assert_eq!(story.symbols().resolve(b[0]), "y");
+        assert_eq!(story.symbols().get("y"), Some(b[0]));
+        assert!(story.tag_symbols("C").is_empty());
+    }
+}
@@ -0,0 +1,142 @@
+//! Interning of passage names, tags and text, so that each distinct string is stored once.
+
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Deref,
+    sync::Arc,
+};
+
+use crate::{Story, TextBlock};
+
+/// Handle to an interned string, cheap to copy, compare and hash.
+///
+/// Symbols are only meaningful for the [`Interner`] that created them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(u32);
+
+/// Stores each distinct string once, handing out a [`Symbol`] for it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Interner {
+    strings: Vec<Arc<str>>,
+    symbols: HashMap<Arc<str>, Symbol>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The symbol of `value`, interning it if needed.
+    pub fn intern(&mut self, value: &str) -> Symbol {
+        if let Some(symbol) = self.symbols.get(value) {
+            return *symbol;
+        }
+        let symbol = Symbol(self.strings.len() as u32);
+        let value: Arc<str> = Arc::from(value);
+        self.strings.push(value.clone());
+        self.symbols.insert(value, symbol);
+        symbol
+    }
+
+    /// The symbol of `value`, if it was interned.
+    pub fn get(&self, value: &str) -> Option<Symbol> {
+        self.symbols.get(value).copied()
+    }
+
+    /// The string behind `symbol`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `symbol` comes from another interner.
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        &self.strings[symbol.0 as usize]
+    }
+
+    /// Number of distinct strings.
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+impl<T> Story<T>
+where
+    T: Deref<Target = str>,
+{
+    /// Stores identical text of the passages once, shared between them, such as boilerplate
+    /// lines repeated across a generated story. Only text the story owns is shared: text
+    /// borrowed from the source takes no extra memory.
+    pub fn dedup_text(&mut self) {
+        let mut shared: HashSet<Arc<str>> = HashSet::new();
+        self.passages.for_each_mut(|passage| {
+            passage.for_each_block(|block| {
+                let TextBlock::Owned(text) = block else {
+                    return;
+                };
+                let text = match shared.get(text.as_str()) {
+                    Some(text) => text.clone(),
+                    None => {
+                        let text: Arc<str> = Arc::from(text.as_str());
+                        shared.insert(text.clone());
+                        text
+                    }
+                };
+                *block = TextBlock::Shared(text);
+            });
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Interner;
+    use crate::{ContentNode, ParseOptions, Passage, Story};
+
+    #[test]
+    fn test_intern() {
+        let mut interner = Interner::new();
+        let a = interner.intern("forest");
+        let b = interner.intern("cave");
+
+        assert_eq!(interner.intern("forest"), a);
+        assert_ne!(a, b);
+        assert_eq!(interner.resolve(b), "cave");
+        assert_eq!(interner.get("cave"), Some(b));
+        assert_eq!(interner.get("sea"), None);
+        assert_eq!(interner.len(), 2);
+    }
+
+    #[test]
+    fn test_dedup_text() {
+        let passage = |title: &str| {
+            let text = ContentNode::text_node("You hear the wind.".to_string());
+            Passage::new(title.to_string(), vec![], None, vec![text])
+        };
+        let mut story: Story<String> = [passage("A"), passage("B")].into_iter().collect();
+        story.dedup_text();
+
+        assert_eq!(text_ptr(&story, "A"), text_ptr(&story, "B"));
+        assert_eq!(
+            story.get_passage("B").unwrap().to_string(),
+            "You hear the wind."
+        );
+
+        let input = ":: A\\[x\\]\nSame \\[text\\]\n\n:: B\nSame \\[text\\]\n";
+        let options = ParseOptions::default().dedup_text(true);
+        let story = Story::parse_with(input, options).unwrap();
+        assert_eq!(text_ptr(&story, "A[x]"), text_ptr(&story, "B"));
+    }
+
+    fn text_ptr<T>(story: &Story<T>, title: &str) -> *const u8
+    where
+        T: std::ops::Deref<Target = str>,
+    {
+        match story.get_passage(title).unwrap().nodes()[0] {
+            ContentNode::Text(text) => text.as_ptr(),
+            _ => unreachable!(),
+        }
+    }
+}
@@ -1,12 +1,22 @@
-use crate::ContentNode;
+use std::{ops::Deref, vec::IntoIter};
+
+use crate::{
+    analysis::choices, prose::contains_macro, ContentNode, Inline, Iter, Passage, Story,
+    StoryFormat,
+};
 
 pub struct LinkIterator<'a, T> {
     nodes: &'a [ContentNode<T>],
+    /// The rest of the paragraph being read.
+    inlines: &'a [Inline<T>],
 }
 
 impl<'a, T> LinkIterator<'a, T> {
     pub fn new(nodes: &'a [ContentNode<T>]) -> Self {
-        Self { nodes }
+        Self {
+            nodes,
+            inlines: &[],
+        }
     }
 }
 
@@ -14,20 +24,27 @@ impl<'a, T> Iterator for LinkIterator<'a, T> {
     type Item = Link<'a, T>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let link;
         loop {
-            if self.nodes.is_empty() {
-                return None;
-            }
-            let node = &self.nodes[0];
-            if let ContentNode::Link { text, target } = node {
-                link = Some(Link { text, target });
-                break;
+            let node = match self.inlines.split_first() {
+                Some((inline, rest)) => {
+                    self.inlines = rest;
+                    match inline {
+                        Inline::Node(node) => node,
+                        _ => continue,
+                    }
+                }
+                None => {
+                    let (node, rest) = self.nodes.split_first()?;
+                    self.nodes = rest;
+                    node
+                }
+            };
+            match node {
+                ContentNode::Link { text, target, .. } => return Some(Link { text, target }),
+                ContentNode::Paragraph(inlines) => self.inlines = inlines,
+                _ => {}
             }
-            self.nodes = &self.nodes[1..];
         }
-        self.nodes = &self.nodes[1..];
-        link
     }
 }
 
@@ -35,3 +52,447 @@ pub struct Link<'a, T> {
     pub text: &'a T,
     pub target: &'a T,
 }
+
+/// A link of a story, with the passage it is in, see [`Story::links`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StoryLink<'a> {
+    pub passage: &'a str,
+    pub text: &'a str,
+    pub target: &'a str,
+    pub setter: Option<&'a str>,
+}
+
+/// A choice offered by a passage, see [`Passage::choices`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Choice<'a> {
+    pub text: &'a str,
+    pub target: &'a str,
+    pub setter: Option<&'a str>,
+    /// Index of the link in [`Passage::nodes`], or of the paragraph holding it.
+    pub index: usize,
+}
+
+/// A text or a node of some content, read through paragraphs.
+enum Leaf<'a, T> {
+    Text(&'a T),
+    Node(&'a ContentNode<T>),
+}
+
+/// The texts and nodes of `content`, in order, with the index of the top-level node holding
+/// each one.
+fn leaves<T>(content: &[ContentNode<T>]) -> impl Iterator<Item = (usize, Leaf<'_, T>)> {
+    content.iter().enumerate().flat_map(|(index, node)| {
+        let (node, inlines) = match node {
+            ContentNode::Paragraph(inlines) => (None, &inlines[..]),
+            node => (Some(Leaf::Node(node)), &[][..]),
+        };
+        let inlines = inlines.iter().filter_map(|inline| match inline {
+            Inline::Text(text) => Some(Leaf::Text(text)),
+            Inline::LineBreak => None,
+            Inline::Node(node) => Some(Leaf::Node(node)),
+        });
+        node.into_iter()
+            .chain(inlines)
+            .map(move |leaf| (index, leaf))
+    })
+}
+
+/// Whether a SugarCube macro is still open after `text`, `open` telling if one was before it.
+fn leaves_macro_open(text: &str, open: bool) -> bool {
+    match (text.rfind("<<"), text.rfind(">>")) {
+        (Some(start), Some(end)) => start > end,
+        (Some(_), None) => true,
+        (None, Some(_)) => false,
+        (None, None) => open,
+    }
+}
+
+impl<T> Passage<T>
+where
+    T: AsRef<str>,
+{
+    /// The choices offered to players, in order: the links of the passage, without those
+    /// written inside a macro, such as `<<set $note to "[[A]]">>`. Links going to the same
+    /// passage with the same setter are only listed the first time.
+    pub fn choices(&self) -> Vec<Choice<'_>> {
+        let mut choices: Vec<Choice> = vec![];
+        let mut in_macro = false;
+        for (index, leaf) in leaves(&self.content) {
+            match leaf {
+                Leaf::Text(text) | Leaf::Node(ContentNode::Text(text)) => {
+                    in_macro = leaves_macro_open(text.as_ref(), in_macro)
+                }
+                Leaf::Node(ContentNode::Link {
+                    text,
+                    target,
+                    setter,
+                }) if !in_macro => {
+                    let choice = Choice {
+                        text: text.as_ref(),
+                        target: target.as_ref(),
+                        setter: setter.as_ref().map(AsRef::as_ref),
+                        index,
+                    };
+                    let seen = choices
+                        .iter()
+                        .any(|seen| seen.target == choice.target && seen.setter == choice.setter);
+                    if !seen {
+                        choices.push(choice);
+                    }
+                }
+                _ => {}
+            }
+        }
+        choices
+    }
+}
+
+/// A passage with the story it belongs to, to navigate from it, see [`Story::passage`].
+///
+/// Dereferences to the passage.
+pub struct PassageRef<'s, T>
+where
+    T: Deref<Target = str>,
+{
+    story: &'s Story<T>,
+    passage: Passage<&'s str>,
+}
+
+impl<'s, T> PassageRef<'s, T>
+where
+    T: Deref<Target = str>,
+{
+    pub fn story(&self) -> &'s Story<T> {
+        self.story
+    }
+
+    pub fn into_passage(self) -> Passage<&'s str> {
+        self.passage
+    }
+
+    /// The passage `target`, if this passage links to it, through a link or an image, and it
+    /// exists.
+    pub fn follow(&self, target: &str) -> Option<PassageRef<'s, T>> {
+        choices(&self.passage)
+            .contains(&target)
+            .then(|| self.story.passage(target))
+            .flatten()
+    }
+
+    /// The passages linking to this one, in source order, see [`Story::backlinks`].
+    pub fn backlinks(&self) -> impl Iterator<Item = PassageRef<'s, T>> + 's {
+        let story = self.story;
+        story
+            .backlinks(self.passage.title)
+            .filter_map(move |name| story.passage(name))
+    }
+}
+
+impl<'s, T> Deref for PassageRef<'s, T>
+where
+    T: Deref<Target = str>,
+{
+    type Target = Passage<&'s str>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.passage
+    }
+}
+
+impl<T> Clone for PassageRef<'_, T>
+where
+    T: Deref<Target = str>,
+{
+    fn clone(&self) -> Self {
+        Self {
+            story: self.story,
+            passage: self.passage.clone(),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for PassageRef<'_, T>
+where
+    T: Deref<Target = str>,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("PassageRef").field(&self.passage).finish()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NodeFilter {
+    All,
+    Links,
+    Text,
+    Macros(StoryFormat),
+}
+
+impl NodeFilter {
+    fn accepts(self, node: &ContentNode<&str>) -> bool {
+        match (self, node) {
+            (NodeFilter::All, _) => true,
+            (NodeFilter::Links, ContentNode::Link { .. }) => true,
+            (NodeFilter::Text, ContentNode::Text(_)) => true,
+            (NodeFilter::Macros(format), ContentNode::Text(text)) => contains_macro(text, format),
+            (NodeFilter::Macros(_), ContentNode::Raw { .. }) => true,
+            _ => false,
+        }
+    }
+}
+
+/// The title of a passage, with the nodes left to yield and their index.
+type PassageNodes<'a> = (&'a str, IntoIter<(usize, ContentNode<&'a str>)>);
+
+/// Iterator over the nodes of a whole story, in source order, see [`Story::nodes`].
+///
+/// Yields the title of the passage of each node, with the index of the node in the passage.
+/// Filtered iterators read the nodes inside paragraphs, their text as text nodes, with the
+/// index of the paragraph.
+pub struct NodeIterator<'a, T>
+where
+    T: Deref<Target = str>,
+{
+    story: &'a Story<T>,
+    passages: Iter<'a, T>,
+    current: Option<PassageNodes<'a>>,
+    filter: NodeFilter,
+}
+
+impl<'a, T> NodeIterator<'a, T>
+where
+    T: Deref<Target = str>,
+{
+    pub fn new(story: &'a Story<T>) -> Self {
+        Self {
+            story,
+            passages: story.iter(),
+            current: None,
+            filter: NodeFilter::All,
+        }
+    }
+
+    pub fn links_only(mut self) -> Self {
+        self.filter = NodeFilter::Links;
+        self
+    }
+
+    pub fn text_only(mut self) -> Self {
+        self.filter = NodeFilter::Text;
+        self
+    }
+
+    /// Only text nodes containing macros of the [story format](Story::story_format), and the
+    /// macros read with [`ParseOptions::raw_sigils`](crate::ParseOptions::raw_sigils).
+    pub fn macros_only(mut self) -> Self {
+        self.filter = NodeFilter::Macros(self.story.story_format());
+        self
+    }
+}
+
+impl<'a, T> Iterator for NodeIterator<'a, T>
+where
+    T: Deref<Target = str>,
+{
+    type Item = (&'a str, usize, ContentNode<&'a str>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let filter = self.filter;
+        loop {
+            if let Some((title, nodes)) = &mut self.current {
+                let title = *title;
+                if let Some((index, node)) = nodes.find(|(_, node)| filter.accepts(node)) {
+                    return Some((title, index, node));
+                }
+            }
+            let passage = self.passages.next()?;
+            let nodes: Vec<_> = passage
+                .content
+                .into_iter()
+                .enumerate()
+                .flat_map(|(index, node)| match node {
+                    ContentNode::Paragraph(inlines) if filter != NodeFilter::All => inlines
+                        .into_iter()
+                        .filter_map(|inline| match inline {
+                            Inline::Text(text) => Some((index, ContentNode::Text(text))),
+                            Inline::LineBreak => None,
+                            Inline::Node(node) => Some((index, node)),
+                        })
+                        .collect(),
+                    node => vec![(index, node)],
+                })
+                .collect();
+            self.current = Some((passage.title, nodes.into_iter()));
+        }
+    }
+}
+
+impl<T> Story<T>
+where
+    T: Deref<Target = str>,
+{
+    /// The passage `name`, with the story to navigate from it.
+    pub fn passage(&self, name: &str) -> Option<PassageRef<'_, T>> {
+        Some(PassageRef {
+            story: self,
+            passage: self.get_passage(name)?,
+        })
+    }
+
+    /// Iterates over the nodes of all passages, in source order.
+    pub fn nodes(&self) -> NodeIterator<'_, T> {
+        NodeIterator::new(self)
+    }
+
+    /// Iterates over the links of all passages, in source order.
+    pub fn links(&self) -> impl Iterator<Item = StoryLink<'_>> {
+        self.nodes()
+            .links_only()
+            .filter_map(|(passage, _, node)| match node {
+                ContentNode::Link {
+                    text,
+                    target,
+                    setter,
+                } => Some(StoryLink {
+                    passage,
+                    text,
+                    target,
+                    setter,
+                }),
+                _ => None,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Choice, StoryLink};
+    use crate::{ContentNode, Dialect, ParseOptions, Story};
+
+    #[test]
+    fn test_nodes() {
+        let input = ":: A\nGo [[B]] now\n\n:: B\n<<set $x to 1>>Back to [[A]]\n";
+        let story = Story::try_from(input).unwrap();
+
+        assert_eq!(story.nodes().count(), 5);
+        assert_eq!(
+            story.nodes().links_only().collect::<Vec<_>>(),
+            [
+                ("A", 1, ContentNode::link_node("B", "B")),
+                ("B", 1, ContentNode::link_node("A", "A")),
+            ]
+        );
+        assert_eq!(
+            story
+                .nodes()
+                .text_only()
+                .map(|(title, index, _)| (title, index))
+                .collect::<Vec<_>>(),
+            [("A", 0), ("A", 2), ("B", 0)]
+        );
+        assert_eq!(
+            story.nodes().macros_only().collect::<Vec<_>>(),
+            [("B", 0, ContentNode::Text("<<set $x to 1>>Back to "))]
+        );
+    }
+
+    #[test]
+    fn test_nodes_raw_macros() {
+        let input = ":: A\nGo [[B]] now\n\n:: B\n<<set $x to 1>>Back to [[A]]\n";
+        let raw = ContentNode::Raw {
+            dialect: Dialect::SugarCube,
+            raw: "<<set $x to 1>>",
+        };
+
+        let story = Story::parse_with(input, ParseOptions::default().raw_sigils(true)).unwrap();
+        assert_eq!(
+            story.nodes().macros_only().collect::<Vec<_>>(),
+            [("B", 0, raw.clone())]
+        );
+
+        let options = ParseOptions::default().raw_sigils(true).paragraphs(true);
+        let story = Story::parse_with(input, options).unwrap();
+        assert_eq!(
+            story.nodes().macros_only().collect::<Vec<_>>(),
+            [("B", 0, raw)]
+        );
+
+        let story = Story::parse_with(input, ParseOptions::default().paragraphs(true)).unwrap();
+        assert_eq!(
+            story.nodes().macros_only().collect::<Vec<_>>(),
+            [("B", 0, ContentNode::Text("<<set $x to 1>>Back to "))]
+        );
+    }
+
+    #[test]
+    fn test_choices() {
+        let input =
+            ":: A\n[[Run|B]] <<set $note to \"[[C]]\">> /* [[D]] */ [[Flee|B]] [[Wait|A][$t++]]\n";
+        let story = Story::try_from(input).unwrap();
+
+        assert_eq!(
+            story.get_passage("A").unwrap().choices(),
+            [
+                Choice {
+                    text: "Run",
+                    target: "B",
+                    setter: None,
+                    index: 0
+                },
+                Choice {
+                    text: "Wait",
+                    target: "A",
+                    setter: Some("$t++"),
+                    index: 8
+                },
+            ]
+        );
+
+        let options = ParseOptions::default().paragraphs(true);
+        let story = Story::parse_with(input, options).unwrap();
+        let passage = story.get_passage("A").unwrap();
+        let choices = passage.choices();
+        let targets: Vec<_> = choices.iter().map(|choice| choice.target).collect();
+        assert_eq!(targets, ["B", "A"]);
+        assert!(choices.iter().all(|choice| choice.index == 0));
+    }
+
+    #[test]
+    fn test_passage_ref() {
+        let input = ":: A\nGo [[B]] or [[Nowhere]]\n\n:: B\n[[A]] [[C]]\n\n:: C\n[[A]]\n";
+        let story = Story::try_from(input).unwrap();
+        let a = story.passage("A").unwrap();
+
+        let b = a.follow("B").unwrap();
+        assert_eq!(*b.title(), "B");
+        assert!(a.follow("Nowhere").is_none());
+        assert!(a.follow("C").is_none());
+        assert_eq!(b.follow("C").unwrap().to_string(), "A");
+        let backlinks: Vec<_> = a.backlinks().map(|passage| *passage.title()).collect();
+        assert_eq!(backlinks, ["B", "C"]);
+    }
+
+    #[test]
+    fn test_story_links() {
+        let input = ":: A\nGo [[B]]\n\n:: B\n[[Back|A][$x to 1]]\n";
+        let story = Story::try_from(input).unwrap();
+
+        assert_eq!(
+            story.links().collect::<Vec<_>>(),
+            [
+                StoryLink {
+                    passage: "A",
+                    text: "B",
+                    target: "B",
+                    setter: None
+                },
+                StoryLink {
+                    passage: "B",
+                    text: "Back",
+                    target: "A",
+                    setter: Some("$x to 1")
+                },
+            ]
+        );
+    }
+}
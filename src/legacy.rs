@@ -0,0 +1,254 @@
+//! Conversion of older story formats to twee 3, for archives predating it: Twee 1 and Twee2
+//! sources, and Twine 1 HTML files.
+//!
+//! Headers that aren't valid twee 3, such as `::Title[tag]`, are rewritten with their names
+//! and tags escaped, and Twee2 positions (`<10,20>`) become metadata. The start passage given
+//! in Twee2Settings, with `@story_start_name` or `@startnode`, moves to StoryData.
+
+use std::borrow::Cow;
+
+use serde_json::json;
+
+use crate::{
+    error::ParsingError,
+    escape::{escape_name, escape_tag, sanitize_title},
+    parser::passage::parse_header,
+    Story,
+};
+
+const SETTINGS: &str = "Twee2Settings";
+const START_DIRECTIVES: &[&str] = &["@story_start_name", "@startnode"];
+
+/// Converts `input` to twee 3, borrowing it when it already is.
+pub fn to_twee3(input: &str) -> Cow<'_, str> {
+    if input.contains("<div tiddler=") {
+        Cow::Owned(from_twine1_html(input))
+    } else {
+        from_twee2(input)
+    }
+}
+
+/// Converts `input` with [`to_twee3`], then parses it.
+pub fn parse(input: &str) -> Result<Story<String>, ParsingError<String>> {
+    Story::try_from(to_twee3(input).into_owned())
+}
+
+fn write_header(output: &mut String, title: &str, tags: &[&str], position: Option<&str>) {
+    output.push_str(":: ");
+    output.push_str(&escape_name(title));
+    if !tags.is_empty() {
+        let tags: Vec<_> = tags.iter().map(|tag| escape_tag(tag)).collect();
+        output.push_str(&format!(" [{}]", tags.join(" ")));
+    }
+    if let Some(position) = position {
+        output.push_str(&format!(" {}", json!({ "position": position })));
+    }
+}
+
+fn is_position(position: &str) -> bool {
+    let parts: Vec<_> = position.split(',').collect();
+    parts.len() == 2 && parts.iter().all(|part| part.trim().parse::<f64>().is_ok())
+}
+
+/// The header `line` in twee 3, if it is a header that needs converting.
+fn convert_header(line: &str) -> Option<String> {
+    let rest = line.strip_prefix("::")?.trim_end();
+    // Twee 2 headers have no space after the `::`, which twee 3 headers now accept.
+    if line.starts_with(":: ") && parse_header(&format!("{line}\n")).is_ok() {
+        return None;
+    }
+    let (rest, position) = match rest
+        .strip_suffix('>')
+        .and_then(|rest| rest.rsplit_once('<'))
+    {
+        Some((rest, position)) if is_position(position) => (rest.trim_end(), Some(position)),
+        _ => (rest, None),
+    };
+    let (title, tags) = match rest.strip_suffix(']').and_then(|rest| rest.split_once('[')) {
+        Some((title, tags)) => (title, tags.split_whitespace().collect()),
+        None => (rest, vec![]),
+    };
+    let title = sanitize_title(title)?;
+
+    let mut header = String::new();
+    write_header(
+        &mut header,
+        &title,
+        &tags,
+        position.map(|p| p.replace(' ', "")).as_deref(),
+    );
+    Some(header)
+}
+
+fn title_of(header: &str) -> Option<Cow<'_, str>> {
+    let (_, (title, _, _)) = parse_header(header).ok()?;
+    Some(crate::escape::unescape(title))
+}
+
+fn from_twee2(input: &str) -> Cow<'_, str> {
+    let mut output = String::with_capacity(input.len());
+    let mut changed = false;
+    let mut in_settings = false;
+    let mut has_story_data = false;
+    let mut start = None;
+    for line in input.split_inclusive('\n') {
+        let text = line.trim_end_matches(['\n', '\r']);
+        let header = match convert_header(text) {
+            Some(header) => {
+                changed = true;
+                Some(format!("{header}{}", &line[text.len()..]))
+            }
+            None => text.starts_with("::").then(|| line.to_string()),
+        };
+        if let Some(header) = header {
+            let title = title_of(&header);
+            in_settings = title.as_deref() == Some(SETTINGS);
+            has_story_data |= title.as_deref() == Some("StoryData");
+            if in_settings {
+                changed = true;
+            } else {
+                output.push_str(&header);
+            }
+            continue;
+        }
+        if !in_settings {
+            output.push_str(line);
+            continue;
+        }
+        let directive = START_DIRECTIVES
+            .iter()
+            .find_map(|directive| text.strip_prefix(directive));
+        if let Some(name) = directive {
+            start = Some(name.trim().trim_matches(['"', '\'']).to_string());
+        }
+    }
+    if !changed {
+        return Cow::Borrowed(input);
+    }
+    if let Some(start) = start.filter(|_| !has_story_data) {
+        if !output.is_empty() && !output.ends_with("\n\n") {
+            output.push_str(if output.ends_with('\n') { "\n" } else { "\n\n" });
+        }
+        output.push_str(&format!(":: StoryData\n{}\n", json!({ "start": start })));
+    }
+    Cow::Owned(output)
+}
+
+fn decode_entities(input: &str) -> String {
+    input
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Value of the attribute `name` in the opening tag `tag`.
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let pattern = format!(" {name}=\"");
+    let start = tag.find(&pattern)? + pattern.len();
+    let end = tag[start..].find('"')?;
+    Some(&tag[start..start + end])
+}
+
+/// Resolves the escapes of Twine 1 passage text: `\n`, `\t` and `\s` for a backslash.
+fn decode_tiddler_text(input: &str) -> String {
+    let mut text = String::with_capacity(input.len());
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => text.push('\n'),
+                Some('t') => text.push('\t'),
+                Some('s') => text.push('\\'),
+                Some(other) => text.push(other),
+                None => text.push('\\'),
+            },
+            c => text.push(c),
+        }
+    }
+    decode_entities(&text)
+}
+
+fn from_twine1_html(input: &str) -> String {
+    let mut output = String::new();
+    let mut rest = input;
+    while let Some(start) = rest.find("<div tiddler=") {
+        rest = &rest[start..];
+        let Some(tag_end) = rest.find('>') else {
+            break;
+        };
+        let tag = &rest[..tag_end];
+        let body = &rest[tag_end + 1..];
+        let body_end = body.find("</div>").unwrap_or(body.len());
+        rest = &body[body_end..];
+
+        let Some(title) = attribute(tag, "tiddler").map(decode_entities) else {
+            continue;
+        };
+        let tags = attribute(tag, "tags")
+            .map(decode_entities)
+            .unwrap_or_default();
+        let tags: Vec<_> = tags.split_whitespace().collect();
+        let position = attribute(tag, "twine-position").filter(|p| is_position(p));
+        let text = decode_tiddler_text(&body[..body_end]);
+
+        if !output.is_empty() {
+            output.push_str("\n\n");
+        }
+        write_header(&mut output, &title, &tags, position);
+        output.push('\n');
+        // A line starting with `::` would be read as a passage header.
+        let text = match text.strip_prefix("::") {
+            Some(text) => format!("\\::{text}"),
+            None => text,
+        };
+        output.push_str(&text.replace("\n::", "\n\\::"));
+    }
+    output.push('\n');
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::{parse, to_twee3};
+
+    #[test]
+    fn test_twee3_is_borrowed() {
+        let input = ":: Start [a]\nHello [[End]]\n\n:: End\nBye\n";
+
+        assert!(matches!(to_twee3(input), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_from_twee2() {
+        let input = "::StoryTitle\nOld\n\n::Twee2Settings [twee2]\n@story_start_name 'Intro'\n\n::Intro [a  b]<10,20>\nGo [[A{1}]]\n\n::A{1}\nEnd\n";
+
+        assert_eq!(
+            to_twee3(input),
+            ":: StoryTitle\nOld\n\n:: Intro [a b] {\"position\":\"10,20\"}\nGo [[A{1}]]\n\n:: A\\{1\\}\nEnd\n\n:: StoryData\n{\"start\":\"Intro\"}\n"
+        );
+        let story = parse(input).unwrap();
+        assert_eq!(story.title(), Some("Old"));
+        assert_eq!(story.start().unwrap().title, "Intro");
+        assert!(story.get_passage("A{1}").is_some());
+    }
+
+    #[test]
+    fn test_from_twine1_html() {
+        let input = r#"<html><div id="storeArea"><div tiddler="Start" tags="intro" twine-position="100,50" modifier="twee">Hi &quot;you&quot;\n[[Next]]</div><div tiddler="Next" tags="">:: not a header</div></div></html>"#;
+
+        assert_eq!(
+            to_twee3(input),
+            ":: Start [intro] {\"position\":\"100,50\"}\nHi \"you\"\n[[Next]]\n\n:: Next\n\\:: not a header\n"
+        );
+        let story = parse(input).unwrap();
+        assert_eq!(
+            story.get_passage("Next").unwrap().to_string(),
+            ":: not a header"
+        );
+    }
+}
@@ -0,0 +1,124 @@
+//! The line-based tokens of twee, for syntax highlighters.
+//!
+//! Header lines are read by the functions of the parser, so that highlighting matches
+//! parsing. The content of passages is left in whole lines.
+
+use std::ops::Range;
+
+use crate::{
+    encoding::strip_bom,
+    parser::passage::{parse_header, parse_tags},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    /// The `::` opening a header line.
+    HeaderMarker,
+    Title,
+    /// The tags of a passage, brackets included.
+    TagBlock,
+    /// The metadata of a passage, a JSON object.
+    Metadata,
+    /// A line of passage content, without its line ending.
+    Content,
+    /// A line starting with `::` that isn't a header, or text outside of passages.
+    Invalid,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+    /// Byte range of the token in the input.
+    pub range: Range<usize>,
+}
+
+/// Splits `input` into tokens, in source order. Blank lines have none.
+pub fn tokenize(input: &str) -> Vec<Token<'_>> {
+    let mut tokens = vec![];
+    let mut push = |kind, range: Range<usize>| {
+        tokens.push(Token {
+            kind,
+            text: &input[range.clone()],
+            range,
+        })
+    };
+    let offset = |slice: &str| slice.as_ptr() as usize - input.as_ptr() as usize;
+
+    let mut position = input.len() - strip_bom(input).len();
+    let mut in_passage = false;
+    while position < input.len() {
+        let rest = &input[position..];
+        let line_length = rest.find('\n').map_or(rest.len(), |end| end + 1);
+        let line = rest[..line_length].trim_end_matches(['\r', '\n']);
+
+        if line.starts_with("::") {
+            match parse_header(rest) {
+                Ok((after, (title, tags, metadata))) => {
+                    push(TokenKind::HeaderMarker, position..position + 2);
+                    let title_end = offset(title) + title.len();
+                    push(TokenKind::Title, offset(title)..title_end);
+                    if tags.is_some() {
+                        let open = title_end + input[title_end..].find('[').unwrap_or_default();
+                        let close = match parse_tags(&input[open..]) {
+                            Ok((after_tags, _)) => offset(after_tags),
+                            Err(_) => open,
+                        };
+                        push(TokenKind::TagBlock, open..close);
+                    }
+                    if let Some(metadata) = metadata {
+                        let start = offset(metadata.content);
+                        push(TokenKind::Metadata, start..start + metadata.content.len());
+                    }
+                    in_passage = true;
+                    position = offset(after);
+                    continue;
+                }
+                Err(_) => {
+                    push(TokenKind::Invalid, position..position + line.len());
+                    in_passage = false;
+                }
+            }
+        } else if !line.trim().is_empty() {
+            let kind = match in_passage {
+                true => TokenKind::Content,
+                false => TokenKind::Invalid,
+            };
+            push(kind, position..position + line.len());
+        }
+        position += line_length;
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tokenize, TokenKind};
+
+    #[test]
+    fn test_tokenize() {
+        let input = "stray\n:: A [x y]  {\"n\":1}\r\nGo [[B]]\n\nOn\n::\n:: B\\[1\\]\nEnd";
+        let tokens: Vec<_> = tokenize(input)
+            .into_iter()
+            .map(|token| (token.kind, token.text))
+            .collect();
+
+        assert_eq!(
+            tokens,
+            [
+                (TokenKind::Invalid, "stray"),
+                (TokenKind::HeaderMarker, "::"),
+                (TokenKind::Title, "A"),
+                (TokenKind::TagBlock, "[x y]"),
+                (TokenKind::Metadata, "{\"n\":1}"),
+                (TokenKind::Content, "Go [[B]]"),
+                (TokenKind::Content, "On"),
+                (TokenKind::Invalid, "::"),
+                (TokenKind::HeaderMarker, "::"),
+                (TokenKind::Title, "B\\[1\\]"),
+                (TokenKind::Content, "End"),
+            ]
+        );
+        assert_eq!(tokenize("\u{feff}:: A\n")[0].range, 3..5);
+    }
+}
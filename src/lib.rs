@@ -3,6 +3,8 @@
 //! .twee files can be generated with Twine.
 //! See [twee-3-specification.md](https://github.com/iftechfoundation/twine-specs/blob/master/twee-3-specification.md).
 //!
+//! Parsing never panics, whatever the input: malformed stories return a [`ParsingError`].
+//!
 //! ```rust
 //! let twee = "your twine content";
 //!
@@ -12,49 +14,122 @@
 //! ```
 
 use std::{
-    collections::HashMap,
+    borrow::Cow,
     fmt::Display,
     ops::{Deref, Range},
+    sync::Arc,
 };
 
+pub use batch::{parse_many, parse_many_parallel};
+pub use diagnostics::{Position, Warning};
+pub use error::ParsingError;
+pub use format::StoryFormat;
+pub use highlight::{HighlightSpan, SpanKind};
+use index::Derived;
 use iter::LinkIterator;
+pub use options::{Limit, LinkDialects, ParseOptions, Strictness};
+use passage_map::PassageMap;
+pub use progress::{CancellationToken, Progress, ProgressCallback};
+pub use roundtrip::{verify_roundtrip, RoundtripMismatch, RoundtripReport};
+pub use scan::{parse_events, ParseEvent};
+pub use story_data::UnknownPassage;
 use utils::escape_string_content;
+pub use writer::{DisplayMode, NodeDisplay, PassageOrder};
 
+pub mod analysis;
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
+#[cfg(feature = "arena")]
+pub mod arena;
+pub mod assets;
+pub mod batch;
+#[cfg(feature = "bevy")]
+pub mod bevy;
+#[cfg(feature = "binary")]
+pub mod binary;
+#[cfg(feature = "compat")]
+pub mod compat;
+pub mod conformance;
+mod diagnostics;
+pub mod dialogue;
+pub mod edit;
+mod editor;
+#[cfg(feature = "embed")]
+pub mod embed;
+pub mod encoding;
 mod error;
+pub mod escape;
+pub mod export;
+pub mod extension;
+mod format;
+mod highlight;
+pub mod i18n;
+mod include;
+pub mod incremental;
+mod index;
+pub mod intern;
 pub mod iter;
+pub mod legacy;
+pub mod lexer;
+pub mod lsp;
+pub mod macros;
+#[cfg(feature = "markup")]
+pub mod markup;
+pub mod metadata;
+mod options;
+#[cfg(feature = "packed")]
+pub mod packed;
 mod parser;
+mod passage_map;
+mod progress;
+pub mod prose;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod render;
+mod roundtrip;
+#[cfg(feature = "samples")]
+pub mod samples;
+pub mod scan;
+mod scripts;
+pub mod search;
+pub mod stats;
+mod story_data;
+#[cfg(feature = "testing")]
+pub mod testing;
 mod utils;
+pub mod validation;
+pub mod visit;
+mod writer;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 enum TextBlock {
     Owned(String),
     Borrowed(Range<usize>),
+    /// Text stored once for the whole story, see [`Story::dedup_text`].
+    Shared(Arc<str>),
 }
 
 impl TextBlock {
-    pub fn owned(s: String) -> Self {
-        Self::Owned(match escape_string_content(&s) {
-            Some(escaped) => escaped,
-            None => s,
-        })
-    }
-
     pub fn borrowed(original: &str, substring: &str) -> Self {
         match escape_string_content(substring) {
             // If the content is escaped, its a copy.
             Some(escaped) => Self::Owned(escaped),
-            None => {
-                let original_begin = original.as_ptr() as usize;
-                let original_end = original_begin + original.len();
-                let substring_begin = substring.as_ptr() as usize;
-                let substring_end = substring_begin + substring.len();
-                if substring_begin < original_begin || substring_end > original_end {
-                    // substring is not a substring of original, so we need to copy it.
-                    Self::Owned(substring.to_owned())
-                } else {
-                    Self::Borrowed(substring_begin - original_begin..substring_end - original_begin)
-                }
-            }
+            None => Self::verbatim(original, substring),
+        }
+    }
+
+    /// Borrows the substring as is, without resolving escaped characters.
+    pub fn verbatim(original: &str, substring: &str) -> Self {
+        let original_begin = original.as_ptr() as usize;
+        let substring_begin = substring.as_ptr() as usize;
+        if substring_begin < original_begin
+            || substring_begin + substring.len() > original_begin + original.len()
+        {
+            // substring is not a substring of original, so we need to copy it.
+            Self::Owned(substring.to_owned())
+        } else {
+            let begin = substring_begin - original_begin;
+            Self::Borrowed(begin..begin + substring.len())
         }
     }
 
@@ -62,6 +137,7 @@ impl TextBlock {
         match self {
             TextBlock::Owned(s) => s.as_str(),
             TextBlock::Borrowed(r) => &original[r.clone()],
+            TextBlock::Shared(s) => s,
         }
     }
 }
@@ -72,33 +148,55 @@ impl From<&str> for TextBlock {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub struct Metadata<T> {
     content: T,
 }
 
 impl<T> Metadata<T> {
-    pub(crate) fn new(content: T) -> Self {
+    /// Metadata of a passage header, `content` being its JSON object.
+    pub fn new(content: T) -> Self {
         Self { content }
     }
+
+    pub fn content(&self) -> &T {
+        &self.content
+    }
+
+    fn map<U>(self, f: impl Fn(T) -> U) -> Metadata<U> {
+        Metadata::new(f(self.content))
+    }
+}
+
+impl Metadata<&str> {
+    fn into_owned(self) -> Metadata<String> {
+        Metadata::new(self.content.to_owned())
+    }
+}
+
+impl Metadata<String> {
+    fn into_blocks(self) -> Metadata<TextBlock> {
+        Metadata::new(TextBlock::Owned(self.content))
+    }
 }
 
 impl Metadata<TextBlock> {
-    fn as_borrowed<'a>(&'a self, original: &'a str) -> Metadata<&str> {
+    fn as_borrowed<'a>(&'a self, original: &'a str) -> Metadata<&'a str> {
         Metadata::new(self.content.as_str(original))
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub struct Passage<T> {
     title: T,
     tags: Vec<Tag<T>>,
     metadata: Option<Metadata<T>>,
     content: Vec<ContentNode<T>>,
+    source: Option<T>,
 }
 
 impl<T> Passage<T> {
-    fn new(
+    pub fn new(
         title: T,
         tags: Vec<Tag<T>>,
         metadata: Option<Metadata<T>>,
@@ -109,6 +207,7 @@ impl<T> Passage<T> {
             tags,
             metadata,
             content,
+            source: None,
         }
     }
 
@@ -116,13 +215,93 @@ impl<T> Passage<T> {
         &self.title
     }
 
+    pub fn tags(&self) -> &[Tag<T>] {
+        &self.tags
+    }
+
+    pub fn metadata(&self) -> Option<&Metadata<T>> {
+        self.metadata.as_ref()
+    }
+
     pub fn nodes(&self) -> &[ContentNode<T>] {
         &self.content
     }
 
-    pub fn links(&self) -> LinkIterator<T> {
+    pub fn links(&self) -> LinkIterator<'_, T> {
         LinkIterator::new(&self.content)
     }
+
+    pub fn images(&self) -> impl Iterator<Item = &Image<T>> {
+        self.content
+            .iter()
+            .flat_map(ContentNode::leaves)
+            .filter_map(|node| match node {
+                ContentNode::Image(image) => Some(image),
+                _ => None,
+            })
+    }
+
+    /// The exact source of the passage, header and trailing blank lines included.
+    ///
+    /// Only available when parsing with [`ParseOptions::verbatim`].
+    pub fn verbatim(&self) -> Option<&T> {
+        self.source.as_ref()
+    }
+
+    pub fn push_text(&mut self, text: T) {
+        self.source = None;
+        self.content.push(ContentNode::text_node(text));
+    }
+
+    pub fn push_link(&mut self, text: T, target: T) {
+        self.source = None;
+        self.content.push(ContentNode::link_node(text, target));
+    }
+
+    /// Replaces the node at `index`, returning the previous one, or `None` if `index` is out of
+    /// bounds.
+    pub fn replace_node(&mut self, index: usize, node: ContentNode<T>) -> Option<ContentNode<T>> {
+        let current = self.content.get_mut(index)?;
+        self.source = None;
+        Some(std::mem::replace(current, node))
+    }
+
+    pub fn set_tags<I>(&mut self, tags: I)
+    where
+        I: IntoIterator<Item = T>,
+    {
+        self.source = None;
+        self.tags = tags.into_iter().map(Tag::new).collect();
+    }
+
+    fn map<U>(self, f: impl Fn(T) -> U) -> Passage<U> {
+        Passage {
+            title: f(self.title),
+            tags: self.tags.into_iter().map(|tag| tag.map(&f)).collect(),
+            metadata: self.metadata.map(|metadata| metadata.map(&f)),
+            content: self.content.into_iter().map(|node| node.map(&f)).collect(),
+            source: self.source.map(&f),
+        }
+    }
+}
+
+/// Passages holding either borrowed or owned text, to mix both in one collection.
+impl<'a> From<Passage<&'a str>> for Passage<Cow<'a, str>> {
+    fn from(passage: Passage<&'a str>) -> Self {
+        passage.map(Cow::Borrowed)
+    }
+}
+
+impl From<Passage<String>> for Passage<Cow<'_, str>> {
+    fn from(passage: Passage<String>) -> Self {
+        passage.map(Cow::Owned)
+    }
+}
+
+impl Passage<Cow<'_, str>> {
+    pub fn into_owned(self) -> Passage<String> {
+        self.map(Cow::into_owned)
+    }
 }
 
 impl<T> Display for Passage<T>
@@ -137,6 +316,38 @@ where
     }
 }
 
+impl Passage<&str> {
+    pub fn into_owned(self) -> Passage<String> {
+        Passage {
+            title: self.title.to_owned(),
+            tags: self.tags.into_iter().map(Tag::into_owned).collect(),
+            metadata: self.metadata.map(Metadata::into_owned),
+            content: self
+                .content
+                .into_iter()
+                .map(ContentNode::into_owned)
+                .collect(),
+            source: self.source.map(str::to_owned),
+        }
+    }
+}
+
+impl Passage<String> {
+    fn into_blocks(self) -> Passage<TextBlock> {
+        Passage {
+            title: TextBlock::Owned(self.title),
+            tags: self.tags.into_iter().map(Tag::into_blocks).collect(),
+            metadata: self.metadata.map(Metadata::into_blocks),
+            content: self
+                .content
+                .into_iter()
+                .map(ContentNode::into_blocks)
+                .collect(),
+            source: self.source.map(TextBlock::Owned),
+        }
+    }
+}
+
 impl<'a> Passage<TextBlock> {
     fn as_borrowed(&'a self, original: &'a str) -> Passage<&'a str> {
         Passage {
@@ -148,11 +359,29 @@ impl<'a> Passage<TextBlock> {
                 .iter()
                 .map(|n| n.as_borrowed(original))
                 .collect(),
+            source: self.source.as_ref().map(|s| s.as_str(original)),
+        }
+    }
+
+    /// Calls `f` on every text block of the passage.
+    fn for_each_block(&mut self, mut f: impl FnMut(&mut TextBlock)) {
+        f(&mut self.title);
+        for tag in &mut self.tags {
+            f(&mut tag.value);
+        }
+        if let Some(metadata) = &mut self.metadata {
+            f(&mut metadata.content);
+        }
+        for node in &mut self.content {
+            node.for_each_block(&mut f);
+        }
+        if let Some(source) = &mut self.source {
+            f(source);
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub struct Tag<T> {
     value: T,
 }
@@ -167,30 +396,162 @@ where
 }
 
 impl<T> Tag<T> {
-    fn new(value: T) -> Self {
+    pub fn new(value: T) -> Self {
         Self { value }
     }
+
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    fn map<U>(self, f: impl Fn(T) -> U) -> Tag<U> {
+        Tag::new(f(self.value))
+    }
+}
+
+impl Tag<&str> {
+    fn into_owned(self) -> Tag<String> {
+        Tag::new(self.value.to_owned())
+    }
+}
+
+impl Tag<String> {
+    fn into_blocks(self) -> Tag<TextBlock> {
+        Tag::new(TextBlock::Owned(self.value))
+    }
 }
 
 impl Tag<TextBlock> {
-    fn as_borrowed<'a>(&'a self, original: &'a str) -> Tag<&str> {
+    fn as_borrowed<'a>(&'a self, original: &'a str) -> Tag<&'a str> {
         Tag::new(self.value.as_str(original))
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub enum ContentNode<T> {
     Text(T),
-    Link { text: T, target: T },
+    Link {
+        text: T,
+        target: T,
+        /// The setter component of `[[Text|Target][$x to 1]]`.
+        setter: Option<T>,
+    },
+    /// A comment, delimiters included: `/* */`, `/% %/` or `<!-- -->`.
+    Comment(T),
+    /// An HTML tag, only read when [`ParseOptions::html`] is enabled.
+    Html(HtmlTag<T>),
+    /// An image, `[img[image.png]]` or `[img[Title|image.png][Target]]`.
+    Image(Image<T>),
+    /// A node of a kind added by a plugin, see [`ParseOptions::node_kind`].
+    Custom(CustomNode<T>),
+    /// A construct of an unknown story format, such as `<<set $x to 1>>`, kept as written. Only
+    /// read when [`ParseOptions::raw_sigils`] is enabled.
+    Raw {
+        dialect: Dialect,
+        raw: T,
+    },
+    /// The nodes between blank lines, only read when [`ParseOptions::paragraphs`] is enabled.
+    /// The blank lines are kept at the end of the paragraph before them.
+    Paragraph(Vec<Inline<T>>),
+}
+
+/// A piece of a [`ContentNode::Paragraph`].
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub enum Inline<T> {
+    /// Text within a line.
+    Text(T),
+    /// The end of a line, `\n` or `\r\n`.
+    LineBreak,
+    /// Any other node, such as a link. Never text or a paragraph: binary and JSON stories can't
+    /// hold paragraphs inside paragraphs, and read text nodes as text.
+    Node(ContentNode<T>),
+}
+
+impl<T> Inline<T> {
+    fn map_ref<U, F: Fn(T) -> U>(self, f: &F) -> Inline<U> {
+        match self {
+            Inline::Text(text) => Inline::Text(f(text)),
+            Inline::LineBreak => Inline::LineBreak,
+            Inline::Node(node) => Inline::Node(node.map_ref(f)),
+        }
+    }
+}
+
+impl<T> Display for Inline<T>
+where
+    T: Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Inline::Text(text) => write!(f, "{text}"),
+            Inline::LineBreak => writeln!(f),
+            Inline::Node(node) => write!(f, "{node}"),
+        }
+    }
 }
 
 impl<T> ContentNode<T> {
-    fn text_node(text: T) -> Self {
+    pub fn text_node(text: T) -> Self {
         Self::Text(text)
     }
 
-    fn link_node(text: T, target: T) -> Self {
-        Self::Link { text, target }
+    pub fn link_node(text: T, target: T) -> Self {
+        Self::Link {
+            text,
+            target,
+            setter: None,
+        }
+    }
+
+    /// A comment node, `comment` including its delimiters.
+    pub fn comment_node(comment: T) -> Self {
+        Self::Comment(comment)
+    }
+
+    /// The node, or the nodes inside it for a paragraph, its text aside.
+    pub(crate) fn leaves(&self) -> impl Iterator<Item = &ContentNode<T>> {
+        let (node, inlines) = match self {
+            ContentNode::Paragraph(inlines) => (None, &inlines[..]),
+            node => (Some(node), &[][..]),
+        };
+        node.into_iter()
+            .chain(inlines.iter().filter_map(|inline| match inline {
+                Inline::Node(node) => Some(node),
+                _ => None,
+            }))
+    }
+
+    fn map<U>(self, f: impl Fn(T) -> U) -> ContentNode<U> {
+        self.map_ref(&f)
+    }
+
+    fn map_ref<U, F: Fn(T) -> U>(self, f: &F) -> ContentNode<U> {
+        match self {
+            ContentNode::Text(text) => ContentNode::Text(f(text)),
+            ContentNode::Link {
+                text,
+                target,
+                setter,
+            } => ContentNode::Link {
+                text: f(text),
+                target: f(target),
+                setter: setter.map(f),
+            },
+            ContentNode::Comment(comment) => ContentNode::Comment(f(comment)),
+            ContentNode::Html(tag) => ContentNode::Html(tag.map(f)),
+            ContentNode::Image(image) => ContentNode::Image(image.map(f)),
+            ContentNode::Custom(node) => ContentNode::Custom(node.map(f)),
+            ContentNode::Raw { dialect, raw } => ContentNode::Raw {
+                dialect,
+                raw: f(raw),
+            },
+            ContentNode::Paragraph(inlines) => ContentNode::Paragraph(
+                inlines
+                    .into_iter()
+                    .map(|inline| inline.map_ref(f))
+                    .collect(),
+            ),
+        }
     }
 }
 
@@ -201,23 +562,484 @@ where
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ContentNode::Text(text) => write!(f, "{text}"),
-            ContentNode::Link { text, target: _ } => write!(f, "{text}"),
+            ContentNode::Link { text, .. } => write!(f, "{text}"),
+            ContentNode::Comment(_)
+            | ContentNode::Html(_)
+            | ContentNode::Image(_)
+            | ContentNode::Custom(_)
+            | ContentNode::Raw { .. } => Ok(()),
+            ContentNode::Paragraph(inlines) => {
+                for inline in inlines {
+                    write!(f, "{inline}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<'a> ContentNode<&'a str> {
+    /// The node, or for a paragraph its text and line breaks as text nodes and the nodes inside
+    /// it.
+    pub(crate) fn flatten(&self) -> Vec<ContentNode<&'a str>> {
+        match self {
+            ContentNode::Paragraph(inlines) => inlines
+                .iter()
+                .map(|inline| match inline {
+                    Inline::Text(text) => ContentNode::Text(*text),
+                    Inline::LineBreak => ContentNode::Text("\n"),
+                    Inline::Node(node) => node.clone(),
+                })
+                .collect(),
+            node => vec![node.clone()],
+        }
+    }
+
+    /// The nodes of [`ContentNode::flatten`], with the index of their inline for a paragraph.
+    pub(crate) fn flatten_indexed(&self) -> Vec<(Option<usize>, ContentNode<&'a str>)> {
+        let paragraph = matches!(self, ContentNode::Paragraph(_));
+        self.flatten()
+            .into_iter()
+            .enumerate()
+            .map(|(inline, node)| (paragraph.then_some(inline), node))
+            .collect()
+    }
+
+    fn into_owned(self) -> ContentNode<String> {
+        match self {
+            ContentNode::Text(text) => ContentNode::Text(text.to_owned()),
+            ContentNode::Link {
+                text,
+                target,
+                setter,
+            } => ContentNode::Link {
+                text: text.to_owned(),
+                target: target.to_owned(),
+                setter: setter.map(str::to_owned),
+            },
+            ContentNode::Comment(comment) => ContentNode::Comment(comment.to_owned()),
+            ContentNode::Html(tag) => ContentNode::Html(tag.into_owned()),
+            ContentNode::Image(image) => ContentNode::Image(image.map(str::to_owned)),
+            ContentNode::Custom(node) => ContentNode::Custom(node.map(str::to_owned)),
+            ContentNode::Raw { dialect, raw } => ContentNode::Raw {
+                dialect,
+                raw: raw.to_owned(),
+            },
+            ContentNode::Paragraph(inlines) => {
+                ContentNode::Paragraph(inlines.into_iter().map(Inline::into_owned).collect())
+            }
+        }
+    }
+}
+
+impl ContentNode<String> {
+    fn into_blocks(self) -> ContentNode<TextBlock> {
+        match self {
+            ContentNode::Text(text) => ContentNode::Text(TextBlock::Owned(text)),
+            ContentNode::Link {
+                text,
+                target,
+                setter,
+            } => ContentNode::Link {
+                text: TextBlock::Owned(text),
+                target: TextBlock::Owned(target),
+                setter: setter.map(TextBlock::Owned),
+            },
+            ContentNode::Comment(comment) => ContentNode::Comment(TextBlock::Owned(comment)),
+            ContentNode::Html(tag) => ContentNode::Html(tag.into_blocks()),
+            ContentNode::Image(image) => ContentNode::Image(image.map(TextBlock::Owned)),
+            ContentNode::Custom(node) => ContentNode::Custom(node.map(TextBlock::Owned)),
+            ContentNode::Raw { dialect, raw } => ContentNode::Raw {
+                dialect,
+                raw: TextBlock::Owned(raw),
+            },
+            ContentNode::Paragraph(inlines) => {
+                ContentNode::Paragraph(inlines.into_iter().map(Inline::into_blocks).collect())
+            }
         }
     }
 }
 
 impl ContentNode<TextBlock> {
-    fn as_borrowed<'a>(&'a self, original: &'a str) -> ContentNode<&str> {
+    fn as_borrowed<'a>(&'a self, original: &'a str) -> ContentNode<&'a str> {
         match self {
             ContentNode::Text(text) => ContentNode::Text(text.as_str(original)),
-            ContentNode::Link { text, target } => ContentNode::Link {
+            ContentNode::Link {
+                text,
+                target,
+                setter,
+            } => ContentNode::Link {
                 text: text.as_str(original),
                 target: target.as_str(original),
+                setter: setter.as_ref().map(|setter| setter.as_str(original)),
             },
+            ContentNode::Comment(comment) => ContentNode::Comment(comment.as_str(original)),
+            ContentNode::Html(tag) => ContentNode::Html(tag.as_borrowed(original)),
+            ContentNode::Image(image) => {
+                ContentNode::Image(image.as_ref().map(|block| block.as_str(original)))
+            }
+            ContentNode::Custom(node) => ContentNode::Custom(CustomNode {
+                kind: node.kind.clone(),
+                raw: node.raw.as_str(original),
+            }),
+            ContentNode::Raw { dialect, raw } => ContentNode::Raw {
+                dialect: *dialect,
+                raw: raw.as_str(original),
+            },
+            ContentNode::Paragraph(inlines) => ContentNode::Paragraph(
+                inlines
+                    .iter()
+                    .map(|inline| inline.as_borrowed(original))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Calls `f` on every text block of the node.
+    fn for_each_block(&mut self, f: &mut impl FnMut(&mut TextBlock)) {
+        match self {
+            ContentNode::Text(text)
+            | ContentNode::Comment(text)
+            | ContentNode::Raw { raw: text, .. } => f(text),
+            ContentNode::Custom(node) => f(&mut node.raw),
+            ContentNode::Link {
+                text,
+                target,
+                setter,
+            } => {
+                f(text);
+                f(target);
+                setter.iter_mut().for_each(f);
+            }
+            ContentNode::Html(tag) => {
+                f(&mut tag.raw);
+                f(&mut tag.name);
+                for (name, value) in &mut tag.attributes {
+                    f(name);
+                    value.iter_mut().for_each(&mut *f);
+                }
+            }
+            ContentNode::Image(image) => {
+                f(&mut image.source);
+                for block in [&mut image.title, &mut image.target, &mut image.setter] {
+                    block.iter_mut().for_each(&mut *f);
+                }
+            }
+            ContentNode::Paragraph(inlines) => {
+                for inline in inlines {
+                    match inline {
+                        Inline::Text(text) => f(text),
+                        Inline::LineBreak => {}
+                        Inline::Node(node) => node.for_each_block(f),
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Inline<&str> {
+    fn into_owned(self) -> Inline<String> {
+        match self {
+            Inline::Text(text) => Inline::Text(text.to_owned()),
+            Inline::LineBreak => Inline::LineBreak,
+            Inline::Node(node) => Inline::Node(node.into_owned()),
+        }
+    }
+}
+
+impl Inline<String> {
+    fn into_blocks(self) -> Inline<TextBlock> {
+        match self {
+            Inline::Text(text) => Inline::Text(TextBlock::Owned(text)),
+            Inline::LineBreak => Inline::LineBreak,
+            Inline::Node(node) => Inline::Node(node.into_blocks()),
+        }
+    }
+}
+
+impl Inline<TextBlock> {
+    fn as_borrowed<'a>(&'a self, original: &'a str) -> Inline<&'a str> {
+        match self {
+            Inline::Text(text) => Inline::Text(text.as_str(original)),
+            Inline::LineBreak => Inline::LineBreak,
+            Inline::Node(node) => Inline::Node(node.as_borrowed(original)),
+        }
+    }
+}
+
+/// The syntax a [`ContentNode::Raw`] construct is written in, hinting at its story format.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Dialect {
+    /// `<<name ...>>`, as in SugarCube.
+    SugarCube,
+    /// `(name: ...)`, as in Harlowe.
+    Harlowe,
+    /// `{{...}}`, as in Mustache or Handlebars templates.
+    Mustache,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum ImageAlign {
+    /// `[<img[...]]`
+    Left,
+    /// `[>img[...]]`
+    Right,
+}
+
+/// An image embedded in passage content, optionally acting as a link.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct Image<T> {
+    source: T,
+    title: Option<T>,
+    target: Option<T>,
+    setter: Option<T>,
+    align: Option<ImageAlign>,
+}
+
+impl<T> Image<T> {
+    pub fn new(source: T) -> Self {
+        Self {
+            source,
+            title: None,
+            target: None,
+            setter: None,
+            align: None,
+        }
+    }
+
+    fn map<U>(self, f: impl Fn(T) -> U) -> Image<U> {
+        Image {
+            source: f(self.source),
+            title: self.title.map(&f),
+            target: self.target.map(&f),
+            setter: self.setter.map(&f),
+            align: self.align,
+        }
+    }
+
+    pub fn with_title(mut self, title: T) -> Self {
+        self.title = Some(title);
+        self
+    }
+
+    pub fn with_target(mut self, target: T) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    pub fn with_setter(mut self, setter: T) -> Self {
+        self.setter = Some(setter);
+        self
+    }
+
+    pub fn with_align(mut self, align: ImageAlign) -> Self {
+        self.align = Some(align);
+        self
+    }
+
+    fn as_ref(&self) -> Image<&T> {
+        Image {
+            source: &self.source,
+            title: self.title.as_ref(),
+            target: self.target.as_ref(),
+            setter: self.setter.as_ref(),
+            align: self.align,
+        }
+    }
+
+    pub fn source(&self) -> &T {
+        &self.source
+    }
+
+    pub fn title(&self) -> Option<&T> {
+        self.title.as_ref()
+    }
+
+    /// The passage the image links to, if any.
+    pub fn target(&self) -> Option<&T> {
+        self.target.as_ref()
+    }
+
+    /// The setter component, `[img[image.png][Target][$x to 1]]`.
+    pub fn setter(&self) -> Option<&T> {
+        self.setter.as_ref()
+    }
+
+    pub fn align(&self) -> Option<ImageAlign> {
+        self.align
+    }
+}
+
+/// An opening or closing HTML tag found in passage content, such as `<span class="red">`.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct HtmlTag<T> {
+    raw: T,
+    name: T,
+    attributes: Vec<(T, Option<T>)>,
+    closing: bool,
+}
+
+impl<T> HtmlTag<T> {
+    /// A tag named `name`, `raw` being the tag as written in the content.
+    pub fn new(raw: T, name: T, attributes: Vec<(T, Option<T>)>, closing: bool) -> Self {
+        Self {
+            raw,
+            name,
+            attributes,
+            closing,
         }
     }
+
+    fn map<U>(self, f: impl Fn(T) -> U) -> HtmlTag<U> {
+        HtmlTag {
+            raw: f(self.raw),
+            name: f(self.name),
+            attributes: self
+                .attributes
+                .into_iter()
+                .map(|(name, value)| (f(name), value.map(&f)))
+                .collect(),
+            closing: self.closing,
+        }
+    }
+}
+
+impl<T> HtmlTag<T>
+where
+    T: Deref<Target = str>,
+{
+    /// The tag as written in the source.
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Attributes in source order. Attributes without a value, like `hidden`, have none.
+    pub fn attributes(&self) -> impl Iterator<Item = (&str, Option<&str>)> {
+        self.attributes
+            .iter()
+            .map(|(name, value)| (name.deref(), value.as_deref()))
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes()
+            .find(|(attribute, _)| attribute.eq_ignore_ascii_case(name))
+            .and_then(|(_, value)| value)
+    }
+
+    /// Whether this is a closing tag, like `</span>`.
+    pub fn is_closing(&self) -> bool {
+        self.closing
+    }
+}
+
+impl HtmlTag<&str> {
+    fn into_owned(self) -> HtmlTag<String> {
+        self.map(str::to_owned)
+    }
+}
+
+impl HtmlTag<String> {
+    fn into_blocks(self) -> HtmlTag<TextBlock> {
+        self.map(TextBlock::Owned)
+    }
+}
+
+impl HtmlTag<TextBlock> {
+    fn as_borrowed<'a>(&'a self, original: &'a str) -> HtmlTag<&'a str> {
+        HtmlTag {
+            raw: self.raw.as_str(original),
+            name: self.name.as_str(original),
+            attributes: self
+                .attributes
+                .iter()
+                .map(|(name, value)| {
+                    (
+                        name.as_str(original),
+                        value.as_ref().map(|value| value.as_str(original)),
+                    )
+                })
+                .collect(),
+            closing: self.closing,
+        }
+    }
+}
+
+/// A node of a kind added by a plugin, such as a macro of a house story format.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct CustomNode<T> {
+    kind: Arc<str>,
+    raw: T,
+}
+
+impl<T> CustomNode<T> {
+    /// A node of the kind named `kind`, `raw` being the node as written in the content.
+    pub fn new(kind: impl Into<Arc<str>>, raw: T) -> Self {
+        Self {
+            kind: kind.into(),
+            raw,
+        }
+    }
+
+    /// The name of the [`NodeKind`](extension::NodeKind) that read the node.
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    /// The node as written in the source.
+    pub fn raw(&self) -> &T {
+        &self.raw
+    }
+
+    fn map<U>(self, f: impl Fn(T) -> U) -> CustomNode<U> {
+        CustomNode {
+            kind: self.kind,
+            raw: f(self.raw),
+        }
+    }
+}
+
+/// A top level block of a story, as laid out in its source.
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum SourceBlock {
+    /// Text before the first passage header.
+    Preamble(TextBlock),
+    Title(TextBlock),
+    Data(TextBlock),
+    Passage(String),
+    /// The source of a passage replaced by a later one with the same name.
+    Replaced(TextBlock),
+}
+
+/// A way to find the passage a story starts at, see [`Story::start_with`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartRule<'a> {
+    /// The start passage defined in StoryData.
+    StoryData,
+    /// The passage with this name, like Twine's conventional "Start".
+    Named(&'a str),
+    /// The first passage of the source.
+    FirstPassage,
 }
 
+impl StartRule<'_> {
+    /// The rules of [`Story::start`].
+    pub const DEFAULT: &'static [StartRule<'static>] = &[
+        StartRule::StoryData,
+        StartRule::Named("Start"),
+        StartRule::FirstPassage,
+    ];
+}
+
+/// A parsed twee story.
+///
+/// Clones share the passages, each copied the first time a clone changes it, so that editors
+/// can keep a clone per undo step. The clones of a `Story<Arc<str>>`, see
+/// [`Story::into_shared`], also share the source.
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Story<T>
 where
@@ -226,7 +1048,19 @@ where
     content: T,
     title: Option<TextBlock>,
     start: Option<TextBlock>,
-    passages: HashMap<String, Passage<TextBlock>>,
+    data: Option<TextBlock>,
+    passages: PassageMap<Passage<TextBlock>>,
+    source_blocks: Option<Vec<SourceBlock>>,
+    warnings: Vec<Warning>,
+    /// Story format overriding the one of StoryData.
+    format: Option<StoryFormat>,
+    /// Link forms the story was parsed with, which it is written back with.
+    link_dialects: LinkDialects,
+    /// Length of the byte order mark the source started with, which `content` leaves out.
+    bom: usize,
+    /// Options the story was parsed with, which [`Story::reparse_range`] parses edits with.
+    options: ParseOptions,
+    derived: Derived,
 }
 
 impl<T> Story<T>
@@ -237,13 +1071,42 @@ where
         content: T,
         title: Option<TextBlock>,
         start: Option<TextBlock>,
-        passages: HashMap<String, Passage<TextBlock>>,
+        data: Option<TextBlock>,
+        passages: PassageMap<Passage<TextBlock>>,
     ) -> Self {
         Self {
             content,
             title,
             start,
+            data,
             passages,
+            source_blocks: None,
+            warnings: vec![],
+            format: None,
+            link_dialects: LinkDialects::default(),
+            bom: 0,
+            options: ParseOptions::default(),
+            derived: Derived::default(),
+        }
+    }
+
+    fn with_content<U>(self, content: U) -> Story<U>
+    where
+        U: Deref<Target = str>,
+    {
+        Story {
+            content,
+            title: self.title,
+            start: self.start,
+            data: self.data,
+            passages: self.passages,
+            source_blocks: self.source_blocks,
+            warnings: self.warnings,
+            format: self.format,
+            link_dialects: self.link_dialects,
+            bom: self.bom,
+            options: self.options,
+            derived: self.derived,
         }
     }
 
@@ -251,35 +1114,142 @@ where
         self.title.as_ref().map(|block| block.as_str(&self.content))
     }
 
+    /// The passage the story starts at: the one defined in StoryData, or else the passage named
+    /// "Start", or else the first passage.
     pub fn start(&self) -> Option<Passage<&str>> {
-        self.start.as_ref().and_then(|block| {
-            let start = block.as_str(&self.content);
-            self.get_passage(start)
+        self.start_with(StartRule::DEFAULT)
+    }
+
+    /// The passage found by the first of `rules` that matches one.
+    pub fn start_with(&self, rules: &[StartRule]) -> Option<Passage<&str>> {
+        rules.iter().find_map(|rule| match rule {
+            StartRule::StoryData => self
+                .start
+                .as_ref()
+                .and_then(|block| self.get_passage(block.as_str(&self.content))),
+            StartRule::Named(name) => self.get_passage(name),
+            StartRule::FirstPassage => self.iter().next(),
         })
     }
 
+    /// The raw JSON content of the StoryData passage.
+    pub fn data(&self) -> Option<&str> {
+        self.data.as_ref().map(|block| block.as_str(&self.content))
+    }
+
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// Takes the warnings out of the story, leaving it without any.
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.warnings)
+    }
+
     pub fn get_passage(&self, name: &str) -> Option<Passage<&str>> {
         self.passages
             .get(name)
             .map(|passage| passage.as_borrowed(&self.content))
     }
 
-    pub fn iter(&self) -> Iter<T> {
+    /// Copies the story, making it own its content.
+    pub(crate) fn to_owned_story(&self) -> Story<String> {
+        Story {
+            content: self.content.to_string(),
+            title: self.title.clone(),
+            start: self.start.clone(),
+            data: self.data.clone(),
+            passages: self.passages.clone(),
+            source_blocks: self.source_blocks.clone(),
+            warnings: self.warnings.clone(),
+            format: self.format,
+            link_dialects: self.link_dialects,
+            bom: self.bom,
+            options: self.options.clone(),
+            derived: self.derived.clone(),
+        }
+    }
+
+    /// Adds a passage to the story, or replaces the passage with the same title, returning it.
+    ///
+    /// A replaced passage keeps its position in the story.
+    pub fn insert_passage(&mut self, passage: Passage<String>) -> Option<Passage<String>> {
+        let name = passage.title.clone();
+        let previous = self
+            .passages
+            .insert(name, passage.into_blocks())
+            .map(|previous| previous.as_borrowed(&self.content).into_owned());
+        self.invalidate_derived();
+        previous
+    }
+
+    /// Iterates over the passages, in the order they appear in the source.
+    pub fn iter(&self) -> Iter<'_, T> {
         Iter {
             story: self,
-            passage_names: self.passages.keys()
+            passages: Box::new(self.passages.iter().map(|(_, passage)| passage)),
+        }
+    }
+
+    /// Same as [`Story::iter`], spelled out for code relying on the source order.
+    pub fn passages_in_order(&self) -> Iter<'_, T> {
+        self.iter()
+    }
+
+    /// Number of passages, StoryTitle and StoryData excluded.
+    pub fn len(&self) -> usize {
+        self.passages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.passages.len() == 0
+    }
+
+    pub fn contains_passage(&self, name: &str) -> bool {
+        self.passages.contains_key(name)
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Story<T>
+where
+    T: Deref<Target = str>,
+{
+    type Item = Passage<&'a str>;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Builds a story without title nor StoryData. A passage replaces any earlier one with the same
+/// title, keeping its position.
+impl FromIterator<Passage<String>> for Story<String> {
+    fn from_iter<I: IntoIterator<Item = Passage<String>>>(passages: I) -> Self {
+        let mut map = PassageMap::new();
+        for passage in passages {
+            map.insert(passage.title.clone(), passage.into_blocks());
         }
+        Story::new(String::new(), None, None, None, map)
     }
 }
 
 impl Story<&str> {
     pub fn into_owned(self) -> Story<String> {
-        Story {
-            content: self.content.to_owned(),
-            title: self.title,
-            start: self.start,
-            passages: self.passages,
-        }
+        let content = self.content.to_owned();
+        self.with_content(content)
+    }
+}
+
+impl<T> Story<T>
+where
+    T: Deref<Target = str>,
+{
+    /// The story with its content behind an [`Arc`], so that it can be cloned and sent to
+    /// other threads without copying the text.
+    pub fn into_shared(self) -> Story<Arc<str>> {
+        let content = Arc::from(&*self.content);
+        self.with_content(content)
     }
 }
 
@@ -288,7 +1258,7 @@ where
     T: Deref<Target = str>,
 {
     story: &'a Story<T>,
-    passage_names: std::collections::hash_map::Keys<'a, String, Passage<TextBlock>>
+    passages: Box<dyn Iterator<Item = &'a Passage<TextBlock>> + 'a>,
 }
 
 impl<'a, T> std::iter::Iterator for Iter<'a, T>
@@ -297,8 +1267,144 @@ where
 {
     type Item = Passage<&'a str>;
     fn next(&mut self) -> Option<Self::Item> {
-        self.passage_names.next().and_then(|name| {
-            self.story.get_passage(name)
-        })
+        self.passages
+            .next()
+            .map(|passage| passage.as_borrowed(&self.story.content))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use crate::{
+        ContentNode, Image, ImageAlign, Metadata, ParseOptions, Passage, StartRule, Story, Tag,
+    };
+
+    const SAMPLE: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/sample/sample.twee"));
+
+    #[test]
+    fn test_start_fallbacks() {
+        let explicit = ":: StoryData\n{\"start\":\"B\"}\n\n:: A\n\n:: Start\n\n:: B\n";
+        let named = ":: A\n\n:: Start\n";
+        let first = ":: A\n\n:: B\n";
+        let start = |input, rules| {
+            let story = Story::try_from(input).unwrap();
+            story.start_with(rules).map(|p| p.title().to_string())
+        };
+
+        assert_eq!(start(explicit, StartRule::DEFAULT).as_deref(), Some("B"));
+        assert_eq!(start(named, StartRule::DEFAULT).as_deref(), Some("Start"));
+        assert_eq!(start(first, StartRule::DEFAULT).as_deref(), Some("A"));
+        assert_eq!(start(first, &[StartRule::StoryData]), None);
+        assert_eq!(start(first, &[StartRule::Named("B")]).as_deref(), Some("B"));
+    }
+
+    #[test]
+    fn test_cow_passages() {
+        let story = Story::try_from(SAMPLE).unwrap();
+        let first = story.get_passage("First").unwrap();
+        let mut edited = story.get_passage("Third").unwrap().into_owned();
+        edited.push_text(" Or is it?".to_string());
+
+        let passages: Vec<Passage<Cow<str>>> = vec![first.clone().into(), edited.clone().into()];
+        assert!(matches!(passages[0].title(), Cow::Borrowed("First")));
+        assert!(matches!(passages[1].title(), Cow::Owned(_)));
+        assert_eq!(passages[0].clone().into_owned(), first.into_owned());
+        assert_eq!(passages[1].clone().into_owned(), edited);
+    }
+
+    #[test]
+    fn test_edit_passage() {
+        let story = Story::try_from(SAMPLE).unwrap();
+        let mut third = story.get_passage("Third").unwrap().into_owned();
+
+        third.push_text(". Or is it? ".to_string());
+        third.push_link("Back".to_string(), "Start".to_string());
+        third.set_tags(["edited".to_string()]);
+        let previous = third.replace_node(0, ContentNode::Text("A dead end".to_string()));
+
+        assert_eq!(
+            previous,
+            Some(ContentNode::Text("This is a dead end".to_string()))
+        );
+        assert_eq!(
+            third.replace_node(10, ContentNode::Text(String::new())),
+            None
+        );
+        assert_eq!(third.to_string(), "A dead end. Or is it? Back");
+        assert_eq!(third.tags()[0].to_string(), "edited");
+    }
+
+    #[test]
+    fn test_insert_passage() {
+        let mut story = Story::try_from(SAMPLE).unwrap();
+        let mut third = story.get_passage("Third").unwrap().into_owned();
+        third.push_link(" Back".to_string(), "Start".to_string());
+
+        let previous = story.insert_passage(third.clone());
+
+        assert_eq!(
+            previous.map(|p| p.to_string()),
+            Some("This is a dead end".to_string())
+        );
+        assert_eq!(story.get_passage("Third").unwrap().into_owned(), third);
+        assert!(story.backlinks("Start").any(|name| name == "Third"));
+    }
+
+    #[test]
+    fn test_insert_passage_verbatim() {
+        let mut story = Story::parse_with(SAMPLE, ParseOptions::default().verbatim(true)).unwrap();
+        let mut third = story.get_passage("Third").unwrap().into_owned();
+        third.push_text(", really.".to_string());
+
+        story.insert_passage(third);
+
+        assert!(story
+            .to_twee()
+            .ends_with(":: Third {\"position\":\"1000,400\",\"size\":\"100,100\"}\nThis is a dead end, really.\n\n"));
+    }
+
+    #[test]
+    fn test_story_collection() {
+        let story = Story::try_from(SAMPLE).unwrap();
+        let titles: Vec<_> = (&story).into_iter().map(|p| p.title).collect();
+
+        assert_eq!(story.len(), titles.len());
+        assert!(!story.is_empty());
+        assert!(story.contains_passage("Third"));
+        assert!(!story.contains_passage("StoryTitle"));
+
+        let copy: Story<String> = story.iter().map(|p| p.into_owned()).collect();
+        assert_eq!(
+            copy.iter().collect::<Vec<_>>(),
+            story.iter().collect::<Vec<_>>()
+        );
+        assert!(copy.backlinks("Start").eq(story.backlinks("Start")));
+    }
+
+    #[test]
+    fn test_build_passage() {
+        let image = Image::new("map.png")
+            .with_target("Map")
+            .with_align(ImageAlign::Right);
+        let passage = Passage::new(
+            "Hall",
+            vec![Tag::new("indoor")],
+            Some(Metadata::new(r#"{"position":"10,20"}"#)),
+            vec![
+                ContentNode::text_node("A door. "),
+                ContentNode::link_node("Open it", "Room"),
+                ContentNode::Image(image),
+            ],
+        );
+
+        assert_eq!(passage.tags()[0].value(), &"indoor");
+        assert_eq!(
+            passage.metadata().unwrap().content(),
+            &r#"{"position":"10,20"}"#
+        );
+        assert_eq!(passage.to_string(), "A door. Open it");
+        assert_eq!(passage.images().next().unwrap().target(), Some(&"Map"));
     }
 }
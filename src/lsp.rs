@@ -0,0 +1,298 @@
+//! Building blocks for language servers: locating passages and nodes in the source, following
+//! links and completing names.
+//!
+//! Offsets are byte offsets in the source, see [`Position::from_offset`](crate::Position::from_offset)
+//! to turn them into lines and columns.
+
+use std::ops::{Deref, Range};
+
+use crate::{
+    error::ParsingError,
+    parser::{
+        passage::{parse_content, parse_header},
+        story::split_text_node,
+    },
+    utils::escape_string_content,
+    ContentNode, ParseOptions, Story, TextBlock,
+};
+
+/// Where a passage and its nodes are in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PassageSpan {
+    name: String,
+    range: Range<usize>,
+    header: Range<usize>,
+    nodes: Vec<Range<usize>>,
+    /// The passage each node links to, if any.
+    targets: Vec<Option<String>>,
+}
+
+impl PassageSpan {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The whole passage, header and trailing blank lines included.
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    /// The header line, `:: Name [tags] {metadata}`.
+    pub fn header(&self) -> Range<usize> {
+        self.header.clone()
+    }
+
+    /// The source of each node, in the order of [`Passage::nodes`](crate::Passage::nodes).
+    pub fn nodes(&self) -> &[Range<usize>] {
+        &self.nodes
+    }
+
+    /// Index of the node containing `offset`.
+    pub fn node_at(&self, offset: usize) -> Option<usize> {
+        self.nodes.iter().position(|node| node.contains(&offset))
+    }
+
+    /// The passage the node at `index` links to, for links and images with a target.
+    pub fn target(&self, index: usize) -> Option<&str> {
+        self.targets.get(index)?.as_deref()
+    }
+}
+
+/// The location of every passage of a story source.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    passages: Vec<PassageSpan>,
+}
+
+impl SourceMap {
+    /// Maps `source`, parsed with `options` so that nodes match those of the story.
+    /// [`ParseOptions::paragraphs`] is ignored, nodes being mapped as read without it.
+    pub fn new<'a>(source: &'a str, options: &ParseOptions) -> Result<Self, ParsingError<&'a str>> {
+        let options = options.clone().verbatim(true).paragraphs(false);
+        let story = Story::parse_with(source, options.clone())?;
+        // Offsets in the story don't count a byte order mark.
+        let shift = source.len() - story.content.len();
+        let offset =
+            |slice: &str| slice.as_ptr() as usize - story.content.as_ptr() as usize + shift;
+        let unescape = |target: &str| match options.unescape {
+            true => escape_string_content(target).unwrap_or_else(|| target.to_string()),
+            false => target.to_string(),
+        };
+
+        let format = story.story_format();
+
+        let mut passages = vec![];
+        for (name, passage) in story.passages.iter() {
+            let Some(TextBlock::Borrowed(range)) = &passage.source else {
+                continue;
+            };
+            let source = &story.content[range.clone()];
+            let Ok((content, _)) = parse_header(source) else {
+                continue;
+            };
+            let Ok((_, mut nodes)) = parse_content(content, options.link_dialects) else {
+                continue;
+            };
+            if options.strip_comments {
+                nodes.retain(|(_, node)| !matches!(node, ContentNode::Comment(_)));
+            }
+            let nodes: Vec<_> = nodes
+                .into_iter()
+                .flat_map(|(source, node)| match node {
+                    ContentNode::Text(text) => split_text_node(text, &options, format),
+                    node => vec![(source, node)],
+                })
+                .collect();
+
+            let start = offset(source);
+            let header_len = source.len() - content.len();
+            passages.push(PassageSpan {
+                name: name.to_string(),
+                range: start..start + source.len(),
+                header: start..start + source[..header_len].trim_end().len(),
+                nodes: nodes
+                    .iter()
+                    .map(|(source, _)| offset(source)..offset(source) + source.len())
+                    .collect(),
+                targets: nodes
+                    .iter()
+                    .map(|(_, node)| match node {
+                        ContentNode::Link { target, .. } => Some(unescape(target)),
+                        ContentNode::Image(image) => image.target.map(unescape),
+                        _ => None,
+                    })
+                    .collect(),
+            });
+        }
+        // A duplicate passage takes the place of the first one in the story.
+        passages.sort_by_key(|passage| passage.range.start);
+        Ok(Self { passages })
+    }
+
+    /// The passages, in source order.
+    pub fn passages(&self) -> &[PassageSpan] {
+        &self.passages
+    }
+
+    pub fn passage(&self, name: &str) -> Option<&PassageSpan> {
+        self.passages.iter().find(|passage| passage.name == name)
+    }
+
+    /// The passage containing `offset`. An offset at the very end of a passage, like the end
+    /// of the source, still belongs to it.
+    pub fn passage_at(&self, offset: usize) -> Option<&PassageSpan> {
+        let index = self
+            .passages
+            .partition_point(|passage| passage.range.start <= offset);
+        let passage = self.passages.get(index.checked_sub(1)?)?;
+        (offset <= passage.range.end).then_some(passage)
+    }
+
+    /// Go to definition: the passage targeted by the link or image at `offset`.
+    pub fn definition(&self, offset: usize) -> Option<&PassageSpan> {
+        let passage = self.passage_at(offset)?;
+        let target = passage.target(passage.node_at(offset)?)?;
+        self.passage(target)
+    }
+}
+
+impl<T> Story<T>
+where
+    T: Deref<Target = str>,
+{
+    /// Passage names starting with `prefix`, ignoring case, to complete a link target.
+    pub fn link_target_completions(&self, prefix: &str) -> Vec<&str> {
+        let prefix = prefix.to_lowercase();
+        self.passages
+            .iter()
+            .map(|(name, _)| name)
+            .filter(|name| name.to_lowercase().starts_with(&prefix))
+            .collect()
+    }
+
+    /// Tags used in the story starting with `prefix`, ignoring case, sorted.
+    pub fn tag_completions(&self, prefix: &str) -> Vec<&str> {
+        let prefix = prefix.to_lowercase();
+        let mut tags: Vec<_> = self
+            .tags()
+            .filter(|tag| tag.to_lowercase().starts_with(&prefix))
+            .collect();
+        tags.sort_unstable();
+        tags
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use super::SourceMap;
+    use crate::{extension::NodeKind, ParseOptions, Story};
+
+    const STORY: &str = ":: StoryTitle\nMap\n\n:: Start [intro]\nGo [[north->North]] /* hm */\n\n:: North [cold Outside]\nBack [[Start]]\n";
+
+    #[test]
+    fn test_passage_and_node_at() {
+        let map = SourceMap::new(STORY, &ParseOptions::default()).unwrap();
+        let start = STORY.find(":: Start").unwrap();
+        let link = STORY.find("[[north").unwrap();
+
+        let passage = map.passage_at(link).unwrap();
+        assert_eq!(passage.name(), "Start");
+        assert_eq!(&STORY[passage.header()], ":: Start [intro]");
+        assert_eq!(passage.range().start, start);
+        assert_eq!(
+            passage
+                .nodes()
+                .iter()
+                .map(|node| &STORY[node.clone()])
+                .collect::<Vec<_>>(),
+            ["Go ", "[[north->North]]", " ", "/* hm */"]
+        );
+        assert_eq!(passage.node_at(link + 3), Some(1));
+        assert_eq!(map.passage_at(3), None);
+        assert_eq!(map.passage_at(STORY.len()).unwrap().name(), "North");
+    }
+
+    #[test]
+    fn test_definition() {
+        let source = format!("\u{feff}{STORY}");
+        let map = SourceMap::new(&source, &ParseOptions::default()).unwrap();
+        let link = source.find("[[north").unwrap();
+
+        let north = map.definition(link + 4).unwrap();
+        assert_eq!(north.name(), "North");
+        assert!(source[north.header()].starts_with(":: North"));
+        assert_eq!(map.definition(link - 1), None);
+    }
+
+    #[test]
+    fn test_html_nodes() {
+        let source = ":: Start\nHello <b>world</b>\n";
+        let map = SourceMap::new(source, &ParseOptions::default().html(true)).unwrap();
+        let story = Story::parse_with(source, ParseOptions::default().html(true)).unwrap();
+
+        let nodes = map.passage("Start").unwrap().nodes();
+        assert_eq!(
+            nodes.len(),
+            story.get_passage("Start").unwrap().nodes().len()
+        );
+        assert_eq!(&source[nodes[1].clone()], "<b>");
+    }
+
+    #[derive(Debug)]
+    struct Placeholder;
+
+    impl NodeKind for Placeholder {
+        fn name(&self) -> &str {
+            "placeholder"
+        }
+
+        fn find(&self, text: &str) -> Option<Range<usize>> {
+            let start = text.find("{{")?;
+            let len = text[start..].find("}}")? + 2;
+            Some(start..start + len)
+        }
+    }
+
+    #[test]
+    fn test_nodes_match_story() {
+        let source = ":: Start\nHi {{name}} <<if $a>><b>x</b><</if>>\n";
+        let options = ParseOptions::default()
+            .html(true)
+            .raw_sigils(true)
+            .node_kind(Placeholder);
+        let map = SourceMap::new(source, &options).unwrap();
+        let story = Story::parse_with(source, options).unwrap();
+
+        let nodes: Vec<_> = map.passage("Start").unwrap().nodes().to_vec();
+        let sources: Vec<_> = nodes.into_iter().map(|node| &source[node]).collect();
+        assert_eq!(
+            sources,
+            [
+                "Hi ",
+                "{{name}}",
+                " ",
+                "<<if $a>>",
+                "<b>",
+                "x",
+                "</b>",
+                "<</if>>"
+            ]
+        );
+        assert_eq!(
+            sources.len(),
+            story.get_passage("Start").unwrap().nodes().len()
+        );
+    }
+
+    #[test]
+    fn test_completions() {
+        let story = Story::try_from(STORY).unwrap();
+
+        assert_eq!(story.link_target_completions("n"), ["North"]);
+        assert_eq!(story.link_target_completions(""), ["Start", "North"]);
+        assert_eq!(story.tag_completions("o"), ["Outside"]);
+        assert_eq!(story.tag_completions(""), ["Outside", "cold", "intro"]);
+    }
+}
@@ -0,0 +1,156 @@
+//! Utilities for code handling the macros of story formats itself, see [`sugarcube`] for the
+//! expressions of SugarCube macros and [`harlowe`] for Harlowe macro calls.
+//!
+//! ```
+//! use twee_v3::macros::{macro_args, MacroArg};
+//!
+//! let args = macro_args(r#""It's \"raining\"" 0.5 $volume loop"#).unwrap();
+//! assert_eq!(
+//!     args,
+//!     [
+//!         MacroArg::String("It's \"raining\"".into()),
+//!         MacroArg::Number(0.5),
+//!         MacroArg::Variable("$volume"),
+//!         MacroArg::Word("loop"),
+//!     ]
+//! );
+//! ```
+
+use std::{borrow::Cow, fmt::Display};
+
+use crate::utils::escape_string_content;
+
+mod balance;
+pub mod harlowe;
+pub mod sugarcube;
+
+pub use balance::{Imbalance, ImbalanceKind};
+
+/// An argument given to a macro, see [`macro_args`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum MacroArg<'a> {
+    /// A string in double or single quotes, or backticks, its escaped characters resolved.
+    String(Cow<'a, str>),
+    Number(f64),
+    /// A story variable `$name` or a temporary variable `_name`, sigil and property accesses
+    /// included, like `$player.name`.
+    Variable(&'a str),
+    /// Any other word, such as a keyword or a passage name written without quotes.
+    Word(&'a str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MacroArgsError {
+    /// A string isn't closed, with the byte offset of its opening quote.
+    UnclosedString(usize),
+    /// A macro isn't closed, with the byte offset of its opening parenthesis.
+    UnclosedMacro(usize),
+}
+
+impl Display for MacroArgsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MacroArgsError::UnclosedString(offset) => {
+                write!(f, "The string at byte {offset} is not closed")
+            }
+            MacroArgsError::UnclosedMacro(offset) => {
+                write!(f, "The macro at byte {offset} is not closed")
+            }
+        }
+    }
+}
+
+impl std::error::Error for MacroArgsError {}
+
+fn is_separator(c: char) -> bool {
+    c.is_whitespace() || c == ','
+}
+
+fn is_number(word: &str) -> bool {
+    let digits = word.strip_prefix(['-', '+']).unwrap_or(word);
+    let digits = digits.strip_prefix('.').unwrap_or(digits);
+    digits.starts_with(|c: char| c.is_ascii_digit())
+}
+
+fn is_variable(word: &str) -> bool {
+    word.strip_prefix(['$', '_'])
+        .is_some_and(|name| name.starts_with(|c: char| c.is_alphabetic() || c == '_'))
+}
+
+/// Length of the quoted string starting `input`, quotes included. A backslash escapes the
+/// character after it, as in the rest of twee.
+fn quoted_len(input: &str) -> Option<usize> {
+    let quote = input.chars().next()?;
+    let mut escaped = false;
+    for (index, c) in input.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            _ if c == quote => return Some(index + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Reads the arguments of a macro, the text after its name such as `"rain" 0.5 $volume` in
+/// `<<audio "rain" 0.5 $volume>>`. Arguments are separated by whitespace or commas.
+pub fn macro_args(input: &str) -> Result<Vec<MacroArg<'_>>, MacroArgsError> {
+    let mut args = vec![];
+    let mut index = 0;
+    while let Some(position) = input[index..].find(|c| !is_separator(c)) {
+        let start = index + position;
+        let rest = &input[start..];
+        if rest.starts_with(['"', '\'', '`']) {
+            let len = quoted_len(rest).ok_or(MacroArgsError::UnclosedString(start))?;
+            let content = &rest[1..len - 1];
+            args.push(MacroArg::String(match escape_string_content(content) {
+                Some(unescaped) => Cow::Owned(unescaped),
+                None => Cow::Borrowed(content),
+            }));
+            index = start + len;
+            continue;
+        }
+        let len = rest.find(is_separator).unwrap_or(rest.len());
+        let word = &rest[..len];
+        args.push(match word.parse() {
+            Ok(number) if is_number(word) => MacroArg::Number(number),
+            _ if is_variable(word) => MacroArg::Variable(word),
+            _ => MacroArg::Word(word),
+        });
+        index = start + len;
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{macro_args, MacroArg, MacroArgsError};
+
+    #[test]
+    fn test_macro_args() {
+        assert_eq!(
+            macro_args("'a b', -2, .5e1 _tmp $x.y inf $ 1st \"\"").unwrap(),
+            [
+                MacroArg::String("a b".into()),
+                MacroArg::Number(-2.0),
+                MacroArg::Number(5.0),
+                MacroArg::Variable("_tmp"),
+                MacroArg::Variable("$x.y"),
+                MacroArg::Word("inf"),
+                MacroArg::Word("$"),
+                MacroArg::Word("1st"),
+                MacroArg::String("".into()),
+            ]
+        );
+        assert_eq!(macro_args("  ").unwrap(), []);
+    }
+
+    #[test]
+    fn test_macro_args_unclosed_string() {
+        assert_eq!(
+            macro_args(r#"1 "a \" b"#),
+            Err(MacroArgsError::UnclosedString(2))
+        );
+    }
+}
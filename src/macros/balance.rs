@@ -0,0 +1,333 @@
+use std::{fmt::Display, ops::Deref};
+
+use crate::{
+    lexer::{tokenize, TokenKind},
+    parser::passage::parse_content,
+    prose::macro_ranges,
+    utils::escape_string_content,
+    ContentNode, LinkDialects, Position, Story,
+};
+
+/// SugarCube macros with contents, closed by `<</name>>`. Macros closed somewhere in the same
+/// passage, such as widgets, count as well.
+const CONTAINERS: [&str; 25] = [
+    "if",
+    "for",
+    "switch",
+    "link",
+    "button",
+    "nobr",
+    "silently",
+    "widget",
+    "script",
+    "capture",
+    "append",
+    "prepend",
+    "replace",
+    "type",
+    "timed",
+    "repeat",
+    "linkappend",
+    "linkprepend",
+    "linkreplace",
+    "cycle",
+    "listbox",
+    "createaudiogroup",
+    "createplaylist",
+    "done",
+    "do",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImbalanceKind {
+    /// The SugarCube macro of this name, such as `if`, is never closed.
+    UnclosedMacro(String),
+    /// `<</name>>` closes no open macro.
+    UnexpectedClose(String),
+    /// A Harlowe hook `[` is never closed.
+    UnclosedHook,
+    /// A `]` closes no hook.
+    UnexpectedHookClose,
+}
+
+/// A macro or hook left open or closed twice, see [`Story::lint_macro_balance`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Imbalance {
+    pub passage: String,
+    pub kind: ImbalanceKind,
+    /// Where the opening or closing is in the source.
+    pub position: Position,
+}
+
+impl Display for Imbalance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Imbalance {
+            passage, position, ..
+        } = self;
+        match &self.kind {
+            ImbalanceKind::UnclosedMacro(name) => {
+                write!(f, "<<{name}>> at {position} in {passage:?} is never closed")
+            }
+            ImbalanceKind::UnexpectedClose(name) => {
+                write!(
+                    f,
+                    "<</{name}>> at {position} in {passage:?} closes no macro"
+                )
+            }
+            ImbalanceKind::UnclosedHook => {
+                write!(f, "Hook at {position} in {passage:?} is never closed")
+            }
+            ImbalanceKind::UnexpectedHookClose => {
+                write!(f, "] at {position} in {passage:?} closes no hook")
+            }
+        }
+    }
+}
+
+#[derive(PartialEq)]
+enum Open<'a> {
+    Macro(&'a str),
+    Hook,
+}
+
+/// The name of the SugarCube macro `<<...>>`, and whether it's a closing one, `<</name>>` or
+/// the older `<<endname>>`.
+fn macro_name(markup: &str) -> (&str, bool) {
+    let inner = markup.trim_start_matches('<');
+    let (inner, closing) = match inner.strip_prefix('/') {
+        Some(inner) => (inner, true),
+        None => (inner, false),
+    };
+    let len = inner
+        .find(|c: char| !c.is_alphanumeric() && c != '-' && c != '_')
+        .unwrap_or(inner.len());
+    let name = &inner[..len];
+    match name.strip_prefix("end") {
+        Some(opened) if !closing && CONTAINERS.contains(&opened) => (opened, true),
+        _ => (name, closing),
+    }
+}
+
+/// Checks one passage, whose content pieces are given with their offset in the source.
+struct Checker<'a> {
+    passage: String,
+    source: &'a str,
+    stack: Vec<(Open<'a>, usize)>,
+    found: Vec<Imbalance>,
+}
+
+impl<'a> Checker<'a> {
+    fn report(&mut self, kind: ImbalanceKind, offset: usize) {
+        self.found.push(Imbalance {
+            passage: self.passage.clone(),
+            kind,
+            position: Position::from_offset(self.source, offset),
+        });
+    }
+
+    fn report_unclosed(&mut self, (open, offset): (Open<'a>, usize)) {
+        let kind = match open {
+            Open::Macro(name) => ImbalanceKind::UnclosedMacro(name.to_string()),
+            Open::Hook => ImbalanceKind::UnclosedHook,
+        };
+        self.report(kind, offset);
+    }
+
+    /// Closes `open`, reporting those opened after it as unclosed, or reports `unexpected`
+    /// when it isn't open.
+    fn close(&mut self, open: Open<'a>, offset: usize, unexpected: ImbalanceKind) {
+        let Some(index) = self.stack.iter().rposition(|(opened, _)| *opened == open) else {
+            self.report(unexpected, offset);
+            return;
+        };
+        for unclosed in self.stack.split_off(index + 1) {
+            self.report_unclosed(unclosed);
+        }
+        self.stack.pop();
+    }
+
+    fn brackets(&mut self, text: &str, offset: usize) {
+        let mut chars = text.char_indices();
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '\\' => {
+                    chars.next();
+                }
+                '[' => self.stack.push((Open::Hook, offset + index)),
+                ']' => self.close(
+                    Open::Hook,
+                    offset + index,
+                    ImbalanceKind::UnexpectedHookClose,
+                ),
+                _ => {}
+            }
+        }
+    }
+
+    fn finish(mut self) -> Vec<Imbalance> {
+        for unclosed in std::mem::take(&mut self.stack) {
+            self.report_unclosed(unclosed);
+        }
+        self.found.sort_by_key(|imbalance| imbalance.position);
+        self.found
+    }
+}
+
+impl<T> Story<T>
+where
+    T: Deref<Target = str>,
+{
+    /// SugarCube container macros such as `<<if>>` without their `<</if>>`, or closings without
+    /// an opening, and unbalanced Harlowe hook brackets, depending on the
+    /// [`Story::story_format`]. A missing closing otherwise swallows the rest of the passage.
+    ///
+    /// Like [`Story::highlight_spans`], this reads the source the story was parsed from.
+    pub fn lint_macro_balance(&self) -> Vec<Imbalance> {
+        let source: &str = &self.content;
+        let format = self.story_format();
+        let offset = |slice: &str| slice.as_ptr() as usize - source.as_ptr() as usize;
+        let mut found = vec![];
+
+        let tokens = tokenize(source);
+        let mut tokens = tokens.iter().peekable();
+        let mut passage = "";
+        while let Some(token) = tokens.next() {
+            if token.kind == TokenKind::Title {
+                passage = token.text;
+                continue;
+            }
+            if token.kind != TokenKind::Content {
+                continue;
+            }
+            let mut end = token.range.end;
+            while let Some(next) = tokens.next_if(|next| next.kind == TokenKind::Content) {
+                end = next.range.end;
+            }
+            let Ok((_, nodes)) =
+                parse_content(&source[token.range.start..end], LinkDialects::default())
+            else {
+                continue;
+            };
+            let texts: Vec<_> = nodes
+                .iter()
+                .filter_map(|(_, node)| match node {
+                    ContentNode::Text(text) => Some(*text),
+                    _ => None,
+                })
+                .collect();
+            let markups: Vec<_> = texts
+                .iter()
+                .flat_map(|text| {
+                    macro_ranges(text, format)
+                        .into_iter()
+                        .map(|range| (&text[range.clone()], offset(text) + range.start))
+                })
+                .collect();
+            let closed: Vec<_> = markups
+                .iter()
+                .filter(|(markup, _)| markup.starts_with("<<"))
+                .map(|(markup, _)| macro_name(markup))
+                .filter(|(_, closing)| *closing)
+                .map(|(name, _)| name)
+                .collect();
+
+            let mut checker = Checker {
+                passage: escape_string_content(passage).unwrap_or_else(|| passage.to_string()),
+                source,
+                stack: vec![],
+                found: vec![],
+            };
+            let mut markups = markups.into_iter().peekable();
+            for text in texts {
+                let mut index = 0;
+                while let Some((markup, start)) =
+                    markups.next_if(|(_, start)| *start < offset(text) + text.len())
+                {
+                    let local = start - offset(text);
+                    if format.harlowe() {
+                        checker.brackets(&text[index..local], offset(text) + index);
+                    }
+                    index = local + markup.len();
+                    if !markup.starts_with("<<") {
+                        continue;
+                    }
+                    match macro_name(markup) {
+                        (name, true) => checker.close(
+                            Open::Macro(name),
+                            start,
+                            ImbalanceKind::UnexpectedClose(name.to_string()),
+                        ),
+                        (name, false) if CONTAINERS.contains(&name) || closed.contains(&name) => {
+                            checker.stack.push((Open::Macro(name), start))
+                        }
+                        _ => {}
+                    }
+                }
+                if format.harlowe() {
+                    checker.brackets(&text[index..], offset(text) + index);
+                }
+            }
+            found.extend(checker.finish());
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Imbalance, ImbalanceKind};
+    use crate::{Position, Story};
+
+    fn kinds(input: &str) -> Vec<(ImbalanceKind, usize)> {
+        let story = Story::try_from(input).unwrap();
+        story
+            .lint_macro_balance()
+            .into_iter()
+            .map(|imbalance| (imbalance.kind, imbalance.position.line))
+            .collect()
+    }
+
+    #[test]
+    fn test_lint_macro_balance_sugarcube() {
+        let data = ":: StoryData\n{\"format\":\"SugarCube\"}\n\n";
+        let input = format!("{data}:: A\n<<if $a>>\n[[B]] <<set $b to 1>>\n<<for _i range $l>>_i<</for>>\n\n:: B\n<<endif>> [sic <<nobr>>x<</nobr>>\n");
+
+        assert_eq!(
+            kinds(&input),
+            [
+                (ImbalanceKind::UnclosedMacro("if".to_string()), 5),
+                (ImbalanceKind::UnexpectedClose("if".to_string()), 10),
+            ]
+        );
+
+        let input = format!("{data}:: A\n<<if $a>><<link 'x'>><</if>>");
+        let story = Story::try_from(input.as_str()).unwrap();
+        assert_eq!(
+            story.lint_macro_balance(),
+            [Imbalance {
+                passage: "A".to_string(),
+                kind: ImbalanceKind::UnclosedMacro("link".to_string()),
+                position: Position {
+                    offset: 51,
+                    line: 5,
+                    column: 10
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn test_lint_macro_balance_harlowe() {
+        let data = ":: StoryData\n{\"format\":\"Harlowe\"}\n\n";
+        let input =
+            format!("{data}:: A\n(if: $a)[ [[B]] (print: \"]\")\n\n:: B\n(else:)[ok] \\] x]\n");
+
+        assert_eq!(
+            kinds(&input),
+            [
+                (ImbalanceKind::UnclosedHook, 5),
+                (ImbalanceKind::UnexpectedHookClose, 8),
+            ]
+        );
+    }
+}
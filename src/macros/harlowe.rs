@@ -0,0 +1,335 @@
+//! Harlowe macros, such as the changers `(text-style: "bold")` or `(link-reveal: "Open")`,
+//! read with their typed arguments so that converters to other engines can map them.
+//!
+//! ```
+//! use twee_v3::macros::harlowe::{harlowe_macros, HarloweArg};
+//!
+//! let macros = harlowe_macros("(text-colour: #f00)[Hot] (if: $a > 1)[Big]").unwrap();
+//! assert_eq!(macros[0].name, "text-colour");
+//! assert_eq!(macros[0].args, [HarloweArg::Color("#f00")]);
+//! assert!(macros[1].is_changer());
+//! assert_eq!(macros[1].args, [HarloweArg::Expression("$a > 1")]);
+//! ```
+
+use std::{borrow::Cow, ops::Range};
+
+use super::{quoted_len, MacroArgsError};
+use crate::utils::escape_string_content;
+
+/// Harlowe's built-in color names.
+const COLORS: [&str; 17] = [
+    "red",
+    "orange",
+    "yellow",
+    "lime",
+    "green",
+    "aqua",
+    "cyan",
+    "blue",
+    "navy",
+    "purple",
+    "fuchsia",
+    "magenta",
+    "white",
+    "black",
+    "grey",
+    "gray",
+    "transparent",
+];
+
+/// Macros returning changers, which are attached to the hook after them. Names are written as
+/// [`HarloweMacro::canonical_name`] gives them.
+const CHANGERS: [&str; 39] = [
+    "if",
+    "unless",
+    "elseif",
+    "else",
+    "hidden",
+    "textstyle",
+    "textcolour",
+    "textcolor",
+    "colour",
+    "color",
+    "textsize",
+    "size",
+    "font",
+    "align",
+    "background",
+    "bg",
+    "css",
+    "transition",
+    "t8n",
+    "transitiontime",
+    "t8ntime",
+    "textrotate",
+    "hoverstyle",
+    "link",
+    "linkreveal",
+    "linkrepeat",
+    "linkrerun",
+    "linkgoto",
+    "click",
+    "clickreplace",
+    "clickappend",
+    "clickprepend",
+    "mouseover",
+    "mouseout",
+    "live",
+    "event",
+    "for",
+    "loop",
+    "replace",
+];
+
+/// An argument of a Harlowe macro, see [`harlowe_macros`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum HarloweArg<'a> {
+    /// A string in double or single quotes, its escaped characters resolved.
+    String(Cow<'a, str>),
+    /// A number. Durations such as `2s` or `500ms` are read in milliseconds.
+    Number(f64),
+    Bool(bool),
+    /// A color, `#rgb`, `#rrggbb` or one of Harlowe's names such as `red`.
+    Color(&'a str),
+    /// A story variable `$name` or a temporary variable `_name`, alone.
+    Variable(&'a str),
+    /// A lambda such as `each _item where _item > 2`, as written.
+    Lambda(&'a str),
+    /// A macro call, such as the `(text-style: "bold")` of `(hover-style: (text-style: "bold"))`.
+    Macro(HarloweMacro<'a>),
+    /// Any other expression, as written.
+    Expression(&'a str),
+}
+
+/// A Harlowe macro call, `(name: args)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HarloweMacro<'a> {
+    /// The name as written, such as `text-colour`.
+    pub name: &'a str,
+    pub args: Vec<HarloweArg<'a>>,
+    /// Where the call is in the text given to [`harlowe_macros`], parentheses included.
+    pub range: Range<usize>,
+}
+
+impl HarloweMacro<'_> {
+    /// The name in lowercase without dashes and underscores, which Harlowe ignores:
+    /// `text-colour`, `textColour` and `text_colour` are the same macro.
+    pub fn canonical_name(&self) -> String {
+        self.name
+            .chars()
+            .filter(|c| !matches!(c, '-' | '_'))
+            .flat_map(char::to_lowercase)
+            .collect()
+    }
+
+    /// Whether the macro is a changer, changing the hook after it, such as `(if:)` or
+    /// `(text-style:)`.
+    pub fn is_changer(&self) -> bool {
+        CHANGERS.contains(&self.canonical_name().as_str())
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '-' || c == '_'
+}
+
+fn is_color(arg: &str) -> bool {
+    let hex = arg.strip_prefix('#').is_some_and(|hex| {
+        matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit())
+    });
+    hex || COLORS.contains(&arg.to_lowercase().as_str())
+}
+
+fn is_lambda(arg: &str) -> bool {
+    let clause = |rest: &str| {
+        ["where ", "when ", "via ", "making "]
+            .iter()
+            .any(|keyword| rest.starts_with(keyword))
+    };
+    if arg.starts_with("each ") || clause(arg) {
+        return true;
+    }
+    // `_item where ...`, a temporary variable then a clause.
+    arg.strip_prefix('_').is_some_and(|rest| {
+        let name_len = rest
+            .find(|c: char| !c.is_alphanumeric() && c != '_')
+            .unwrap_or(rest.len());
+        name_len > 0 && clause(rest[name_len..].trim_start())
+    })
+}
+
+fn number(arg: &str) -> Option<f64> {
+    if !arg.starts_with(|c: char| c.is_ascii_digit() || matches!(c, '-' | '.')) {
+        return None;
+    }
+    if let Some(ms) = arg.strip_suffix("ms") {
+        return ms.parse().ok();
+    }
+    if let Some(seconds) = arg.strip_suffix(['s', 'S']) {
+        return seconds.parse().ok().map(|seconds: f64| seconds * 1000.0);
+    }
+    arg.parse().ok()
+}
+
+/// Reads the argument in `text[range]`, trimmed.
+fn arg(text: &str, range: Range<usize>) -> HarloweArg<'_> {
+    let raw = &text[range.clone()];
+    let start = range.start + (raw.len() - raw.trim_start().len());
+    let arg = raw.trim();
+    if let Some(Ok((call, end))) = macro_at(text, start) {
+        if end == start + arg.len() {
+            return HarloweArg::Macro(call);
+        }
+    }
+    if arg.starts_with(['"', '\'']) && quoted_len(arg) == Some(arg.len()) {
+        let content = &arg[1..arg.len() - 1];
+        return HarloweArg::String(match escape_string_content(content) {
+            Some(unescaped) => Cow::Owned(unescaped),
+            None => Cow::Borrowed(content),
+        });
+    }
+    let is_variable = arg.len() > 1
+        && arg.starts_with(['$', '_'])
+        && arg[1..].chars().all(|c| c.is_alphanumeric() || c == '_');
+    match arg {
+        "true" => HarloweArg::Bool(true),
+        "false" => HarloweArg::Bool(false),
+        _ if is_variable => HarloweArg::Variable(arg),
+        _ if is_color(arg) => HarloweArg::Color(arg),
+        _ if is_lambda(arg) => HarloweArg::Lambda(arg),
+        _ => match number(arg) {
+            Some(number) => HarloweArg::Number(number),
+            None => HarloweArg::Expression(arg),
+        },
+    }
+}
+
+/// Reads the macro call starting at `start` in `text`, with the offset after it, if there is
+/// one.
+fn macro_at(text: &str, start: usize) -> Option<Result<(HarloweMacro<'_>, usize), MacroArgsError>> {
+    let input = text[start..].strip_prefix('(')?;
+    if !input.starts_with(char::is_alphabetic) {
+        return None;
+    }
+    let name_len = input.find(|c| !is_name_char(c))?;
+    if !input[name_len..].starts_with(':') {
+        return None;
+    }
+    let name = &input[..name_len];
+
+    let mut args = vec![];
+    let mut depth = 0;
+    let mut arg_start = start + 1 + name_len + 1;
+    let mut index = arg_start;
+    while index < text.len() {
+        let rest = &text[index..];
+        let c = rest.chars().next()?;
+        match c {
+            '"' | '\'' => match quoted_len(rest) {
+                Some(len) => {
+                    index += len;
+                    continue;
+                }
+                None => return Some(Err(MacroArgsError::UnclosedString(index))),
+            },
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' if depth > 0 => depth -= 1,
+            ',' | ')' if depth == 0 => {
+                if !(c == ')' && args.is_empty() && text[arg_start..index].trim().is_empty()) {
+                    args.push(arg(text, arg_start..index));
+                }
+                if c == ')' {
+                    let call = HarloweMacro {
+                        name,
+                        args,
+                        range: start..index + 1,
+                    };
+                    return Some(Ok((call, index + 1)));
+                }
+                arg_start = index + 1;
+            }
+            _ => {}
+        }
+        index += c.len_utf8();
+    }
+    Some(Err(MacroArgsError::UnclosedMacro(start)))
+}
+
+/// The Harlowe macro calls in `text`, such as the content of a passage, in order. Macros given
+/// as arguments are read as [`HarloweArg::Macro`].
+pub fn harlowe_macros(text: &str) -> Result<Vec<HarloweMacro<'_>>, MacroArgsError> {
+    let mut macros = vec![];
+    let mut index = 0;
+    while let Some(position) = text[index..].find('(') {
+        let start = index + position;
+        match macro_at(text, start) {
+            Some(result) => {
+                let (call, end) = result?;
+                macros.push(call);
+                index = end;
+            }
+            None => index = start + 1,
+        }
+    }
+    Ok(macros)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{harlowe_macros, HarloweArg, HarloweMacro};
+    use crate::macros::MacroArgsError;
+
+    #[test]
+    fn test_harlowe_macros() {
+        let text = "Go (link-reveal: \"Open (now)\")[door] (hover-style: (text-style: 'bold', 'blur'))[x] (transition-time: 2s)(for: each _i where _i > 2, ...$list)[y] (else:)[z]";
+        let macros = harlowe_macros(text).unwrap();
+
+        assert_eq!(
+            macros[0],
+            HarloweMacro {
+                name: "link-reveal",
+                args: vec![HarloweArg::String("Open (now)".into())],
+                range: 3..30,
+            }
+        );
+        assert_eq!(
+            macros[1].args,
+            [HarloweArg::Macro(HarloweMacro {
+                name: "text-style",
+                args: vec![
+                    HarloweArg::String("bold".into()),
+                    HarloweArg::String("blur".into())
+                ],
+                range: 51..79,
+            })]
+        );
+        assert_eq!(macros[2].args, [HarloweArg::Number(2000.0)]);
+        assert_eq!(
+            macros[3].args,
+            [
+                HarloweArg::Lambda("each _i where _i > 2"),
+                HarloweArg::Expression("...$list"),
+            ]
+        );
+        assert_eq!(macros[4].args, []);
+        assert_eq!(
+            macros
+                .iter()
+                .map(HarloweMacro::is_changer)
+                .collect::<Vec<_>>(),
+            [true; 5]
+        );
+    }
+
+    #[test]
+    fn test_harlowe_macros_unclosed() {
+        assert_eq!(
+            harlowe_macros("(a) (set: $x to \"y)"),
+            Err(MacroArgsError::UnclosedString(16))
+        );
+        assert_eq!(
+            harlowe_macros("(print: (a: 1)"),
+            Err(MacroArgsError::UnclosedMacro(0))
+        );
+    }
+}
@@ -0,0 +1,962 @@
+//! SugarCube expressions, the TwineScript of `<<if>>` and `<<set>>`, parsed into an [`Expr`]
+//! tree that runtimes can evaluate without a JavaScript engine.
+//!
+//! ```
+//! use std::collections::HashMap;
+//! use twee_v3::macros::sugarcube::{parse_expression, Value};
+//!
+//! let condition = parse_expression("$a gt 3 and not $b").unwrap();
+//! let mut variables = HashMap::from([
+//!     ("$a".to_string(), Value::Number(5.0)),
+//!     ("$b".to_string(), Value::Bool(false)),
+//! ]);
+//! assert_eq!(condition.evaluate(&mut variables), Ok(Value::Bool(true)));
+//! ```
+//!
+//! Values follow the JavaScript rules for truthiness, comparisons and `+`, except that arrays
+//! and objects are compared by value. JavaScript beyond these everyday forms, such as arrow
+//! functions or `new`, isn't supported.
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+};
+
+use super::quoted_len;
+use crate::utils::escape_string_content;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    /// `not` or `!`
+    Not,
+    /// `-`
+    Neg,
+    /// `+`
+    Plus,
+    /// `def`, whether the operand is defined.
+    Def,
+    /// `ndef`, whether the operand is undefined.
+    Ndef,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    /// `or` or `||`
+    Or,
+    /// `and` or `&&`
+    And,
+    /// `eq` or `==`
+    Eq,
+    /// `neq` or `!=`
+    Ne,
+    /// `is` or `===`
+    StrictEq,
+    /// `isnot` or `!==`
+    StrictNe,
+    /// `lt` or `<`
+    Lt,
+    /// `lte` or `<=`
+    Le,
+    /// `gt` or `>`
+    Gt,
+    /// `gte` or `>=`
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+/// A SugarCube expression, see [`parse_expression`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr<'a> {
+    Number(f64),
+    /// A string in double or single quotes, or backticks, its escaped characters resolved.
+    String(Cow<'a, str>),
+    Bool(bool),
+    Null,
+    Undefined,
+    /// A story variable `$name` or a temporary variable `_name`, sigil included.
+    Variable(&'a str),
+    /// Any other name, such as a function or a global like `setup`.
+    Identifier(&'a str),
+    Array(Vec<Expr<'a>>),
+    /// `{name: value}`, names given as strings or numbers read as strings.
+    Object(Vec<(Cow<'a, str>, Expr<'a>)>),
+    /// `object.name`
+    Property {
+        object: Box<Expr<'a>>,
+        name: &'a str,
+    },
+    /// `object[index]`
+    Index {
+        object: Box<Expr<'a>>,
+        index: Box<Expr<'a>>,
+    },
+    Call {
+        callee: Box<Expr<'a>>,
+        args: Vec<Expr<'a>>,
+    },
+    Unary {
+        op: UnaryOp,
+        operand: Box<Expr<'a>>,
+    },
+    Binary {
+        op: BinaryOp,
+        left: Box<Expr<'a>>,
+        right: Box<Expr<'a>>,
+    },
+    /// `condition ? then : otherwise`
+    Conditional {
+        condition: Box<Expr<'a>>,
+        then: Box<Expr<'a>>,
+        otherwise: Box<Expr<'a>>,
+    },
+    /// `target to value` or `target = value`, or with `op` the compound assignments such as
+    /// `target += value`.
+    Assign {
+        target: Box<Expr<'a>>,
+        op: Option<BinaryOp>,
+        value: Box<Expr<'a>>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpressionError {
+    /// A string isn't closed, with the byte offset of its opening quote.
+    UnclosedString(usize),
+    /// The token at this byte offset doesn't fit the expression.
+    Unexpected(usize),
+    /// The expression ends before it's complete.
+    UnexpectedEnd,
+    /// The expression nests deeper than 64 levels at this byte offset.
+    TooDeep(usize),
+}
+
+impl Display for ExpressionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExpressionError::UnclosedString(offset) => {
+                write!(f, "The string at byte {offset} is not closed")
+            }
+            ExpressionError::Unexpected(offset) => write!(f, "Unexpected token at byte {offset}"),
+            ExpressionError::UnexpectedEnd => write!(f, "The expression is incomplete"),
+            ExpressionError::TooDeep(offset) => {
+                write!(f, "The expression nests too deep at byte {offset}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExpressionError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token<'a> {
+    Number(f64),
+    String(Cow<'a, str>),
+    Variable(&'a str),
+    Word(&'a str),
+    Punct(&'static str),
+}
+
+/// Punctuation, longest first so that `===` isn't read as `==` then `=`.
+const PUNCTUATION: [&str; 33] = [
+    "===", "!==", "==", "!=", "<=", ">=", "&&", "||", "+=", "-=", "*=", "/=", "%=", "<", ">", "!",
+    "=", "+", "-", "*", "/", "%", "(", ")", "[", "]", "{", "}", ",", ".", "?", ":", ";",
+];
+
+/// Compound assignments, with the operator they apply.
+const COMPOUND: [(&str, BinaryOp); 5] = [
+    ("+=", BinaryOp::Add),
+    ("-=", BinaryOp::Sub),
+    ("*=", BinaryOp::Mul),
+    ("/=", BinaryOp::Div),
+    ("%=", BinaryOp::Rem),
+];
+
+/// How deep the expression tree may get, so that parsing, evaluating or dropping it can't
+/// overflow the stack. Operands chained by binary operators, or by `.`, `[]` and calls, count
+/// as a level each since they nest to the left.
+const MAX_DEPTH: usize = 64;
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+fn tokenize(input: &str) -> Result<Vec<(usize, Token<'_>)>, ExpressionError> {
+    let mut tokens = vec![];
+    let mut index = 0;
+    while let Some(position) = input[index..].find(|c: char| !c.is_whitespace()) {
+        let start = index + position;
+        let rest = &input[start..];
+        let (len, token) = if rest.starts_with(['"', '\'', '`']) {
+            let len = quoted_len(rest).ok_or(ExpressionError::UnclosedString(start))?;
+            let content = &rest[1..len - 1];
+            let string = match escape_string_content(content) {
+                Some(unescaped) => Cow::Owned(unescaped),
+                None => Cow::Borrowed(content),
+            };
+            (len, Token::String(string))
+        } else if rest.starts_with(|c: char| c.is_ascii_digit())
+            || (rest.starts_with('.') && rest[1..].starts_with(|c: char| c.is_ascii_digit()))
+        {
+            let mut len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '.'))
+                .unwrap_or(rest.len());
+            // An exponent may have a sign.
+            if rest[..len].ends_with(['e', 'E']) && rest[len..].starts_with(['-', '+']) {
+                len += 1 + rest[len + 1..]
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len() - len - 1);
+            }
+            let number = rest[..len]
+                .parse()
+                .map_err(|_| ExpressionError::Unexpected(start))?;
+            (len, Token::Number(number))
+        } else if rest.starts_with(is_name_char) {
+            let len = rest.find(|c| !is_name_char(c)).unwrap_or(rest.len());
+            let name = &rest[..len];
+            match name.len() > 1 && name.starts_with(['$', '_']) {
+                true => (len, Token::Variable(name)),
+                false => (len, Token::Word(name)),
+            }
+        } else {
+            let punct = PUNCTUATION
+                .into_iter()
+                .find(|punct| rest.starts_with(punct))
+                .ok_or(ExpressionError::Unexpected(start))?;
+            (punct.len(), Token::Punct(punct))
+        };
+        tokens.push((start, token));
+        index = start + len;
+    }
+    Ok(tokens)
+}
+
+/// Operators of a precedence level, with the tokens standing for them.
+type Level = &'static [(&'static str, BinaryOp)];
+
+/// Binary operators from the lowest precedence to the highest.
+const LEVELS: [Level; 6] = [
+    &[("or", BinaryOp::Or), ("||", BinaryOp::Or)],
+    &[("and", BinaryOp::And), ("&&", BinaryOp::And)],
+    &[
+        ("is", BinaryOp::StrictEq),
+        ("isnot", BinaryOp::StrictNe),
+        ("eq", BinaryOp::Eq),
+        ("neq", BinaryOp::Ne),
+        ("===", BinaryOp::StrictEq),
+        ("!==", BinaryOp::StrictNe),
+        ("==", BinaryOp::Eq),
+        ("!=", BinaryOp::Ne),
+    ],
+    &[
+        ("lt", BinaryOp::Lt),
+        ("lte", BinaryOp::Le),
+        ("gt", BinaryOp::Gt),
+        ("gte", BinaryOp::Ge),
+        ("<", BinaryOp::Lt),
+        ("<=", BinaryOp::Le),
+        (">", BinaryOp::Gt),
+        (">=", BinaryOp::Ge),
+    ],
+    &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+    &[
+        ("*", BinaryOp::Mul),
+        ("/", BinaryOp::Div),
+        ("%", BinaryOp::Rem),
+    ],
+];
+
+struct Parser<'a> {
+    tokens: Vec<(usize, Token<'a>)>,
+    index: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token<'a>> {
+        self.tokens.get(self.index).map(|(_, token)| token)
+    }
+
+    /// The word or punctuation of the next token.
+    fn peek_symbol(&self) -> Option<&'a str> {
+        match self.peek()? {
+            Token::Word(word) => Some(word),
+            Token::Punct(punct) => Some(punct),
+            _ => None,
+        }
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        let found = self.peek_symbol() == Some(symbol);
+        self.index += usize::from(found);
+        found
+    }
+
+    fn unexpected(&self) -> ExpressionError {
+        match self.tokens.get(self.index) {
+            Some((offset, _)) => ExpressionError::Unexpected(*offset),
+            None => ExpressionError::UnexpectedEnd,
+        }
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), ExpressionError> {
+        match self.eat(symbol) {
+            true => Ok(()),
+            false => Err(self.unexpected()),
+        }
+    }
+
+    /// Goes one level deeper, failing past [`MAX_DEPTH`].
+    fn descend(&mut self) -> Result<(), ExpressionError> {
+        if self.depth == MAX_DEPTH {
+            return Err(match self.tokens.get(self.index) {
+                Some((offset, _)) => ExpressionError::TooDeep(*offset),
+                None => ExpressionError::UnexpectedEnd,
+            });
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// Runs `parse` one level deeper, failing past [`MAX_DEPTH`].
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, ExpressionError>,
+    ) -> Result<T, ExpressionError> {
+        self.descend()?;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn assignment(&mut self) -> Result<Expr<'a>, ExpressionError> {
+        self.nested(|parser| {
+            let target = parser.conditional()?;
+            let op = match parser.peek_symbol() {
+                Some("to" | "=") => None,
+                Some(symbol) => match COMPOUND.iter().find(|(compound, _)| *compound == symbol) {
+                    Some(&(_, op)) => Some(op),
+                    None => return Ok(target),
+                },
+                None => return Ok(target),
+            };
+            parser.index += 1;
+            let value = parser.assignment()?;
+            Ok(Expr::Assign {
+                target: Box::new(target),
+                op,
+                value: Box::new(value),
+            })
+        })
+    }
+
+    fn conditional(&mut self) -> Result<Expr<'a>, ExpressionError> {
+        let condition = self.binary(0)?;
+        if !self.eat("?") {
+            return Ok(condition);
+        }
+        let then = self.assignment()?;
+        self.expect(":")?;
+        let otherwise = self.assignment()?;
+        Ok(Expr::Conditional {
+            condition: Box::new(condition),
+            then: Box::new(then),
+            otherwise: Box::new(otherwise),
+        })
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr<'a>, ExpressionError> {
+        let Some(operators) = LEVELS.get(level) else {
+            return self.unary();
+        };
+        let depth = self.depth;
+        let mut left = self.binary(level + 1)?;
+        while let Some(&(_, op)) = operators
+            .iter()
+            .find(|(symbol, _)| self.peek_symbol() == Some(symbol))
+        {
+            self.descend()?;
+            self.index += 1;
+            let right = self.binary(level + 1)?;
+            left = Expr::Binary {
+                op,
+                left: Box::new(left),
+                right: Box::new(right),
+            };
+        }
+        self.depth = depth;
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr<'a>, ExpressionError> {
+        let op = match self.peek_symbol() {
+            Some("not" | "!") => UnaryOp::Not,
+            Some("-") => UnaryOp::Neg,
+            Some("+") => UnaryOp::Plus,
+            Some("def") => UnaryOp::Def,
+            Some("ndef") => UnaryOp::Ndef,
+            _ => return self.postfix(),
+        };
+        self.index += 1;
+        let operand = self.nested(Self::unary)?;
+        Ok(Expr::Unary {
+            op,
+            operand: Box::new(operand),
+        })
+    }
+
+    /// Expressions separated by commas, up to `closing`.
+    fn list(&mut self, closing: &str) -> Result<Vec<Expr<'a>>, ExpressionError> {
+        let mut items = vec![];
+        while !self.eat(closing) {
+            if !items.is_empty() {
+                self.expect(",")?;
+            }
+            items.push(self.assignment()?);
+        }
+        Ok(items)
+    }
+
+    fn postfix(&mut self) -> Result<Expr<'a>, ExpressionError> {
+        let depth = self.depth;
+        let mut expr = self.primary()?;
+        loop {
+            if matches!(self.peek_symbol(), Some("." | "[" | "(")) {
+                self.descend()?;
+            }
+            expr = if self.eat(".") {
+                let Some(Token::Word(name) | Token::Variable(name)) = self.peek().cloned() else {
+                    return Err(self.unexpected());
+                };
+                self.index += 1;
+                Expr::Property {
+                    object: Box::new(expr),
+                    name,
+                }
+            } else if self.eat("[") {
+                let index = self.assignment()?;
+                self.expect("]")?;
+                Expr::Index {
+                    object: Box::new(expr),
+                    index: Box::new(index),
+                }
+            } else if self.eat("(") {
+                Expr::Call {
+                    callee: Box::new(expr),
+                    args: self.list(")")?,
+                }
+            } else {
+                self.depth = depth;
+                return Ok(expr);
+            };
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr<'a>, ExpressionError> {
+        let Some(token) = self.peek().cloned() else {
+            return Err(ExpressionError::UnexpectedEnd);
+        };
+        if token == Token::Punct("(") {
+            self.index += 1;
+            let expr = self.assignment()?;
+            self.expect(")")?;
+            return Ok(expr);
+        }
+        if token == Token::Punct("[") {
+            self.index += 1;
+            return Ok(Expr::Array(self.list("]")?));
+        }
+        if token == Token::Punct("{") {
+            self.index += 1;
+            return self.object();
+        }
+        let expr = match token {
+            Token::Number(number) => Expr::Number(number),
+            Token::String(string) => Expr::String(string),
+            Token::Variable(name) => Expr::Variable(name),
+            Token::Word("true") => Expr::Bool(true),
+            Token::Word("false") => Expr::Bool(false),
+            Token::Word("null") => Expr::Null,
+            Token::Word("undefined") => Expr::Undefined,
+            Token::Word(name) if !name.starts_with(|c: char| c.is_ascii_digit()) => {
+                Expr::Identifier(name)
+            }
+            _ => return Err(self.unexpected()),
+        };
+        self.index += 1;
+        Ok(expr)
+    }
+
+    /// The fields of an object literal, after its `{`.
+    fn object(&mut self) -> Result<Expr<'a>, ExpressionError> {
+        let mut fields = vec![];
+        while !self.eat("}") {
+            if !fields.is_empty() {
+                self.expect(",")?;
+            }
+            let name = match self.peek().cloned() {
+                Some(Token::Word(name) | Token::Variable(name)) => Cow::Borrowed(name),
+                Some(Token::String(name)) => name,
+                Some(Token::Number(number)) => Cow::Owned(Value::Number(number).to_string()),
+                _ => return Err(self.unexpected()),
+            };
+            self.index += 1;
+            self.expect(":")?;
+            fields.push((name, self.assignment()?));
+        }
+        Ok(Expr::Object(fields))
+    }
+}
+
+/// Parses a SugarCube expression, such as the condition of `<<if $a gt 3 and not $b>>` or
+/// the assignment of `<<set $gold to $gold + 10>>` or `<<set $gold += 10>>`.
+pub fn parse_expression(input: &str) -> Result<Expr<'_>, ExpressionError> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        index: 0,
+        depth: 0,
+    };
+    let expr = parser.assignment()?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some(_) => Err(parser.unexpected()),
+    }
+}
+
+/// The value of an expression, see [`Expr::evaluate`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Undefined,
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(BTreeMap<String, Value>),
+}
+
+impl Value {
+    /// Whether the value counts as true in a condition.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Undefined | Value::Null => false,
+            Value::Bool(value) => *value,
+            Value::Number(number) => *number != 0.0 && !number.is_nan(),
+            Value::String(string) => !string.is_empty(),
+            Value::Array(_) | Value::Object(_) => true,
+        }
+    }
+
+    pub fn to_number(&self) -> f64 {
+        match self {
+            Value::Null => 0.0,
+            Value::Bool(value) => f64::from(u8::from(*value)),
+            Value::Number(number) => *number,
+            Value::String(string) if string.trim().is_empty() => 0.0,
+            Value::String(string) => string.trim().parse().unwrap_or(f64::NAN),
+            Value::Undefined | Value::Array(_) | Value::Object(_) => f64::NAN,
+        }
+    }
+
+    fn is_nullish(&self) -> bool {
+        matches!(self, Value::Undefined | Value::Null)
+    }
+
+    fn is_primitive(&self) -> bool {
+        matches!(self, Value::Bool(_) | Value::Number(_) | Value::String(_))
+    }
+
+    /// `==`, converting strings and booleans compared with numbers.
+    fn loose_eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (a, b) if a.is_nullish() || b.is_nullish() => a.is_nullish() && b.is_nullish(),
+            (a, b)
+                if a.is_primitive()
+                    && b.is_primitive()
+                    && std::mem::discriminant(a) != std::mem::discriminant(b) =>
+            {
+                a.to_number() == b.to_number()
+            }
+            (a, b) => a == b,
+        }
+    }
+
+    fn compare(&self, other: &Value) -> Option<std::cmp::Ordering> {
+        match (self, other) {
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            (a, b) => a.to_number().partial_cmp(&b.to_number()),
+        }
+    }
+
+    fn property(&self, key: &Value) -> Value {
+        match (self, key) {
+            (Value::Array(items), Value::String(key)) if key == "length" => {
+                Value::Number(items.len() as f64)
+            }
+            (Value::String(string), Value::String(key)) if key == "length" => {
+                Value::Number(string.chars().count() as f64)
+            }
+            (Value::Array(items), Value::Number(index)) if index.fract() == 0.0 => {
+                let item = (*index >= 0.0)
+                    .then(|| items.get(*index as usize))
+                    .flatten();
+                item.cloned().unwrap_or(Value::Undefined)
+            }
+            (Value::Object(fields), key) => fields
+                .get(&key.to_string())
+                .cloned()
+                .unwrap_or(Value::Undefined),
+            _ => Value::Undefined,
+        }
+    }
+}
+
+/// Values written as JavaScript's `String` does.
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Undefined => write!(f, "undefined"),
+            Value::Null => write!(f, "null"),
+            Value::Bool(value) => write!(f, "{value}"),
+            Value::Number(number) if number.is_infinite() => match number.is_sign_positive() {
+                true => write!(f, "Infinity"),
+                false => write!(f, "-Infinity"),
+            },
+            Value::Number(number) => write!(f, "{number}"),
+            Value::String(string) => write!(f, "{string}"),
+            Value::Array(items) => {
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    if !item.is_nullish() {
+                        write!(f, "{item}")?;
+                    }
+                }
+                Ok(())
+            }
+            Value::Object(_) => write!(f, "[object Object]"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvalError {
+    /// The environment has no function of this name.
+    UnknownFunction(String),
+    /// The expression called isn't a function name or a method.
+    NotCallable,
+    /// The target of an assignment isn't a variable the environment can set.
+    InvalidAssignment,
+}
+
+impl Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvalError::UnknownFunction(name) => write!(f, "Unknown function {name:?}"),
+            EvalError::NotCallable => write!(f, "The expression called is not a function"),
+            EvalError::InvalidAssignment => write!(f, "Cannot assign to this expression"),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+/// The variables and functions of a runtime, for [`Expr::evaluate`].
+pub trait Environment {
+    /// The value of a variable, `$name` and `_name` with their sigil, or of another name such
+    /// as `setup`.
+    fn variable(&self, name: &str) -> Value;
+
+    fn set_variable(&mut self, name: &str, value: Value) -> Result<(), EvalError> {
+        let _ = (name, value);
+        Err(EvalError::InvalidAssignment)
+    }
+
+    /// Calls a function such as `visited` or `Math.max`, named as written, or a method on
+    /// `this`, like `includes` in `$items.includes("key")`.
+    fn call(
+        &mut self,
+        function: &str,
+        this: Option<&Value>,
+        args: &[Value],
+    ) -> Result<Value, EvalError> {
+        let _ = (this, args);
+        Err(EvalError::UnknownFunction(function.to_string()))
+    }
+}
+
+/// Variables stored by name, without functions.
+impl Environment for HashMap<String, Value> {
+    fn variable(&self, name: &str) -> Value {
+        self.get(name).cloned().unwrap_or(Value::Undefined)
+    }
+
+    fn set_variable(&mut self, name: &str, value: Value) -> Result<(), EvalError> {
+        self.insert(name.to_string(), value);
+        Ok(())
+    }
+}
+
+/// The dotted name of `expr` if it's made of names only, such as `Math.max`.
+fn dotted_name(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Identifier(name) => Some(name.to_string()),
+        Expr::Property { object, name } => Some(format!("{}.{name}", dotted_name(object)?)),
+        _ => None,
+    }
+}
+
+/// Applies a binary operator other than `and` and `or`, which may skip their right operand.
+fn binary(op: BinaryOp, left: Value, right: Value) -> Value {
+    let number = |f: fn(f64, f64) -> f64| Value::Number(f(left.to_number(), right.to_number()));
+    let ordering = left.compare(&right);
+    match op {
+        BinaryOp::Eq => Value::Bool(left.loose_eq(&right)),
+        BinaryOp::Ne => Value::Bool(!left.loose_eq(&right)),
+        BinaryOp::StrictEq => Value::Bool(left == right),
+        BinaryOp::StrictNe => Value::Bool(left != right),
+        BinaryOp::Lt => Value::Bool(ordering.is_some_and(|o| o.is_lt())),
+        BinaryOp::Le => Value::Bool(ordering.is_some_and(|o| o.is_le())),
+        BinaryOp::Gt => Value::Bool(ordering.is_some_and(|o| o.is_gt())),
+        BinaryOp::Ge => Value::Bool(ordering.is_some_and(|o| o.is_ge())),
+        BinaryOp::Add => match (&left, &right) {
+            (Value::String(_), _) | (_, Value::String(_)) => {
+                Value::String(format!("{left}{right}"))
+            }
+            _ => number(|a, b| a + b),
+        },
+        BinaryOp::Sub => number(|a, b| a - b),
+        BinaryOp::Mul => number(|a, b| a * b),
+        BinaryOp::Div => number(|a, b| a / b),
+        BinaryOp::Rem => number(|a, b| a % b),
+        BinaryOp::And | BinaryOp::Or => unreachable!(),
+    }
+}
+
+impl Expr<'_> {
+    /// Evaluates the expression in `env`, which assignments and calls may change.
+    pub fn evaluate(&self, env: &mut impl Environment) -> Result<Value, EvalError> {
+        let value = match self {
+            Expr::Number(number) => Value::Number(*number),
+            Expr::String(string) => Value::String(string.to_string()),
+            Expr::Bool(value) => Value::Bool(*value),
+            Expr::Null => Value::Null,
+            Expr::Undefined => Value::Undefined,
+            Expr::Variable(name) | Expr::Identifier(name) => env.variable(name),
+            Expr::Array(items) => Value::Array(
+                items
+                    .iter()
+                    .map(|item| item.evaluate(env))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Expr::Object(fields) => Value::Object(
+                fields
+                    .iter()
+                    .map(|(name, value)| Ok((name.to_string(), value.evaluate(env)?)))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Expr::Property { object, name } => object
+                .evaluate(env)?
+                .property(&Value::String(name.to_string())),
+            Expr::Index { object, index } => {
+                let object = object.evaluate(env)?;
+                object.property(&index.evaluate(env)?)
+            }
+            Expr::Call { callee, args } => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.evaluate(env))
+                    .collect::<Result<Vec<_>, _>>()?;
+                match (dotted_name(callee), &**callee) {
+                    (Some(function), _) => env.call(&function, None, &args)?,
+                    (None, Expr::Property { object, name }) => {
+                        let this = object.evaluate(env)?;
+                        env.call(name, Some(&this), &args)?
+                    }
+                    _ => return Err(EvalError::NotCallable),
+                }
+            }
+            Expr::Unary { op, operand } => {
+                let operand = operand.evaluate(env)?;
+                match op {
+                    UnaryOp::Not => Value::Bool(!operand.is_truthy()),
+                    UnaryOp::Neg => Value::Number(-operand.to_number()),
+                    UnaryOp::Plus => Value::Number(operand.to_number()),
+                    UnaryOp::Def => Value::Bool(operand != Value::Undefined),
+                    UnaryOp::Ndef => Value::Bool(operand == Value::Undefined),
+                }
+            }
+            Expr::Binary { op, left, right } => {
+                let left = left.evaluate(env)?;
+                // `and` and `or` give one of their operands, evaluating the right one only
+                // when needed.
+                match op {
+                    BinaryOp::And if !left.is_truthy() => return Ok(left),
+                    BinaryOp::Or if left.is_truthy() => return Ok(left),
+                    BinaryOp::And | BinaryOp::Or => return right.evaluate(env),
+                    _ => {}
+                }
+                binary(*op, left, right.evaluate(env)?)
+            }
+            Expr::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => match condition.evaluate(env)?.is_truthy() {
+                true => then.evaluate(env)?,
+                false => otherwise.evaluate(env)?,
+            },
+            Expr::Assign { target, op, value } => {
+                let (Expr::Variable(name) | Expr::Identifier(name)) = &**target else {
+                    return Err(EvalError::InvalidAssignment);
+                };
+                let mut value = value.evaluate(env)?;
+                if let Some(op) = op {
+                    value = binary(*op, env.variable(name), value);
+                }
+                env.set_variable(name, value.clone())?;
+                value
+            }
+        };
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{parse_expression, BinaryOp, EvalError, Expr, ExpressionError, UnaryOp, Value};
+
+    #[test]
+    fn test_parse_expression() {
+        assert_eq!(
+            parse_expression("$a gt 3 and not $b").unwrap(),
+            Expr::Binary {
+                op: BinaryOp::And,
+                left: Box::new(Expr::Binary {
+                    op: BinaryOp::Gt,
+                    left: Box::new(Expr::Variable("$a")),
+                    right: Box::new(Expr::Number(3.0)),
+                }),
+                right: Box::new(Expr::Unary {
+                    op: UnaryOp::Not,
+                    operand: Box::new(Expr::Variable("$b")),
+                }),
+            }
+        );
+        assert_eq!(
+            parse_expression("visited('Cave')").unwrap(),
+            Expr::Call {
+                callee: Box::new(Expr::Identifier("visited")),
+                args: vec![Expr::String("Cave".into())],
+            }
+        );
+
+        assert_eq!(
+            parse_expression("$a gt"),
+            Err(ExpressionError::UnexpectedEnd)
+        );
+        assert_eq!(
+            parse_expression("$a $b"),
+            Err(ExpressionError::Unexpected(3))
+        );
+        assert_eq!(
+            parse_expression("'open"),
+            Err(ExpressionError::UnclosedString(0))
+        );
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let mut env = HashMap::from([
+            ("$gold".to_string(), Value::Number(5.0)),
+            (
+                "$items".to_string(),
+                Value::Array(vec![Value::String("key".to_string())]),
+            ),
+        ]);
+        let mut evaluate = |input| parse_expression(input).unwrap().evaluate(&mut env);
+
+        assert_eq!(evaluate("$gold to $gold * 2 + 1"), Ok(Value::Number(11.0)));
+        assert_eq!(evaluate("$gold is 11"), Ok(Value::Bool(true)));
+        assert_eq!(
+            evaluate("$gold eq '11' and $gold isnot '11'"),
+            Ok(Value::Bool(true))
+        );
+        assert_eq!(
+            evaluate("$items.length gte 1 ? $items[0] : 'none'"),
+            Ok(Value::String("key".to_string()))
+        );
+        assert_eq!(
+            evaluate("'Gold: ' + $gold"),
+            Ok(Value::String("Gold: 11".to_string()))
+        );
+        assert_eq!(
+            evaluate("def $missing or ndef $missing"),
+            Ok(Value::Bool(true))
+        );
+        assert_eq!(
+            evaluate("$items.includes('key')"),
+            Err(EvalError::UnknownFunction("includes".to_string()))
+        );
+        assert_eq!(evaluate("1 to 2"), Err(EvalError::InvalidAssignment));
+        assert_eq!(evaluate("$gold += 10"), Ok(Value::Number(21.0)));
+        assert_eq!(evaluate("$gold %= 4"), Ok(Value::Number(1.0)));
+        assert_eq!(
+            evaluate("$o to {name: 'Ada', \"age\": 36, 1: []}"),
+            Ok(Value::Object(
+                [
+                    ("1".to_string(), Value::Array(vec![])),
+                    ("age".to_string(), Value::Number(36.0)),
+                    ("name".to_string(), Value::String("Ada".to_string())),
+                ]
+                .into()
+            ))
+        );
+        assert_eq!(evaluate("$o.name"), Ok(Value::String("Ada".to_string())));
+    }
+
+    #[test]
+    fn test_parse_expression_too_deep() {
+        let parens = format!("{}1{}", "(".repeat(200_000), ")".repeat(200_000));
+        assert!(matches!(
+            parse_expression(&parens),
+            Err(ExpressionError::TooDeep(_))
+        ));
+        let nots = format!("{}$a", "not ".repeat(200_000));
+        assert!(matches!(
+            parse_expression(&nots),
+            Err(ExpressionError::TooDeep(_))
+        ));
+        let assignments = format!("{}1", "$a to ".repeat(200_000));
+        assert!(matches!(
+            parse_expression(&assignments),
+            Err(ExpressionError::TooDeep(_))
+        ));
+
+        let nested = format!("{}1{}", "[(".repeat(31), ")]".repeat(31));
+        assert!(parse_expression(&nested).is_ok());
+
+        let sum = vec!["1"; 50_000].join("+");
+        assert!(matches!(
+            parse_expression(&sum),
+            Err(ExpressionError::TooDeep(_))
+        ));
+        let properties = format!("$a{}", ".b".repeat(50_000));
+        assert!(matches!(
+            parse_expression(&properties),
+            Err(ExpressionError::TooDeep(_))
+        ));
+
+        let sum = vec!["1"; 60].join("+");
+        let sum = parse_expression(&sum).unwrap();
+        assert_eq!(sum.evaluate(&mut HashMap::new()), Ok(Value::Number(60.0)));
+        drop(sum);
+    }
+}
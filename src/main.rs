@@ -0,0 +1,127 @@
+use std::{fs, path::PathBuf, process::ExitCode};
+
+use clap::{Parser, Subcommand};
+use twee_v3::{encoding::decode, export, Story, Warning};
+
+#[derive(Parser)]
+#[command(
+    name = "twee",
+    version,
+    about = "Inspect, validate and convert twee 3 stories"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Parse a story and report problems such as broken links.
+    Validate { file: PathBuf },
+    /// Print passage, word, link and tag counts.
+    Stats { file: PathBuf },
+    /// Convert a story to JSON.
+    ToJson { file: PathBuf },
+    /// Convert the passages to CSV, one row per passage.
+    ToCsv { file: PathBuf },
+    /// Print the passage graph in the Graphviz dot format.
+    Graph { file: PathBuf },
+    /// Print the story as a linear script, walking it from the start passage.
+    Screenplay { file: PathBuf },
+    /// Reformat a story. Stories with text before the first passage or duplicate passages,
+    /// which reformatting drops, aren't written.
+    Fmt {
+        file: PathBuf,
+        /// Overwrite the file instead of printing the result.
+        #[arg(short, long)]
+        write: bool,
+    },
+}
+
+impl Command {
+    fn file(&self) -> &PathBuf {
+        match self {
+            Command::Validate { file }
+            | Command::Stats { file }
+            | Command::ToJson { file }
+            | Command::ToCsv { file }
+            | Command::Graph { file }
+            | Command::Screenplay { file }
+            | Command::Fmt { file, .. } => file,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let path = cli.command.file();
+
+    let content = match fs::read(path).map(decode) {
+        Ok(Ok(content)) => content,
+        Ok(Err(error)) => {
+            eprintln!("Could not decode {}: {error}", path.display());
+            return ExitCode::FAILURE;
+        }
+        Err(error) => {
+            eprintln!("Could not read {}: {error}", path.display());
+            return ExitCode::FAILURE;
+        }
+    };
+    let story = match Story::try_from(content.as_str()) {
+        Ok(story) => story,
+        Err(error) => {
+            eprintln!("Could not parse {}: {error}", path.display());
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match &cli.command {
+        Command::Validate { .. } => {
+            let issues = story.validate();
+            for warning in story.warnings() {
+                println!("{warning}");
+            }
+            for issue in &issues {
+                println!("{issue}");
+            }
+            if !issues.is_empty() || !story.warnings().is_empty() {
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Stats { .. } => {
+            let stats = story.stats();
+            println!("passages:     {}", stats.passages);
+            println!("words:        {}", stats.words);
+            println!("links:        {}", stats.links);
+            println!("broken links: {}", stats.broken_links);
+            println!("tags:         {}", stats.tags);
+        }
+        Command::ToJson { .. } => println!("{:#}", export::json::to_json(&story)),
+        Command::ToCsv { .. } => print!("{}", export::csv::to_csv(&story)),
+        Command::Graph { .. } => print!("{}", export::dot::to_dot(&story)),
+        Command::Screenplay { .. } => print!("{}", export::screenplay::to_screenplay(&story)),
+        Command::Fmt { write, .. } => {
+            let formatted = story.to_twee();
+            if *write {
+                let lossy = story.warnings().iter().find(|warning| {
+                    matches!(
+                        warning,
+                        Warning::Preamble(_) | Warning::DuplicatePassage { .. }
+                    )
+                });
+                if let Some(warning) = lossy {
+                    eprintln!("Not writing {}: {warning}", path.display());
+                    return ExitCode::FAILURE;
+                }
+                if let Err(error) = fs::write(path, formatted) {
+                    eprintln!("Could not write {}: {error}", path.display());
+                    return ExitCode::FAILURE;
+                }
+            } else {
+                print!("{formatted}");
+            }
+        }
+    }
+
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,265 @@
+//! Basic Twine markup: headings, emphasis, lists and horizontal rules.
+//!
+//! Markup is read line by line over the content nodes of a passage, so links can appear in
+//! headings, list items or emphasized text.
+
+use crate::{ContentNode, Passage};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    Paragraph,
+    /// `#` to `######`.
+    Heading(usize),
+    /// `*` items, nested by repeating the star.
+    BulletItem(usize),
+    /// `0.` items, nested by repeating the zero.
+    NumberedItem(usize),
+    /// `---`, alone on its line.
+    HorizontalRule,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Inline<'a> {
+    Text(&'a str),
+    Link {
+        text: &'a str,
+        target: &'a str,
+    },
+    /// `''bold''` or `**bold**`.
+    Bold(Vec<Inline<'a>>),
+    /// `//italic//`.
+    Italic(Vec<Inline<'a>>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line<'a> {
+    pub kind: LineKind,
+    pub content: Vec<Inline<'a>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Marker {
+    Bold,
+    AltBold,
+    Italic,
+}
+
+impl Marker {
+    const ALL: [(&'static str, Marker); 3] = [
+        ("''", Marker::Bold),
+        ("**", Marker::AltBold),
+        ("//", Marker::Italic),
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token<'a> {
+    Text(&'a str),
+    Link(&'a str, &'a str),
+    Marker(Marker, &'a str),
+}
+
+fn line_kind(line: &str) -> (LineKind, &str) {
+    let trimmed = line.trim();
+    if trimmed.len() >= 3 && trimmed.chars().all(|c| c == '-') {
+        return (LineKind::HorizontalRule, "");
+    }
+
+    let count = |c: char| line.chars().take_while(|&x| x == c).count();
+    let hashes = count('#');
+    if (1..=6).contains(&hashes) {
+        return (LineKind::Heading(hashes), line[hashes..].trim_start());
+    }
+    let stars = count('*');
+    if stars > 0 && line[stars..].starts_with(' ') {
+        return (LineKind::BulletItem(stars), line[stars..].trim_start());
+    }
+    let zeros = line
+        .find("0. ")
+        .filter(|&index| index % 2 == 0 && line[..index + 2].chars().all(|c| c == '0' || c == '.'));
+    if let Some(index) = zeros {
+        let depth = index / 2 + 1;
+        if line[..index + 2] == "0.".repeat(depth)[..] {
+            return (
+                LineKind::NumberedItem(depth),
+                line[index + 3..].trim_start(),
+            );
+        }
+    }
+    (LineKind::Paragraph, line)
+}
+
+fn tokenize<'a>(text: &'a str, tokens: &mut Vec<Token<'a>>) {
+    let mut start = 0;
+    let mut index = 0;
+    while index < text.len() {
+        let marker = Marker::ALL
+            .iter()
+            .find(|(pattern, _)| text[index..].starts_with(pattern));
+        match marker {
+            Some((pattern, marker)) => {
+                if start < index {
+                    tokens.push(Token::Text(&text[start..index]));
+                }
+                tokens.push(Token::Marker(*marker, &text[index..index + pattern.len()]));
+                index += pattern.len();
+                start = index;
+            }
+            None => index += text[index..].chars().next().map_or(1, char::len_utf8),
+        }
+    }
+    if start < text.len() {
+        tokens.push(Token::Text(&text[start..]));
+    }
+}
+
+fn parse_inlines<'a>(
+    tokens: &[Token<'a>],
+    index: &mut usize,
+    closing: Option<Marker>,
+) -> Vec<Inline<'a>> {
+    let mut inlines = vec![];
+    while let Some(token) = tokens.get(*index) {
+        *index += 1;
+        match *token {
+            Token::Text(text) => inlines.push(Inline::Text(text)),
+            Token::Link(text, target) => inlines.push(Inline::Link { text, target }),
+            Token::Marker(marker, _) if Some(marker) == closing => return inlines,
+            Token::Marker(marker, raw) => {
+                let closed = tokens[*index..]
+                    .iter()
+                    .any(|token| matches!(token, Token::Marker(m, _) if *m == marker));
+                if closed {
+                    let children = parse_inlines(tokens, index, Some(marker));
+                    inlines.push(match marker {
+                        Marker::Bold | Marker::AltBold => Inline::Bold(children),
+                        Marker::Italic => Inline::Italic(children),
+                    });
+                } else {
+                    inlines.push(Inline::Text(raw));
+                }
+            }
+        }
+    }
+    inlines
+}
+
+fn parse_line<'a>(segments: &[Token<'a>]) -> Line<'a> {
+    let (kind, first) = match segments.first() {
+        Some(Token::Text(text)) => line_kind(text),
+        _ => (LineKind::Paragraph, ""),
+    };
+
+    let mut tokens = vec![];
+    for (position, segment) in segments.iter().enumerate() {
+        match segment {
+            Token::Text(text) => tokenize(if position == 0 { first } else { text }, &mut tokens),
+            token => tokens.push(*token),
+        }
+    }
+
+    let content = if kind == LineKind::HorizontalRule {
+        vec![]
+    } else {
+        parse_inlines(&tokens, &mut 0, None)
+    };
+    Line { kind, content }
+}
+
+impl<'a> Passage<&'a str> {
+    /// Reads the markup of the passage, one entry per line. Comments are skipped.
+    pub fn markup(&self) -> Vec<Line<'a>> {
+        let mut lines = vec![];
+        let mut current = vec![];
+        for node in self.content.iter().flat_map(ContentNode::flatten) {
+            match node {
+                ContentNode::Text(text) => {
+                    let mut parts = text.split('\n');
+                    if let Some(part) = parts.next() {
+                        if !part.is_empty() {
+                            current.push(Token::Text(part));
+                        }
+                    }
+                    for part in parts {
+                        lines.push(parse_line(&current));
+                        current.clear();
+                        if !part.is_empty() {
+                            current.push(Token::Text(part));
+                        }
+                    }
+                }
+                ContentNode::Link { text, target, .. } => current.push(Token::Link(text, target)),
+                ContentNode::Comment(_)
+                | ContentNode::Html(_)
+                | ContentNode::Image(_)
+                | ContentNode::Custom(_)
+                | ContentNode::Raw { .. }
+                | ContentNode::Paragraph(_) => {}
+            }
+        }
+        lines.push(parse_line(&current));
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{line_kind, Inline, Line, LineKind};
+    use crate::Story;
+
+    #[test]
+    fn test_line_kind() {
+        assert_eq!(line_kind("## Title"), (LineKind::Heading(2), "Title"));
+        assert_eq!(line_kind("** item"), (LineKind::BulletItem(2), "item"));
+        assert_eq!(line_kind("**bold**"), (LineKind::Paragraph, "**bold**"));
+        assert_eq!(line_kind("0.0. item"), (LineKind::NumberedItem(2), "item"));
+        assert_eq!(line_kind("0 items"), (LineKind::Paragraph, "0 items"));
+        assert_eq!(line_kind("-----"), (LineKind::HorizontalRule, ""));
+    }
+
+    #[test]
+    fn test_markup() {
+        let story = Story::try_from(
+            ":: A\n# The ''big'' [[door]]\n---\n* //Knock ''twice''//\nA lone '' quote",
+        )
+        .unwrap();
+        let passage = story.get_passage("A").unwrap();
+
+        assert_eq!(
+            passage.markup(),
+            vec![
+                Line {
+                    kind: LineKind::Heading(1),
+                    content: vec![
+                        Inline::Text("The "),
+                        Inline::Bold(vec![Inline::Text("big")]),
+                        Inline::Text(" "),
+                        Inline::Link {
+                            text: "door",
+                            target: "door"
+                        },
+                    ]
+                },
+                Line {
+                    kind: LineKind::HorizontalRule,
+                    content: vec![]
+                },
+                Line {
+                    kind: LineKind::BulletItem(1),
+                    content: vec![Inline::Italic(vec![
+                        Inline::Text("Knock "),
+                        Inline::Bold(vec![Inline::Text("twice")]),
+                    ])]
+                },
+                Line {
+                    kind: LineKind::Paragraph,
+                    content: vec![
+                        Inline::Text("A lone "),
+                        Inline::Text("''"),
+                        Inline::Text(" quote"),
+                    ]
+                },
+            ]
+        );
+    }
+}
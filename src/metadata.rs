@@ -0,0 +1,121 @@
+//! Passage metadata read as a type of the application, such as quest IDs or audio cues stored
+//! next to the position of the passage.
+
+use std::{collections::HashMap, ops::Deref};
+
+use serde::de::DeserializeOwned;
+
+use crate::{ParseOptions, ParsingError, Story};
+
+/// Metadata of a passage that couldn't be read as the requested type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataError {
+    pub passage: String,
+    pub message: String,
+}
+
+/// The metadata of the passages of a story, read as `M`, see [`Story::typed_metadata`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypedMetadata<M> {
+    values: HashMap<String, M>,
+    errors: Vec<MetadataError>,
+}
+
+impl<M> TypedMetadata<M> {
+    /// The metadata of the passage `name`, if it has metadata that could be read.
+    pub fn get(&self, name: &str) -> Option<&M> {
+        self.values.get(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &M)> {
+        self.values
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
+    }
+
+    /// The passages whose metadata couldn't be read, in source order.
+    pub fn errors(&self) -> &[MetadataError] {
+        &self.errors
+    }
+}
+
+impl<T> Story<T>
+where
+    T: Deref<Target = str>,
+{
+    /// Reads the metadata of every passage as `M`. Passages without metadata are skipped.
+    pub fn typed_metadata<M>(&self) -> TypedMetadata<M>
+    where
+        M: DeserializeOwned,
+    {
+        let mut values = HashMap::new();
+        let mut errors = vec![];
+        for passage in self.iter() {
+            let Some(metadata) = passage.metadata else {
+                continue;
+            };
+            match serde_json::from_str(metadata.content) {
+                Ok(value) => {
+                    values.insert(passage.title.to_string(), value);
+                }
+                Err(error) => errors.push(MetadataError {
+                    passage: passage.title.to_string(),
+                    message: error.to_string(),
+                }),
+            }
+        }
+        TypedMetadata { values, errors }
+    }
+}
+
+impl<'a> Story<&'a str> {
+    /// Parses `input` with `options`, then reads the metadata of its passages as `M`, see
+    /// [`Story::typed_metadata`].
+    pub fn parse_with_metadata<M>(
+        input: &'a str,
+        options: ParseOptions,
+    ) -> Result<(Self, TypedMetadata<M>), ParsingError<&'a str>>
+    where
+        M: DeserializeOwned,
+    {
+        let story = Story::parse_with(input, options)?;
+        let metadata = story.typed_metadata();
+        Ok((story, metadata))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::{MetadataError, TypedMetadata};
+    use crate::{ParseOptions, Story};
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Cue {
+        quest: u32,
+        audio: Option<String>,
+    }
+
+    #[test]
+    fn test_parse_with_metadata() {
+        let input = ":: A {\"position\":\"0,0\",\"quest\":3,\"audio\":\"rain.ogg\"}\nHi\n\n:: B {\"quest\":\"three\"}\nHo\n\n:: C\nHu\n";
+        let (story, metadata): (_, TypedMetadata<Cue>) =
+            Story::parse_with_metadata(input, ParseOptions::default()).unwrap();
+
+        assert_eq!(story.len(), 3);
+        assert_eq!(
+            metadata.get("A"),
+            Some(&Cue {
+                quest: 3,
+                audio: Some("rain.ogg".to_string())
+            })
+        );
+        assert_eq!(metadata.get("B"), None);
+        assert_eq!(metadata.get("C"), None);
+        assert!(matches!(
+            metadata.errors(),
+            [MetadataError { passage, .. }] if passage == "B"
+        ));
+    }
+}
@@ -0,0 +1,246 @@
+use std::{fmt::Display, time::Instant};
+
+use crate::{
+    extension::{NodeKind, NodeKinds},
+    progress::{CancellationToken, Progress, ProgressCallback},
+    StoryFormat,
+};
+
+/// How forgiving the parser is with malformed input.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Strictness {
+    /// Recover when possible, for instance by reading a StoryData passage with invalid JSON as
+    /// a regular passage.
+    #[default]
+    Lenient,
+    /// Fail on anything that doesn't follow the twee 3 specification, such as duplicate
+    /// passages or invalid metadata.
+    Strict,
+}
+
+/// A resource limit of [`ParseOptions`], with its configured maximum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Passages(usize),
+    /// Size of a passage in bytes, header included.
+    PassageSize(usize),
+    /// Depth of nested `[` and `{` delimiters in a passage.
+    Nesting(usize),
+}
+
+impl Display for Limit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Limit::Passages(max) => write!(f, "limit of {max} passages"),
+            Limit::PassageSize(max) => write!(f, "limit of {max} bytes per passage"),
+            Limit::Nesting(max) => write!(f, "limit of {max} nested delimiters"),
+        }
+    }
+}
+
+/// Link forms read besides `[[Target]]`, see [`ParseOptions::link_dialects`]. All are enabled
+/// by default; a disabled separator is kept in the target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkDialects {
+    /// `[[Text|Target]]`
+    pub pipe: bool,
+    /// `[[Text->Target]]`
+    pub right_arrow: bool,
+    /// `[[Target<-Text]]`
+    pub left_arrow: bool,
+}
+
+impl Default for LinkDialects {
+    fn default() -> Self {
+        Self {
+            pipe: true,
+            right_arrow: true,
+            left_arrow: true,
+        }
+    }
+}
+
+impl LinkDialects {
+    pub fn pipe(mut self, pipe: bool) -> Self {
+        self.pipe = pipe;
+        self
+    }
+
+    pub fn right_arrow(mut self, right_arrow: bool) -> Self {
+        self.right_arrow = right_arrow;
+        self
+    }
+
+    pub fn left_arrow(mut self, left_arrow: bool) -> Self {
+        self.left_arrow = left_arrow;
+        self
+    }
+}
+
+/// Options controlling how a story is parsed, see [`Story::parse_with`](crate::Story::parse_with).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Keep the exact source of every block, so that [`Story::to_twee`](crate::Story::to_twee)
+    /// reproduces the input byte for byte as long as the story is unchanged.
+    pub verbatim: bool,
+    /// Drop comment nodes instead of keeping them as [`ContentNode::Comment`](crate::ContentNode::Comment).
+    pub strip_comments: bool,
+    pub strictness: Strictness,
+    /// Read StoryTitle and StoryData as story information. When disabled, they are kept as
+    /// regular passages.
+    pub special_passages: bool,
+    /// Resolve escaped characters (`\[`, `\]`, ...) in titles, tags and content. When
+    /// disabled, the text is kept as written.
+    pub unescape: bool,
+    /// Read HTML tags in content as [`ContentNode::Html`](crate::ContentNode::Html) nodes
+    /// instead of text.
+    pub html: bool,
+    /// In stories of the [`StoryFormat::Plain`] format, read `<<...>>`, `(name: ...)` and
+    /// `{{...}}` constructs as [`ContentNode::Raw`](crate::ContentNode::Raw) nodes instead of
+    /// text, so that they can be found without knowing their format.
+    pub raw_sigils: bool,
+    /// Group the content of passages into
+    /// [`ContentNode::Paragraph`](crate::ContentNode::Paragraph) nodes, split at blank lines.
+    pub paragraphs: bool,
+    pub link_dialects: LinkDialects,
+    /// Node kinds of plugins, read out of text nodes before HTML tags, see
+    /// [`ParseOptions::node_kind`].
+    pub node_kinds: NodeKinds,
+    /// Store identical text copied out of the source once, see
+    /// [`Story::dedup_text`](crate::Story::dedup_text).
+    pub dedup_text: bool,
+    /// Story format of the content, instead of the one named in StoryData.
+    pub story_format: Option<StoryFormat>,
+    /// Limits guarding against pathological input, unlimited by default. Exceeding one fails
+    /// with [`ParsingError::LimitExceeded`](crate::ParsingError::LimitExceeded).
+    pub max_passages: Option<usize>,
+    pub max_passage_size: Option<usize>,
+    pub max_nesting: Option<usize>,
+    /// Called after each block of the story, for progress bars.
+    pub progress: Option<ProgressCallback>,
+    /// Stops parsing once cancelled, failing with
+    /// [`ParsingError::Cancelled`](crate::ParsingError::Cancelled).
+    pub cancellation: Option<CancellationToken>,
+    /// Stops parsing after the block during which this instant passes, keeping the passages
+    /// parsed so far with a [`Warning::DeadlineExceeded`](crate::Warning::DeadlineExceeded).
+    pub deadline: Option<Instant>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            verbatim: false,
+            strip_comments: false,
+            strictness: Strictness::default(),
+            special_passages: true,
+            unescape: true,
+            html: false,
+            raw_sigils: false,
+            paragraphs: false,
+            link_dialects: LinkDialects::default(),
+            node_kinds: NodeKinds::default(),
+            dedup_text: false,
+            story_format: None,
+            max_passages: None,
+            max_passage_size: None,
+            max_nesting: None,
+            progress: None,
+            cancellation: None,
+            deadline: None,
+        }
+    }
+}
+
+impl ParseOptions {
+    pub fn verbatim(mut self, verbatim: bool) -> Self {
+        self.verbatim = verbatim;
+        self
+    }
+
+    pub fn strip_comments(mut self, strip_comments: bool) -> Self {
+        self.strip_comments = strip_comments;
+        self
+    }
+
+    pub fn strictness(mut self, strictness: Strictness) -> Self {
+        self.strictness = strictness;
+        self
+    }
+
+    pub fn special_passages(mut self, special_passages: bool) -> Self {
+        self.special_passages = special_passages;
+        self
+    }
+
+    pub fn unescape(mut self, unescape: bool) -> Self {
+        self.unescape = unescape;
+        self
+    }
+
+    pub fn html(mut self, html: bool) -> Self {
+        self.html = html;
+        self
+    }
+
+    pub fn raw_sigils(mut self, raw_sigils: bool) -> Self {
+        self.raw_sigils = raw_sigils;
+        self
+    }
+
+    pub fn paragraphs(mut self, paragraphs: bool) -> Self {
+        self.paragraphs = paragraphs;
+        self
+    }
+
+    pub fn link_dialects(mut self, link_dialects: LinkDialects) -> Self {
+        self.link_dialects = link_dialects;
+        self
+    }
+
+    /// Registers a node kind, read as [`ContentNode::Custom`](crate::ContentNode::Custom)
+    /// nodes.
+    pub fn node_kind(mut self, kind: impl NodeKind + 'static) -> Self {
+        self.node_kinds.push(kind);
+        self
+    }
+
+    pub fn dedup_text(mut self, dedup_text: bool) -> Self {
+        self.dedup_text = dedup_text;
+        self
+    }
+
+    pub fn story_format(mut self, story_format: StoryFormat) -> Self {
+        self.story_format = Some(story_format);
+        self
+    }
+
+    pub fn max_passages(mut self, max_passages: usize) -> Self {
+        self.max_passages = Some(max_passages);
+        self
+    }
+
+    pub fn max_passage_size(mut self, max_passage_size: usize) -> Self {
+        self.max_passage_size = Some(max_passage_size);
+        self
+    }
+
+    pub fn max_nesting(mut self, max_nesting: usize) -> Self {
+        self.max_nesting = Some(max_nesting);
+        self
+    }
+
+    pub fn progress(mut self, f: impl Fn(Progress) + Send + Sync + 'static) -> Self {
+        self.progress = Some(ProgressCallback::new(f));
+        self
+    }
+
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+}
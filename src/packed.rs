@@ -0,0 +1,227 @@
+//! A binary container for shipping stories, compressed and optionally obfuscated so the
+//! narrative isn't readable as plain text in the files of a game.
+//!
+//! The container starts with `TWPK`, a version byte and a byte telling the obfuscation used,
+//! followed by the story as deflated twee. Obfuscation hides the story from casual readers;
+//! the key ships with the game, so it doesn't protect it from anyone determined.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    fmt::Display,
+    hash::{Hash, Hasher},
+    ops::Deref,
+};
+
+use crate::Story;
+
+const MAGIC: &[u8; 4] = b"TWPK";
+const VERSION: u8 = 1;
+const COMPRESSION_LEVEL: u8 = 9;
+/// The most bytes a container may decompress to, far more than any story needs, so a small
+/// damaged or malicious container can't exhaust memory.
+pub const MAX_UNPACKED_LEN: usize = 64 * 1024 * 1024;
+
+/// How the content of a container is hidden.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Obfuscation<'a> {
+    None,
+    /// Bytes XORed with the key, repeated. An empty key leaves them as is.
+    Xor(&'a [u8]),
+    /// AES-128 in counter mode.
+    #[cfg(feature = "packed-aes")]
+    Aes128(&'a [u8; 16]),
+}
+
+impl Obfuscation<'_> {
+    fn id(&self) -> u8 {
+        match self {
+            Obfuscation::None => 0,
+            Obfuscation::Xor(_) => 1,
+            #[cfg(feature = "packed-aes")]
+            Obfuscation::Aes128(_) => 2,
+        }
+    }
+
+    /// Obfuscates or reveals `bytes`, both being the same operation.
+    fn apply(&self, bytes: &mut [u8], _nonce: &[u8; 16]) {
+        match self {
+            Obfuscation::None => {}
+            Obfuscation::Xor(key) if key.is_empty() => {}
+            Obfuscation::Xor(key) => {
+                for (byte, key) in bytes.iter_mut().zip(key.iter().cycle()) {
+                    *byte ^= key;
+                }
+            }
+            #[cfg(feature = "packed-aes")]
+            Obfuscation::Aes128(key) => {
+                use ctr::cipher::{KeyIvInit, StreamCipher};
+
+                let mut cipher = ctr::Ctr128BE::<aes::Aes128>::new((*key).into(), _nonce.into());
+                cipher.apply_keystream(bytes);
+            }
+        }
+    }
+
+    fn uses_nonce(&self) -> bool {
+        self.id() == 2
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PackError {
+    /// The bytes don't start like a container.
+    NotPacked,
+    UnsupportedVersion(u8),
+    /// The container uses another obfuscation than the one given, with its identifier.
+    WrongObfuscation(u8),
+    /// The content can't be decompressed, the container being damaged or the key wrong.
+    Corrupted,
+    /// The content decompresses to more than [`MAX_UNPACKED_LEN`] bytes.
+    TooLarge,
+    /// The story doesn't parse, with the error message.
+    Parsing(String),
+}
+
+impl Display for PackError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PackError::NotPacked => write!(f, "Not a packed story"),
+            PackError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported packed story version {version}")
+            }
+            PackError::WrongObfuscation(id) => {
+                write!(f, "The story is obfuscated differently, with method {id}")
+            }
+            PackError::Corrupted => write!(f, "The packed story is corrupted or the key is wrong"),
+            PackError::TooLarge => write!(
+                f,
+                "The packed story is larger than {MAX_UNPACKED_LEN} bytes once decompressed"
+            ),
+            PackError::Parsing(error) => write!(f, "Cannot parse the story: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for PackError {}
+
+/// A nonce for counter mode that differs between contents.
+fn nonce(content: &[u8]) -> [u8; 16] {
+    let mut nonce = [0; 16];
+    for (seed, part) in nonce.chunks_mut(8).enumerate() {
+        let mut hasher = DefaultHasher::new();
+        (seed, content).hash(&mut hasher);
+        part.copy_from_slice(&hasher.finish().to_le_bytes());
+    }
+    nonce
+}
+
+impl<T> Story<T>
+where
+    T: Deref<Target = str>,
+{
+    /// The story in a container, as twee, see [`Story::from_packed`].
+    pub fn to_packed(&self, obfuscation: Obfuscation) -> Vec<u8> {
+        let mut content =
+            miniz_oxide::deflate::compress_to_vec(self.to_twee().as_bytes(), COMPRESSION_LEVEL);
+        let mut packed = MAGIC.to_vec();
+        packed.extend([VERSION, obfuscation.id()]);
+        let nonce = nonce(&content);
+        if obfuscation.uses_nonce() {
+            packed.extend(nonce);
+        }
+        obfuscation.apply(&mut content, &nonce);
+        packed.extend(content);
+        packed
+    }
+}
+
+impl Story<String> {
+    /// Reads a story written by [`Story::to_packed`], with the same obfuscation.
+    pub fn from_packed(bytes: &[u8], obfuscation: Obfuscation) -> Result<Self, PackError> {
+        let rest = bytes.strip_prefix(MAGIC).ok_or(PackError::NotPacked)?;
+        let [version, id, rest @ ..] = rest else {
+            return Err(PackError::NotPacked);
+        };
+        if *version != VERSION {
+            return Err(PackError::UnsupportedVersion(*version));
+        }
+        if *id != obfuscation.id() {
+            return Err(PackError::WrongObfuscation(*id));
+        }
+        let (nonce, rest) = match obfuscation.uses_nonce() {
+            true => match rest.split_first_chunk::<16>() {
+                Some((nonce, rest)) => (*nonce, rest),
+                None => return Err(PackError::Corrupted),
+            },
+            false => ([0; 16], rest),
+        };
+
+        let mut content = rest.to_vec();
+        obfuscation.apply(&mut content, &nonce);
+        let content =
+            miniz_oxide::inflate::decompress_to_vec_with_limit(&content, MAX_UNPACKED_LEN)
+                .map_err(|error| match error.status {
+                    miniz_oxide::inflate::TINFLStatus::HasMoreOutput => PackError::TooLarge,
+                    _ => PackError::Corrupted,
+                })?;
+        let content = String::from_utf8(content).map_err(|_| PackError::Corrupted)?;
+        Story::try_from(content).map_err(|error| PackError::Parsing(error.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Obfuscation, PackError, MAGIC, MAX_UNPACKED_LEN, VERSION};
+    use crate::Story;
+
+    const SAMPLE: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/sample/sample.twee"));
+
+    #[test]
+    fn test_packed_roundtrip() {
+        let story = Story::try_from(SAMPLE).unwrap();
+        let key = Obfuscation::Xor(b"not a secret");
+        let packed = story.to_packed(key);
+
+        assert!(packed.starts_with(b"TWPK"));
+        assert!(!packed.windows(6).any(|window| window == b"Hello!"));
+        let unpacked = Story::from_packed(&packed, key).unwrap();
+        assert_eq!(unpacked.to_twee(), story.to_twee());
+
+        assert_eq!(
+            Story::from_packed(&packed, Obfuscation::None),
+            Err(PackError::WrongObfuscation(1))
+        );
+        assert_eq!(
+            Story::from_packed(&packed, Obfuscation::Xor(b"wrong")),
+            Err(PackError::Corrupted)
+        );
+        assert_eq!(
+            Story::from_packed(SAMPLE.as_bytes(), key),
+            Err(PackError::NotPacked)
+        );
+    }
+
+    #[test]
+    fn test_packed_too_large() {
+        let content = vec![b'a'; MAX_UNPACKED_LEN + 1];
+        let mut packed = MAGIC.to_vec();
+        packed.extend([VERSION, Obfuscation::None.id()]);
+        packed.extend(miniz_oxide::deflate::compress_to_vec(&content, 1));
+
+        assert_eq!(
+            Story::from_packed(&packed, Obfuscation::None),
+            Err(PackError::TooLarge)
+        );
+    }
+
+    #[cfg(feature = "packed-aes")]
+    #[test]
+    fn test_packed_aes() {
+        let story = Story::try_from(SAMPLE).unwrap();
+        let key = Obfuscation::Aes128(b"0123456789abcdef");
+        let packed = story.to_packed(key);
+
+        let unpacked = Story::from_packed(&packed, key).unwrap();
+        assert_eq!(unpacked.to_twee(), story.to_twee());
+    }
+}
@@ -0,0 +1,136 @@
+use nom::{
+    branch::alt,
+    bytes::complete::{is_not, tag, take_while, take_while1},
+    character::complete::{char, multispace0, multispace1},
+    combinator::{opt, recognize},
+    multi::many0,
+    sequence::{delimited, pair, preceded, tuple},
+    IResult,
+};
+
+use crate::{ContentNode, HtmlTag};
+
+fn parse_tag_name(input: &str) -> IResult<&str, &str> {
+    recognize(pair(
+        take_while1(|c: char| c.is_ascii_alphabetic()),
+        take_while(|c: char| c.is_ascii_alphanumeric() || c == '-'),
+    ))(input)
+}
+
+fn parse_attribute(input: &str) -> IResult<&str, (&str, Option<&str>)> {
+    let name = take_while1(|c: char| !c.is_whitespace() && !"\"'=/>".contains(c));
+    let unquoted = take_while1(|c: char| !c.is_whitespace() && !"\"'=<>`".contains(c));
+    let quoted = alt((
+        delimited(char('"'), opt(is_not("\"")), char('"')),
+        delimited(char('\''), opt(is_not("'")), char('\'')),
+    ));
+    let value = preceded(
+        tuple((multispace0, char('='), multispace0)),
+        alt((quoted, opt(unquoted))),
+    );
+
+    let (input, (_, name, value)) = tuple((multispace1, name, opt(value)))(input)?;
+    Ok((input, (name, value.map(Option::unwrap_or_default))))
+}
+
+pub fn parse_html_tag(input: &str) -> IResult<&str, HtmlTag<&str>> {
+    let (rest, (_, closing, name, attributes, _, _)) = tuple((
+        char('<'),
+        opt(char('/')),
+        parse_tag_name,
+        many0(parse_attribute),
+        multispace0,
+        alt((tag("/>"), tag(">"))),
+    ))(input)?;
+
+    let raw = &input[..input.len() - rest.len()];
+    Ok((rest, HtmlTag::new(raw, name, attributes, closing.is_some())))
+}
+
+/// Splits the HTML tags out of a text node. Escaped `<` are left in the text.
+pub fn split_html(text: &str) -> Vec<ContentNode<&str>> {
+    let mut nodes = vec![];
+    let mut start = 0;
+    let mut index = 0;
+    while let Some(position) = text[index..].find('<') {
+        index += position;
+        // An odd run of backslashes escapes the `<`, an even one is escaped backslashes.
+        let backslashes = text[..index].len() - text[..index].trim_end_matches('\\').len();
+        if backslashes % 2 == 1 {
+            index += 1;
+            continue;
+        }
+        match parse_html_tag(&text[index..]) {
+            Ok((rest, tag)) => {
+                if start < index {
+                    nodes.push(ContentNode::text_node(&text[start..index]));
+                }
+                nodes.push(ContentNode::Html(tag));
+                index = text.len() - rest.len();
+                start = index;
+            }
+            Err(_) => index += 1,
+        }
+    }
+    if start < text.len() {
+        nodes.push(ContentNode::text_node(&text[start..]));
+    }
+    nodes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_html_tag, split_html};
+    use crate::{ContentNode, HtmlTag};
+
+    #[test]
+    fn test_parse_html_tag() {
+        let input = r#"<span class="red" data-x='1' hidden id=main>"#;
+        let (rest, tag) = parse_html_tag(input).unwrap();
+
+        assert_eq!(rest, "");
+        assert_eq!(
+            tag,
+            HtmlTag::new(
+                input,
+                "span",
+                vec![
+                    ("class", Some("red")),
+                    ("data-x", Some("1")),
+                    ("hidden", None),
+                    ("id", Some("main"))
+                ],
+                false
+            )
+        );
+        assert_eq!(tag.attribute("class"), Some("red"));
+        assert!(parse_html_tag("</span>").unwrap().1.is_closing());
+        assert_eq!(parse_html_tag("<br/>").unwrap().1.name(), "br");
+        assert!(parse_html_tag("< span>").is_err());
+        assert!(parse_html_tag("<3 hearts").is_err());
+    }
+
+    #[test]
+    fn test_split_html() {
+        assert_eq!(
+            split_html("a <b>bold</b> 1 < 2 \\<i>"),
+            vec![
+                ContentNode::Text("a "),
+                ContentNode::Html(HtmlTag::new("<b>", "b", vec![], false)),
+                ContentNode::Text("bold"),
+                ContentNode::Html(HtmlTag::new("</b>", "b", vec![], true)),
+                ContentNode::Text(" 1 < 2 \\<i>"),
+            ]
+        );
+        assert_eq!(
+            split_html("C:\\\\<b>x</b> \\\\\\<i>"),
+            vec![
+                ContentNode::Text("C:\\\\"),
+                ContentNode::Html(HtmlTag::new("<b>", "b", vec![], false)),
+                ContentNode::Text("x"),
+                ContentNode::Html(HtmlTag::new("</b>", "b", vec![], true)),
+                ContentNode::Text(" \\\\\\<i>"),
+            ]
+        );
+    }
+}
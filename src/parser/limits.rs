@@ -0,0 +1,125 @@
+use crate::{
+    encoding::strip_bom, error::ParsingError, parser::story::starts_with_header, Limit,
+    ParseOptions, Position,
+};
+
+/// Offsets of the passage headers, lines starting with `::`.
+fn block_starts(input: &str) -> impl Iterator<Item = usize> + '_ {
+    input
+        .starts_with("::")
+        .then_some(0)
+        .into_iter()
+        .chain(input.match_indices("\n::").map(|(index, _)| index + 1))
+}
+
+fn nesting(block: &str) -> (usize, usize) {
+    let (mut depth, mut max, mut max_offset) = (0usize, 0, 0);
+    let mut chars = block.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '[' | '{' => {
+                depth += 1;
+                if depth > max {
+                    (max, max_offset) = (depth, index);
+                }
+            }
+            ']' | '}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    (max, max_offset)
+}
+
+/// Checks the limits of the options in a single pass, before any parsing happens.
+///
+/// Positions are relative to `input` without its byte order mark, as for parsing.
+pub(crate) fn check_limits<T>(input: &str, options: &ParseOptions) -> Result<(), ParsingError<T>> {
+    if options.max_passages.is_none()
+        && options.max_passage_size.is_none()
+        && options.max_nesting.is_none()
+    {
+        return Ok(());
+    }
+    let input = strip_bom(input);
+    let exceeded = |limit, offset| {
+        Err(ParsingError::LimitExceeded {
+            limit,
+            position: Position::from_offset(input, offset),
+        })
+    };
+
+    let starts: Vec<_> = block_starts(input).collect();
+    let mut passages = 0;
+    for (index, &start) in starts.iter().enumerate() {
+        let end = starts.get(index + 1).copied().unwrap_or(input.len());
+        let block = &input[start..end];
+
+        if !starts_with_header(block, "StoryTitle") && !starts_with_header(block, "StoryData") {
+            passages += 1;
+            match options.max_passages {
+                Some(max) if passages > max => return exceeded(Limit::Passages(max), start),
+                _ => {}
+            }
+        }
+        match options.max_passage_size {
+            Some(max) if block.len() > max => return exceeded(Limit::PassageSize(max), start),
+            _ => {}
+        }
+        if let Some(max) = options.max_nesting {
+            let (depth, offset) = nesting(block);
+            if depth > max {
+                return exceeded(Limit::Nesting(max), start + offset);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::ParsingError, Limit, ParseOptions, Story};
+
+    fn limit_exceeded(input: &str, options: ParseOptions) -> Option<(Limit, usize)> {
+        match Story::parse_with(input, options) {
+            Err(ParsingError::LimitExceeded { limit, position }) => Some((limit, position.line)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        let input = ":: StoryTitle\nT\n\n:: A\n[[B]]\n\n:: B\n{[[{x}]]}\n";
+
+        assert!(Story::parse_with(input, ParseOptions::default().max_passages(2)).is_ok());
+        assert_eq!(
+            limit_exceeded(input, ParseOptions::default().max_passages(1)),
+            Some((Limit::Passages(1), 7))
+        );
+        assert_eq!(
+            limit_exceeded(input, ParseOptions::default().max_passage_size(12)),
+            Some((Limit::PassageSize(12), 1))
+        );
+        assert_eq!(
+            limit_exceeded(input, ParseOptions::default().max_nesting(3)),
+            Some((Limit::Nesting(3), 8))
+        );
+        assert!(Story::parse_with(input, ParseOptions::default().max_nesting(4)).is_ok());
+    }
+
+    #[test]
+    fn test_limits_after_bom_and_loose_headers() {
+        let input = "\u{feff}::StoryTitle\nT\n\n::  StoryData\n{}\n\n:: A\n[[B]]\n";
+        assert!(Story::parse_with(input, ParseOptions::default().max_passages(1)).is_ok());
+
+        let input = "\u{feff}:: A\n{[x]}\n";
+        match Story::parse_with(input, ParseOptions::default().max_nesting(1)) {
+            Err(ParsingError::LimitExceeded { position, .. }) => {
+                assert_eq!((position.offset, position.line, position.column), (6, 2, 2));
+            }
+            _ => panic!("the nesting limit is exceeded"),
+        }
+    }
+}
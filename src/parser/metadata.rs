@@ -1,15 +1,16 @@
 use nom::IResult;
 
-use crate::{utils::take_delimited_greedy, Metadata};
+use crate::{utils::take_json_object, Metadata};
 
 pub(crate) fn parse_metadata(input: &str) -> IResult<&str, Metadata<&str>> {
-    let (input, content) = take_delimited_greedy('{', '}')(input)?;
+    let (input, content) = take_json_object(input)?;
     Ok((input, Metadata::new(content)))
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{parse_metadata, take_delimited_greedy, Metadata};
+    use super::{parse_metadata, Metadata};
+    use crate::utils::take_delimited_greedy;
 
     #[test]
     fn test_take_greedy_simple_metadata() {
@@ -1,9 +1,24 @@
-use crate::{error::ParsingError, Story};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use self::story::parse_story;
+use crate::{
+    encoding::strip_bom, error::ParsingError, CancellationToken, ParseOptions, Position, Story,
+    Strictness,
+};
 
+use self::{
+    limits::check_limits,
+    story::{parse_story, parse_story_with},
+};
+
+pub(crate) mod html;
+mod limits;
 pub(crate) mod metadata;
+pub(crate) mod paragraph;
 pub(crate) mod passage;
+pub(crate) mod raw;
 pub(crate) mod story;
 
 impl<'a> TryFrom<&'a str> for Story<&'a str> {
@@ -17,27 +32,277 @@ impl<'a> TryFrom<&'a str> for Story<&'a str> {
     }
 }
 
+impl<'a> Story<&'a str> {
+    pub fn parse_with(
+        input: &'a str,
+        options: ParseOptions,
+    ) -> Result<Self, ParsingError<&'a str>> {
+        check_limits(input, &options)?;
+        match parse_story_with(input, &options) {
+            Ok((rest, story))
+                if !rest.is_empty()
+                    && options
+                        .cancellation
+                        .as_ref()
+                        .is_some_and(CancellationToken::is_cancelled) =>
+            {
+                let offset = story.content.len() - rest.len();
+                Err(ParsingError::Cancelled(Position::from_offset(
+                    story.content,
+                    offset,
+                )))
+            }
+            Ok((_, story)) if options.strictness == Strictness::Strict => {
+                match story.warnings.iter().find_map(ParsingError::from_warning) {
+                    Some(error) => Err(error),
+                    None => Ok(story),
+                }
+            }
+            Ok((_, story)) => Ok(story),
+            Result::Err(error) => Result::Err(error.into()),
+        }
+    }
+
+    /// Parses `input` with the default options, giving up after `budget`: the passages parsed
+    /// by then are kept, with a [`Warning::DeadlineExceeded`](crate::Warning::DeadlineExceeded)
+    /// where parsing stopped.
+    pub fn try_from_with_deadline(
+        input: &'a str,
+        budget: Duration,
+    ) -> Result<Self, ParsingError<&'a str>> {
+        Self::parse_with(
+            input,
+            ParseOptions::default().deadline(Instant::now() + budget),
+        )
+    }
+}
+
 impl TryFrom<String> for Story<String> {
     type Error = ParsingError<String>;
 
     fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::parse_owned(value, ParseOptions::default())
+    }
+}
+
+impl Story<String> {
+    /// Parses `value` with `options`, as [`Story::parse_with`] does, moving it into the story.
+    pub(crate) fn parse_owned(
+        mut value: String,
+        options: ParseOptions,
+    ) -> Result<Self, ParsingError<String>> {
+        // Detach the story from the borrowed input before moving the input in.
+        let story = Story::parse_with(&value, options)
+            .map(|story| story.with_content(String::new()))
+            .map_err(ParsingError::into_owned)?;
+        // Offsets in the story are relative to the input without its byte order mark.
+        let bom = value.len() - strip_bom(&value).len();
+        value.drain(..bom);
+        Ok(Story {
+            content: value,
+            ..story
+        })
+    }
+}
+
+/// Parses a story sharing its content, so that clones of the story, for other threads for
+/// instance, don't copy the text.
+impl TryFrom<Arc<str>> for Story<Arc<str>> {
+    type Error = ParsingError<String>;
+
+    fn try_from(value: Arc<str>) -> Result<Self, Self::Error> {
         match parse_story(&value) {
             Ok((_, story)) => {
-                let Story {
-                    content: _,
-                    title,
-                    start,
-                    passages,
-                } = story;
-
-                Ok(Story {
-                    content: value,
-                    title,
-                    start,
-                    passages,
-                })
+                // Offsets in the story are relative to the input without its byte order mark.
+                let content = match strip_bom(&value).len() == value.len() {
+                    true => value.clone(),
+                    false => Arc::from(strip_bom(&value)),
+                };
+                Ok(story.with_content(content))
             }
             Result::Err(error) => Result::Err(error.into()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    use proptest::{prelude::any, proptest};
+
+    use crate::{
+        encoding::normalize_line_endings, error::ParsingError, parse_events, scan, ParseOptions,
+        Story, Strictness, Warning,
+    };
+
+    /// Parses `input` with every entry point, which must all return, whatever the input.
+    fn parse_all_ways(input: &str) {
+        if let Ok(story) = Story::try_from(input) {
+            story.to_twee();
+            story.stats();
+            story.validate();
+        }
+        let _ = Story::try_from(input.to_string());
+        let options = ParseOptions::default()
+            .verbatim(true)
+            .html(true)
+            .strip_comments(true);
+        let _ = Story::parse_with(input, options.clone());
+        let _ = Story::parse_with(
+            input,
+            options.strictness(Strictness::Strict).unescape(false),
+        );
+        scan::passages(input).for_each(drop);
+        let _ = parse_events(input, drop);
+    }
+
+    proptest! {
+        #[test]
+        fn test_parse_never_panics(input in any::<String>()) {
+            parse_all_ways(&input);
+        }
+
+        #[test]
+        fn test_parse_twee_like_never_panics(
+            input in r"(:: |::|\t|\[|\]|\{|\}|\\|/\*|\*/|<|>|->|\||\n|\r|é|img|\[\[|\]\]|a| |\x{feff})*"
+        ) {
+            parse_all_ways(&input);
+        }
+    }
+
+    /// `input` with the line endings of each line chosen by `crlf`, from its line number.
+    fn with_line_endings(input: &str, crlf: impl Fn(usize) -> bool) -> String {
+        input
+            .split('\n')
+            .enumerate()
+            .map(|(line, text)| match crlf(line) {
+                true => format!("{text}\r"),
+                false => text.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn test_parse_line_endings() {
+        let sample = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/sample/sample.twee"));
+        let input = format!(":: StoryTitle\nTitle\n\n{sample}\n:: Last\nEnd");
+        let expected = Story::try_from(input.as_str()).unwrap();
+        let endings: [fn(usize) -> bool; 3] =
+            [|_| true, |line| line % 2 == 0, |line| line % 3 == 1];
+
+        for crlf in endings {
+            let input = with_line_endings(&input, crlf);
+            let story = Story::try_from(input.as_str()).unwrap();
+
+            assert_eq!(story.title(), expected.title());
+            // Line endings are kept in content, but nothing else differs.
+            assert_eq!(normalize_line_endings(&story.to_twee()), expected.to_twee());
+        }
+    }
+
+    #[test]
+    fn test_parse_with_strict_invalid_metadata() {
+        let input = ":: A {\"position\":\"1,2\",}\nText\n";
+
+        assert!(Story::parse_with(input, ParseOptions::default()).is_ok());
+        match Story::parse_with(
+            input,
+            ParseOptions::default().strictness(Strictness::Strict),
+        ) {
+            Err(ParsingError::InvalidMetadata(passage)) => assert_eq!(passage, "A"),
+            other => panic!("Expected an invalid metadata error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_try_from_with_deadline() {
+        let input = ":: A\na\n\n:: B\nb\n";
+
+        let story = Story::try_from_with_deadline(input, Duration::from_secs(60)).unwrap();
+        assert_eq!(story.iter().count(), 2);
+        assert!(story.warnings().is_empty());
+
+        let story = Story::try_from_with_deadline(input, Duration::ZERO).unwrap();
+        assert_eq!(story.iter().count(), 1);
+        assert!(matches!(
+            story.warnings(),
+            [Warning::DeadlineExceeded(position)] if position.line == 4
+        ));
+
+        let options = ParseOptions::default()
+            .deadline(Instant::now())
+            .strictness(Strictness::Strict);
+        assert!(matches!(
+            Story::parse_with(input, options),
+            Err(ParsingError::DeadlineExceeded(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_with_strict_preamble() {
+        let options = ParseOptions::default().strictness(Strictness::Strict);
+
+        match Story::parse_with("Notes\n:: A\nText\n", options) {
+            Err(error @ ParsingError::Preamble(_)) => assert_eq!(
+                error.to_string(),
+                "Text at 1:1 comes before the first passage header"
+            ),
+            other => panic!("Expected a preamble error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_with_strict_duplicate_passage() {
+        let input = ":: A\nFirst\n\n:: A\nSecond\n";
+        let options = ParseOptions::default().strictness(Strictness::Strict);
+
+        match Story::parse_with(input, options) {
+            Err(ParsingError::DuplicatePassage {
+                name,
+                first,
+                second,
+            }) => {
+                assert_eq!(name, "A");
+                assert_eq!((first.line, second.line), (1, 4));
+            }
+            other => panic!("Expected a duplicate passage error, got {other:?}"),
+        }
+    }
+    #[test]
+    fn test_shared_story() {
+        let input: Arc<str> =
+            Arc::from("\u{feff}:: StoryTitle\nShared\n\n:: A\nGo [[B]]\n\n:: B\nEnd\n");
+        let story = Story::try_from(input).unwrap();
+
+        let handles: Vec<_> = ["A", "B"]
+            .into_iter()
+            .map(|name| {
+                let story = story.clone();
+                std::thread::spawn(move || story.get_passage(name).unwrap().to_string())
+            })
+            .collect();
+        let texts: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(texts, ["Go B", "End"]);
+        assert_eq!(story.title(), Some("Shared"));
+
+        let shared = Story::try_from(":: A\nHi\n").unwrap().into_shared();
+        assert_eq!(shared.get_passage("A").unwrap().to_string(), "Hi");
+    }
+
+    #[test]
+    fn test_owned_story_with_bom() {
+        let input = "\u{feff}:: StoryTitle\nTitle\n\n:: A\nSome text here\n";
+        let story = Story::try_from(input.to_string()).unwrap();
+
+        assert_eq!(story.title(), Some("Title"));
+        assert_eq!(
+            story.get_passage("A").unwrap().to_string(),
+            "Some text here"
+        );
+    }
+}
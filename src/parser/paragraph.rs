@@ -0,0 +1,124 @@
+use crate::{ContentNode, Inline};
+
+/// Paragraphs being read, see [`split_paragraphs`].
+#[derive(Default)]
+struct Paragraphs<'a> {
+    done: Vec<ContentNode<&'a str>>,
+    current: Vec<Inline<&'a str>>,
+    /// Whitespace read after a blank line, which belongs to the next paragraph if one starts on
+    /// its line.
+    pending: Vec<Inline<&'a str>>,
+    has_content: bool,
+    line_has_content: bool,
+    /// Whether a blank line followed the content of the current paragraph.
+    broken: bool,
+}
+
+impl<'a> Paragraphs<'a> {
+    fn content(&mut self, inline: Inline<&'a str>) {
+        if self.broken {
+            let paragraph = std::mem::take(&mut self.current);
+            self.done.push(ContentNode::Paragraph(paragraph));
+            self.broken = false;
+        }
+        self.current.append(&mut self.pending);
+        self.current.push(inline);
+        self.has_content = true;
+        self.line_has_content = true;
+    }
+
+    fn whitespace(&mut self, text: &'a str) {
+        match self.broken {
+            true => self.pending.push(Inline::Text(text)),
+            false => self.current.push(Inline::Text(text)),
+        }
+    }
+
+    fn line_break(&mut self) {
+        self.current.append(&mut self.pending);
+        self.current.push(Inline::LineBreak);
+        if self.has_content && !self.line_has_content {
+            self.broken = true;
+        }
+        self.line_has_content = false;
+    }
+
+    fn finish(mut self) -> Vec<ContentNode<&'a str>> {
+        self.current.append(&mut self.pending);
+        if !self.current.is_empty() {
+            self.done.push(ContentNode::Paragraph(self.current));
+        }
+        self.done
+    }
+}
+
+/// Groups `content` into paragraphs split at blank lines. The line breaks of text nodes become
+/// [`Inline::LineBreak`], without their `\r`.
+pub fn split_paragraphs(content: Vec<ContentNode<&str>>) -> Vec<ContentNode<&str>> {
+    let mut paragraphs = Paragraphs::default();
+    for node in content {
+        let ContentNode::Text(text) = node else {
+            paragraphs.content(Inline::Node(node));
+            continue;
+        };
+        let mut lines = text.split('\n').peekable();
+        while let Some(line) = lines.next() {
+            let is_last = lines.peek().is_none();
+            let line = match is_last {
+                true => line,
+                false => line.strip_suffix('\r').unwrap_or(line),
+            };
+            if line.trim().is_empty() {
+                if !line.is_empty() {
+                    paragraphs.whitespace(line);
+                }
+            } else {
+                paragraphs.content(Inline::Text(line));
+            }
+            if !is_last {
+                paragraphs.line_break();
+            }
+        }
+    }
+    paragraphs.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_paragraphs;
+    use crate::{ContentNode, Inline};
+
+    #[test]
+    fn test_split_paragraphs() {
+        let content = vec![
+            ContentNode::Text("\nOne\r\ntwo "),
+            ContentNode::link_node("Go", "Go"),
+            ContentNode::Text("\n \n\n  "),
+            ContentNode::link_node("Back", "Back"),
+            ContentNode::Text(" three\n"),
+        ];
+
+        assert_eq!(
+            split_paragraphs(content),
+            vec![
+                ContentNode::Paragraph(vec![
+                    Inline::LineBreak,
+                    Inline::Text("One"),
+                    Inline::LineBreak,
+                    Inline::Text("two "),
+                    Inline::Node(ContentNode::link_node("Go", "Go")),
+                    Inline::LineBreak,
+                    Inline::Text(" "),
+                    Inline::LineBreak,
+                    Inline::LineBreak,
+                ]),
+                ContentNode::Paragraph(vec![
+                    Inline::Text("  "),
+                    Inline::Node(ContentNode::link_node("Back", "Back")),
+                    Inline::Text(" three"),
+                    Inline::LineBreak,
+                ]),
+            ]
+        );
+    }
+}
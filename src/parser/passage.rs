@@ -1,67 +1,247 @@
 use nom::{
     branch::alt,
-    bytes::complete::tag,
-    character::complete::{anychar, char, line_ending, multispace0, none_of, space0},
-    combinator::{map, opt, recognize, value},
-    multi::{many1_count, separated_list0, separated_list1},
-    sequence::{delimited, pair, preceded},
-    IResult,
+    bytes::complete::{tag, take_until},
+    character::complete::{char, multispace0, none_of, one_of, space0, space1},
+    combinator::{map, opt, recognize},
+    error::{Error, ErrorKind, ParseError},
+    multi::{many1_count, separated_list0},
+    sequence::{delimited, pair, preceded, tuple},
+    Err, IResult,
 };
 
 use crate::{
     parser::metadata::parse_metadata,
-    utils::{split_escaped, until_link1},
-    ContentNode, Passage, Tag,
+    utils::{
+        ensure_progress, find_comment, find_header_line, line_end, split_escaped,
+        take_delimited_greedy, until_link1,
+    },
+    ContentNode, Image, ImageAlign, LinkDialects, Metadata, Passage, Tag,
 };
 
+/// Reads a backslash and the character it escapes. Line endings can't be escaped: a header
+/// always ends with its line.
 fn parse_escaped_char(input: &str) -> IResult<&str, char> {
-    preceded(char('\\'), anychar)(input)
+    preceded(char('\\'), none_of("\n\r"))(input)
 }
 
 fn parse_tag(input: &str) -> IResult<&str, Tag<&str>> {
-    let parse_tag = recognize(many1_count(alt((parse_escaped_char, none_of(" ]")))));
+    let parse_tag = recognize(many1_count(alt((parse_escaped_char, none_of(" \t\n\r]")))));
     map(parse_tag, Tag::new)(input)
 }
 
+/// Reads the tags of a header, separated by any number of spaces or tabs.
 pub fn parse_tags(input: &str) -> IResult<&str, Vec<Tag<&str>>> {
-    let each_tags = separated_list0(tag(" "), parse_tag);
+    let each_tags = separated_list0(space1, parse_tag);
 
-    let mut parse_tags = delimited(tag("["), each_tags, tag("]"));
+    let mut parse_tags = delimited(pair(tag("["), space0), each_tags, pair(space0, tag("]")));
     parse_tags(input)
 }
 
+/// Reads the title of a header, which may hold several spaces in a row. Whitespace after the
+/// `::` and before the tags, metadata or line ending isn't part of it, unless escaped.
 fn parse_title(input: &str) -> IResult<&str, &str> {
-    let parse_word = recognize(many1_count(alt((parse_escaped_char, none_of(" \n\r[{")))));
-
-    let title_block = recognize(separated_list1(tag(" "), value((), parse_word)));
-
-    preceded(tag(":: "), title_block)(input)
+    let title_char = alt((parse_escaped_char, none_of("\n\r[{")));
+    let (rest, title) =
+        preceded(pair(tag("::"), space0), recognize(many1_count(title_char)))(input)?;
+
+    let trimmed = title.trim_end_matches([' ', '\t']);
+    let backslashes = trimmed.len() - trimmed.trim_end_matches('\\').len();
+    let end = match backslashes % 2 {
+        // The whitespace right after an odd number of backslashes is escaped.
+        1 if trimmed.len() < title.len() => trimmed.len() + 1,
+        _ => trimmed.len(),
+    };
+    if end == 0 {
+        return Err(Err::Error(Error::from_error_kind(input, ErrorKind::Space)));
+    }
+    let rest = &input[input.len() - rest.len() - (title.len() - end)..];
+    Ok((rest, &title[..end]))
 }
 
 fn find_content_block(input: &str) -> IResult<&str, &str> {
-    match input.find("\r\n::") {
+    match find_header_line(input) {
         Some(index) => Ok((&input[index..], &input[..index])),
-        None => match input.find("\n::") {
-            Some(index) => Ok((&input[index..], &input[..index])),
-            None => Ok(("", input)),
+        None => Ok(("", input)),
+    }
+}
+
+/// Finds the start of the first image that can be parsed, ignoring escaped openings.
+fn find_image(input: &str) -> Option<usize> {
+    let mut index = 0;
+    while let Some(position) = input[index..].find("img[") {
+        let start = index + position;
+        index = start + 1;
+        let Some(opening) = input[..start]
+            .strip_suffix(['<', '>'])
+            .unwrap_or(&input[..start])
+            .strip_suffix('[')
+        else {
+            continue;
+        };
+        let backslashes = opening.len() - opening.trim_end_matches('\\').len();
+        if backslashes % 2 == 0 && parse_image_node(&input[opening.len()..]).is_ok() {
+            return Some(opening.len());
+        }
+    }
+    None
+}
+
+/// The next comment and image of some content, found once and kept until a node passes them,
+/// rather than searched for again from each text node.
+struct NextNodes<'a> {
+    content: &'a str,
+    comment: Option<usize>,
+    image: Option<usize>,
+}
+
+impl<'a> NextNodes<'a> {
+    fn new(content: &'a str) -> Self {
+        Self {
+            content,
+            comment: find_comment(content),
+            image: find_image(content),
+        }
+    }
+
+    /// Returns the start of the next comment or image in `input`, the rest of the content.
+    fn find(&mut self, input: &str) -> Option<usize> {
+        let offset = self.content.len() - input.len();
+        let next = |found: &mut Option<usize>, find: fn(&str) -> Option<usize>| {
+            if found.is_some_and(|start| start < offset) {
+                *found = find(input).map(|start| offset + start);
+            }
+            found.map(|start| start - offset)
+        };
+        let comment = next(&mut self.comment, find_comment);
+        let image = next(&mut self.image, find_image);
+        comment.into_iter().chain(image).min()
+    }
+}
+
+fn parse_text_node<'a>(
+    input: &'a str,
+    next: &mut NextNodes,
+) -> IResult<&'a str, ContentNode<&'a str>> {
+    let (_, text) = until_link1(input)?;
+    let end = next
+        .find(input)
+        .filter(|&start| start < text.len())
+        .unwrap_or(text.len());
+    if end == 0 {
+        return Err(Err::Error(Error::from_error_kind(
+            input,
+            ErrorKind::TakeUntil,
+        )));
+    }
+    Ok((&input[end..], ContentNode::text_node(&input[..end])))
+}
+
+fn parse_comment_node(input: &str) -> IResult<&str, ContentNode<&str>> {
+    let comment =
+        |opening, closing| recognize(tuple((tag(opening), take_until(closing), tag(closing))));
+    let (input, comment) = alt((
+        comment("/*", "*/"),
+        comment("/%", "%/"),
+        comment("<!--", "-->"),
+    ))(input)?;
+    Ok((input, ContentNode::comment_node(comment)))
+}
+
+fn parse_image_node(input: &str) -> IResult<&str, ContentNode<&str>> {
+    let part = || recognize(many1_count(alt((parse_escaped_char, none_of("\n\r]")))));
+    let (input, (_, align, _, source, _, target, setter, _)) = tuple((
+        char('['),
+        opt(one_of("<>")),
+        tag("img["),
+        part(),
+        char(']'),
+        opt(delimited(char('['), part(), char(']'))),
+        opt(delimited(char('['), part(), char(']'))),
+        char(']'),
+    ))(input)?;
+
+    let mut image = match split_escaped(source, "|") {
+        Some((title, source)) => Image {
+            title: Some(title),
+            ..Image::new(source)
         },
+        None => Image::new(source),
+    };
+    image.target = target;
+    image.setter = setter;
+    image.align = align.map(|align| match align {
+        '<' => ImageAlign::Left,
+        _ => ImageAlign::Right,
+    });
+    Ok((input, ContentNode::Image(image)))
+}
+
+/// Reads a link with balanced brackets, so that targets and setters may contain `[...]`.
+fn parse_balanced_link(input: &str) -> IResult<&str, &str> {
+    let (rest, link) = take_delimited_greedy('[', ']')(input)?;
+    match link
+        .strip_prefix("[[")
+        .and_then(|link| link.strip_suffix("]]"))
+    {
+        Some(content) if !content.is_empty() && !content.contains(['\n', '\r']) => {
+            Ok((rest, content))
+        }
+        _ => Err(Err::Error(Error::from_error_kind(input, ErrorKind::Verify))),
     }
 }
 
-fn parse_text_node(input: &str) -> IResult<&str, ContentNode<&str>> {
-    let (input, text) = until_link1(input)?;
-    Ok((input, ContentNode::text_node(text)))
+/// Splits the setter out of `Text|Target][$x to 1`, at the first `][` outside of brackets.
+fn split_setter(content: &str) -> (&str, Option<&str>) {
+    let mut depth = 0usize;
+    let mut chars = content.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '[' => depth += 1,
+            ']' if depth == 0 && content[index..].starts_with("][") => {
+                return (&content[..index], Some(&content[index + 2..]));
+            }
+            ']' if depth == 0 => {}
+            ']' => depth -= 1,
+            _ => {}
+        }
+    }
+    (content, None)
 }
 
-fn parse_link_node<'a>(input: &'a str) -> IResult<&str, ContentNode<&str>> {
+fn parse_link_node<'a>(
+    input: &'a str,
+    dialects: LinkDialects,
+) -> IResult<&'a str, ContentNode<&'a str>> {
     let parse_link_content = recognize(many1_count(alt((parse_escaped_char, none_of("\n\r]")))));
 
-    let (input, link_content) = delimited(tag("[["), parse_link_content, tag("]]"))(input)?;
-
-    let piped = |link_content| split_escaped(link_content, "|");
-    let to_right = |link_content| split_escaped(link_content, "->");
-    let to_left =
-        |link_content| split_escaped(link_content, "<-").map(|(target, text)| (text, target));
+    let (input, link_content) = alt((
+        parse_balanced_link,
+        delimited(tag("[["), parse_link_content, tag("]]")),
+    ))(input)?;
+    let (link_content, setter) = split_setter(link_content);
+
+    let piped = |link_content| {
+        dialects
+            .pipe
+            .then(|| split_escaped(link_content, "|"))
+            .flatten()
+    };
+    let to_right = |link_content| {
+        dialects
+            .right_arrow
+            .then(|| split_escaped(link_content, "->"))
+            .flatten()
+    };
+    let to_left = |link_content| {
+        dialects
+            .left_arrow
+            .then(|| split_escaped(link_content, "<-"))
+            .flatten()
+            .map(|(target, text)| (text, target))
+    };
     let simple = |link_content: &'a str| -> (&str, &str) { (link_content, link_content) };
 
     let (text, target) = piped(link_content)
@@ -69,30 +249,75 @@ fn parse_link_node<'a>(input: &'a str) -> IResult<&str, ContentNode<&str>> {
         .or_else(|| to_left(link_content))
         .unwrap_or_else(|| simple(link_content));
 
-    Ok((input, ContentNode::link_node(text, target)))
+    Ok((
+        input,
+        ContentNode::Link {
+            text,
+            target,
+            setter,
+        },
+    ))
 }
 
-fn parse_node(input: &str) -> IResult<&str, ContentNode<&str>> {
-    alt((parse_text_node, parse_link_node))(input)
+fn parse_node<'a>(
+    input: &'a str,
+    dialects: LinkDialects,
+    next: &mut NextNodes,
+) -> IResult<&'a str, ContentNode<&'a str>> {
+    alt((
+        parse_comment_node,
+        |input| parse_text_node(input, next),
+        parse_image_node,
+        |input| parse_link_node(input, dialects),
+    ))(input)
 }
 
-pub fn parse_passage(input: &str) -> IResult<&str, Passage<&str>> {
+type Header<'a> = (
+    &'a str,
+    Option<Vec<Tag<&'a str>>>,
+    Option<Metadata<&'a str>>,
+);
+
+/// Parses the header line of a passage: its title, tags and metadata.
+pub(crate) fn parse_header(input: &str) -> IResult<&str, Header<'_>> {
     let (input, title) = parse_title(input)?;
     let (input, _) = space0(input)?;
     let (input, tags) = opt(parse_tags)(input)?;
     let (input, _) = space0(input)?;
     let (input, metadata) = opt(parse_metadata)(input)?;
-    let (input, _) = recognize(pair(space0, line_ending))(input)?;
+    let (input, _) = recognize(pair(space0, line_end))(input)?;
+    Ok((input, (title, tags, metadata)))
+}
+
+/// Parses the content of a passage, following its header, into nodes along with the source of
+/// each node.
+pub(crate) fn parse_content(
+    input: &str,
+    dialects: LinkDialects,
+) -> IResult<&str, Vec<(&str, ContentNode<&str>)>> {
     let (input, content) = find_content_block(input)?;
     let (input, _) = multispace0(input)?;
 
     let mut nodes = vec![];
-    let mut content = content.trim_end_matches(&['\r', '\n']);
+    let mut content = content.trim_end_matches(['\r', '\n']);
+    let mut next = NextNodes::new(content);
     while !content.is_empty() {
-        let (c, node) = parse_node(content)?;
-        nodes.push(node);
+        let (c, node) = parse_node(content, dialects, &mut next)?;
+        ensure_progress(content, c)?;
+        nodes.push((&content[..content.len() - c.len()], node));
         content = c;
     }
+    Ok((input, nodes))
+}
+
+pub fn parse_passage(input: &str) -> IResult<&str, Passage<&str>> {
+    parse_passage_with(input, LinkDialects::default())
+}
+
+pub fn parse_passage_with(input: &str, dialects: LinkDialects) -> IResult<&str, Passage<&str>> {
+    let (input, (title, tags, metadata)) = parse_header(input)?;
+    let (input, nodes) = parse_content(input, dialects)?;
+    let nodes = nodes.into_iter().map(|(_, node)| node).collect();
 
     Ok((
         input,
@@ -109,10 +334,17 @@ mod tests {
 
     use crate::{
         parser::passage::{find_content_block, parse_passage, parse_tags, parse_title},
-        Metadata, Passage, Tag,
+        LinkDialects, Metadata, Passage, Tag,
+    };
+
+    use super::{
+        parse_comment_node, parse_image_node, parse_link_node, parse_node, ContentNode, NextNodes,
     };
+    use crate::{Image, ImageAlign};
 
-    use super::{parse_link_node, parse_text_node, ContentNode};
+    fn parse_text_node(input: &str) -> nom::IResult<&str, ContentNode<&str>> {
+        super::parse_text_node(input, &mut NextNodes::new(input))
+    }
 
     #[test]
     fn test_tags() {
@@ -187,6 +419,24 @@ mod tests {
         assert_eq!(parse_title(input), Ok((r#" [tag]"#, r"\ Second")));
     }
 
+    #[test]
+    fn test_title_whitespace() {
+        assert_eq!(
+            parse_title("::Two  spaces\t[t]"),
+            Ok(("\t[t]", "Two  spaces"))
+        );
+        assert_eq!(
+            parse_title(r":: Trailing\  [t]"),
+            Ok((" [t]", r"Trailing\ "))
+        );
+        assert!(parse_title("::  \n").is_err());
+        assert_eq!(parse_title(":: \\\n"), Ok(("\n", "\\")));
+        assert_eq!(
+            parse_tags("[ t  u ]"),
+            Ok(("", vec![Tag::new("t"), Tag::new("u")]))
+        );
+    }
+
     #[test]
     fn test_passage() {
         let input = ":: Hello, this is a title [tag1 tag2]\n";
@@ -267,12 +517,96 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_text_node_until_comment() {
+        let input = "Hello /* comment */";
+
+        assert_eq!(
+            parse_text_node(input),
+            Ok(("/* comment */", ContentNode::text_node("Hello ")))
+        );
+    }
+
+    #[test]
+    fn test_parse_text_node_unclosed_comment() {
+        let input = "Hello /* comment";
+
+        assert_eq!(
+            parse_text_node(input),
+            Ok(("", ContentNode::text_node(input)))
+        );
+    }
+
+    #[test]
+    fn test_parse_comment_node() {
+        assert_eq!(
+            parse_comment_node("/* a */ b"),
+            Ok((" b", ContentNode::comment_node("/* a */")))
+        );
+        assert_eq!(
+            parse_comment_node("/% a %/"),
+            Ok(("", ContentNode::comment_node("/% a %/")))
+        );
+        assert_eq!(
+            parse_comment_node("<!-- a -->"),
+            Ok(("", ContentNode::comment_node("<!-- a -->")))
+        );
+    }
+
+    #[test]
+    fn test_parse_node_link_inside_comment() {
+        let input = "/* [[link]] */";
+
+        assert_eq!(
+            parse_node(input, LinkDialects::default(), &mut NextNodes::new(input)),
+            Ok(("", ContentNode::comment_node(input)))
+        );
+    }
+
+    #[test]
+    fn test_parse_image_node() {
+        assert_eq!(
+            parse_image_node("[img[cat.png]]"),
+            Ok(("", ContentNode::Image(Image::new("cat.png"))))
+        );
+
+        let (_, node) = parse_image_node("[<img[A cat|cat.png][Garden][$pet to 1]]").unwrap();
+        let ContentNode::Image(image) = node else {
+            panic!("Expected an image, got {node:?}");
+        };
+        assert_eq!(image.source(), &"cat.png");
+        assert_eq!(image.title(), Some(&"A cat"));
+        assert_eq!(image.target(), Some(&"Garden"));
+        assert_eq!(image.setter(), Some(&"$pet to 1"));
+        assert_eq!(image.align(), Some(ImageAlign::Left));
+    }
+
+    #[test]
+    fn test_parse_text_node_until_image() {
+        assert_eq!(
+            parse_text_node("See [img[cat.png]]"),
+            Ok(("[img[cat.png]]", ContentNode::text_node("See ")))
+        );
+        assert_eq!(
+            parse_text_node("Not [img[an image"),
+            Ok(("", ContentNode::text_node("Not [img[an image")))
+        );
+        assert_eq!(
+            parse_text_node("\\[img[cat.png]]"),
+            Ok(("", ContentNode::text_node("\\[img[cat.png]]")))
+        );
+        assert_eq!(
+            parse_text_node("a \\\\[img[cat.png]]"),
+            Ok(("[img[cat.png]]", ContentNode::text_node("a \\\\")))
+        );
+    }
+
     #[test]
     fn test_parse_link_node_simple() {
         let input = "[[link]]";
 
         assert_eq!(
-            parse_link_node(input),
+            parse_link_node(input, LinkDialects::default()),
             Ok(("", ContentNode::link_node("link", "link")))
         )
     }
@@ -282,7 +616,7 @@ mod tests {
         let input = "[[first|First]]";
 
         assert_eq!(
-            parse_link_node(input),
+            parse_link_node(input, LinkDialects::default()),
             Ok(("", ContentNode::link_node("first", "First")))
         )
     }
@@ -292,7 +626,7 @@ mod tests {
         let input = "[[some text->First page]]";
 
         assert_eq!(
-            parse_link_node(input),
+            parse_link_node(input, LinkDialects::default()),
             Ok(("", ContentNode::link_node("some text", "First page")))
         )
     }
@@ -302,11 +636,59 @@ mod tests {
         let input = "[[A page<-going somewhere?]]";
 
         assert_eq!(
-            parse_link_node(input),
+            parse_link_node(input, LinkDialects::default()),
             Ok(("", ContentNode::link_node("going somewhere?", "A page")))
         )
     }
 
+    #[test]
+    fn test_parse_link_node_disabled_dialects() {
+        let dialects = LinkDialects::default().right_arrow(false).left_arrow(false);
+
+        assert_eq!(
+            parse_link_node("[[Go->Room|A->B]]", dialects),
+            Ok(("", ContentNode::link_node("Go->Room", "A->B")))
+        );
+        assert_eq!(
+            parse_link_node("[[A <- B]]", dialects),
+            Ok(("", ContentNode::link_node("A <- B", "A <- B")))
+        );
+    }
+
+    #[test]
+    fn test_parse_link_node_nested_brackets() {
+        assert_eq!(
+            parse_link_node("[[Go->Room[1]]] after", LinkDialects::default()),
+            Ok((" after", ContentNode::link_node("Go", "Room[1]")))
+        );
+        assert_eq!(
+            parse_link_node(r"[[Go->Room\]]]", LinkDialects::default()),
+            Ok(("", ContentNode::link_node("Go", r"Room\]")))
+        );
+        assert_eq!(
+            parse_link_node("[[a[b]]", LinkDialects::default()),
+            Ok(("", ContentNode::link_node("a[b", "a[b")))
+        );
+    }
+
+    #[test]
+    fn test_parse_link_node_setter() {
+        assert_eq!(
+            parse_link_node(
+                "[[Take it|Room][$items[0] to \"key\"]]",
+                LinkDialects::default()
+            ),
+            Ok((
+                "",
+                ContentNode::Link {
+                    text: "Take it",
+                    target: "Room",
+                    setter: Some("$items[0] to \"key\""),
+                }
+            ))
+        );
+    }
+
     #[test]
     fn test_find_content_block_weird_char() {
         let input = "C'est ça\n:: Okay";
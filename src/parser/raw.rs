@@ -0,0 +1,104 @@
+use crate::{prose::markup_len, ContentNode, Dialect, StoryFormat};
+
+/// Closings missing from the rest of a text, so later openings don't search for them again.
+#[derive(Default)]
+struct Unclosed {
+    sugarcube: bool,
+    mustache: bool,
+}
+
+/// Finds the end of the construct starting `input` at `closing`, unless it is known to be missing.
+fn closed_len(input: &str, closing: &str, missing: &mut bool) -> Option<usize> {
+    if *missing {
+        return None;
+    }
+    let end = input.find(closing);
+    *missing = end.is_none();
+    end.map(|end| end + closing.len())
+}
+
+/// The dialect and length of the construct starting `input`, if any.
+fn raw_len(input: &str, unclosed: &mut Unclosed) -> Option<(Dialect, usize)> {
+    if input.starts_with("<<") {
+        closed_len(input, ">>", &mut unclosed.sugarcube).map(|len| (Dialect::SugarCube, len))
+    } else if input.starts_with("{{") {
+        closed_len(input, "}}", &mut unclosed.mustache).map(|len| (Dialect::Mustache, len))
+    } else {
+        markup_len(input, StoryFormat::Harlowe)
+            .filter(|_| input.starts_with('('))
+            .map(|len| (Dialect::Harlowe, len))
+    }
+}
+
+/// Splits the `<<...>>`, `(name: ...)` and `{{...}}` constructs out of a text node. Escaped
+/// delimiters are left in the text.
+pub fn split_raw(text: &str) -> Vec<ContentNode<&str>> {
+    let mut nodes = vec![];
+    let mut start = 0;
+    let mut index = 0;
+    let mut unclosed = Unclosed::default();
+    while let Some(position) = text[index..].find(['<', '(', '{']) {
+        index += position;
+        let before = &text[..index];
+        if (before.len() - before.trim_end_matches('\\').len()) % 2 == 1 {
+            index += 1;
+            continue;
+        }
+        match raw_len(&text[index..], &mut unclosed) {
+            Some((dialect, len)) => {
+                if start < index {
+                    nodes.push(ContentNode::text_node(&text[start..index]));
+                }
+                let raw = &text[index..index + len];
+                nodes.push(ContentNode::Raw { dialect, raw });
+                index += len;
+                start = index;
+            }
+            None => index += 1,
+        }
+    }
+    if start < text.len() {
+        nodes.push(ContentNode::text_node(&text[start..]));
+    }
+    nodes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_raw;
+    use crate::{ContentNode, Dialect};
+
+    #[test]
+    fn test_split_raw() {
+        assert_eq!(
+            split_raw("a <<set $x to 1>>(if: $x)[b] {{name}} (c) \\<<e>> << d"),
+            vec![
+                ContentNode::Text("a "),
+                ContentNode::Raw {
+                    dialect: Dialect::SugarCube,
+                    raw: "<<set $x to 1>>"
+                },
+                ContentNode::Raw {
+                    dialect: Dialect::Harlowe,
+                    raw: "(if: $x)"
+                },
+                ContentNode::Text("[b] "),
+                ContentNode::Raw {
+                    dialect: Dialect::Mustache,
+                    raw: "{{name}}"
+                },
+                ContentNode::Text(" (c) \\<<e>> << d"),
+            ]
+        );
+        assert_eq!(
+            split_raw("\\\\<<a>>"),
+            vec![
+                ContentNode::Text("\\\\"),
+                ContentNode::Raw {
+                    dialect: Dialect::SugarCube,
+                    raw: "<<a>>"
+                },
+            ]
+        );
+    }
+}
@@ -1,46 +1,65 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Instant};
 
 use nom::{
     branch::alt,
-    bytes::streaming::tag,
-    character::{
-        complete::{line_ending, multispace0},
-        streaming::not_line_ending,
-    },
+    bytes::complete::tag,
+    character::complete::{multispace0, space0},
     combinator::map,
     error::{Error, ErrorKind, ParseError},
+    sequence::tuple,
     Err, IResult,
 };
 use serde_json::Value;
 
 use crate::{
-    parser::passage::parse_passage, utils::take_delimited_greedy, ContentNode, Metadata, Passage,
-    Story, Tag, TextBlock,
+    diagnostics::PositionTracker,
+    encoding::strip_bom,
+    parser::{
+        html::split_html, paragraph::split_paragraphs, passage::parse_passage_with, raw::split_raw,
+    },
+    passage_map::PassageMap,
+    story_data::STORY_DATA_FIELDS,
+    utils::{ensure_progress, escape_string_content, line_end, rest_of_line, take_json_object},
+    CancellationToken, ContentNode, Inline, Metadata, ParseOptions, Passage, Position, Progress,
+    SourceBlock, Story, StoryFormat, Strictness, Tag, TextBlock, Warning,
 };
 
-enum StoryBlock<'a> {
+pub(crate) enum StoryBlock<'a> {
     Title(&'a str),
-    StoryData(StoryData),
+    StoryData(StoryData<'a>),
     Passage(Passage<&'a str>),
 }
 
 #[derive(Debug, PartialEq, Eq)]
-struct StoryData {
-    start: Option<String>,
+pub(crate) struct StoryData<'a> {
+    pub start: Option<String>,
+    pub content: &'a str,
+    unknown_fields: Vec<String>,
+}
+
+/// Whether `input` starts with the header of the passage `name`, followed by the end of the
+/// line, whitespace, tags or metadata.
+pub(crate) fn starts_with_header(input: &str, name: &str) -> bool {
+    input
+        .strip_prefix("::")
+        .and_then(|header| header.trim_start_matches([' ', '\t']).strip_prefix(name))
+        .is_some_and(|rest| {
+            rest.is_empty() || rest.starts_with(char::is_whitespace) || rest.starts_with(['[', '{'])
+        })
 }
 
 fn parse_story_title(input: &str) -> IResult<&str, &str> {
-    let (input, _) = nom::sequence::pair(tag(":: StoryTitle"), line_ending)(input)?;
+    let (input, _) = tuple((tag("::"), space0, tag("StoryTitle"), space0, line_end))(input)?;
 
-    let (input, title) = not_line_ending(input)?;
+    let (input, title) = rest_of_line(input)?;
     let (input, _) = multispace0(input)?;
 
     Ok((input, title))
 }
 
-fn parse_story_data(input: &str) -> IResult<&str, StoryData> {
-    let (input, _) = nom::sequence::pair(tag(":: StoryData"), line_ending)(input)?;
-    let (input, data) = take_delimited_greedy('{', '}')(input)?;
+fn parse_story_data(input: &str) -> IResult<&str, StoryData<'_>> {
+    let (input, _) = tuple((tag("::"), space0, tag("StoryData"), space0, line_end))(input)?;
+    let (input, data) = take_json_object(input)?;
     let (input, _) = multispace0(input)?;
 
     // Now look for start in data
@@ -52,12 +71,40 @@ fn parse_story_data(input: &str) -> IResult<&str, StoryData> {
         .and_then(|value| value.as_str())
         .map(|value| value.to_string());
 
-    let data = StoryData { start };
+    let unknown_fields = dictionary
+        .as_object()
+        .map(|fields| {
+            fields
+                .keys()
+                .filter(|key| !STORY_DATA_FIELDS.contains(&key.as_str()))
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let data = StoryData {
+        start,
+        content: data,
+        unknown_fields,
+    };
 
     Ok((input, data))
 }
 
-fn parse_story_block(input: &str) -> IResult<&str, StoryBlock> {
+pub(crate) fn parse_story_block<'a>(
+    input: &'a str,
+    options: &ParseOptions,
+) -> IResult<&'a str, StoryBlock<'a>> {
+    let parse_passage = |input| parse_passage_with(input, options.link_dialects);
+    if !options.special_passages {
+        return map(parse_passage, StoryBlock::Passage)(input);
+    }
+    if options.strictness == Strictness::Strict && starts_with_header(input, "StoryData") {
+        return map(parse_story_data, StoryBlock::StoryData)(input).map_err(|error| match error {
+            Err::Error(error) => Err::Failure(error),
+            error => error,
+        });
+    }
     alt((
         map(parse_story_title, StoryBlock::Title),
         map(parse_story_data, StoryBlock::StoryData),
@@ -65,63 +112,348 @@ fn parse_story_block(input: &str) -> IResult<&str, StoryBlock> {
     ))(input)
 }
 
+/// Splits the text before the first passage header, if any, from the rest of `input`.
+pub(crate) fn split_preamble(input: &str) -> (&str, &str) {
+    let end = match input.starts_with("::") {
+        true => 0,
+        false => input.find("\n::").map_or(input.len(), |index| index + 1),
+    };
+    input.split_at(end)
+}
+
 pub fn parse_story(input: &str) -> IResult<&str, Story<&str>> {
+    parse_story_with(input, &ParseOptions::default())
+}
+
+pub fn parse_story_with<'a>(
+    input: &'a str,
+    options: &ParseOptions,
+) -> IResult<&'a str, Story<&'a str>> {
+    // A byte order mark would hide the first passage header.
+    let stripped = strip_bom(input);
+    let bom = &input[..input.len() - stripped.len()];
+    let input = stripped;
     let original = input;
     let mut title = None;
     let mut start = None;
-    let mut passages = HashMap::new();
+    let mut data = None;
+    let mut passages = PassageMap::new();
+    let mut source_blocks = vec![];
+    let mut positions = HashMap::new();
+    let mut tracker = PositionTracker::new(original);
+    let mut warnings = vec![];
+    // Verbatim stories write the mark back.
+    if !bom.is_empty() {
+        source_blocks.push(SourceBlock::Preamble(TextBlock::Owned(bom.to_string())));
+    }
 
-    let mut input = input;
+    // Text before the first header is skipped, with a warning unless it's only blank lines.
+    let (preamble, mut input) = split_preamble(input);
+    if let Some(offset) = preamble.find(|c: char| !c.is_whitespace()) {
+        warnings.push(Warning::Preamble(Position::from_offset(original, offset)));
+    }
+    if !preamble.is_empty() {
+        source_blocks.push(SourceBlock::Preamble(TextBlock::verbatim(
+            original, preamble,
+        )));
+    }
     while !input.is_empty() {
-        let (i, block) = parse_story_block(input)?;
+        let (i, block) = parse_story_block(input, options)?;
+        ensure_progress(input, i)?;
+        let source = &input[..input.len() - i.len()];
         match block {
-            StoryBlock::Title(extracted_title) => title = Some(extracted_title),
-            StoryBlock::StoryData(extracted_start) => start = extracted_start.start,
-            StoryBlock::Passage(passage) => {
-                passages.insert(passage.title().to_string(), passage);
+            StoryBlock::Title(extracted_title) => {
+                title = Some(extracted_title);
+                source_blocks.push(SourceBlock::Title(TextBlock::verbatim(original, source)));
+            }
+            StoryBlock::StoryData(extracted_data) => {
+                start = extracted_data.start;
+                data = Some(extracted_data.content);
+                warnings.extend(
+                    extracted_data
+                        .unknown_fields
+                        .into_iter()
+                        .map(Warning::UnknownStoryDataField),
+                );
+                source_blocks.push(SourceBlock::Data(TextBlock::verbatim(original, source)));
+            }
+            StoryBlock::Passage(mut passage) => {
+                if options.verbatim {
+                    passage.source = Some(source);
+                }
+                if options.strip_comments {
+                    passage
+                        .content
+                        .retain(|node| !matches!(node, ContentNode::Comment(_)));
+                }
+                // Passages are looked up by their unescaped name.
+                let name = match options.unescape {
+                    true => escape_string_content(passage.title)
+                        .unwrap_or_else(|| passage.title.to_string()),
+                    false => passage.title.to_string(),
+                };
+                let position = tracker.position(original.len() - input.len());
+                if let Some(first) = positions.insert(name.clone(), position) {
+                    warnings.push(Warning::DuplicatePassage {
+                        name: name.clone(),
+                        first,
+                        second: position,
+                    });
+                    // The replaced passage keeps its own source.
+                    let replaced = passages
+                        .get(&name)
+                        .and_then(|passage: &Passage<&str>| passage.source);
+                    let block = source_blocks.iter_mut().rfind(
+                        |block| matches!(block, SourceBlock::Passage(other) if *other == name),
+                    );
+                    if let (Some(block), Some(source)) = (block, replaced) {
+                        *block = SourceBlock::Replaced(TextBlock::verbatim(original, source));
+                    }
+                }
+                check_passage(&name, &passage, &mut warnings);
+                source_blocks.push(SourceBlock::Passage(name.clone()));
+                passages.insert(name, passage);
             }
         }
         input = i;
+        if let Some(progress) = &options.progress {
+            progress.report(Progress {
+                bytes: original.len() - input.len(),
+                total: original.len(),
+                passages: passages.len(),
+            });
+        }
+        // The rest of the input is left unparsed, see `Story::parse_with`.
+        if options
+            .cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+        {
+            break;
+        }
+        if !input.is_empty()
+            && options
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            let position = tracker.position(original.len() - input.len());
+            warnings.push(Warning::DeadlineExceeded(position));
+            break;
+        }
+    }
+    let text_block = |substring| {
+        if options.unescape {
+            TextBlock::borrowed(original, substring)
+        } else {
+            TextBlock::verbatim(original, substring)
+        }
+    };
+    // Done once StoryData, which may come last, names the format.
+    let format = options.story_format.unwrap_or_else(|| {
+        data.and_then(|data| serde_json::from_str::<Value>(data).ok())
+            .and_then(|data| data.get("format")?.as_str().map(StoryFormat::from_name))
+            .unwrap_or_default()
+    });
+
+    let title = title.map(text_block);
+    let start = start.map(TextBlock::Owned);
+    let data = data.map(|data| TextBlock::verbatim(original, data));
+    let splits_text = !options.node_kinds.is_empty()
+        || (options.raw_sigils && format == StoryFormat::Plain)
+        || options.html;
+    let passages = passages.map(|mut passage| {
+        if splits_text {
+            passage.content = std::mem::take(&mut passage.content)
+                .into_iter()
+                .flat_map(|node| match node {
+                    ContentNode::Text(text) => split_text_node(text, options, format)
+                        .into_iter()
+                        .map(|(_, node)| node)
+                        .collect(),
+                    node => vec![node],
+                })
+                .collect();
+        }
+        if options.paragraphs {
+            passage.content = split_paragraphs(std::mem::take(&mut passage.content));
+        }
+        passage_as_str_to_blocks(original, passage, text_block)
+    });
+
+    let mut story = Story::new(original, title, start, data, passages);
+    if options.verbatim {
+        story.source_blocks = Some(source_blocks);
     }
-    let title = title.map(|title| TextBlock::borrowed(original, title));
-    let start = start.map(TextBlock::owned);
-    let passages: HashMap<_, _> = passages
+    story.warnings = warnings;
+    story.format = options.story_format;
+    story.link_dialects = options.link_dialects;
+    story.bom = bom.len();
+    // Re-parsing doesn't report progress, nor stops.
+    story.options = ParseOptions {
+        progress: None,
+        cancellation: None,
+        deadline: None,
+        ..options.clone()
+    };
+    if options.dedup_text {
+        story.dedup_text();
+    }
+
+    Ok((input, story))
+}
+
+/// Replaces the text nodes of `passage` with the nodes `split` reads out of them.
+/// Splits the custom nodes, then the raw macros and then the HTML tags out of the text node
+/// `text`, as enabled by `options` for a story in `format`, each node with its source.
+pub(crate) fn split_text_node<'a>(
+    text: &'a str,
+    options: &ParseOptions,
+    format: StoryFormat,
+) -> Vec<(&'a str, ContentNode<&'a str>)> {
+    let split = |nodes: Vec<ContentNode<&'a str>>, split_one: &dyn Fn(&'a str) -> Vec<_>| {
+        nodes
+            .into_iter()
+            .flat_map(|node| match node {
+                ContentNode::Text(text) => split_one(text),
+                node => vec![node],
+            })
+            .collect()
+    };
+    let mut nodes = match options.node_kinds.is_empty() {
+        true => vec![ContentNode::Text(text)],
+        false => options.node_kinds.split(text),
+    };
+    // Before HTML, which would read `<if $a>` out of `<<if $a>>`.
+    if options.raw_sigils && format == StoryFormat::Plain {
+        nodes = split(nodes, &split_raw);
+    }
+    if options.html {
+        nodes = split(nodes, &split_html);
+    }
+    nodes
         .into_iter()
-        .map(|(key, passage)| (key, passage_as_str_to_blocks(original, passage)))
-        .collect();
+        .map(|node| {
+            let source = match &node {
+                ContentNode::Text(text) => *text,
+                ContentNode::Custom(custom) => custom.raw,
+                ContentNode::Raw { raw, .. } => *raw,
+                ContentNode::Html(tag) => tag.raw,
+                _ => text,
+            };
+            (source, node)
+        })
+        .collect()
+}
 
-    Ok((input, Story::new(original, title, start, passages)))
+/// Checks `passage`, reported under `name`, its name as passages are looked up by.
+fn check_passage(name: &str, passage: &Passage<&str>, warnings: &mut Vec<Warning>) {
+    let name = || name.to_string();
+
+    let is_empty = passage.content.iter().all(|node| match node {
+        ContentNode::Text(text) => text.trim().is_empty(),
+        _ => false,
+    });
+    if is_empty {
+        warnings.push(Warning::EmptyPassage(name()));
+    }
+    for tag in &passage.tags {
+        if tag.value.starts_with('-') {
+            warnings.push(Warning::TagWithLeadingDash {
+                passage: name(),
+                tag: tag.value.to_string(),
+            });
+        }
+    }
+    if passage.links().any(|link| *link.target == passage.title) {
+        warnings.push(Warning::LinkToSelf(name()));
+    }
+    if let Some(metadata) = &passage.metadata {
+        if serde_json::from_str::<Value>(metadata.content).is_err() {
+            warnings.push(Warning::InvalidMetadata(name()));
+        }
+    }
 }
 
-fn passage_as_str_to_blocks(original: &str, passage: Passage<&str>) -> Passage<TextBlock> {
-    let title = TextBlock::borrowed(original, passage.title);
+fn passage_as_str_to_blocks<'a>(
+    original: &str,
+    passage: Passage<&'a str>,
+    text_block: impl Fn(&'a str) -> TextBlock,
+) -> Passage<TextBlock> {
+    let title = text_block(passage.title);
     let tags: Vec<_> = passage
         .tags
         .iter()
-        .map(|tag| Tag::new(TextBlock::borrowed(original, tag.value)))
+        .map(|tag| Tag::new(text_block(tag.value)))
         .collect();
     let metadata = passage
         .metadata
-        .map(|metadata| Metadata::new(TextBlock::borrowed(original, metadata.content)));
+        .map(|metadata| Metadata::new(TextBlock::verbatim(original, metadata.content)));
     let content: Vec<_> = passage
         .content
         .iter()
-        .map(|node| match node {
-            ContentNode::Text(text) => ContentNode::Text(TextBlock::borrowed(original, text)),
-            ContentNode::Link { text, target } => ContentNode::Link {
-                text: TextBlock::borrowed(original, text),
-                target: TextBlock::borrowed(original, target),
-            },
-        })
+        .map(|node| node_as_str_to_block(original, node, &text_block))
         .collect();
 
-    Passage::new(title, tags, metadata, content)
+    let mut blocks = Passage::new(title, tags, metadata, content);
+    blocks.source = passage
+        .source
+        .map(|source| TextBlock::verbatim(original, source));
+    blocks
+}
+
+fn node_as_str_to_block<'a>(
+    original: &str,
+    node: &ContentNode<&'a str>,
+    text_block: &impl Fn(&'a str) -> TextBlock,
+) -> ContentNode<TextBlock> {
+    match node {
+        ContentNode::Text(text) => ContentNode::Text(text_block(text)),
+        ContentNode::Link {
+            text,
+            target,
+            setter,
+        } => ContentNode::Link {
+            text: text_block(text),
+            target: text_block(target),
+            setter: setter.map(|setter| TextBlock::verbatim(original, setter)),
+        },
+        ContentNode::Comment(comment) => {
+            ContentNode::Comment(TextBlock::verbatim(original, comment))
+        }
+        ContentNode::Html(tag) => {
+            ContentNode::Html(tag.clone().map(|s| TextBlock::verbatim(original, s)))
+        }
+        ContentNode::Image(image) => ContentNode::Image(image.clone().map(text_block)),
+        ContentNode::Custom(node) => {
+            ContentNode::Custom(node.clone().map(|s| TextBlock::verbatim(original, s)))
+        }
+        ContentNode::Raw { dialect, raw } => ContentNode::Raw {
+            dialect: *dialect,
+            raw: TextBlock::verbatim(original, raw),
+        },
+        ContentNode::Paragraph(inlines) => ContentNode::Paragraph(
+            inlines
+                .iter()
+                .map(|inline| match inline {
+                    Inline::Text(text) => Inline::Text(text_block(text)),
+                    Inline::LineBreak => Inline::LineBreak,
+                    Inline::Node(node) => {
+                        Inline::Node(node_as_str_to_block(original, node, text_block))
+                    }
+                })
+                .collect(),
+        ),
+    }
 }
 
 #[cfg(test)]
 mod tests {
 
-    use super::{parse_story, parse_story_data, parse_story_title, StoryData};
+    use super::{
+        parse_story, parse_story_data, parse_story_title, parse_story_with, starts_with_header,
+        StoryData,
+    };
+    use crate::{ContentNode, Dialect, ParseOptions, Position, Strictness, Warning};
 
     const TITLE_AND_DATA: &str = include_str!(concat!(
         env!("CARGO_MANIFEST_DIR"),
@@ -134,14 +466,14 @@ mod tests {
     fn test_parse_story_title() {
         let input = ":: StoryTitle\nTest Story\n\n";
 
-        assert_eq!(parse_story_title(input), Ok(("", "Test Story".into())))
+        assert_eq!(parse_story_title(input), Ok(("", "Test Story")))
     }
 
     #[test]
     fn test_parse_story_title_until_next_dots() {
         let input = ":: StoryTitle\nTest Story\n\n::";
 
-        assert_eq!(parse_story_title(input), Ok(("::", "Test Story".into())))
+        assert_eq!(parse_story_title(input), Ok(("::", "Test Story")))
     }
 
     #[test]
@@ -154,7 +486,9 @@ mod tests {
             Ok((
                 "::",
                 StoryData {
-                    start: Some("Start story".into())
+                    start: Some("Start story".into()),
+                    content: "{    \"ifid\": \"77599634\",\n    \"start\": \"Start story\"\n}",
+                    unknown_fields: vec![],
                 }
             ))
         )
@@ -185,4 +519,268 @@ mod tests {
         let start = story.get_passage("Start").unwrap();
         assert_eq!(&"Start", start.title());
     }
+
+    #[test]
+    fn test_parse_story_verbatim() {
+        let input = SAMPLE;
+
+        let (_, story) = parse_story_with(input, &ParseOptions::default().verbatim(true)).unwrap();
+
+        let third = story.get_passage("Third").unwrap();
+        assert_eq!(
+            third.verbatim(),
+            Some(
+                &":: Third {\"position\":\"1000,400\",\"size\":\"100,100\"}\nThis is a dead end\n"
+            )
+        );
+        let first = story.get_passage("First").unwrap();
+        assert!(first
+            .verbatim()
+            .unwrap()
+            .ends_with("[[back->Start]]\n\n\n\n"));
+    }
+
+    #[test]
+    fn test_parse_story_verbatim_bom() {
+        let input = "\u{feff}Notes\n:: A\nx\n";
+
+        let (_, story) = parse_story_with(input, &ParseOptions::default().verbatim(true)).unwrap();
+
+        assert_eq!(story.to_twee(), input);
+        assert!(story.contains_passage("A"));
+    }
+
+    #[test]
+    fn test_parse_story_strip_comments() {
+        let input = ":: Start\nHello /* note */world\n";
+        let options = ParseOptions::default().strip_comments(true);
+
+        let (_, story) = parse_story_with(input, &options).unwrap();
+
+        assert_eq!(
+            story.get_passage("Start").unwrap().nodes(),
+            &[ContentNode::Text("Hello "), ContentNode::Text("world")]
+        );
+    }
+
+    #[test]
+    fn test_parse_story_html() {
+        let input = ":: Start\nHello <span class=\"red\">world</span>\n";
+
+        let (_, story) = parse_story(input).unwrap();
+        assert_eq!(story.get_passage("Start").unwrap().nodes().len(), 1);
+
+        let options = ParseOptions::default().html(true);
+        let (_, story) = parse_story_with(input, &options).unwrap();
+        let passage = story.get_passage("Start").unwrap();
+
+        match &passage.nodes()[1] {
+            ContentNode::Html(tag) => assert_eq!(tag.attribute("class"), Some("red")),
+            node => panic!("Expected an HTML node, got {node:?}"),
+        }
+        assert_eq!(passage.nodes().len(), 4);
+        assert_eq!(story.to_twee(), format!("{input}\n"));
+    }
+
+    #[test]
+    fn test_parse_story_raw_sigils() {
+        let input = ":: Start\n<<if $a>><i>(print: $b)</i>{{c}}<</if>> x\n";
+        let options = ParseOptions::default().html(true).raw_sigils(true);
+        let (_, story) = parse_story_with(input, &options).unwrap();
+        let passage = story.get_passage("Start").unwrap();
+
+        let dialects: Vec<_> = passage
+            .nodes()
+            .iter()
+            .filter_map(|node| match node {
+                ContentNode::Raw { dialect, .. } => Some(*dialect),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            dialects,
+            [
+                Dialect::SugarCube,
+                Dialect::Harlowe,
+                Dialect::Mustache,
+                Dialect::SugarCube
+            ]
+        );
+        assert!(matches!(passage.nodes()[1], ContentNode::Html(_)));
+        assert_eq!(story.to_twee(), format!("{input}\n"));
+
+        // Known formats keep their macros as text.
+        let data = ":: StoryData\n{\"format\":\"SugarCube\"}\n\n";
+        let input = format!("{input}\n{data}");
+        let (_, story) = parse_story_with(&input, &options).unwrap();
+        let passage = story.get_passage("Start").unwrap();
+        let is_raw = |node: &ContentNode<&str>| matches!(node, ContentNode::Raw { .. });
+        assert!(!passage.nodes().iter().any(is_raw));
+    }
+
+    #[test]
+    fn test_parse_story_paragraphs() {
+        let input = ":: Start\nHello [[North]],\n  friend.\n \n\n<i>Bye</i> [[South]]\n";
+        let options = ParseOptions::default().html(true).paragraphs(true);
+        let (_, story) = parse_story_with(input, &options).unwrap();
+        let passage = story.get_passage("Start").unwrap();
+
+        assert_eq!(passage.nodes().len(), 2);
+        assert!(passage
+            .nodes()
+            .iter()
+            .all(|node| matches!(node, ContentNode::Paragraph(_))));
+        let targets: Vec<_> = passage.links().map(|link| link.target).collect();
+        assert_eq!(targets, [&"North", &"South"]);
+        assert_eq!(story.to_twee(), format!("{input}\n"));
+    }
+
+    #[test]
+    fn test_parse_story_escaped_names() {
+        let input = ":: StoryData\n{\"start\":\"a]{b\"}\n\n:: a\\]\\{b\n[[a\\]{b]]\n";
+
+        let (_, story) = parse_story(input).unwrap();
+
+        assert_eq!(story.start().map(|start| *start.title()), Some("a]{b"));
+        assert_eq!(story.backlinks("a]{b").collect::<Vec<_>>(), ["a]{b"]);
+    }
+
+    #[test]
+    fn test_parse_story_not_verbatim() {
+        let (_, story) = parse_story(SAMPLE).unwrap();
+
+        assert_eq!(story.get_passage("Third").unwrap().verbatim(), None);
+    }
+
+    #[test]
+    fn test_parse_story_preamble() {
+        let input = "\nNotes for later\n:: A\nText\n";
+        let (_, story) = parse_story(input).unwrap();
+
+        assert!(story.get_passage("A").is_some());
+        assert_eq!(
+            story.warnings(),
+            [Warning::Preamble(Position {
+                offset: 1,
+                line: 2,
+                column: 1
+            })]
+        );
+
+        let options = ParseOptions::default().verbatim(true);
+        let (_, story) = parse_story_with(input, &options).unwrap();
+        assert_eq!(story.to_twee(), input);
+
+        let (_, story) = parse_story("\n\n:: A\nText\n").unwrap();
+        assert!(story.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_parse_story_lenient_invalid_story_data() {
+        let input = ":: StoryData\n{ not json }\n";
+
+        let (_, story) = parse_story(input).unwrap();
+
+        assert!(story.get_passage("StoryData").is_some());
+    }
+
+    #[test]
+    fn test_parse_story_strict_invalid_story_data() {
+        let input = ":: StoryData\n{ not json }\n";
+        let options = ParseOptions::default().strictness(Strictness::Strict);
+
+        assert!(parse_story_with(input, &options).is_err());
+    }
+
+    #[test]
+    fn test_parse_story_strict_similar_names() {
+        let input = ":: StoryDataX\n{ not json }\n\n:: StoryTitle2\nText\n";
+        let options = ParseOptions::default().strictness(Strictness::Strict);
+
+        let (_, story) = parse_story_with(input, &options).unwrap();
+        assert!(story.get_passage("StoryDataX").is_some());
+        assert!(story.get_passage("StoryTitle2").is_some());
+        assert!(starts_with_header("::StoryData {}", "StoryData"));
+        assert!(starts_with_header(":: StoryTitle\n", "StoryTitle"));
+        assert!(!starts_with_header(":: StoryTitle2\n", "StoryTitle"));
+    }
+
+    #[test]
+    fn test_parse_story_without_special_passages() {
+        let options = ParseOptions::default().special_passages(false);
+
+        let (_, story) = parse_story_with(TITLE_AND_DATA, &options).unwrap();
+
+        assert_eq!(story.title(), None);
+        assert_eq!(
+            story.get_passage("StoryTitle").unwrap().nodes(),
+            &[ContentNode::Text("Test Story")]
+        );
+    }
+
+    #[test]
+    fn test_parse_story_without_unescape() {
+        let options = ParseOptions::default().unescape(false);
+
+        let (_, story) = parse_story_with(SAMPLE, &options).unwrap();
+
+        let tags: Vec<_> = story
+            .get_passage("Start")
+            .unwrap()
+            .tags()
+            .iter()
+            .map(|tag| tag.to_string())
+            .collect();
+        assert_eq!(tags, vec![r"osef\]"]);
+    }
+
+    #[test]
+    fn test_parse_story_duplicate_passage_warning() {
+        let input = ":: A\nFirst\n\n:: B\nOther\n\n:: A\nSecond\n";
+
+        let (_, story) = parse_story(input).unwrap();
+
+        assert_eq!(
+            story.warnings(),
+            &[Warning::DuplicatePassage {
+                name: "A".to_string(),
+                first: Position::from_offset(input, 0),
+                second: Position::from_offset(input, 24),
+            }]
+        );
+        assert_eq!(
+            story.get_passage("A").unwrap().nodes(),
+            &[ContentNode::Text("Second")]
+        );
+    }
+
+    #[test]
+    fn test_parse_story_warnings() {
+        let input = ":: StoryData\n{\"start\":\"A\",\"tool\":\"x\"}\n\n:: A [-dash] {bad}\nGo [[A]]\n\n:: B\n\n";
+
+        let (_, mut story) = parse_story(input).unwrap();
+
+        assert_eq!(
+            story.take_warnings(),
+            vec![
+                Warning::UnknownStoryDataField("tool".to_string()),
+                Warning::TagWithLeadingDash {
+                    passage: "A".to_string(),
+                    tag: "-dash".to_string()
+                },
+                Warning::LinkToSelf("A".to_string()),
+                Warning::InvalidMetadata("A".to_string()),
+                Warning::EmptyPassage("B".to_string()),
+            ]
+        );
+        assert!(story.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_parse_story_keeps_passage_order() {
+        let (_, story) = parse_story(SAMPLE).unwrap();
+
+        let titles: Vec<_> = story.passages_in_order().map(|p| *p.title()).collect();
+        assert_eq!(titles, vec!["First", "Second", "Start", "Third"]);
+    }
 }
@@ -0,0 +1,305 @@
+use std::sync::Arc;
+
+use imbl::{HashMap, OrdMap};
+
+/// Space left between the keys of passages appended, to insert others between them.
+const GAP: u64 = 1 << 32;
+
+/// Passages by name, iterated in insertion order.
+///
+/// Inserting a passage with an existing name replaces it in place.
+///
+/// The map is persistent: clones share its structure and the passages, each part copied the
+/// first time it changes, so that keeping a clone of a story per undo step stays cheap. Looking
+/// up, inserting, removing or renaming a passage takes logarithmic time; only
+/// [`PassageMap::insert_at`] and [`PassageMap::position`] walk the passages.
+#[derive(Debug, Clone)]
+pub(crate) struct PassageMap<P> {
+    /// The key of each passage in `entries`.
+    keys: HashMap<Arc<str>, u64>,
+    /// Passages in order, by keys with gaps between them.
+    entries: OrdMap<u64, (Arc<str>, Arc<P>)>,
+}
+
+impl<P> Default for PassageMap<P> {
+    fn default() -> Self {
+        Self {
+            keys: HashMap::new(),
+            entries: OrdMap::new(),
+        }
+    }
+}
+
+/// Maps are equal with the same passages in the same order, whatever their keys.
+impl<P> PartialEq for PassageMap<P>
+where
+    P: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.entries.len() == other.entries.len()
+            && self.entries.values().eq(other.entries.values())
+    }
+}
+
+impl<P> Eq for PassageMap<P> where P: Eq {}
+
+/// The value behind `arc`, copied if shared.
+fn unwrap_or_clone<T: Clone>(arc: Arc<T>) -> T {
+    Arc::try_unwrap(arc).unwrap_or_else(|arc| (*arc).clone())
+}
+
+/// A key between `lower` and `upper`, either missing at the ends of the map.
+fn key_between(lower: Option<u64>, upper: Option<u64>) -> Option<u64> {
+    // Keys start at 1, so that there's always room for a first one.
+    let lower = lower.unwrap_or(0);
+    match upper {
+        None => lower.checked_add(GAP),
+        Some(upper) => (upper - lower > 1).then(|| lower + (upper - lower) / 2),
+    }
+}
+
+impl<P> PassageMap<P>
+where
+    P: Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A free key for a passage inserted at `position`, the map being at least as long.
+    fn key_at(&mut self, position: usize) -> u64 {
+        let neighbours = |entries: &OrdMap<u64, (Arc<str>, Arc<P>)>| {
+            if position == entries.len() {
+                return (entries.get_max().map(|(key, _)| *key), None);
+            }
+            let mut keys = entries.keys().copied();
+            let lower = position.checked_sub(1).and_then(|index| keys.nth(index));
+            (lower, keys.next())
+        };
+        let (lower, upper) = neighbours(&self.entries);
+        if let Some(key) = key_between(lower, upper) {
+            return key;
+        }
+        // No room left between the neighbours, spread the keys again.
+        self.entries = std::mem::take(&mut self.entries)
+            .into_iter()
+            .zip(1..)
+            .map(|((_, entry), index)| (index * GAP, entry))
+            .collect();
+        self.keys = self
+            .entries
+            .iter()
+            .map(|(key, (name, _))| (name.clone(), *key))
+            .collect();
+        let (lower, upper) = neighbours(&self.entries);
+        key_between(lower, upper).expect("keys are spread out")
+    }
+
+    pub fn insert(&mut self, name: String, passage: P) -> Option<P> {
+        if let Some(key) = self.keys.get(name.as_str()) {
+            let (_, previous) = self.entries.get_mut(key)?;
+            return Some(unwrap_or_clone(std::mem::replace(
+                previous,
+                Arc::new(passage),
+            )));
+        }
+        let key = self.key_at(self.len());
+        let name: Arc<str> = name.into();
+        self.keys.insert(name.clone(), key);
+        self.entries.insert(key, (name, Arc::new(passage)));
+        None
+    }
+
+    /// Removes the passage named `name`, keeping the order of the others.
+    pub fn remove(&mut self, name: &str) -> Option<P> {
+        let key = self.keys.remove(name)?;
+        let (_, passage) = self.entries.remove(&key)?;
+        Some(unwrap_or_clone(passage))
+    }
+
+    /// Inserts a passage at `position`, or last if the map is shorter, unless its name is
+    /// taken.
+    pub fn insert_at(&mut self, position: usize, name: String, passage: P) -> bool {
+        if self.keys.contains_key(name.as_str()) {
+            return false;
+        }
+        let key = self.key_at(position.min(self.len()));
+        let name: Arc<str> = name.into();
+        self.keys.insert(name.clone(), key);
+        self.entries.insert(key, (name, Arc::new(passage)));
+        true
+    }
+
+    /// Gives the passage named `from` the name `to`, keeping its position, unless `to` is
+    /// taken.
+    pub fn rename(&mut self, from: &str, to: String) -> bool {
+        if self.keys.contains_key(to.as_str()) {
+            return false;
+        }
+        let Some(key) = self.keys.remove(from) else {
+            return false;
+        };
+        let to: Arc<str> = to.into();
+        if let Some((name, _)) = self.entries.get_mut(&key) {
+            *name = to.clone();
+        }
+        self.keys.insert(to, key);
+        true
+    }
+
+    pub fn position(&self, name: &str) -> Option<usize> {
+        let key = self.keys.get(name)?;
+        Some(self.entries.range(..*key).count())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&P> {
+        let (_, passage) = self.entries.get(self.keys.get(name)?)?;
+        Some(passage)
+    }
+
+    /// The passage named `name`, copied first if shared with a clone.
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut P> {
+        let (_, passage) = self.entries.get_mut(self.keys.get(name)?)?;
+        Some(Arc::make_mut(passage))
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.keys.contains_key(name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &P)> {
+        self.entries
+            .values()
+            .map(|(name, passage)| (&**name, &**passage))
+    }
+
+    /// Runs `f` on every passage, each copied first if shared with a clone. Prefer
+    /// [`PassageMap::get_mut`] on the passages to change.
+    pub fn for_each_mut(&mut self, mut f: impl FnMut(&mut P)) {
+        let keys: Vec<u64> = self.entries.keys().copied().collect();
+        for key in keys {
+            if let Some((_, passage)) = self.entries.get_mut(&key) {
+                f(Arc::make_mut(passage));
+            }
+        }
+    }
+
+    pub fn into_entries(self) -> impl Iterator<Item = (String, P)> {
+        self.entries
+            .into_iter()
+            .map(|(_, (name, passage))| (name.to_string(), unwrap_or_clone(passage)))
+    }
+
+    pub fn map<Q>(self, mut f: impl FnMut(P) -> Q) -> PassageMap<Q> {
+        PassageMap {
+            entries: self
+                .entries
+                .into_iter()
+                .map(|(key, (name, passage))| (key, (name, Arc::new(f(unwrap_or_clone(passage))))))
+                .collect(),
+            keys: self.keys,
+        }
+    }
+
+    /// Whether the passage named `name` is shared with `other`, a clone of this map.
+    #[cfg(test)]
+    pub fn shares(&self, other: &Self, name: &str) -> bool {
+        let (Some(a), Some(b)) = (self.keys.get(name), other.keys.get(name)) else {
+            return false;
+        };
+        match (self.entries.get(a), other.entries.get(b)) {
+            (Some((_, a)), Some((_, b))) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PassageMap;
+
+    #[test]
+    fn test_insertion_order() {
+        let mut map = PassageMap::new();
+        map.insert("b".to_string(), 1);
+        map.insert("a".to_string(), 2);
+        map.insert("c".to_string(), 3);
+
+        assert_eq!(
+            map.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+            vec!["b", "a", "c"]
+        );
+    }
+
+    #[test]
+    fn test_replace_keeps_position() {
+        let mut map = PassageMap::new();
+        map.insert("b".to_string(), 1);
+        map.insert("a".to_string(), 2);
+
+        assert_eq!(map.insert("b".to_string(), 3), Some(1));
+        assert_eq!(map.iter().collect::<Vec<_>>(), vec![("b", &3), ("a", &2)]);
+        assert_eq!(map.get("b"), Some(&3));
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut map = PassageMap::new();
+        map.insert("b".to_string(), 1);
+        map.insert("a".to_string(), 2);
+        map.insert("c".to_string(), 3);
+
+        assert_eq!(map.remove("b"), Some(1));
+        assert_eq!(map.remove("b"), None);
+        assert_eq!(map.get("c"), Some(&3));
+        assert_eq!(map.insert("c".to_string(), 4), Some(3));
+        assert_eq!(map.len(), 2);
+
+        assert!(map.insert_at(0, "d".to_string(), 5));
+        assert!(map.rename("a", "e".to_string()));
+        assert!(!map.rename("e", "c".to_string()));
+        assert_eq!(
+            map.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+            vec!["d", "e", "c"]
+        );
+        assert_eq!(map.position("c"), Some(2));
+    }
+
+    #[test]
+    fn test_clones_share_passages() {
+        let mut map = PassageMap::new();
+        map.insert("a".to_string(), vec![1]);
+        map.insert("b".to_string(), vec![2]);
+        let snapshot = map.clone();
+        map.get_mut("b").unwrap().push(3);
+
+        assert!(map.shares(&snapshot, "a"));
+        assert!(!map.shares(&snapshot, "b"));
+        assert_eq!(snapshot.get("b"), Some(&vec![2]));
+        assert_eq!(map.get("b"), Some(&vec![2, 3]));
+    }
+
+    #[test]
+    fn test_insert_at_front_and_equality() {
+        let mut map = PassageMap::new();
+        map.insert("last".to_string(), 0);
+        let snapshot = map.clone();
+        // More inserts than the gaps between keys allow without spreading them again.
+        for index in 1..=100 {
+            assert!(map.insert_at(0, index.to_string(), index));
+        }
+        assert_eq!(map.position("100"), Some(0));
+        assert_eq!(map.position("last"), Some(100));
+        assert_eq!(map.get("1"), Some(&1));
+        assert_eq!(snapshot.len(), 1);
+
+        for index in 1..=100 {
+            assert_eq!(map.remove(&index.to_string()), Some(index));
+        }
+        assert_eq!(map, snapshot);
+    }
+}
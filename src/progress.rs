@@ -0,0 +1,132 @@
+use std::{
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+/// How far parsing went, reported to [`ParseOptions::progress`](crate::ParseOptions::progress)
+/// after each block of the story.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// Bytes of the input parsed so far.
+    pub bytes: usize,
+    /// Size of the input in bytes, without its byte order mark.
+    pub total: usize,
+    pub passages: usize,
+}
+
+/// The callback of [`ParseOptions::progress`](crate::ParseOptions::progress).
+#[derive(Clone)]
+pub struct ProgressCallback(Arc<dyn Fn(Progress) + Send + Sync>);
+
+impl ProgressCallback {
+    pub fn new(f: impl Fn(Progress) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(f))
+    }
+
+    pub(crate) fn report(&self, progress: Progress) {
+        (self.0)(progress)
+    }
+}
+
+impl Debug for ProgressCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ProgressCallback")
+    }
+}
+
+/// Callbacks are compared by identity, clones of the same callback being equal.
+impl PartialEq for ProgressCallback {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for ProgressCallback {}
+
+/// Stops parsing from another thread, see
+/// [`ParseOptions::cancellation`](crate::ParseOptions::cancellation). Clones share the same
+/// state.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Tokens are compared by identity, clones of the same token being equal.
+impl PartialEq for CancellationToken {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for CancellationToken {}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::{CancellationToken, Progress};
+    use crate::{ParseOptions, ParsingError, Story};
+
+    const SAMPLE: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/sample/sample.twee"));
+
+    #[test]
+    fn test_progress() {
+        let reports = Arc::new(Mutex::new(vec![]));
+        let options = ParseOptions::default().progress({
+            let reports = reports.clone();
+            move |progress| reports.lock().unwrap().push(progress)
+        });
+        let input = ":: StoryTitle\nT\n\n:: A\na\n\n:: B\nb\n";
+        Story::parse_with(input, options).unwrap();
+
+        assert_eq!(
+            *reports.lock().unwrap(),
+            [
+                Progress {
+                    bytes: 17,
+                    total: 32,
+                    passages: 0
+                },
+                Progress {
+                    bytes: 25,
+                    total: 32,
+                    passages: 1
+                },
+                Progress {
+                    bytes: 32,
+                    total: 32,
+                    passages: 2
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cancellation() {
+        let token = CancellationToken::new();
+        let options = ParseOptions::default().cancellation(token.clone());
+        assert!(Story::parse_with(SAMPLE, options.clone()).is_ok());
+
+        // Cancelled once the first block is parsed.
+        let options = options.progress(move |_| token.cancel());
+        match Story::parse_with(SAMPLE, options) {
+            Err(ParsingError::Cancelled(position)) => assert_eq!(position.line, 5),
+            other => panic!("Expected a cancellation, got {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,337 @@
+//! The prose of a story, as read by players, for spellcheckers and style linters.
+
+use std::ops::{Deref, Range};
+
+use crate::{
+    search::{MatchField, SearchMatch},
+    ContentNode, Story, StoryFormat,
+};
+
+/// A piece of text shown to players.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextSegment<'a> {
+    pub passage: &'a str,
+    /// Index of the node in [`Passage::nodes`](crate::Passage::nodes).
+    pub node: usize,
+    /// Index of the inline in the node, when it is a paragraph, see
+    /// [`ParseOptions::paragraphs`](crate::ParseOptions::paragraphs).
+    pub inline: Option<usize>,
+    /// Either [`MatchField::Text`] or [`MatchField::LinkText`].
+    pub field: MatchField,
+    /// Byte range of the segment in the field.
+    pub range: Range<usize>,
+    pub text: &'a str,
+}
+
+/// Words and phrases that must not appear in the prose of a story, see [`Story::lint_banned`].
+#[derive(Debug, Default, Clone)]
+pub struct BannedWords {
+    words: Vec<String>,
+    #[cfg(feature = "regex")]
+    patterns: Vec<regex::Regex>,
+}
+
+impl BannedWords {
+    /// Bans `words`, matched as whole words and ignoring case.
+    pub fn new<I, S>(words: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            words: words
+                .into_iter()
+                .map(Into::into)
+                .filter(|word| !word.is_empty())
+                .collect(),
+            #[cfg(feature = "regex")]
+            patterns: vec![],
+        }
+    }
+
+    /// Also bans whatever `pattern` matches.
+    #[cfg(feature = "regex")]
+    pub fn with_regex(mut self, pattern: regex::Regex) -> Self {
+        self.patterns.push(pattern);
+        self
+    }
+
+    fn find(&self, text: &str) -> Vec<Range<usize>> {
+        let mut found: Vec<_> = self
+            .words
+            .iter()
+            .flat_map(|word| find_word(text, word))
+            .collect();
+        #[cfg(feature = "regex")]
+        found.extend(self.patterns.iter().flat_map(|pattern| {
+            pattern
+                .find_iter(text)
+                .filter(|found| !found.is_empty())
+                .map(|found| found.range())
+        }));
+        found.sort_by_key(|range| (range.start, range.end));
+        found
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '\''
+}
+
+/// Occurrences of `word` in `text` as a whole word, ignoring case.
+fn find_word(text: &str, word: &str) -> Vec<Range<usize>> {
+    let mut found = vec![];
+    let mut previous = None;
+    for (start, _) in text.char_indices() {
+        let at_boundary = !previous.is_some_and(is_word_char);
+        previous = text[start..].chars().next();
+        if !at_boundary {
+            continue;
+        }
+        let mut chars = text[start..].char_indices();
+        let matches = word.chars().all(|w| {
+            chars
+                .next()
+                .is_some_and(|(_, c)| c.to_lowercase().eq(w.to_lowercase()))
+        });
+        if !matches {
+            continue;
+        }
+        let end = chars
+            .next()
+            .map_or(text.len(), |(offset, _)| start + offset);
+        if !text[end..].starts_with(is_word_char) {
+            found.push(start..end);
+        }
+    }
+    found
+}
+
+/// Length of the markup of `format` starting `input`: an HTML tag, a SugarCube macro `<<...>>`,
+/// a Harlowe macro `(name: ...)`, a Chapbook insert `{...}`, Snowman code `<% ... %>` or a
+/// `$variable`.
+pub(crate) fn markup_len(input: &str, format: StoryFormat) -> Option<usize> {
+    let mut chars = input.chars();
+    match (chars.next()?, chars.next()) {
+        ('<', Some('<')) if format.sugarcube() => input.find(">>").map(|end| end + 2),
+        ('<', Some('%')) if matches!(format, StoryFormat::Snowman | StoryFormat::Plain) => {
+            input.find("%>").map(|end| end + 2)
+        }
+        ('<', Some(c)) if c == '/' || c.is_ascii_alphabetic() => input.find('>').map(|end| end + 1),
+        // Braces are common in prose, so they are only inserts in Chapbook stories.
+        ('{', Some(_)) if format == StoryFormat::Chapbook => input.find('}').map(|end| end + 1),
+        ('$', Some(c)) if format != StoryFormat::Chapbook && (c.is_alphabetic() || c == '_') => {
+            Some(
+                input[1..]
+                    .find(|c: char| !c.is_alphanumeric() && c != '_')
+                    .map_or(input.len(), |end| end + 1),
+            )
+        }
+        ('(', Some(c)) if format.harlowe() && c.is_alphabetic() => {
+            let name_len = input[1..].find(|c: char| !c.is_alphanumeric() && c != '-')?;
+            if !input[1 + name_len..].starts_with(':') {
+                return None;
+            }
+            let mut depth = 0;
+            for (index, c) in input.char_indices() {
+                match c {
+                    '(' => depth += 1,
+                    ')' if depth == 1 => return Some(index + 1),
+                    ')' => depth -= 1,
+                    _ => {}
+                }
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+/// Whether `text` contains a macro of `format`, as opposed to HTML tags and variables.
+pub(crate) fn contains_macro(text: &str, format: StoryFormat) -> bool {
+    text.match_indices(['<', '(', '{']).any(|(start, c)| {
+        let macro_start = match c {
+            "<" => text[start..].starts_with("<<") || text[start..].starts_with("<%"),
+            _ => true,
+        };
+        macro_start && markup_len(&text[start..], format).is_some()
+    })
+}
+
+/// Ranges of the macros of `format` in `text`, as opposed to HTML tags and variables.
+pub(crate) fn macro_ranges(text: &str, format: StoryFormat) -> Vec<Range<usize>> {
+    let mut ranges = vec![];
+    let mut index = 0;
+    while let Some(position) = text[index..].find(['<', '(', '{']) {
+        let start = index + position;
+        let macro_start = match &text[start..start + 1] {
+            "<" => text[start..].starts_with("<<") || text[start..].starts_with("<%"),
+            _ => true,
+        };
+        match markup_len(&text[start..], format).filter(|_| macro_start) {
+            Some(len) => {
+                ranges.push(start..start + len);
+                index = start + len;
+            }
+            None => index = start + 1,
+        }
+    }
+    ranges
+}
+
+/// Ranges of `text` that aren't markup of `format` and contain more than whitespace.
+pub(crate) fn prose_ranges(text: &str, format: StoryFormat) -> Vec<Range<usize>> {
+    let mut ranges = vec![];
+    let mut start = 0;
+    let mut index = 0;
+    while let Some(position) = text[index..].find(['<', '$', '(', '{']) {
+        let markup = index + position;
+        match markup_len(&text[markup..], format) {
+            Some(len) => {
+                ranges.push(start..markup);
+                start = markup + len;
+                index = start;
+            }
+            None => index = markup + 1,
+        }
+    }
+    ranges.push(start..text.len());
+    ranges.retain(|range| !text[range.clone()].trim().is_empty());
+    ranges
+}
+
+impl<T> Story<T>
+where
+    T: Deref<Target = str>,
+{
+    /// The prose of the story, in source order: text and link texts, without macros,
+    /// variables, HTML tags, comments, link targets or metadata. Macros are those of the
+    /// [story format](Story::story_format).
+    pub fn text_segments(&self) -> impl Iterator<Item = TextSegment<'_>> {
+        let format = self.story_format();
+        self.iter().flat_map(move |passage| {
+            let mut segments = vec![];
+            let nodes = passage.content.iter().enumerate();
+            for (index, inline, node) in nodes.flat_map(|(index, node)| {
+                node.flatten_indexed()
+                    .into_iter()
+                    .map(move |(inline, node)| (index, inline, node))
+            }) {
+                let (field, text) = match node {
+                    ContentNode::Text(text) => (MatchField::Text, text),
+                    ContentNode::Link { text, .. } => (MatchField::LinkText, text),
+                    _ => continue,
+                };
+                segments.extend(
+                    prose_ranges(text, format)
+                        .into_iter()
+                        .map(|range| TextSegment {
+                            passage: passage.title,
+                            node: index,
+                            inline,
+                            field,
+                            text: &text[range.clone()],
+                            range,
+                        }),
+                );
+            }
+            segments
+        })
+    }
+
+    /// Occurrences of banned words in the prose of the story, see [`Story::text_segments`].
+    pub fn lint_banned(&self, banned: &BannedWords) -> Vec<SearchMatch<'_>> {
+        self.text_segments()
+            .flat_map(|segment| {
+                banned
+                    .find(segment.text)
+                    .into_iter()
+                    .map(move |range| SearchMatch {
+                        passage: segment.passage,
+                        node: segment.node,
+                        inline: segment.inline,
+                        field: segment.field,
+                        matched: &segment.text[range.clone()],
+                        range: segment.range.start + range.start..segment.range.start + range.end,
+                    })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_word, prose_ranges, BannedWords};
+    use crate::{search::MatchField, Story, StoryFormat};
+
+    #[test]
+    fn test_prose_ranges() {
+        let text = "Hi $name, <<if $x>>you (print: (a: 1)) see<br> a (cat).<</if>>";
+        let prose = |format| -> Vec<_> {
+            prose_ranges(text, format)
+                .into_iter()
+                .map(|range| &text[range])
+                .collect()
+        };
+
+        assert_eq!(
+            prose(StoryFormat::Plain),
+            ["Hi ", ", ", "you ", " see", " a (cat)."]
+        );
+        assert_eq!(
+            prose(StoryFormat::SugarCube),
+            ["Hi ", ", ", "you (print: (a: 1)) see", " a (cat)."]
+        );
+    }
+
+    #[test]
+    fn test_text_segments() {
+        let story = Story::try_from(":: A\nGo <<set $x to 1>>[[north|B]] /* note */\n").unwrap();
+        let segments: Vec<_> = story
+            .text_segments()
+            .map(|segment| (segment.node, segment.field, segment.range, segment.text))
+            .collect();
+
+        assert_eq!(
+            segments,
+            [
+                (0, MatchField::Text, 0..3, "Go "),
+                (1, MatchField::LinkText, 0..5, "north"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_word() {
+        assert_eq!(find_word("Darn, darned DARN", "darn"), [0..4, 13..17]);
+        assert_eq!(find_word("Ébène ébène", "ÉBÈNE"), [0..7, 8..15]);
+    }
+
+    #[test]
+    fn test_lint_banned() {
+        let story =
+            Story::try_from(":: A\n<<set $heck to 1>>What the heck, [[Heck|B]]!\n").unwrap();
+        let matches = story.lint_banned(&BannedWords::new(["heck"]));
+
+        assert_eq!(
+            matches
+                .iter()
+                .map(|m| (m.node, m.field, m.range.clone(), m.matched))
+                .collect::<Vec<_>>(),
+            [
+                (0, MatchField::Text, 27..31, "heck"),
+                (1, MatchField::LinkText, 0..4, "Heck"),
+            ]
+        );
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_lint_banned_regex() {
+        let story = Story::try_from(":: A\nTODO: write this\n").unwrap();
+        let banned = BannedWords::new(Vec::<String>::new())
+            .with_regex(regex::Regex::new("TODO|FIXME").unwrap());
+
+        assert_eq!(story.lint_banned(&banned)[0].matched, "TODO");
+    }
+}
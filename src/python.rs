@@ -0,0 +1,124 @@
+//! Python bindings, built with [pyo3](https://pyo3.rs).
+//!
+//! Build the extension module with `maturin build`, configured in `pyproject.toml`. Maturin
+//! builds the crate as a `cdylib` itself, so other dependents only get the Rust library.
+
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+use crate::{iter::Link, Passage, Story};
+
+#[pyclass(name = "Story", module = "twee_v3", frozen)]
+pub struct PyStory {
+    story: Story<String>,
+}
+
+#[pymethods]
+impl PyStory {
+    #[new]
+    fn new(content: String) -> PyResult<Self> {
+        Story::try_from(content)
+            .map(|story| Self { story })
+            .map_err(|error| PyValueError::new_err(error.to_string()))
+    }
+
+    #[getter]
+    fn title(&self) -> Option<&str> {
+        self.story.title()
+    }
+
+    fn start(&self) -> Option<PyPassage> {
+        self.story.start().map(PyPassage::from)
+    }
+
+    fn get_passage(&self, name: &str) -> Option<PyPassage> {
+        self.story.get_passage(name).map(PyPassage::from)
+    }
+
+    fn passages(&self) -> Vec<PyPassage> {
+        self.story.iter().map(PyPassage::from).collect()
+    }
+}
+
+#[pyclass(name = "Passage", module = "twee_v3", frozen)]
+pub struct PyPassage {
+    passage: Passage<String>,
+}
+
+impl From<Passage<&str>> for PyPassage {
+    fn from(passage: Passage<&str>) -> Self {
+        Self {
+            passage: passage.into_owned(),
+        }
+    }
+}
+
+#[pymethods]
+impl PyPassage {
+    #[getter]
+    fn title(&self) -> &str {
+        self.passage.title()
+    }
+
+    #[getter]
+    fn tags(&self) -> Vec<String> {
+        self.passage
+            .tags()
+            .iter()
+            .map(|tag| tag.to_string())
+            .collect()
+    }
+
+    #[getter]
+    fn text(&self) -> String {
+        self.passage.to_string()
+    }
+
+    fn links(&self) -> Vec<PyLink> {
+        self.passage.links().map(PyLink::from).collect()
+    }
+
+    fn __str__(&self) -> String {
+        self.passage.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Passage({:?})", self.passage.title())
+    }
+}
+
+#[pyclass(
+    name = "Link",
+    module = "twee_v3",
+    frozen,
+    get_all,
+    skip_from_py_object
+)]
+#[derive(Clone)]
+pub struct PyLink {
+    text: String,
+    target: String,
+}
+
+impl From<Link<'_, String>> for PyLink {
+    fn from(link: Link<'_, String>) -> Self {
+        Self {
+            text: link.text.clone(),
+            target: link.target.clone(),
+        }
+    }
+}
+
+#[pymethods]
+impl PyLink {
+    fn __repr__(&self) -> String {
+        format!("Link(text={:?}, target={:?})", self.text, self.target)
+    }
+}
+
+#[pymodule]
+fn twee_v3(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyStory>()?;
+    module.add_class::<PyPassage>()?;
+    module.add_class::<PyLink>()?;
+    Ok(())
+}
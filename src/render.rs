@@ -0,0 +1,687 @@
+//! Rendering of passages to displayable text.
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    ops::Deref,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use crate::{ContentNode, Inline, Passage, Story};
+
+/// Special passages SugarCube shows before and after every passage.
+const PASSAGE_HEADER: &str = "PassageHeader";
+const PASSAGE_FOOTER: &str = "PassageFooter";
+
+pub trait Renderer {
+    fn render(&self, passage: &Passage<&str>) -> String;
+}
+
+/// Renders passages as plain text, for terminals. Links are shown as their text, and macros
+/// read with [`ParseOptions::raw_sigils`](crate::ParseOptions::raw_sigils) as they are.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PlainRenderer;
+
+fn render_plain(node: &ContentNode<&str>, output: &mut String) {
+    match node {
+        ContentNode::Raw { raw, .. } => output.push_str(raw),
+        ContentNode::Paragraph(inlines) => {
+            for inline in inlines {
+                match inline {
+                    Inline::Node(node) => render_plain(node, output),
+                    inline => output.push_str(&inline.to_string()),
+                }
+            }
+        }
+        node => output.push_str(&node.to_string()),
+    }
+}
+
+impl Renderer for PlainRenderer {
+    fn render(&self, passage: &Passage<&str>) -> String {
+        let mut output = String::new();
+        for node in passage.nodes() {
+            render_plain(node, &mut output);
+        }
+        output
+    }
+}
+
+/// Renders passages as HTML fragments. Links become `<a>` elements carrying their target in
+/// a `data-passage` attribute. HTML nodes, and macros read with
+/// [`ParseOptions::raw_sigils`](crate::ParseOptions::raw_sigils), are escaped, shown as text,
+/// unless [`HtmlRenderer::raw_html`] is set.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HtmlRenderer {
+    raw_html: bool,
+}
+
+pub(crate) fn escape_html(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for c in input.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl HtmlRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes HTML nodes and raw macros as they are in the story, `<script>` elements and event
+    /// handler attributes included, instead of escaping them. Only for trusted stories.
+    pub fn raw_html(mut self, raw_html: bool) -> Self {
+        self.raw_html = raw_html;
+        self
+    }
+
+    fn render_node(&self, node: &ContentNode<&str>, output: &mut String) {
+        match node {
+            ContentNode::Text(text) => output.push_str(&escape_html(text).replace('\n', "<br>\n")),
+            ContentNode::Link { text, target, .. } => output.push_str(&format!(
+                "<a class=\"link\" data-passage=\"{}\">{}</a>",
+                escape_html(target),
+                escape_html(text)
+            )),
+            ContentNode::Comment(_) | ContentNode::Custom(_) => {}
+            ContentNode::Raw { raw, .. } if self.raw_html => output.push_str(raw),
+            ContentNode::Raw { raw, .. } => output.push_str(&escape_html(raw)),
+            ContentNode::Html(tag) if self.raw_html => output.push_str(tag.raw()),
+            ContentNode::Html(tag) => output.push_str(&escape_html(tag.raw())),
+            ContentNode::Image(image) => {
+                let mut img = format!("<img src=\"{}\"", escape_html(image.source()));
+                if let Some(title) = image.title() {
+                    img.push_str(&format!(" alt=\"{}\"", escape_html(title)));
+                }
+                img.push('>');
+                match image.target() {
+                    Some(target) => output.push_str(&format!(
+                        "<a class=\"link\" data-passage=\"{}\">{img}</a>",
+                        escape_html(target)
+                    )),
+                    None => output.push_str(&img),
+                }
+            }
+            ContentNode::Paragraph(inlines) => {
+                // The blank lines closing the paragraph are shown by the `<p>` itself.
+                let end = inlines
+                    .iter()
+                    .rposition(|inline| match inline {
+                        Inline::Text(text) => !text.trim().is_empty(),
+                        Inline::LineBreak => false,
+                        Inline::Node(_) => true,
+                    })
+                    .map_or(0, |index| index + 1);
+                output.push_str("<p>");
+                for inline in &inlines[..end] {
+                    match inline {
+                        Inline::Text(text) => output.push_str(&escape_html(text)),
+                        Inline::LineBreak => output.push_str("<br>\n"),
+                        Inline::Node(node) => self.render_node(node, output),
+                    }
+                }
+                output.push_str("</p>\n");
+            }
+        }
+    }
+}
+
+impl Renderer for HtmlRenderer {
+    fn render(&self, passage: &Passage<&str>) -> String {
+        let mut output = String::new();
+        for node in passage.nodes() {
+            self.render_node(node, &mut output);
+        }
+        output
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Replaces the `$variables` and `{placeholders}` of `text` with the values `resolve` gives for
+/// their names, keeping those it gives none for.
+fn interpolate(text: &str, resolve: &mut impl FnMut(&str) -> Option<String>) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(['$', '{']) {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let name_len = after.find(|c| !is_name_char(c)).unwrap_or(after.len());
+        let name = &after[..name_len];
+        let (len, value) = match &rest[start..start + 1] {
+            "$" if name.starts_with(|c: char| !c.is_ascii_digit()) => (1 + name_len, resolve(name)),
+            "{" if !name.is_empty() && after[name_len..].starts_with('}') => {
+                (name_len + 2, resolve(name))
+            }
+            _ => (1, None),
+        };
+        match value {
+            Some(value) => output.push_str(&value),
+            None => output.push_str(&rest[start..start + len]),
+        }
+        rest = &rest[start + len..];
+    }
+    output.push_str(rest);
+    output
+}
+
+/// The runs of text and line breaks of a paragraph, between its nodes, as text.
+fn paragraph_texts(inlines: &[Inline<&str>]) -> Vec<String> {
+    let mut texts = vec![];
+    let mut current: Option<String> = None;
+    for inline in inlines {
+        match inline {
+            Inline::Text(text) => current.get_or_insert_with(String::new).push_str(text),
+            Inline::LineBreak => current.get_or_insert_with(String::new).push('\n'),
+            Inline::Node(_) => texts.extend(current.take()),
+        }
+    }
+    texts.extend(current);
+    texts
+}
+
+/// Collapses the whitespace of `text` the way most story formats show it: whitespace holding
+/// a single line break becomes a space, and whitespace holding several becomes a paragraph
+/// break, `\n\n`.
+pub fn collapse_whitespace(text: &str) -> Cow<'_, str> {
+    let mut output = String::new();
+    let mut copied = 0;
+    let mut index = 0;
+    while let Some(position) = text[index..].find(char::is_whitespace) {
+        let start = index + position;
+        let len = text[start..]
+            .find(|c: char| !c.is_whitespace())
+            .unwrap_or(text.len() - start);
+        let run = &text[start..start + len];
+        let replacement = match run.matches('\n').count() {
+            0 => run,
+            1 => " ",
+            _ => "\n\n",
+        };
+        if replacement != run {
+            output.push_str(&text[copied..start]);
+            output.push_str(replacement);
+            copied = start + len;
+        }
+        index = start + len;
+    }
+    if copied == 0 {
+        return Cow::Borrowed(text);
+    }
+    output.push_str(&text[copied..]);
+    Cow::Owned(output)
+}
+
+impl<T> Passage<T>
+where
+    T: AsRef<str>,
+{
+    /// Renders the passage as plain text, like [`PlainRenderer`], replacing `$variables` and
+    /// `{placeholders}` in its text and link texts with the value `resolve` gives for their
+    /// name, such as `"gold"` for `$gold`. Those `resolve` returns `None` for are kept as is.
+    pub fn render_with<F>(&self, mut resolve: F) -> String
+    where
+        F: FnMut(&str) -> Option<String>,
+    {
+        let mut output = String::new();
+        for node in &self.content {
+            render_node_with(node, &mut resolve, &mut output);
+        }
+        output
+    }
+}
+
+fn render_node_with<T, F>(node: &ContentNode<T>, resolve: &mut F, output: &mut String)
+where
+    T: AsRef<str>,
+    F: FnMut(&str) -> Option<String>,
+{
+    match node {
+        ContentNode::Text(text) | ContentNode::Link { text, .. } => {
+            output.push_str(&interpolate(text.as_ref(), resolve))
+        }
+        ContentNode::Paragraph(inlines) => {
+            for inline in inlines {
+                match inline {
+                    Inline::Text(text) => output.push_str(&interpolate(text.as_ref(), resolve)),
+                    Inline::LineBreak => output.push('\n'),
+                    Inline::Node(node) => render_node_with(node, resolve, output),
+                }
+            }
+        }
+        ContentNode::Raw { raw, .. } => output.push_str(raw.as_ref()),
+        ContentNode::Comment(_)
+        | ContentNode::Html(_)
+        | ContentNode::Image(_)
+        | ContentNode::Custom(_) => {}
+    }
+}
+
+/// How [`Story::render`] prepares passages before rendering them.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RenderOptions {
+    /// Surround passages with the content of PassageHeader and PassageFooter, see
+    /// [`Story::composed_passage`].
+    pub header_footer: bool,
+    /// Collapse the whitespace of text nodes, see [`collapse_whitespace`], instead of keeping
+    /// the line breaks of the source.
+    pub collapse_whitespace: bool,
+}
+
+impl RenderOptions {
+    pub fn header_footer(mut self, header_footer: bool) -> Self {
+        self.header_footer = header_footer;
+        self
+    }
+
+    pub fn collapse_whitespace(mut self, collapse_whitespace: bool) -> Self {
+        self.collapse_whitespace = collapse_whitespace;
+        self
+    }
+}
+
+impl<T> Story<T>
+where
+    T: Deref<Target = str>,
+{
+    /// The passage `name` with the content of PassageHeader before it and the content of
+    /// PassageFooter after it, as SugarCube shows it. The header and footer passages themselves
+    /// are returned as is.
+    pub fn composed_passage(&self, name: &str) -> Option<Passage<&str>> {
+        let mut passage = self.get_passage(name)?;
+        if name == PASSAGE_HEADER || name == PASSAGE_FOOTER {
+            return Some(passage);
+        }
+        let header = self.get_passage(PASSAGE_HEADER);
+        let footer = self.get_passage(PASSAGE_FOOTER);
+        if header.is_none() && footer.is_none() {
+            return Some(passage);
+        }
+        passage.content = header
+            .into_iter()
+            .flat_map(|header| header.content)
+            .chain(passage.content)
+            .chain(footer.into_iter().flat_map(|footer| footer.content))
+            .collect();
+        passage.source = None;
+        Some(passage)
+    }
+
+    /// Renders the passage `name` with `renderer`.
+    ///
+    /// With [`RenderOptions::collapse_whitespace`], each run of text and line breaks in a
+    /// paragraph is collapsed as a whole, into a single text.
+    pub fn render<R>(&self, name: &str, renderer: &R, options: RenderOptions) -> Option<String>
+    where
+        R: Renderer,
+    {
+        let texts: Vec<Cow<str>>;
+        let mut passage = match options.header_footer {
+            true => self.composed_passage(name)?,
+            false => self.get_passage(name)?,
+        };
+        if options.collapse_whitespace {
+            // The collapsed texts, in the order they replace text nodes and paragraph runs.
+            texts = passage
+                .content
+                .iter()
+                .flat_map(|node| match node {
+                    ContentNode::Text(text) => vec![collapse_whitespace(text)],
+                    ContentNode::Paragraph(inlines) => paragraph_texts(inlines)
+                        .iter()
+                        .map(|text| Cow::Owned(collapse_whitespace(text).into_owned()))
+                        .collect(),
+                    _ => vec![],
+                })
+                .collect();
+            let mut texts = texts.iter().map(|text| &**text);
+            passage.content = passage
+                .content
+                .into_iter()
+                .map(|node| match node {
+                    ContentNode::Text(_) => ContentNode::Text(texts.next().unwrap_or_default()),
+                    ContentNode::Paragraph(inlines) => {
+                        let mut collapsed = vec![];
+                        for inline in inlines {
+                            match inline {
+                                Inline::Node(node) => collapsed.push(Inline::Node(node)),
+                                // The rest of a run already replaced by its collapsed text.
+                                _ if matches!(collapsed.last(), Some(Inline::Text(_))) => {}
+                                _ => collapsed.extend(texts.next().map(Inline::Text)),
+                            }
+                        }
+                        ContentNode::Paragraph(collapsed)
+                    }
+                    node => node,
+                })
+                .collect();
+        }
+        Some(renderer.render(&passage))
+    }
+}
+
+/// Hashes of the passages a rendering was made from: the passage, then PassageHeader and
+/// PassageFooter when they are rendered with it.
+type Version = [Option<u64>; 3];
+
+/// Rendered passages by name, dropping the least recently used first.
+#[derive(Default)]
+struct Lru {
+    /// Each rendering, with the version it was rendered from and the tick of its last use.
+    entries: HashMap<Arc<str>, (Version, Arc<str>, u64)>,
+    /// The names of the passages, by the tick of their last use.
+    order: BTreeMap<u64, Arc<str>>,
+    clock: u64,
+}
+
+impl Lru {
+    /// The rendering of `name`, if it was rendered from `version`.
+    fn get(&mut self, name: &str, version: Version) -> Option<Arc<str>> {
+        let (rendered_version, rendered, last_used) = self.entries.get_mut(name)?;
+        if *rendered_version != version {
+            return None;
+        }
+        let key = self.order.remove(last_used)?;
+        self.clock += 1;
+        *last_used = self.clock;
+        self.order.insert(self.clock, key);
+        Some(rendered.clone())
+    }
+
+    fn insert(&mut self, name: &str, version: Version, rendered: Arc<str>, capacity: usize) {
+        if let Some((_, _, last_used)) = self.entries.remove(name) {
+            self.order.remove(&last_used);
+        }
+        while self.entries.len() >= capacity {
+            let Some((_, oldest)) = self.order.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+        let name: Arc<str> = name.into();
+        self.clock += 1;
+        self.entries
+            .insert(name.clone(), (version, rendered, self.clock));
+        self.order.insert(self.clock, name);
+    }
+}
+
+/// Caches rendered passages by name, along with the hash of the passage they were rendered
+/// from. The hash is computed once per version of the story, so a hit doesn't walk the passage.
+///
+/// The cache can be shared between threads. Once `capacity` entries are stored, the least
+/// recently used one is dropped to make room for a new one.
+pub struct RenderCache<R> {
+    renderer: R,
+    options: RenderOptions,
+    capacity: usize,
+    entries: Mutex<Lru>,
+}
+
+impl<R> RenderCache<R>
+where
+    R: Renderer,
+{
+    pub fn new(renderer: R) -> Self {
+        Self::with_capacity(renderer, usize::MAX)
+    }
+
+    pub fn with_capacity(renderer: R, capacity: usize) -> Self {
+        Self {
+            renderer,
+            options: RenderOptions::default(),
+            capacity,
+            entries: Mutex::new(Lru::default()),
+        }
+    }
+
+    /// Renders passages as [`Story::render`] does with `options`.
+    pub fn options(mut self, options: RenderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Renders the passage `name` of `story`, or returns its cached rendering.
+    pub fn render<T>(&self, story: &Story<T>, name: &str) -> Option<Arc<str>>
+    where
+        T: Deref<Target = str>,
+    {
+        let hash = story.passage_hash(name)?;
+        let version = match self.options.header_footer {
+            true => [
+                Some(hash),
+                story.passage_hash(PASSAGE_HEADER),
+                story.passage_hash(PASSAGE_FOOTER),
+            ],
+            false => [Some(hash), None, None],
+        };
+        if let Some(rendered) = self.lock_entries().get(name, version) {
+            return Some(rendered);
+        }
+
+        let rendered: Arc<str> = story.render(name, &self.renderer, self.options)?.into();
+        self.lock_entries()
+            .insert(name, version, rendered.clone(), self.capacity);
+        Some(rendered)
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock_entries().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock_entries().entries.is_empty()
+    }
+
+    pub fn clear(&self) {
+        *self.lock_entries() = Lru::default();
+    }
+
+    fn lock_entries(&self) -> MutexGuard<'_, Lru> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, sync::Arc};
+
+    use super::{
+        collapse_whitespace, HtmlRenderer, PlainRenderer, RenderCache, RenderOptions, Renderer,
+    };
+    use crate::{ParseOptions, Story};
+
+    const SAMPLE: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/sample/sample.twee"));
+
+    #[test]
+    fn test_plain_renderer() {
+        let story = Story::try_from(SAMPLE).unwrap();
+        let passage = story.get_passage("Second").unwrap();
+
+        assert_eq!(
+            PlainRenderer.render(&passage),
+            "This is a second passage.\nNothing to see here.\n\nGo first"
+        );
+    }
+
+    #[test]
+    fn test_html_renderer() {
+        let story = Story::try_from(":: A\n<b> & [[go|B]]\n").unwrap();
+        let passage = story.get_passage("A").unwrap();
+
+        assert_eq!(
+            HtmlRenderer::new().render(&passage),
+            "&lt;b&gt; &amp; <a class=\"link\" data-passage=\"B\">go</a>"
+        );
+
+        let input = ":: A\nOne <\ntwo\n\n[[B]]\n";
+        let story = Story::parse_with(input, ParseOptions::default().paragraphs(true)).unwrap();
+        let passage = story.get_passage("A").unwrap();
+        assert_eq!(
+            HtmlRenderer::new().render(&passage),
+            "<p>One &lt;<br>\ntwo</p>\n<p><a class=\"link\" data-passage=\"B\">B</a></p>\n"
+        );
+
+        let input = ":: A\n<img src=x onerror=\"alert(1)\"><b>Hi</b>\n";
+        let story = Story::parse_with(input, ParseOptions::default().html(true)).unwrap();
+        let passage = story.get_passage("A").unwrap();
+        assert_eq!(
+            HtmlRenderer::new().render(&passage),
+            "&lt;img src=x onerror=&quot;alert(1)&quot;&gt;&lt;b&gt;Hi&lt;/b&gt;"
+        );
+        assert_eq!(
+            HtmlRenderer::new().raw_html(true).render(&passage),
+            "<img src=x onerror=\"alert(1)\"><b>Hi</b>"
+        );
+    }
+
+    #[test]
+    fn test_render_raw_macros() {
+        let input = ":: A\n<<if $a < 1>>Hi [[B]]<</if>>\n";
+        let options = ParseOptions::default().raw_sigils(true);
+        let story = Story::parse_with(input, options.clone()).unwrap();
+        let passage = story.get_passage("A").unwrap();
+
+        assert_eq!(PlainRenderer.render(&passage), "<<if $a < 1>>Hi B<</if>>");
+        assert_eq!(
+            HtmlRenderer::new().render(&passage),
+            "&lt;&lt;if $a &lt; 1&gt;&gt;Hi <a class=\"link\" data-passage=\"B\">B</a>&lt;&lt;/if&gt;&gt;"
+        );
+        assert_eq!(
+            HtmlRenderer::new().raw_html(true).render(&passage),
+            "<<if $a < 1>>Hi <a class=\"link\" data-passage=\"B\">B</a><</if>>"
+        );
+        assert_eq!(passage.render_with(|_| None), "<<if $a < 1>>Hi B<</if>>");
+
+        let story = Story::parse_with(input, options.paragraphs(true)).unwrap();
+        let passage = story.get_passage("A").unwrap();
+        assert_eq!(PlainRenderer.render(&passage), "<<if $a < 1>>Hi B<</if>>");
+    }
+
+    #[test]
+    fn test_render_with() {
+        let input =
+            ":: A\nYou have $gold coins, {name}. $5 {not a name} $unknown [[Spend $gold|B]]\n";
+        let story = Story::try_from(input).unwrap();
+        let passage = story.get_passage("A").unwrap();
+
+        let rendered = passage.render_with(|name| match name {
+            "gold" => Some("12".to_string()),
+            "name" => Some("Ada".to_string()),
+            _ => None,
+        });
+        assert_eq!(
+            rendered,
+            "You have 12 coins, Ada. $5 {not a name} $unknown Spend 12"
+        );
+    }
+
+    #[test]
+    fn test_render_header_footer() {
+        let input = ":: PassageHeader\n[[Home|Start]] \n\n:: PassageFooter\n (the end)\n\n:: Start\nHello\n";
+        let story = Story::try_from(input).unwrap();
+        let options = RenderOptions::default().header_footer(true);
+
+        assert_eq!(
+            story.render("Start", &PlainRenderer, options).as_deref(),
+            Some("Home Hello (the end)")
+        );
+        assert_eq!(
+            story
+                .render("Start", &PlainRenderer, RenderOptions::default())
+                .as_deref(),
+            Some("Hello")
+        );
+        assert_eq!(
+            story.composed_passage("PassageHeader"),
+            story.get_passage("PassageHeader")
+        );
+        assert_eq!(story.render("Nowhere", &PlainRenderer, options), None);
+    }
+
+    #[test]
+    fn test_collapse_whitespace() {
+        assert_eq!(
+            collapse_whitespace("A long\nline,  wrapped.\n \n\n\tNext \n"),
+            "A long line,  wrapped.\n\nNext "
+        );
+        assert!(matches!(collapse_whitespace("a  b"), Cow::Borrowed("a  b")));
+
+        let story = Story::try_from(":: A\nGo\n[[north|B]]\nnow.\n\nOr stay.\n").unwrap();
+        let options = RenderOptions::default().collapse_whitespace(true);
+        assert_eq!(
+            story.render("A", &PlainRenderer, options).as_deref(),
+            Some("Go north now.\n\nOr stay.")
+        );
+
+        let input = ":: A\nGo\n[[north|B]]\nnow.\n\nOr stay.\n";
+        let story = Story::parse_with(input, ParseOptions::default().paragraphs(true)).unwrap();
+        assert_eq!(
+            story.render("A", &PlainRenderer, options).as_deref(),
+            Some("Go north now.\n\nOr stay.")
+        );
+    }
+
+    #[test]
+    fn test_render_cache() {
+        let story = Story::try_from(SAMPLE).unwrap();
+        let cache = RenderCache::with_capacity(HtmlRenderer::new(), 2);
+
+        let first = cache.render(&story, "First").unwrap();
+        let again = cache.render(&story, "First").unwrap();
+        assert!(Arc::ptr_eq(&first, &again));
+        assert_eq!(cache.len(), 1);
+        assert!(cache.render(&story, "Nowhere").is_none());
+
+        cache.render(&story, "Second");
+        cache.render(&story, "First");
+        cache.render(&story, "Third");
+        assert_eq!(cache.len(), 2);
+        let again = cache.render(&story, "First").unwrap();
+        assert!(Arc::ptr_eq(&first, &again));
+
+        let mut changed = Story::try_from(SAMPLE.to_string()).unwrap();
+        let mut passage = changed.get_passage("First").unwrap().into_owned();
+        passage.push_text("!".to_string());
+        changed.insert_passage(passage);
+        assert!(!Arc::ptr_eq(
+            &first,
+            &cache.render(&changed, "First").unwrap()
+        ));
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_render_cache_options() {
+        let input = ":: PassageHeader\n[[Home|Start]] \n\n:: Start\nHello\nthere\n";
+        let mut story = Story::try_from(input.to_string()).unwrap();
+        let options = RenderOptions::default()
+            .header_footer(true)
+            .collapse_whitespace(true);
+        let cache = RenderCache::new(PlainRenderer).options(options);
+
+        assert_eq!(
+            cache.render(&story, "Start").as_deref(),
+            Some("Home Hello there")
+        );
+
+        let mut header = story.get_passage("PassageHeader").unwrap().into_owned();
+        header.push_text("Menu ".to_string());
+        story.insert_passage(header);
+        assert_eq!(
+            cache.render(&story, "Start").as_deref(),
+            Some("Home Menu Hello there")
+        );
+        assert_eq!(cache.len(), 1);
+    }
+}
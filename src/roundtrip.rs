@@ -0,0 +1,242 @@
+use std::fmt::{Debug, Display};
+
+use crate::{Passage, Story};
+
+/// A difference between a story and the story parsed back from its twee, see
+/// [`verify_roundtrip`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RoundtripMismatch {
+    /// The input doesn't parse, with the error message.
+    InvalidInput(String),
+    /// The written twee doesn't parse, with the error message.
+    Reparsing(String),
+    Title {
+        original: Option<String>,
+        reparsed: Option<String>,
+    },
+    Start {
+        original: Option<String>,
+        reparsed: Option<String>,
+    },
+    Data {
+        original: Option<String>,
+        reparsed: Option<String>,
+    },
+    /// A passage of the input is missing once parsed back.
+    MissingPassage(String),
+    /// A passage parsed back isn't in the input.
+    ExtraPassage(String),
+    Tags {
+        passage: String,
+        original: Vec<String>,
+        reparsed: Vec<String>,
+    },
+    Metadata {
+        passage: String,
+        original: Option<String>,
+        reparsed: Option<String>,
+    },
+    /// The first node that differs in a passage, each node written with [`Debug`].
+    Node {
+        passage: String,
+        index: usize,
+        original: Option<String>,
+        reparsed: Option<String>,
+    },
+}
+
+impl Display for RoundtripMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RoundtripMismatch::InvalidInput(error) => write!(f, "Cannot parse the input: {error}"),
+            RoundtripMismatch::Reparsing(error) => {
+                write!(f, "Cannot parse the written story: {error}")
+            }
+            RoundtripMismatch::Title { original, reparsed } => {
+                write!(f, "Title {original:?} became {reparsed:?}")
+            }
+            RoundtripMismatch::Start { original, reparsed } => {
+                write!(f, "Start {original:?} became {reparsed:?}")
+            }
+            RoundtripMismatch::Data { original, reparsed } => {
+                write!(f, "StoryData {original:?} became {reparsed:?}")
+            }
+            RoundtripMismatch::MissingPassage(passage) => {
+                write!(f, "Passage {passage:?} is missing")
+            }
+            RoundtripMismatch::ExtraPassage(passage) => {
+                write!(f, "Passage {passage:?} appeared")
+            }
+            RoundtripMismatch::Tags {
+                passage,
+                original,
+                reparsed,
+            } => write!(f, "Tags of {passage:?}: {original:?} became {reparsed:?}"),
+            RoundtripMismatch::Metadata {
+                passage,
+                original,
+                reparsed,
+            } => write!(
+                f,
+                "Metadata of {passage:?}: {original:?} became {reparsed:?}"
+            ),
+            RoundtripMismatch::Node {
+                passage,
+                index,
+                original,
+                reparsed,
+            } => write!(
+                f,
+                "Node {index} of {passage:?}: {} became {}",
+                original.as_deref().unwrap_or("nothing"),
+                reparsed.as_deref().unwrap_or("nothing")
+            ),
+        }
+    }
+}
+
+/// What went wrong in a round trip, see [`verify_roundtrip`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoundtripReport {
+    /// The twee written from the input, empty if the input doesn't parse.
+    pub twee: String,
+    pub mismatches: Vec<RoundtripMismatch>,
+}
+
+impl Display for RoundtripReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for mismatch in &self.mismatches {
+            writeln!(f, "{mismatch}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for RoundtripReport {}
+
+fn to_strings(values: impl IntoIterator<Item = impl ToString>) -> Vec<String> {
+    values.into_iter().map(|value| value.to_string()).collect()
+}
+
+fn compare_passages(
+    original: &Passage<&str>,
+    reparsed: &Passage<&str>,
+    mismatches: &mut Vec<RoundtripMismatch>,
+) {
+    let passage = original.title.to_string();
+    if original.tags != reparsed.tags {
+        mismatches.push(RoundtripMismatch::Tags {
+            passage: passage.clone(),
+            original: to_strings(&original.tags),
+            reparsed: to_strings(&reparsed.tags),
+        });
+    }
+    if original.metadata != reparsed.metadata {
+        let content = |passage: &Passage<&str>| {
+            passage
+                .metadata
+                .as_ref()
+                .map(|metadata| metadata.content.to_string())
+        };
+        mismatches.push(RoundtripMismatch::Metadata {
+            passage: passage.clone(),
+            original: content(original),
+            reparsed: content(reparsed),
+        });
+    }
+    let len = original.content.len().max(reparsed.content.len());
+    let differing =
+        (0..len).find(|&index| original.content.get(index) != reparsed.content.get(index));
+    if let Some(index) = differing {
+        let debug = |node: Option<&_>| node.map(|node| format!("{node:?}"));
+        mismatches.push(RoundtripMismatch::Node {
+            passage,
+            index,
+            original: debug(original.content.get(index)),
+            reparsed: debug(reparsed.content.get(index)),
+        });
+    }
+}
+
+/// Parses `input`, writes it back as twee with [`Story::to_twee`], parses the result and
+/// checks that both stories hold the same title, StoryData and passages, listing every
+/// difference otherwise.
+pub fn verify_roundtrip(input: &str) -> Result<(), RoundtripReport> {
+    let story = Story::try_from(input).map_err(|error| RoundtripReport {
+        twee: String::new(),
+        mismatches: vec![RoundtripMismatch::InvalidInput(error.to_string())],
+    })?;
+    let twee = story.to_twee();
+    let reparsed = match Story::try_from(twee.as_str()) {
+        Ok(reparsed) => reparsed,
+        Err(error) => {
+            let mismatches = vec![RoundtripMismatch::Reparsing(error.to_string())];
+            return Err(RoundtripReport { twee, mismatches });
+        }
+    };
+
+    let mut mismatches = vec![];
+    let owned = |value: Option<&str>| value.map(str::to_owned);
+    if story.title() != reparsed.title() {
+        mismatches.push(RoundtripMismatch::Title {
+            original: owned(story.title()),
+            reparsed: owned(reparsed.title()),
+        });
+    }
+    let start = |story: &Story<&str>| {
+        story
+            .start
+            .as_ref()
+            .map(|start| start.as_str(story.content).to_string())
+    };
+    if start(&story) != start(&reparsed) {
+        mismatches.push(RoundtripMismatch::Start {
+            original: start(&story),
+            reparsed: start(&reparsed),
+        });
+    }
+    if story.data() != reparsed.data() {
+        mismatches.push(RoundtripMismatch::Data {
+            original: owned(story.data()),
+            reparsed: owned(reparsed.data()),
+        });
+    }
+    for passage in story.iter() {
+        match reparsed.get_passage(passage.title) {
+            Some(other) => compare_passages(&passage, &other, &mut mismatches),
+            None => mismatches.push(RoundtripMismatch::MissingPassage(passage.title.to_string())),
+        }
+    }
+    for passage in reparsed.iter() {
+        if story.get_passage(passage.title).is_none() {
+            mismatches.push(RoundtripMismatch::ExtraPassage(passage.title.to_string()));
+        }
+    }
+
+    match mismatches.is_empty() {
+        true => Ok(()),
+        false => Err(RoundtripReport { twee, mismatches }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{verify_roundtrip, RoundtripMismatch};
+
+    const SAMPLE: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/sample/sample.twee"));
+
+    #[test]
+    fn test_verify_roundtrip() {
+        assert_eq!(verify_roundtrip(SAMPLE), Ok(()));
+        assert_eq!(verify_roundtrip(":: A \\[1\\]\n\\[[not a link]]\n"), Ok(()));
+
+        assert_eq!(verify_roundtrip(":: A\n[[a->b->c]] /* open\n"), Ok(()));
+
+        let report = verify_roundtrip("::\nstray\n:: A\nz\n").unwrap_err();
+        assert!(report.twee.is_empty());
+        assert!(matches!(
+            report.mismatches[..],
+            [RoundtripMismatch::InvalidInput(_)]
+        ));
+    }
+}
@@ -0,0 +1,24 @@
+//! Ready-made stories, for tutorials, examples and tests.
+
+use crate::Story;
+
+const DEMO: &str = include_str!("../sample/demo.twee");
+
+/// A small parsed story with a title, a start passage, a few links and two endings.
+pub fn demo_story() -> Story<&'static str> {
+    Story::try_from(DEMO).expect("The demo story is valid twee")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::demo_story;
+
+    #[test]
+    fn test_demo_story() {
+        let story = demo_story();
+
+        assert_eq!(Some("The Lighthouse"), story.title());
+        assert_eq!(Some(&"Shore"), story.start().as_ref().map(|p| p.title()));
+        assert_eq!(4, story.iter().count());
+    }
+}
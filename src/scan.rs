@@ -0,0 +1,205 @@
+//! Read-only scanning of passages, without building a [`Story`](crate::Story).
+//!
+//! Passages are views over the input: nothing is copied and escaped characters are kept as
+//! written, as with [`ParseOptions::unescape`](crate::ParseOptions::unescape) disabled. This
+//! suits quick passes over large stories, like counting words or extracting links.
+
+use crate::{
+    encoding::strip_bom,
+    error::ParsingError,
+    parser::{passage::parse_passage, story::split_preamble},
+    utils::ensure_progress,
+    ContentNode, Image, Passage,
+};
+
+/// Iterates over the passages of `input`, skipping StoryTitle, StoryData and the text before
+/// the first passage header.
+pub fn passages(input: &str) -> Passages<'_> {
+    Passages {
+        input: split_preamble(strip_bom(input)).1,
+    }
+}
+
+/// Iterator over the passages of a story source, see [`passages`].
+///
+/// Iteration stops after the first error.
+#[derive(Debug, Clone)]
+pub struct Passages<'a> {
+    input: &'a str,
+}
+
+impl<'a> Iterator for Passages<'a> {
+    type Item = Result<Passage<&'a str>, ParsingError<&'a str>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.input.is_empty() {
+            let parsed = parse_passage(self.input).and_then(|(input, passage)| {
+                ensure_progress(self.input, input).map(|_| (input, passage))
+            });
+            match parsed {
+                Ok((input, passage)) => {
+                    self.input = input;
+                    if !matches!(passage.title, "StoryTitle" | "StoryData") {
+                        return Some(Ok(passage));
+                    }
+                }
+                Err(error) => {
+                    self.input = "";
+                    return Some(Err(error.into()));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// A piece of a story, reported by [`parse_events`] in source order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseEvent<'a> {
+    /// Text before the first passage header, which isn't part of any passage.
+    Preamble(&'a str),
+    PassageStart {
+        title: &'a str,
+    },
+    Tag(&'a str),
+    /// The metadata of the passage, as written.
+    Metadata(&'a str),
+    Text(&'a str),
+    Link {
+        text: &'a str,
+        target: &'a str,
+        setter: Option<&'a str>,
+    },
+    Comment(&'a str),
+    Image(Image<&'a str>),
+    PassageEnd {
+        title: &'a str,
+    },
+}
+
+/// Reports the pieces of `input` to `f` as they are parsed, without building a story, for
+/// streaming transforms. StoryTitle and StoryData are reported as passages.
+///
+/// Parsing stops at the first error, after reporting the passages before it.
+pub fn parse_events<'a, F>(input: &'a str, mut f: F) -> Result<(), ParsingError<&'a str>>
+where
+    F: FnMut(ParseEvent<'a>),
+{
+    let (preamble, mut input) = split_preamble(strip_bom(input));
+    if !preamble.is_empty() {
+        f(ParseEvent::Preamble(preamble));
+    }
+    while !input.is_empty() {
+        let (rest, passage) = parse_passage(input)?;
+        ensure_progress(input, rest)?;
+        input = rest;
+
+        f(ParseEvent::PassageStart {
+            title: passage.title,
+        });
+        for tag in passage.tags {
+            f(ParseEvent::Tag(tag.value));
+        }
+        if let Some(metadata) = passage.metadata {
+            f(ParseEvent::Metadata(metadata.content));
+        }
+        for node in passage.content {
+            match node {
+                ContentNode::Text(text) => f(ParseEvent::Text(text)),
+                ContentNode::Link {
+                    text,
+                    target,
+                    setter,
+                } => f(ParseEvent::Link {
+                    text,
+                    target,
+                    setter,
+                }),
+                ContentNode::Comment(comment) => f(ParseEvent::Comment(comment)),
+                ContentNode::Image(image) => f(ParseEvent::Image(image)),
+                // HTML, custom, raw and paragraph nodes come from options scanning doesn't take.
+                ContentNode::Html(_)
+                | ContentNode::Custom(_)
+                | ContentNode::Raw { .. }
+                | ContentNode::Paragraph(_) => {}
+            }
+        }
+        f(ParseEvent::PassageEnd {
+            title: passage.title,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_events, passages, ParseEvent};
+    use crate::{ParseOptions, Story};
+
+    const SAMPLE: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/sample/sample.twee"));
+
+    #[test]
+    fn test_passages_match_story() {
+        let story = Story::parse_with(SAMPLE, ParseOptions::default().unescape(false)).unwrap();
+        let scanned: Vec<_> = passages(SAMPLE).collect::<Result<_, _>>().unwrap();
+
+        assert_eq!(scanned, story.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_passages_keep_escapes() {
+        let input = ":: A\\[1\\]\nGo [[B]]\n\n:: B\nEnd";
+        let titles: Vec<_> = passages(input).map(|p| *p.unwrap().title()).collect();
+
+        assert_eq!(titles, ["A\\[1\\]", "B"]);
+    }
+
+    #[test]
+    fn test_parse_events() {
+        let input = ":: StoryTitle\nT\n\n:: A [x y] {\"n\":1}\nGo [[B]] /* c */\n";
+        let mut events = vec![];
+        parse_events(input, |event| events.push(event)).unwrap();
+
+        assert_eq!(
+            events,
+            [
+                ParseEvent::PassageStart {
+                    title: "StoryTitle"
+                },
+                ParseEvent::Text("T"),
+                ParseEvent::PassageEnd {
+                    title: "StoryTitle"
+                },
+                ParseEvent::PassageStart { title: "A" },
+                ParseEvent::Tag("x"),
+                ParseEvent::Tag("y"),
+                ParseEvent::Metadata("{\"n\":1}"),
+                ParseEvent::Text("Go "),
+                ParseEvent::Link {
+                    text: "B",
+                    target: "B",
+                    setter: None
+                },
+                ParseEvent::Text(" "),
+                ParseEvent::Comment("/* c */"),
+                ParseEvent::PassageEnd { title: "A" },
+            ]
+        );
+
+        let mut events = vec![];
+        parse_events("Notes\n:: A\n", |event| events.push(event)).unwrap();
+        assert_eq!(events[0], ParseEvent::Preamble("Notes\n"));
+
+        let mut count = 0;
+        assert!(parse_events(":: \nNot a story\n:: A\nFine", |_| count += 1).is_err());
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn test_passages_stop_at_error() {
+        let mut scanned = passages(":: \nNot a story\n:: A\nFine");
+
+        assert!(scanned.next().unwrap().is_err());
+        assert!(scanned.next().is_none());
+    }
+}
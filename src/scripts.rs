@@ -0,0 +1,67 @@
+//! Story-wide scripts and stylesheets, as published by Tweego.
+
+use std::ops::Deref;
+
+use crate::{writer::write_node_unescaped, Story};
+
+impl<T> Story<T>
+where
+    T: Deref<Target = str>,
+{
+    /// The content of the passages tagged `tag`, in source order, one after the other.
+    fn combined(&self, tag: &str) -> String {
+        let mut output = String::new();
+        for passage in self.iter() {
+            if !passage.tags.iter().any(|t| t.value == tag) {
+                continue;
+            }
+            if !output.is_empty() {
+                output.push('\n');
+            }
+            for node in &passage.content {
+                write_node_unescaped(&mut output, node);
+            }
+        }
+        output
+    }
+
+    /// The content of all the passages tagged `script`, in source order.
+    ///
+    /// Scripts are read like any other passage, so parse the story with
+    /// [`ParseOptions::unescape`](crate::ParseOptions::unescape) disabled to keep their
+    /// backslashes.
+    pub fn combined_script(&self) -> String {
+        self.combined("script")
+    }
+
+    /// The content of all the passages tagged `stylesheet`, in source order.
+    pub fn combined_stylesheet(&self) -> String {
+        self.combined("stylesheet")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ParseOptions, Story};
+
+    const STORY: &str = ":: Story JavaScript [script]\nwindow.a = [[1, 2]];\n\n:: Start\nHi\n\n:: Story Stylesheet [stylesheet]\nbody { color: red; }\n\n:: More [script]\nconst re = /\\d+/;\n";
+
+    #[test]
+    fn test_combined_script() {
+        let options = ParseOptions::default().unescape(false);
+        let story = Story::parse_with(STORY, options).unwrap();
+
+        assert_eq!(
+            story.combined_script(),
+            "window.a = [[1, 2]];\nconst re = /\\d+/;"
+        );
+        assert_eq!(story.combined_stylesheet(), "body { color: red; }");
+    }
+
+    #[test]
+    fn test_combined_without_passages() {
+        let story = Story::try_from(":: Start\nHi\n").unwrap();
+
+        assert_eq!(story.combined_script(), "");
+    }
+}
@@ -0,0 +1,189 @@
+//! Searching the content of a story while keeping track of where matches are.
+
+use std::ops::{Deref, Range};
+
+use crate::{ContentNode, Story};
+
+/// Which nodes a search looks into.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SearchScope {
+    /// Text, link texts, link targets and comments.
+    #[default]
+    AllNodes,
+    TextOnly,
+}
+
+/// The part of a node a match was found in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchField {
+    Text,
+    LinkText,
+    LinkTarget,
+    Comment,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchMatch<'a> {
+    pub passage: &'a str,
+    /// Index of the node in [`Passage::nodes`](crate::Passage::nodes).
+    pub node: usize,
+    /// Index of the inline in the node, when it is a paragraph, see
+    /// [`ParseOptions::paragraphs`](crate::ParseOptions::paragraphs).
+    pub inline: Option<usize>,
+    pub field: MatchField,
+    /// Byte range of the match in the field.
+    pub range: Range<usize>,
+    pub matched: &'a str,
+}
+
+impl<T> Story<T>
+where
+    T: Deref<Target = str>,
+{
+    pub fn search(&self, query: &str, scope: SearchScope) -> Vec<SearchMatch<'_>> {
+        if query.is_empty() {
+            return vec![];
+        }
+        self.search_with(scope, |text| {
+            text.match_indices(query)
+                .map(|(start, matched)| start..start + matched.len())
+                .collect()
+        })
+    }
+
+    #[cfg(feature = "regex")]
+    pub fn search_regex(&self, regex: &regex::Regex, scope: SearchScope) -> Vec<SearchMatch<'_>> {
+        self.search_with(scope, |text| {
+            regex
+                .find_iter(text)
+                .filter(|found| !found.is_empty())
+                .map(|found| found.range())
+                .collect()
+        })
+    }
+
+    fn search_with<F>(&self, scope: SearchScope, find: F) -> Vec<SearchMatch<'_>>
+    where
+        F: Fn(&str) -> Vec<Range<usize>>,
+    {
+        let mut matches = vec![];
+        for passage in self.iter() {
+            let nodes = passage.content.iter().enumerate();
+            for (index, inline, node) in nodes.flat_map(|(index, node)| {
+                node.flatten_indexed()
+                    .into_iter()
+                    .map(move |(inline, node)| (index, inline, node))
+            }) {
+                let fields = match (node, scope) {
+                    (ContentNode::Text(text), _) => vec![(MatchField::Text, text)],
+                    (_, SearchScope::TextOnly) => vec![],
+                    (ContentNode::Link { text, target, .. }, _) => vec![
+                        (MatchField::LinkText, text),
+                        (MatchField::LinkTarget, target),
+                    ],
+                    (ContentNode::Comment(comment), _) => vec![(MatchField::Comment, comment)],
+                    (ContentNode::Html(_), _)
+                    | (ContentNode::Image(_), _)
+                    | (ContentNode::Custom(_), _)
+                    | (ContentNode::Raw { .. }, _)
+                    | (ContentNode::Paragraph(_), _) => vec![],
+                };
+                for (field, text) in fields {
+                    matches.extend(find(text).into_iter().map(|range| SearchMatch {
+                        passage: passage.title,
+                        node: index,
+                        inline,
+                        field,
+                        matched: &text[range.clone()],
+                        range,
+                    }));
+                }
+            }
+        }
+        matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MatchField, SearchMatch, SearchScope};
+    use crate::{ParseOptions, Story};
+
+    const SAMPLE: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/sample/sample.twee"));
+
+    #[test]
+    fn test_search() {
+        let story = Story::try_from(SAMPLE).unwrap();
+
+        assert_eq!(
+            story.search("First", SearchScope::AllNodes),
+            vec![
+                SearchMatch {
+                    passage: "Second",
+                    node: 1,
+                    inline: None,
+                    field: MatchField::LinkTarget,
+                    range: 0..5,
+                    matched: "First",
+                },
+                SearchMatch {
+                    passage: "Start",
+                    node: 1,
+                    inline: None,
+                    field: MatchField::LinkText,
+                    range: 0..5,
+                    matched: "First",
+                },
+                SearchMatch {
+                    passage: "Start",
+                    node: 1,
+                    inline: None,
+                    field: MatchField::LinkTarget,
+                    range: 0..5,
+                    matched: "First",
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_search_text_only() {
+        let story = Story::try_from(SAMPLE).unwrap();
+
+        assert_eq!(story.search("First", SearchScope::TextOnly), vec![]);
+        let matches = story.search("is", SearchScope::TextOnly);
+        assert_eq!(matches.len(), 6);
+        assert_eq!(matches[0].passage, "First");
+        assert_eq!(matches[0].range, 2..4);
+    }
+
+    #[test]
+    fn test_search_paragraphs() {
+        let options = ParseOptions::default().paragraphs(true);
+        let story = Story::parse_with(":: A\nHello\nworld", options).unwrap();
+
+        assert_eq!(
+            story.search("world", SearchScope::TextOnly),
+            vec![SearchMatch {
+                passage: "A",
+                node: 0,
+                inline: Some(2),
+                field: MatchField::Text,
+                range: 0..5,
+                matched: "world",
+            }]
+        );
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_search_regex() {
+        let story = Story::try_from(SAMPLE).unwrap();
+        let regex = regex::Regex::new(r"dead \w+").unwrap();
+
+        let matches = story.search_regex(&regex, SearchScope::TextOnly);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].passage, "Third");
+        assert_eq!(matches[0].matched, "dead end");
+    }
+}
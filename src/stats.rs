@@ -0,0 +1,118 @@
+//! Counts describing a story.
+
+use std::{collections::HashSet, ops::Deref};
+
+use crate::{ContentNode, Inline, Passage, Story};
+
+#[cfg(feature = "readability")]
+mod readability;
+
+#[cfg(feature = "readability")]
+pub use readability::Readability;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct StoryStats {
+    pub passages: usize,
+    pub words: usize,
+    pub links: usize,
+    pub broken_links: usize,
+    pub tags: usize,
+}
+
+pub(crate) fn word_count<T>(passage: &Passage<T>) -> usize
+where
+    T: AsRef<str>,
+{
+    passage.content.iter().map(node_word_count).sum()
+}
+
+fn node_word_count<T>(node: &ContentNode<T>) -> usize
+where
+    T: AsRef<str>,
+{
+    match node {
+        ContentNode::Text(text) => text.as_ref().split_whitespace().count(),
+        ContentNode::Link { text, .. } => text.as_ref().split_whitespace().count(),
+        ContentNode::Comment(_)
+        | ContentNode::Html(_)
+        | ContentNode::Image(_)
+        | ContentNode::Custom(_)
+        | ContentNode::Raw { .. } => 0,
+        ContentNode::Paragraph(inlines) => inlines
+            .iter()
+            .map(|inline| match inline {
+                Inline::Text(text) => text.as_ref().split_whitespace().count(),
+                Inline::LineBreak => 0,
+                Inline::Node(node) => node_word_count(node),
+            })
+            .sum(),
+    }
+}
+
+impl<T> Story<T>
+where
+    T: Deref<Target = str>,
+{
+    /// Statistics of the story, computed once until the story changes.
+    pub fn stats(&self) -> StoryStats {
+        *self.derived.stats.get_or_init(|| self.compute_stats())
+    }
+
+    fn compute_stats(&self) -> StoryStats {
+        let mut stats = StoryStats::default();
+        let mut tags = HashSet::new();
+
+        for passage in self.iter() {
+            stats.passages += 1;
+            stats.words += word_count(&passage);
+            for link in passage.links() {
+                stats.links += 1;
+                if !self.passages.contains_key(link.target) {
+                    stats.broken_links += 1;
+                }
+            }
+            tags.extend(passage.tags.iter().map(|tag| tag.value));
+        }
+        stats.tags = tags.len();
+
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StoryStats;
+    use crate::Story;
+
+    const SAMPLE: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/sample/sample.twee"));
+
+    #[test]
+    fn test_stats() {
+        let story = Story::try_from(SAMPLE).unwrap();
+
+        assert_eq!(
+            story.stats(),
+            StoryStats {
+                passages: 4,
+                words: 30,
+                links: 5,
+                broken_links: 0,
+                tags: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_stats_after_change() {
+        let mut story = Story::try_from(SAMPLE).unwrap();
+        let before = story.stats();
+        let mut third = story.get_passage("Third").unwrap().into_owned();
+        third.push_link("Nowhere".to_string(), "Nowhere".to_string());
+
+        story.insert_passage(third);
+
+        assert_eq!(story.stats().links, before.links + 1);
+        assert_eq!(story.stats().broken_links, 1);
+        assert!(story.backlinks("Nowhere").eq(["Third"]));
+    }
+}
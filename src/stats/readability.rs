@@ -0,0 +1,148 @@
+//! Readability scores of the prose of passages.
+
+use std::ops::Deref;
+
+use crate::{prose::prose_ranges, ContentNode, Inline, Passage, Story, StoryFormat};
+
+/// How easy the prose of a passage is to read, see [`Story::readability`].
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Readability {
+    pub words: usize,
+    pub sentences: usize,
+    pub syllables: usize,
+    /// Flesch-Kincaid grade level, the US school grade needed to understand the text.
+    pub grade_level: f64,
+    /// Flesch reading ease, from 0 (very hard) to 100 (very easy) for most texts.
+    pub reading_ease: f64,
+    /// Number of sentences by length in words: `sentence_lengths[5]` sentences have five words.
+    pub sentence_lengths: Vec<usize>,
+}
+
+/// Estimates the syllables of an English word from its groups of vowels.
+fn syllables(word: &str) -> usize {
+    let word = word.to_lowercase();
+    let mut count = 0;
+    let mut previous_vowel = false;
+    for c in word.chars() {
+        let vowel = "aeiouy".contains(c);
+        if vowel && !previous_vowel {
+            count += 1;
+        }
+        previous_vowel = vowel;
+    }
+    if word.ends_with('e') && !word.ends_with("le") && count > 1 {
+        count -= 1;
+    }
+    count.max(1)
+}
+
+fn prose<T>(passage: &Passage<T>, format: StoryFormat) -> String
+where
+    T: AsRef<str>,
+{
+    let mut prose = String::new();
+    for node in &passage.content {
+        let texts = match node {
+            ContentNode::Text(text) | ContentNode::Link { text, .. } => vec![text.as_ref()],
+            ContentNode::Paragraph(inlines) => inlines
+                .iter()
+                .filter_map(|inline| match inline {
+                    Inline::Text(text) | Inline::Node(ContentNode::Link { text, .. }) => {
+                        Some(text.as_ref())
+                    }
+                    _ => None,
+                })
+                .collect(),
+            _ => continue,
+        };
+        for text in texts {
+            for range in prose_ranges(text, format) {
+                prose.push_str(&text[range]);
+                prose.push(' ');
+            }
+        }
+    }
+    prose
+}
+
+pub(crate) fn readability(text: &str) -> Readability {
+    let mut readability = Readability::default();
+    let mut sentence = 0;
+    for token in text.split_whitespace() {
+        let word: String = token.chars().filter(|c| c.is_alphanumeric()).collect();
+        if !word.is_empty() {
+            readability.words += 1;
+            readability.syllables += syllables(&word);
+            sentence += 1;
+        }
+        if sentence > 0 && token.ends_with(['.', '!', '?', '…']) {
+            end_sentence(&mut readability, &mut sentence);
+        }
+    }
+    if sentence > 0 {
+        end_sentence(&mut readability, &mut sentence);
+    }
+
+    if readability.words > 0 {
+        let words_per_sentence = readability.words as f64 / readability.sentences as f64;
+        let syllables_per_word = readability.syllables as f64 / readability.words as f64;
+        readability.grade_level = 0.39 * words_per_sentence + 11.8 * syllables_per_word - 15.59;
+        readability.reading_ease = 206.835 - 1.015 * words_per_sentence - 84.6 * syllables_per_word;
+    }
+    readability
+}
+
+fn end_sentence(readability: &mut Readability, length: &mut usize) {
+    readability.sentences += 1;
+    if readability.sentence_lengths.len() <= *length {
+        readability.sentence_lengths.resize(*length + 1, 0);
+    }
+    readability.sentence_lengths[*length] += 1;
+    *length = 0;
+}
+
+impl<T> Story<T>
+where
+    T: Deref<Target = str>,
+{
+    /// Readability of the prose of the passage `name`, see [`Story::text_segments`].
+    pub fn readability(&self, name: &str) -> Option<Readability> {
+        self.passages.get(name).map(|passage| {
+            let passage = passage.as_borrowed(&self.content);
+            readability(&prose(&passage, self.story_format()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{readability, syllables};
+    use crate::Story;
+
+    #[test]
+    fn test_syllables() {
+        assert_eq!(syllables("cat"), 1);
+        assert_eq!(syllables("table"), 2);
+        assert_eq!(syllables("home"), 1);
+        assert_eq!(syllables("beautiful"), 3);
+    }
+
+    #[test]
+    fn test_readability() {
+        let score = readability("The cat sat. It was a very happy cat! Why");
+
+        assert_eq!((score.words, score.sentences, score.syllables), (10, 3, 12));
+        assert_eq!(score.sentence_lengths, [0, 1, 0, 1, 0, 0, 1]);
+        assert!((score.reading_ease - 101.93).abs() < 0.1, "{score:?}");
+        assert!((score.grade_level + 0.13).abs() < 0.1, "{score:?}");
+    }
+
+    #[test]
+    fn test_passage_readability() {
+        let story = Story::try_from(":: A\n<<set $x to 1>>You wake up. [[Get up|B]]\n").unwrap();
+
+        let score = story.readability("A").unwrap();
+        assert_eq!((score.words, score.sentences), (5, 2));
+        assert_eq!(story.readability("Nowhere"), None);
+    }
+}
@@ -0,0 +1,216 @@
+//! Typed access to the fields of StoryData.
+
+use std::{collections::BTreeMap, fmt::Display, ops::Deref};
+
+use serde_json::{json, Map, Value};
+
+use crate::{SourceBlock, Story, TextBlock};
+
+/// The StoryData fields defined by the twee 3 specification.
+pub(crate) const STORY_DATA_FIELDS: [&str; 6] = [
+    "ifid",
+    "format",
+    "format-version",
+    "start",
+    "tag-colors",
+    "zoom",
+];
+
+/// The passage named by an operation isn't in the story.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownPassage(pub String);
+
+impl Display for UnknownPassage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "No passage named {:?}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownPassage {}
+
+impl<T> Story<T>
+where
+    T: Deref<Target = str>,
+{
+    fn data_object(&self) -> Option<Map<String, Value>> {
+        match serde_json::from_str(self.data()?).ok()? {
+            Value::Object(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    /// The field `field` of StoryData, known to the specification or not.
+    pub fn data_field(&self, field: &str) -> Option<Value> {
+        self.data_object()?.remove(field)
+    }
+
+    /// The fields of StoryData that aren't part of the twee 3 specification, such as settings
+    /// of the tools building the story.
+    pub fn custom_data(&self) -> Map<String, Value> {
+        let mut data = self.data_object().unwrap_or_default();
+        data.retain(|field, _| !STORY_DATA_FIELDS.contains(&field.as_str()));
+        data
+    }
+
+    /// Sets the field `field` of StoryData, creating StoryData if needed, and returns its
+    /// previous value. Setting `start` also changes the start passage.
+    ///
+    /// StoryData that isn't a JSON object is replaced.
+    pub fn set_data_field(&mut self, field: &str, value: Value) -> Option<Value> {
+        let mut data = self.data_object().unwrap_or_default();
+        if field == "start" {
+            self.start = value
+                .as_str()
+                .map(|start| TextBlock::Owned(start.to_string()));
+        }
+        let previous = data.insert(field.to_string(), value);
+        let data = format!("{:#}", Value::Object(data));
+
+        if let Some(source_blocks) = &mut self.source_blocks {
+            let block = SourceBlock::Data(TextBlock::Owned(format!(":: StoryData\n{data}\n\n")));
+            match source_blocks
+                .iter_mut()
+                .find(|block| matches!(block, SourceBlock::Data(_)))
+            {
+                Some(existing) => *existing = block,
+                None => {
+                    let index = source_blocks
+                        .iter()
+                        .take_while(|block| {
+                            matches!(block, SourceBlock::Preamble(_) | SourceBlock::Title(_))
+                        })
+                        .count();
+                    source_blocks.insert(index, block);
+                }
+            }
+        }
+        self.data = Some(TextBlock::Owned(data));
+        self.invalidate_derived();
+        previous
+    }
+
+    /// The start passage named in StoryData, whether the story has it or not, unlike
+    /// [`Story::start`].
+    pub fn start_title(&self) -> Option<&str> {
+        self.start.as_ref().map(|block| block.as_str(&self.content))
+    }
+
+    /// Makes `title` the start passage, in StoryData, if the story has it.
+    pub fn set_start(&mut self, title: &str) -> Result<(), UnknownPassage> {
+        if !self.contains_passage(title) {
+            return Err(UnknownPassage(title.to_string()));
+        }
+        self.set_data_field("start", json!(title));
+        Ok(())
+    }
+
+    /// Name of the story format, from the `format` field of StoryData.
+    pub fn format_name(&self) -> Option<String> {
+        match self.data_field("format")? {
+            Value::String(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    pub fn format_version(&self) -> Option<String> {
+        match self.data_field("format-version")? {
+            Value::String(version) => Some(version),
+            _ => None,
+        }
+    }
+
+    /// The `tag-colors` field of StoryData, read once until the story changes.
+    pub(crate) fn tag_color_map(&self) -> &BTreeMap<String, String> {
+        self.derived.tag_colors.get_or_init(|| {
+            let Some(Value::Object(colors)) = self.data_field("tag-colors") else {
+                return BTreeMap::new();
+            };
+            colors
+                .into_iter()
+                .filter_map(|(tag, color)| match color {
+                    Value::String(color) => Some((tag, color)),
+                    _ => None,
+                })
+                .collect()
+        })
+    }
+
+    /// The colors given to tags in the Twine editor, from the `tag-colors` field of StoryData,
+    /// sorted by tag.
+    pub fn tag_colors(&self) -> Vec<(String, String)> {
+        self.tag_color_map()
+            .iter()
+            .map(|(tag, color)| (tag.clone(), color.clone()))
+            .collect()
+    }
+
+    pub fn tag_color(&self, tag: &str) -> Option<String> {
+        self.tag_color_map().get(tag).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::UnknownPassage;
+    use crate::{ParseOptions, Story};
+
+    #[test]
+    fn test_custom_data() {
+        let input = ":: StoryTitle\nT\n\n:: StoryData\n{\"ifid\": \"X\", \"tool\": {\"v\": 2}}\n\n:: A\nHi\n";
+        let mut story = Story::parse_with(input, ParseOptions::default().verbatim(true)).unwrap();
+
+        assert_eq!(story.custom_data().len(), 1);
+        assert_eq!(story.data_field("tool"), Some(json!({ "v": 2 })));
+
+        assert_eq!(story.set_data_field("build", json!(7)), None);
+        assert_eq!(story.set_data_field("start", json!("A")), None);
+        assert_eq!(story.start().unwrap().title, "A");
+        assert_eq!(story.custom_data().get("build"), Some(&json!(7)));
+        assert_eq!(story.data_field("ifid"), Some(json!("X")));
+
+        let reparsed = Story::try_from(story.to_twee().as_str())
+            .unwrap()
+            .custom_data();
+        assert_eq!(reparsed, story.custom_data());
+    }
+
+    #[test]
+    fn test_set_start() {
+        let input = ":: StoryData\n{\"start\":\"Gone\"}\n\n:: A\n\n:: B\n";
+        let mut story = Story::try_from(input).unwrap().into_owned();
+
+        assert_eq!(story.start_title(), Some("Gone"));
+        assert_eq!(story.start_with(&[crate::StartRule::StoryData]), None);
+        assert_eq!(story.set_start("C"), Err(UnknownPassage("C".to_string())));
+        assert_eq!(story.start_title(), Some("Gone"));
+
+        story.set_start("B").unwrap();
+        assert_eq!(story.start_title(), Some("B"));
+        assert_eq!(story.start().unwrap().title, "B");
+        assert_eq!(story.data_field("start"), Some(json!("B")));
+    }
+
+    #[test]
+    fn test_tag_colors() {
+        let input = ":: StoryData\n{\"tag-colors\":{\"scene\":\"green\",\"danger\":\"red\",\"odd\":1}}\n\n:: A [scene]\n";
+        let mut story = Story::try_from(input).unwrap();
+
+        assert_eq!(
+            story.tag_colors(),
+            [
+                ("danger".to_string(), "red".to_string()),
+                ("scene".to_string(), "green".to_string())
+            ]
+        );
+        assert_eq!(story.tag_color("scene").as_deref(), Some("green"));
+        assert_eq!(story.tag_color("odd"), None);
+        assert_eq!(story.tag_color("none"), None);
+        assert!(Story::try_from(":: A\n").unwrap().tag_colors().is_empty());
+
+        story.set_data_field("tag-colors", json!({ "scene": "blue" }));
+        assert_eq!(story.tag_color("scene").as_deref(), Some("blue"));
+        assert_eq!(story.tag_color("danger"), None);
+    }
+}
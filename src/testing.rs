@@ -0,0 +1,81 @@
+//! Helpers for tests and benchmarks of code using twee stories.
+
+use std::fmt::Write;
+
+const WORDS: [&str; 16] = [
+    "the", "lantern", "flickers", "as", "waves", "crash", "against", "old", "stone", "and", "a",
+    "voice", "calls", "from", "below", "again",
+];
+
+/// A small deterministic pseudo random generator, so that corpora are the same across runs.
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self, bound: usize) -> usize {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        ((self.0 >> 33) % bound as u64) as usize
+    }
+}
+
+/// Generates a valid twee story with `n_passages` passages, each with some text, tags,
+/// metadata and links to other passages. The output only depends on `n_passages`.
+pub fn generate_story(n_passages: usize) -> String {
+    let mut rng = Lcg(n_passages as u64);
+    let mut output = String::from(":: StoryTitle\nGenerated story\n\n");
+    let _ = write!(
+        output,
+        ":: StoryData\n{{\n  \"ifid\": \"D674C58C-DEFA-4F70-B7A2-27742230C0FC\",\n  \"format\": \"SugarCube\",\n  \"start\": \"Passage 0\"\n}}\n\n"
+    );
+
+    for index in 0..n_passages {
+        let _ = write!(output, ":: Passage {index}");
+        if index % 3 == 0 {
+            output.push_str(" [generated chapter-");
+            let _ = write!(output, "{}]", index / 10);
+        }
+        let _ = writeln!(
+            output,
+            " {{\"position\":\"{},{}\",\"size\":\"100,100\"}}",
+            (index % 10) * 150,
+            (index / 10) * 150
+        );
+
+        for line in 0..1 + rng.next(4) {
+            let words: Vec<_> = (0..5 + rng.next(20))
+                .map(|_| WORDS[rng.next(WORDS.len())])
+                .collect();
+            let _ = writeln!(output, "{}.", words.join(" "));
+            if line == 0 && index % 5 == 0 {
+                output.push_str("/* generated comment */\n");
+            }
+        }
+        if n_passages > 1 {
+            for _ in 0..1 + rng.next(3) {
+                let target = rng.next(n_passages);
+                let _ = writeln!(output, "[[Go on->Passage {target}]]");
+            }
+        }
+        output.push('\n');
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::generate_story;
+    use crate::Story;
+
+    #[test]
+    fn test_generate_story() {
+        let twee = generate_story(50);
+        let story = Story::try_from(twee.as_str()).unwrap();
+
+        assert_eq!(twee, generate_story(50));
+        assert_eq!(story.iter().count(), 50);
+        assert_eq!(story.title(), Some("Generated story"));
+        assert!(story.validate().is_empty());
+    }
+}
@@ -1,10 +1,42 @@
 use nom::{
     bytes::complete::escaped_transform,
-    character::complete::{anychar, char, none_of},
+    character::complete::{anychar, char, line_ending, none_of},
     error::{Error, ErrorKind, ParseError},
-    Err, FindSubstring, IResult,
+    Err, IResult,
 };
 
+/// Reads a line ending. `\n` and `\r\n` are the line endings of twee, and may be mixed in a
+/// file: every line of the parser ends with this.
+pub(crate) fn line_end(input: &str) -> IResult<&str, &str> {
+    line_ending(input)
+}
+
+/// Reads up to the next line ending, or to the end of input.
+pub(crate) fn rest_of_line(input: &str) -> IResult<&str, &str> {
+    let end = input.find('\n').unwrap_or(input.len());
+    let line = input[..end].strip_suffix('\r').unwrap_or(&input[..end]);
+    Ok((&input[line.len()..], line))
+}
+
+/// Fails when a parser consumed nothing of `input`, leaving `rest`, so that the loops calling
+/// parsers until the end of input can't run forever. Like nom's `many0`, this fails with
+/// [`ErrorKind::Many0`] at the input that wasn't consumed.
+pub(crate) fn ensure_progress<'a>(input: &'a str, rest: &str) -> Result<(), Err<Error<&'a str>>> {
+    match rest.len() < input.len() {
+        true => Ok(()),
+        false => Err(Err::Failure(Error::from_error_kind(
+            input,
+            ErrorKind::Many0,
+        ))),
+    }
+}
+
+/// Start of the line ending before the next line starting with `::`.
+pub(crate) fn find_header_line(input: &str) -> Option<usize> {
+    let index = input.find("\n::")?;
+    Some(index - usize::from(input[..index].ends_with('\r')))
+}
+
 pub(crate) fn take_delimited_greedy(
     opening_char: char,
     closing_char: char,
@@ -12,54 +44,66 @@ pub(crate) fn take_delimited_greedy(
     move |i: &str| {
         // Validate that we start with the opening char.
         char(opening_char)(i)?;
-        let mut index = 0;
-        let mut bracket_counter = 0;
-
-        while let Some(n) = &i[index..].find(&[opening_char, closing_char, '\\'][..]) {
-            index += n;
-            let mut it = i[index..].chars();
-            match it.next().unwrap_or_default() {
-                c if c == '\\' => {
-                    // Skip the escape char `\`.
-                    index += '\\'.len_utf8();
-                    // Skip also the following char.
-                    let c = it.next().unwrap_or_default();
-                    index += c.len_utf8();
-                }
-                c if c == opening_char => {
-                    bracket_counter += 1;
-                    index += opening_char.len_utf8();
+        let mut bracket_counter = 0usize;
+        let mut chars = i.char_indices();
+
+        while let Some((index, c)) = chars.next() {
+            match c {
+                // Skip the escape char `\` and the following char.
+                '\\' => {
+                    chars.next();
                 }
+                c if c == opening_char => bracket_counter += 1,
                 c if c == closing_char => {
-                    // Closing bracket.
                     bracket_counter -= 1;
-                    index += closing_char.len_utf8();
+                    // We found the unmatched closing bracket.
+                    if bracket_counter == 0 {
+                        let end = index + closing_char.len_utf8();
+                        return Ok((&i[end..], &i[..end]));
+                    }
                 }
-                // Can not happen.
-                _ => unreachable!(),
-            };
-            // We found the unmatched closing bracket.
-            if bracket_counter == 0 {
-                return Ok((&i[index..], &i[0..index]));
-            };
+                _ => {}
+            }
         }
 
-        if bracket_counter == 0 {
-            Ok(("", i))
-        } else {
-            Err(Err::Error(Error::from_error_kind(i, ErrorKind::TakeUntil)))
+        Err(Err::Error(Error::from_error_kind(i, ErrorKind::TakeUntil)))
+    }
+}
+
+/// Takes a JSON object, ignoring the braces inside of strings.
+pub(crate) fn take_json_object(input: &str) -> IResult<&str, &str> {
+    char('{')(input)?;
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut chars = input.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\\' if in_string => {
+                chars.next();
+            }
+            '"' => in_string = !in_string,
+            '{' if !in_string => depth += 1,
+            '}' if !in_string => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok((&input[index + 1..], &input[..index + 1]));
+                }
+            }
+            _ => {}
         }
     }
+    Err(Err::Error(Error::from_error_kind(
+        input,
+        ErrorKind::TakeUntil,
+    )))
 }
 
 pub(crate) fn until_link1(input: &str) -> IResult<&str, &str> {
-    let mut index = 0;
-
-    loop {
-        if let Some(position) = (&input[index..]).find_substring("\\[[") {
-            index += position + 2;
-        } else if let Some(position) = (&input[index..]).find_substring("[[") {
-            index += position;
+    let mut chars = input.char_indices();
+    while let Some((index, c)) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if input[index..].starts_with("[[") {
             return if index == 0 {
                 Err(Err::Error(Error::from_error_kind(
                     input,
@@ -68,28 +112,52 @@ pub(crate) fn until_link1(input: &str) -> IResult<&str, &str> {
             } else {
                 Ok((&input[index..], &input[0..index]))
             };
-        } else {
-            break;
         }
     }
 
     Ok(("", input))
 }
 
-pub(crate) fn split_escaped<'a>(input: &'a str, pat: &str) -> Option<(&'a str, &'a str)> {
-    let mut index = 0;
-    let escaped_pat = format!("\\{pat}");
+pub(crate) const COMMENT_DELIMITERS: [(&str, &str); 3] =
+    [("/*", "*/"), ("/%", "%/"), ("<!--", "-->")];
 
-    while let Some(position) = (&input[index..]).find_substring(&escaped_pat) {
-        index += position + 2;
+/// Finds the start of the first closed comment, ignoring escaped openings.
+pub(crate) fn find_comment(input: &str) -> Option<usize> {
+    // An opening without a closing after it means no later one of its kind has one either.
+    let mut unclosed = [false; COMMENT_DELIMITERS.len()];
+    let mut chars = input.char_indices();
+    while let Some((index, c)) = chars.next() {
+        if c == '\\' {
+            chars.next();
+            continue;
+        }
+        for (i, (opening, closing)) in COMMENT_DELIMITERS.iter().enumerate() {
+            let Some(rest) = input[index..].strip_prefix(opening) else {
+                continue;
+            };
+            if unclosed[i] {
+                continue;
+            }
+            match rest.contains(closing) {
+                true => return Some(index),
+                false => unclosed[i] = true,
+            }
+        }
     }
+    None
+}
 
-    if let Some(position) = (&input[index..]).find_substring(pat) {
-        index += position;
-        Some((&input[..index], &input[index + pat.len()..]))
-    } else {
-        None
+/// Splits `input` around the first occurrence of `pat` that is not escaped.
+pub(crate) fn split_escaped<'a>(input: &'a str, pat: &str) -> Option<(&'a str, &'a str)> {
+    let mut chars = input.char_indices();
+    while let Some((index, c)) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if input[index..].starts_with(pat) {
+            return Some((&input[..index], &input[index + pat.len()..]));
+        }
     }
+    None
 }
 
 pub(crate) fn escape_string_content(input: &str) -> Option<String> {
@@ -111,7 +179,40 @@ mod tests {
         Err,
     };
 
-    use super::{split_escaped, until_link1};
+    use super::{
+        ensure_progress, find_comment, split_escaped, take_delimited_greedy, take_json_object,
+        until_link1,
+    };
+
+    #[test]
+    fn test_ensure_progress() {
+        assert_eq!(ensure_progress("ab", "b"), Ok(()));
+        assert_eq!(
+            ensure_progress("ab", "ab"),
+            Err(Err::Failure(Error::from_error_kind("ab", ErrorKind::Many0)))
+        );
+    }
+
+    #[test]
+    fn test_take_delimited_greedy_incomplete() {
+        let take = take_delimited_greedy('[', ']');
+
+        assert_eq!(take("[[é]]"), Ok(("", "[[é]]")));
+        assert!(take("[é\\").is_err());
+        assert!(take("[[é\\]]").is_err());
+        assert!(take("").is_err());
+    }
+
+    #[test]
+    fn test_take_json_object() {
+        let input = r#"{"start":"a{\"}b","nested":{}} rest"#;
+
+        assert_eq!(
+            take_json_object(input),
+            Ok((" rest", r#"{"start":"a{\"}b","nested":{}}"#))
+        );
+        assert!(take_json_object(r#"{"a":"}"#).is_err());
+    }
 
     #[test]
     fn test_until_link1() {
@@ -174,4 +275,13 @@ mod tests {
 
         assert_eq!(split_escaped(input, "-"), Some(("hello\\-", "I'm happy")));
     }
+
+    #[test]
+    fn test_find_comment() {
+        assert_eq!(find_comment("Hello /* there */"), Some(6));
+        assert_eq!(find_comment("a <!-- b --> /% c %/"), Some(2));
+        assert_eq!(find_comment("/% unclosed"), None);
+        assert_eq!(find_comment(r"\/* escaped */ /%c%/"), Some(15));
+        assert_eq!(find_comment(r"\\/* not escaped */"), Some(2));
+    }
 }
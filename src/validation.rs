@@ -0,0 +1,169 @@
+//! Checks for problems in a parsed story.
+
+use std::{fmt::Display, ops::Deref};
+
+use crate::Story;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Issue {
+    /// No start passage is found, see [`Story::start`]: StoryData doesn't define one and the
+    /// story has no passage to fall back on.
+    MissingStart,
+    /// The start passage defined in StoryData doesn't exist.
+    StartNotFound(String),
+    BrokenLink {
+        passage: String,
+        target: String,
+        /// Titles of the story close to the target, nearest first, in case of a typo.
+        suggestions: Vec<String>,
+    },
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Issue::MissingStart => write!(f, "No start passage is defined"),
+            Issue::StartNotFound(start) => write!(f, "Start passage {start:?} does not exist"),
+            Issue::BrokenLink {
+                passage,
+                target,
+                suggestions,
+            } => {
+                write!(f, "Passage {passage:?} links to missing passage {target:?}")?;
+                let suggestions: Vec<_> = suggestions
+                    .iter()
+                    .map(|suggestion| format!("{suggestion:?}"))
+                    .collect();
+                match suggestions.is_empty() {
+                    true => Ok(()),
+                    false => write!(f, ", did you mean {}?", suggestions.join(" or ")),
+                }
+            }
+        }
+    }
+}
+
+/// Number of characters to insert, remove or replace to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let replaced = diagonal + usize::from(a != *b);
+            diagonal = row[j + 1];
+            row[j + 1] = replaced.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Up to three of `titles` close to `target`, ignoring case, nearest first.
+fn suggestions<'a>(target: &str, titles: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let target = target.to_lowercase();
+    let max_distance = (target.chars().count() / 3).max(1);
+    let mut close: Vec<_> = titles
+        .map(|title| (edit_distance(&target, &title.to_lowercase()), title))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    close.sort();
+    close
+        .into_iter()
+        .take(3)
+        .map(|(_, title)| title.to_string())
+        .collect()
+}
+
+impl<T> Story<T>
+where
+    T: Deref<Target = str>,
+{
+    pub fn validate(&self) -> Vec<Issue> {
+        let mut issues = vec![];
+
+        match &self.start {
+            None if self.start().is_none() => issues.push(Issue::MissingStart),
+            None => {}
+            Some(start) => {
+                let start = start.as_str(&self.content);
+                if !self.passages.contains_key(start) {
+                    issues.push(Issue::StartNotFound(start.to_string()));
+                }
+            }
+        }
+
+        for link in self.links() {
+            if !self.passages.contains_key(link.target) {
+                issues.push(Issue::BrokenLink {
+                    passage: link.passage.to_string(),
+                    target: link.target.to_string(),
+                    suggestions: suggestions(link.target, self.iter().map(|p| p.title)),
+                });
+            }
+        }
+
+        issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{edit_distance, Issue};
+    use crate::Story;
+
+    #[test]
+    fn test_validate_valid_story() {
+        let input = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/sample/sample.twee"));
+        let story = Story::try_from(input).unwrap();
+
+        assert_eq!(story.validate(), vec![]);
+    }
+
+    #[test]
+    fn test_validate_issues() {
+        let input = ":: StoryData\n{\"start\":\"Begin\"}\n\n:: Start\nGo [[there]]\n";
+        let story = Story::try_from(input).unwrap();
+
+        assert_eq!(
+            story.validate(),
+            vec![
+                Issue::StartNotFound("Begin".to_string()),
+                Issue::BrokenLink {
+                    passage: "Start".to_string(),
+                    target: "there".to_string(),
+                    suggestions: vec![]
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_broken_link_suggestions() {
+        let input = ":: Start\nGo [[Foerst]]\n\n:: Forest\n\n:: forest path\n\n:: Fort\n";
+        let issues = Story::try_from(input).unwrap().validate();
+
+        assert_eq!(
+            issues,
+            [Issue::BrokenLink {
+                passage: "Start".to_string(),
+                target: "Foerst".to_string(),
+                suggestions: vec!["Forest".to_string(), "Fort".to_string()]
+            }]
+        );
+        assert_eq!(
+            issues[0].to_string(),
+            "Passage \"Start\" links to missing passage \"Foerst\", did you mean \"Forest\" or \"Fort\"?"
+        );
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_validate_start_fallback() {
+        let story = Story::try_from(":: Intro\nHi\n\n:: Start\nHello\n").unwrap();
+        assert_eq!(story.validate(), vec![]);
+
+        let story = Story::try_from(":: StoryTitle\nEmpty\n").unwrap();
+        assert_eq!(story.validate(), vec![Issue::MissingStart]);
+    }
+}
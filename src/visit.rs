@@ -0,0 +1,230 @@
+//! Visitors over the passages and nodes of a story.
+//!
+//! Implement only the methods you need, the default ones walk down the tree:
+//!
+//! ```rust
+//! use twee_v3::{visit::Visitor, Story};
+//!
+//! struct LinkCounter(usize);
+//!
+//! impl<'a> Visitor<'a> for LinkCounter {
+//!     fn visit_link(&mut self, _text: &'a str, _target: &'a str) {
+//!         self.0 += 1;
+//!     }
+//! }
+//!
+//! let story = Story::try_from(":: Start\n[[A]] [[B]]\n").unwrap();
+//! let mut counter = LinkCounter(0);
+//! story.walk(&mut counter);
+//! assert_eq!(counter.0, 2);
+//! ```
+
+use std::ops::Deref;
+
+use crate::{ContentNode, CustomNode, Dialect, HtmlTag, Image, Inline, Passage, Story};
+
+pub trait Visitor<'a> {
+    fn visit_passage(&mut self, passage: &Passage<&'a str>) {
+        walk_passage(self, passage);
+    }
+
+    fn visit_node(&mut self, node: &ContentNode<&'a str>) {
+        walk_node(self, node);
+    }
+
+    fn visit_text(&mut self, _text: &'a str) {}
+
+    fn visit_link(&mut self, _text: &'a str, _target: &'a str) {}
+
+    fn visit_comment(&mut self, _comment: &'a str) {}
+
+    fn visit_html(&mut self, _tag: &HtmlTag<&'a str>) {}
+
+    fn visit_image(&mut self, _image: &Image<&'a str>) {}
+
+    fn visit_custom(&mut self, _node: &CustomNode<&'a str>) {}
+
+    fn visit_raw(&mut self, _dialect: Dialect, _raw: &'a str) {}
+}
+
+pub fn walk_passage<'a, V>(visitor: &mut V, passage: &Passage<&'a str>)
+where
+    V: Visitor<'a> + ?Sized,
+{
+    for node in &passage.content {
+        visitor.visit_node(node);
+    }
+}
+
+pub fn walk_node<'a, V>(visitor: &mut V, node: &ContentNode<&'a str>)
+where
+    V: Visitor<'a> + ?Sized,
+{
+    match node {
+        ContentNode::Text(text) => visitor.visit_text(text),
+        ContentNode::Link { text, target, .. } => visitor.visit_link(text, target),
+        ContentNode::Comment(comment) => visitor.visit_comment(comment),
+        ContentNode::Html(tag) => visitor.visit_html(tag),
+        ContentNode::Image(image) => visitor.visit_image(image),
+        ContentNode::Custom(node) => visitor.visit_custom(node),
+        ContentNode::Raw { dialect, raw } => visitor.visit_raw(*dialect, raw),
+        ContentNode::Paragraph(inlines) => {
+            for inline in inlines {
+                match inline {
+                    Inline::Text(text) => visitor.visit_text(text),
+                    Inline::LineBreak => {}
+                    Inline::Node(node) => visitor.visit_node(node),
+                }
+            }
+        }
+    }
+}
+
+/// A visitor allowed to modify passages. Passages are visited as owned copies, and put back
+/// into the story when they were changed.
+pub trait VisitorMut {
+    fn visit_passage_mut(&mut self, passage: &mut Passage<String>) {
+        walk_passage_mut(self, passage);
+    }
+
+    fn visit_node_mut(&mut self, node: &mut ContentNode<String>) {
+        walk_node_mut(self, node);
+    }
+
+    fn visit_text_mut(&mut self, _text: &mut String) {}
+
+    fn visit_link_mut(&mut self, _text: &mut String, _target: &mut String) {}
+
+    fn visit_comment_mut(&mut self, _comment: &mut String) {}
+}
+
+pub fn walk_passage_mut<V>(visitor: &mut V, passage: &mut Passage<String>)
+where
+    V: VisitorMut + ?Sized,
+{
+    for node in &mut passage.content {
+        visitor.visit_node_mut(node);
+    }
+}
+
+pub fn walk_node_mut<V>(visitor: &mut V, node: &mut ContentNode<String>)
+where
+    V: VisitorMut + ?Sized,
+{
+    match node {
+        ContentNode::Text(text) => visitor.visit_text_mut(text),
+        ContentNode::Link { text, target, .. } => visitor.visit_link_mut(text, target),
+        ContentNode::Comment(comment) => visitor.visit_comment_mut(comment),
+        ContentNode::Paragraph(inlines) => {
+            for inline in inlines {
+                match inline {
+                    Inline::Text(text) => visitor.visit_text_mut(text),
+                    Inline::LineBreak => {}
+                    Inline::Node(node) => visitor.visit_node_mut(node),
+                }
+            }
+        }
+        ContentNode::Html(_)
+        | ContentNode::Image(_)
+        | ContentNode::Custom(_)
+        | ContentNode::Raw { .. } => {}
+    }
+}
+
+impl<T> Story<T>
+where
+    T: Deref<Target = str>,
+{
+    pub fn walk<'a, V>(&'a self, visitor: &mut V)
+    where
+        V: Visitor<'a> + ?Sized,
+    {
+        for passage in self.iter() {
+            visitor.visit_passage(&passage);
+        }
+    }
+
+    pub fn walk_mut<V>(&mut self, visitor: &mut V)
+    where
+        V: VisitorMut + ?Sized,
+    {
+        let changed: Vec<_> = self
+            .iter()
+            .filter_map(|passage| {
+                let original = passage.into_owned();
+                let mut passage = original.clone();
+                visitor.visit_passage_mut(&mut passage);
+                (passage != original).then_some(passage)
+            })
+            .collect();
+        for passage in changed {
+            self.insert_passage(passage);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Visitor, VisitorMut};
+    use crate::{Passage, Story};
+
+    const SAMPLE: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/sample/sample.twee"));
+
+    #[derive(Default)]
+    struct Collector<'a> {
+        passages: Vec<&'a str>,
+        targets: Vec<&'a str>,
+    }
+
+    impl<'a> Visitor<'a> for Collector<'a> {
+        fn visit_passage(&mut self, passage: &Passage<&'a str>) {
+            self.passages.push(passage.title);
+            super::walk_passage(self, passage);
+        }
+
+        fn visit_link(&mut self, _text: &'a str, target: &'a str) {
+            self.targets.push(target);
+        }
+    }
+
+    #[test]
+    fn test_walk() {
+        let story = Story::try_from(SAMPLE).unwrap();
+        let mut collector = Collector::default();
+
+        story.walk(&mut collector);
+
+        assert_eq!(
+            collector.passages,
+            vec!["First", "Second", "Start", "Third"]
+        );
+        assert_eq!(
+            collector.targets,
+            vec!["Start", "First", "First", "Second", "Third"]
+        );
+    }
+
+    struct Shout;
+
+    impl VisitorMut for Shout {
+        fn visit_text_mut(&mut self, text: &mut String) {
+            *text = text.to_uppercase();
+        }
+    }
+
+    #[test]
+    fn test_walk_mut() {
+        let mut story = Story::try_from(SAMPLE).unwrap();
+
+        story.walk_mut(&mut Shout);
+
+        assert_eq!(
+            story.get_passage("Third").unwrap().to_string(),
+            "THIS IS A DEAD END"
+        );
+        assert_eq!(
+            story.get_passage("Start").unwrap().to_string(),
+            "HELLO!\nFirst\nGoing there\nA third link"
+        );
+    }
+}
@@ -0,0 +1,458 @@
+//! Serialization of stories back to twee.
+
+use std::{
+    cmp::Ordering,
+    collections::{HashSet, VecDeque},
+    fmt::{Display, Write},
+    ops::Deref,
+};
+
+use crate::{
+    analysis::choices,
+    escape::{escape_link_part, escape_name, escape_tag, escape_text},
+    ContentNode, ImageAlign, Inline, LinkDialects, Passage, SourceBlock, Story,
+};
+
+/// The order passages are written in by [`Story::to_twee_ordered`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PassageOrder {
+    /// The order of the source, passages added after parsing going last.
+    #[default]
+    Source,
+    /// Sorted by title.
+    Alphabetical,
+    /// From the start passage, following links, images and includes breadth-first. Passages
+    /// that aren't reached go last, in source order.
+    BreadthFirst,
+}
+
+pub(crate) fn write_node<T>(output: &mut String, node: &ContentNode<T>)
+where
+    T: AsRef<str>,
+{
+    write_node_with(
+        output,
+        node,
+        LinkDialects::default(),
+        escape_text,
+        escape_link_part,
+    )
+}
+
+/// Writes a node as twee, without escaping, as it would appear in the content of a script or a
+/// stylesheet.
+pub(crate) fn write_node_unescaped<T>(output: &mut String, node: &ContentNode<T>)
+where
+    T: AsRef<str>,
+{
+    write_node_with(
+        output,
+        node,
+        LinkDialects::default(),
+        str::to_owned,
+        str::to_owned,
+    )
+}
+
+/// The separator of links in a form `dialects` read, pipe first, and whether the target comes
+/// first. `None` if only `[[Target]]` is read.
+fn link_separator(dialects: LinkDialects) -> Option<(&'static str, bool)> {
+    if dialects.pipe {
+        Some(("|", false))
+    } else if dialects.right_arrow {
+        Some(("->", false))
+    } else if dialects.left_arrow {
+        Some(("<-", true))
+    } else {
+        None
+    }
+}
+
+fn write_node_with<T>(
+    output: &mut String,
+    node: &ContentNode<T>,
+    dialects: LinkDialects,
+    escape_text: fn(&str) -> String,
+    escape_link_part: fn(&str) -> String,
+) where
+    T: AsRef<str>,
+{
+    match node {
+        ContentNode::Text(text) => output.push_str(&escape_text(text.as_ref())),
+        ContentNode::Link {
+            text,
+            target,
+            setter,
+        } => {
+            let (text, target) = (
+                escape_link_part(text.as_ref()),
+                escape_link_part(target.as_ref()),
+            );
+            let link = match link_separator(dialects) {
+                _ if text == target && setter.is_none() => target,
+                Some((separator, false)) => format!("{text}{separator}{target}"),
+                Some((separator, true)) => format!("{target}{separator}{text}"),
+                // The text can't be written.
+                None => target,
+            };
+            let _ = match setter {
+                Some(setter) => write!(output, "[[{link}][{}]]", setter.as_ref()),
+                None => write!(output, "[[{link}]]"),
+            };
+        }
+        ContentNode::Comment(comment) => output.push_str(comment.as_ref()),
+        ContentNode::Html(tag) => output.push_str(tag.raw.as_ref()),
+        ContentNode::Custom(node) => output.push_str(node.raw.as_ref()),
+        ContentNode::Raw { raw, .. } => output.push_str(raw.as_ref()),
+        ContentNode::Paragraph(inlines) => {
+            for inline in inlines {
+                match inline {
+                    Inline::Text(text) => output.push_str(&escape_text(text.as_ref())),
+                    Inline::LineBreak => output.push('\n'),
+                    Inline::Node(node) => {
+                        write_node_with(output, node, dialects, escape_text, escape_link_part)
+                    }
+                }
+            }
+        }
+        ContentNode::Image(image) => {
+            output.push('[');
+            match image.align {
+                Some(ImageAlign::Left) => output.push('<'),
+                Some(ImageAlign::Right) => output.push('>'),
+                None => {}
+            }
+            output.push_str("img[");
+            if let Some(title) = &image.title {
+                let _ = write!(output, "{}|", escape_link_part(title.as_ref()));
+            }
+            output.push_str(&escape_link_part(image.source.as_ref()));
+            output.push(']');
+            for part in [&image.target, &image.setter].into_iter().flatten() {
+                let _ = write!(output, "[{}]", escape_link_part(part.as_ref()));
+            }
+            output.push(']');
+        }
+    }
+}
+
+/// How a node is formatted by [`ContentNode::display`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DisplayMode {
+    /// What players see, as the `Display` implementation of nodes: text and link texts only.
+    #[default]
+    Player,
+    /// The node written back as twee, see [`ContentNode::to_twee_string`].
+    Twee,
+}
+
+/// A node formatted with a [`DisplayMode`].
+pub struct NodeDisplay<'a, T> {
+    node: &'a ContentNode<T>,
+    mode: DisplayMode,
+}
+
+impl<T> Display for NodeDisplay<'_, T>
+where
+    T: AsRef<str> + Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.mode {
+            DisplayMode::Player => write!(f, "{}", self.node),
+            DisplayMode::Twee => f.write_str(&self.node.to_twee_string()),
+        }
+    }
+}
+
+impl<T> ContentNode<T>
+where
+    T: AsRef<str>,
+{
+    /// The node as twee, with links and images in full, such as `[[Text|Target]]`.
+    pub fn to_twee_string(&self) -> String {
+        let mut output = String::new();
+        write_node(&mut output, self);
+        output
+    }
+
+    pub fn display(&self, mode: DisplayMode) -> NodeDisplay<'_, T> {
+        NodeDisplay { node: self, mode }
+    }
+}
+
+pub(crate) fn write_passage<T>(output: &mut String, passage: &Passage<T>, dialects: LinkDialects)
+where
+    T: AsRef<str>,
+{
+    output.push_str(":: ");
+    output.push_str(&escape_name(passage.title.as_ref()));
+    if !passage.tags.is_empty() {
+        let tags: Vec<_> = passage
+            .tags
+            .iter()
+            .map(|tag| escape_tag(tag.value.as_ref()))
+            .collect();
+        let _ = write!(output, " [{}]", tags.join(" "));
+    }
+    if let Some(metadata) = &passage.metadata {
+        output.push(' ');
+        output.push_str(metadata.content.as_ref());
+    }
+    output.push('\n');
+    for node in &passage.content {
+        write_node_with(output, node, dialects, escape_text, escape_link_part);
+    }
+    output.push_str("\n\n");
+}
+
+impl<T> Passage<T>
+where
+    T: AsRef<str>,
+{
+    pub fn to_twee(&self) -> String {
+        let mut output = String::new();
+        write_passage(&mut output, self, LinkDialects::default());
+        output
+    }
+}
+
+impl<T> Story<T>
+where
+    T: Deref<Target = str>,
+{
+    pub fn to_twee(&self) -> String {
+        if let Some(source_blocks) = &self.source_blocks {
+            return self.to_twee_verbatim(source_blocks);
+        }
+        self.write_story(self.iter())
+    }
+
+    /// The story as twee, its passages written in `order`.
+    ///
+    /// Only [`PassageOrder::Source`] keeps the source as is when parsed with
+    /// [`ParseOptions::verbatim`](crate::ParseOptions::verbatim).
+    pub fn to_twee_ordered(&self, order: PassageOrder) -> String {
+        match order {
+            PassageOrder::Source => self.to_twee(),
+            PassageOrder::Alphabetical => self.to_twee_sorted_by(|a, b| a.title.cmp(b.title)),
+            PassageOrder::BreadthFirst => self.write_story(self.breadth_first().into_iter()),
+        }
+    }
+
+    /// The story as twee, its passages sorted with `compare`. Passages comparing equal keep
+    /// their source order.
+    pub fn to_twee_sorted_by<F>(&self, mut compare: F) -> String
+    where
+        F: FnMut(&Passage<&str>, &Passage<&str>) -> Ordering,
+    {
+        let mut passages: Vec<_> = self.iter().collect();
+        passages.sort_by(|a, b| compare(a, b));
+        self.write_story(passages.into_iter())
+    }
+
+    fn breadth_first(&self) -> Vec<Passage<&str>> {
+        let mut passages = vec![];
+        let mut seen = HashSet::new();
+        let mut queue: VecDeque<_> = self.start().into_iter().collect();
+        while let Some(passage) = queue.pop_front() {
+            if !seen.insert(passage.title) {
+                continue;
+            }
+            let targets = choices(&passage)
+                .into_iter()
+                .chain(self.includes(passage.title));
+            queue.extend(targets.filter_map(|target| self.get_passage(target)));
+            passages.push(passage);
+        }
+        passages.extend(self.iter().filter(|passage| !seen.contains(passage.title)));
+        passages
+    }
+
+    fn write_story<'a>(&'a self, passages: impl Iterator<Item = Passage<&'a str>>) -> String {
+        let mut output = String::new();
+
+        if let Some(title) = self.title() {
+            let _ = write!(output, ":: StoryTitle\n{}\n\n", title.replace('\\', "\\\\"));
+        }
+        if let Some(data) = self.data() {
+            let _ = write!(output, ":: StoryData\n{data}\n\n");
+        } else if let Some(start) = &self.start {
+            let data = serde_json::json!({ "start": start.as_str(&self.content) });
+            let _ = write!(output, ":: StoryData\n{data:#}\n\n");
+        }
+
+        for passage in passages {
+            write_passage(&mut output, &passage, self.link_dialects);
+        }
+
+        output
+    }
+
+    fn to_twee_verbatim(&self, source_blocks: &[SourceBlock]) -> String {
+        let mut output = String::with_capacity(self.content.len());
+        let mut written = HashSet::new();
+
+        for block in source_blocks {
+            match block {
+                SourceBlock::Preamble(source)
+                | SourceBlock::Title(source)
+                | SourceBlock::Data(source)
+                | SourceBlock::Replaced(source) => output.push_str(source.as_str(&self.content)),
+                SourceBlock::Passage(name) => {
+                    let Some(passage) = self.passages.get(name) else {
+                        continue;
+                    };
+                    written.insert(name.as_str());
+                    match &passage.source {
+                        Some(source) => output.push_str(source.as_str(&self.content)),
+                        None => write_passage(
+                            &mut output,
+                            &passage.as_borrowed(&self.content),
+                            self.link_dialects,
+                        ),
+                    }
+                }
+            }
+        }
+
+        // Passages added after parsing go last.
+        let added = self
+            .passages
+            .iter()
+            .filter(|(name, _)| !written.contains(name))
+            .map(|(_, passage)| passage.as_borrowed(&self.content));
+        for passage in added {
+            write_passage(&mut output, &passage, self.link_dialects);
+        }
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DisplayMode, PassageOrder};
+    use crate::{ContentNode, LinkDialects, ParseOptions, Story};
+
+    const SAMPLE: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/sample/sample.twee"));
+
+    #[test]
+    fn test_node_display() {
+        let link = ContentNode::link_node("Go", "Cave");
+
+        assert_eq!(link.to_string(), "Go");
+        assert_eq!(link.to_twee_string(), "[[Go|Cave]]");
+        assert_eq!(link.display(DisplayMode::Player).to_string(), "Go");
+        assert_eq!(
+            format!("{}", link.display(DisplayMode::Twee)),
+            "[[Go|Cave]]"
+        );
+    }
+
+    #[test]
+    fn test_passage_to_twee() {
+        let story = Story::try_from(SAMPLE).unwrap();
+        let start = story.get_passage("Start").unwrap();
+
+        assert_eq!(
+            start.to_twee(),
+            ":: Start [osef\\]] {\"position\":\"600,150\",\"size\":\"200,200\"}\nHello!\n[[First]]\n[[Going there|Second]]\n[[A third link|Third]]\n\n"
+        );
+    }
+
+    #[test]
+    fn test_image_to_twee() {
+        let input = ":: Start\nLook [>img[A \\] cat|cat.png][Garden]] \\[img[text]]\n\n";
+        let story = Story::try_from(input).unwrap();
+
+        assert_eq!(story.get_passage("Start").unwrap().to_twee(), input);
+    }
+
+    #[test]
+    fn test_setter_link_to_twee() {
+        let input = ":: Start\n[[Take it|Room[1]][$items[0] to 1]]\n";
+        let story = Story::try_from(input).unwrap();
+        let twee = story.get_passage("Start").unwrap().to_twee();
+
+        assert_eq!(
+            twee,
+            ":: Start\n[[Take it|Room\\[1\\]][$items[0] to 1]]\n\n"
+        );
+        assert_eq!(
+            Story::try_from(twee.as_str()).unwrap().get_passage("Start"),
+            story.get_passage("Start")
+        );
+    }
+
+    #[test]
+    fn test_story_to_twee_uses_link_dialects() {
+        let reparse = |input: &str, dialects: LinkDialects| {
+            let options = ParseOptions::default().link_dialects(dialects);
+            let story = Story::parse_with(input, options.clone()).unwrap();
+            let twee = story.to_twee_ordered(PassageOrder::Alphabetical);
+            let reparsed = Story::parse_with(twee.as_str(), options).unwrap();
+            assert_eq!(reparsed.get_passage("A"), story.get_passage("A"));
+            twee
+        };
+
+        let input = ":: A\n[[Go|B]] [[Take|B][$x to 1]]\n";
+        let twee = reparse(input, LinkDialects::default().right_arrow(false));
+        assert_eq!(twee, input.to_string() + "\n");
+        let input = ":: A\n[[B<-Go]] [[B<-Take][$x to 1]]\n";
+        let twee = reparse(
+            input,
+            LinkDialects::default().pipe(false).right_arrow(false),
+        );
+        assert_eq!(twee, input.to_string() + "\n");
+    }
+
+    #[test]
+    fn test_story_to_twee_reparses() {
+        let story = Story::try_from(SAMPLE).unwrap();
+        let twee = story.to_twee();
+        let reparsed = Story::try_from(twee.as_str()).unwrap();
+
+        assert_eq!(reparsed.title(), story.title());
+        assert_eq!(reparsed.data(), story.data());
+        for passage in story.iter() {
+            assert_eq!(reparsed.get_passage(passage.title()), Some(passage));
+        }
+    }
+
+    #[test]
+    fn test_story_to_twee_verbatim_is_identical() {
+        let options = ParseOptions::default().verbatim(true);
+        let story = Story::parse_with(SAMPLE, options.clone()).unwrap();
+        assert_eq!(story.to_twee(), SAMPLE);
+
+        let input = ":: A\nFirst\n\n:: B\nb\n\n:: A\nSecond\n";
+        let story = Story::parse_with(input, options).unwrap();
+        assert_eq!(story.to_twee(), input);
+    }
+    #[test]
+    fn test_story_to_twee_ordered() {
+        let input = ":: StoryData\n{\"start\": \"B\"}\n\n:: A\nEnd\n\n:: C\nAlone\n\n:: B\n[[D]] [[A]]\n\n:: D\n[[B]]\n";
+        let story = Story::parse_with(input, ParseOptions::default().verbatim(true)).unwrap();
+        let titles = |twee: String| -> Vec<String> {
+            let story = Story::try_from(twee.as_str()).unwrap();
+            story
+                .iter()
+                .map(|passage| passage.title.to_string())
+                .collect()
+        };
+
+        assert_eq!(story.to_twee_ordered(PassageOrder::Source), input);
+        assert_eq!(
+            titles(story.to_twee_ordered(PassageOrder::Alphabetical)),
+            ["A", "B", "C", "D"]
+        );
+        assert_eq!(
+            titles(story.to_twee_ordered(PassageOrder::BreadthFirst)),
+            ["B", "D", "A", "C"]
+        );
+        assert_eq!(
+            titles(story.to_twee_sorted_by(|a, b| b.nodes().len().cmp(&a.nodes().len()))),
+            ["B", "A", "C", "D"]
+        );
+    }
+}